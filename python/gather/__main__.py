@@ -12,7 +12,7 @@ def _run():
     if binary is None:
         print(f"Error: could not find '{name}' on PATH.", file=sys.stderr)
         print("Reinstall the package: uv tool install gather", file=sys.stderr)
-        sys.exit(1)
+        sys.exit(4)
     if sys.platform == "win32":
         sys.exit(subprocess.call([binary, *sys.argv[1:]]))
     else:
@@ -0,0 +1,153 @@
+use crate::git::git_output;
+use crate::CollectedFile;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// How a line differs from the base ref.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Change {
+    Added,
+    Modified,
+}
+
+/// Changed lines per root-relative path, as 1-based line number -> change.
+type ChangedLines = HashMap<String, HashMap<usize, Change>>;
+
+/// Prefix every line of each file that changed since `base` with a gutter:
+/// `+ ` for added lines, `~ ` for modified lines and two spaces otherwise.
+/// Untracked files count as entirely added; unchanged files are left as is.
+/// Returns the number of annotated files, or an error if `base` isn't a
+/// commit.
+pub fn annotate_changes(
+    root: &Path,
+    base: &str,
+    files: &mut [CollectedFile],
+) -> Result<usize, String> {
+    if git_output(
+        root,
+        &["rev-parse", "--verify", &format!("{base}^{{commit}}")],
+    )
+    .is_none()
+    {
+        return Err(format!("--annotate-changes: unknown revision '{base}'"));
+    }
+
+    let diff = git_output(
+        root,
+        &[
+            "diff",
+            "--relative",
+            "--no-prefix",
+            "--no-color",
+            "--no-ext-diff",
+            "-U0",
+            base,
+            "--",
+        ],
+    )
+    .unwrap_or_default();
+    let mut changes = parse_diff(&diff);
+    let untracked =
+        git_output(root, &["ls-files", "--others", "--exclude-standard"]).unwrap_or_default();
+
+    let mut annotated = 0;
+    for file in files.iter_mut() {
+        let rel = file.relative_path.replace('\\', "/");
+        let lines = if untracked.lines().any(|u| u == rel) {
+            (1..=file.content.lines().count())
+                .map(|n| (n, Change::Added))
+                .collect()
+        } else {
+            match changes.remove(&rel) {
+                Some(lines) => lines,
+                None => continue,
+            }
+        };
+        file.content = with_gutter(&file.content, &lines);
+        annotated += 1;
+    }
+    Ok(annotated)
+}
+
+/// Parse `git diff -U0 --no-prefix` output into changed new-side lines.
+/// A hunk that replaces old lines marks its lines modified; a hunk that only
+/// inserts marks them added. Pure deletions mark nothing.
+fn parse_diff(diff: &str) -> ChangedLines {
+    let mut changes = ChangedLines::new();
+    let mut current: Option<String> = None;
+
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            current = (path != "/dev/null").then(|| path.trim_matches('"').to_string());
+        } else if let (Some(hunk), Some(path)) = (line.strip_prefix("@@ -"), &current) {
+            // "a[,b] +c[,d] @@ ..."
+            let mut ranges = hunk.split(' ');
+            let (Some(old), Some(new)) = (ranges.next(), ranges.next()) else {
+                continue;
+            };
+            let count = |range: &str| -> Option<(usize, usize)> {
+                let mut parts = range.splitn(2, ',');
+                let start = parts.next()?.parse().ok()?;
+                let len = parts.next().map_or(Some(1), |l| l.parse().ok())?;
+                Some((start, len))
+            };
+            let (Some((_, old_len)), Some((start, len))) =
+                (count(old), count(new.trim_start_matches('+')))
+            else {
+                continue;
+            };
+            let change = if old_len == 0 {
+                Change::Added
+            } else {
+                Change::Modified
+            };
+            let entry = changes.entry(path.clone()).or_default();
+            entry.extend((start..start + len).map(|n| (n, change)));
+        }
+    }
+    changes
+}
+
+fn with_gutter(content: &str, lines: &HashMap<usize, Change>) -> String {
+    let mut out = String::with_capacity(content.len() + content.len() / 8);
+    for (i, line) in content.lines().enumerate() {
+        out.push_str(match lines.get(&(i + 1)) {
+            Some(Change::Added) => "+ ",
+            Some(Change::Modified) => "~ ",
+            None => "  ",
+        });
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff_and_gutter() {
+        let diff = "diff --git src/lib.rs src/lib.rs\n\
+                    --- src/lib.rs\n\
+                    +++ src/lib.rs\n\
+                    @@ -2 +2 @@ fn a() {\n\
+                    -    old();\n\
+                    +    new();\n\
+                    @@ -5,0 +6,2 @@\n\
+                    +fn b() {}\n\
+                    +fn c() {}\n\
+                    diff --git gone.rs gone.rs\n\
+                    --- gone.rs\n\
+                    +++ /dev/null\n\
+                    @@ -1,3 +0,0 @@\n";
+        let changes = parse_diff(diff);
+        assert_eq!(changes.len(), 1);
+
+        let content = "fn a() {\n    new();\n}\n\n// end\nfn b() {}\nfn c() {}\n";
+        assert_eq!(
+            with_gutter(content, &changes["src/lib.rs"]),
+            "  fn a() {\n~     new();\n  }\n  \n  // end\n+ fn b() {}\n+ fn c() {}\n"
+        );
+    }
+}
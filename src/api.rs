@@ -0,0 +1,230 @@
+use crate::{
+    apply_token_budget, check_filter, collect_files, format_json, format_markdown, format_summary,
+    format_xml, BudgetOrder, CollectOptions, CollectedFile, Collection, Error, FormatOptions,
+    OutputFormat,
+};
+use std::path::PathBuf;
+
+/// Gathers files from a directory the way `gather collect` does: walks it
+/// respecting .gitignore, applies the filters and limits, and reads what is
+/// left.
+///
+/// ```no_run
+/// use gather::{Collector, Formatter, OutputFormat};
+///
+/// let collection = Collector::new("path/to/repo")
+///     .glob("src/**/*.rs")
+///     .no_tests(true)
+///     .max_tokens(50_000)
+///     .collect()?;
+/// let context = Formatter::new(OutputFormat::Markdown)
+///     .show_tokens(true)
+///     .format(&collection.files);
+/// # Ok::<(), gather::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Collector {
+    root: PathBuf,
+    opts: CollectOptions,
+    max_tokens: Option<usize>,
+    budget_order: Option<BudgetOrder>,
+}
+
+impl Collector {
+    /// Gather from `root` with the CLI's defaults: everything not ignored,
+    /// up to 100KB per file.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Collector {
+            root: root.into(),
+            opts: CollectOptions::default(),
+            max_tokens: None,
+            budget_order: None,
+        }
+    }
+
+    /// Only include files matching `pattern` (call again for more).
+    pub fn glob(mut self, pattern: impl Into<String>) -> Self {
+        self.opts.filter.globs.push(pattern.into());
+        self
+    }
+
+    /// Leave out files matching `pattern` (call again for more).
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.opts.filter.excludes.push(pattern.into());
+        self
+    }
+
+    /// Leave out test directories and test-named files.
+    pub fn no_tests(mut self, yes: bool) -> Self {
+        self.opts.filter.no_tests = yes;
+        self
+    }
+
+    /// Descend into initialized git submodules.
+    pub fn submodules(mut self, yes: bool) -> Self {
+        self.opts.filter.submodules = yes;
+        self
+    }
+
+    /// Only gather one workspace member (by package name or directory),
+    /// plus the workspace's root manifests.
+    pub fn package(mut self, name: impl Into<String>) -> Self {
+        self.opts.filter.package = Some(name.into());
+        self
+    }
+
+    /// Order paths with numbers by value (part2 before part10).
+    pub fn natural_sort(mut self, yes: bool) -> Self {
+        self.opts.filter.natural_sort = yes;
+        self
+    }
+
+    /// Skip files larger than `bytes`.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.opts.max_size = bytes;
+        self
+    }
+
+    /// Skip files estimated at more than `tokens`.
+    pub fn max_file_tokens(mut self, tokens: usize) -> Self {
+        self.opts.max_file_tokens = Some(tokens);
+        self
+    }
+
+    /// Stop adding files once the estimated total would pass `tokens`.
+    pub fn max_tokens(mut self, tokens: usize) -> Self {
+        self.max_tokens = Some(tokens);
+        self
+    }
+
+    /// Which files [`max_tokens`](Self::max_tokens) keeps first.
+    pub fn budget_order(mut self, order: BudgetOrder) -> Self {
+        self.budget_order = Some(order);
+        self
+    }
+
+    /// Walk the tree and read the selected files, sorted by path. Fails on
+    /// an unreadable root, an invalid pattern or an unknown package; files
+    /// and directories that can't be read are reported on stderr and left
+    /// out.
+    pub fn collect(&self) -> Result<Collection, Error> {
+        check_filter(&self.root, &self.opts.filter)?;
+        let mut collection = collect_files(&self.root, &self.opts);
+        if let Some(budget) = self.max_tokens {
+            apply_token_budget(
+                &self.root,
+                &mut collection,
+                budget,
+                self.budget_order,
+                &self.opts.filter,
+            );
+        }
+        Ok(collection)
+    }
+}
+
+/// Renders collected files as one markdown, XML or JSON document, as
+/// `gather collect --format` does.
+#[derive(Default)]
+pub struct Formatter {
+    format: OutputFormat,
+    opts: FormatOptions,
+}
+
+impl Formatter {
+    /// Render as `format` with no header, footer or counts.
+    pub fn new(format: OutputFormat) -> Self {
+        Formatter {
+            format,
+            opts: FormatOptions::default(),
+        }
+    }
+
+    /// Add a file/byte/token count line (a summary record in JSON).
+    pub fn show_tokens(mut self, yes: bool) -> Self {
+        self.opts.show_tokens = yes;
+        self
+    }
+
+    /// Text to put ahead of the files.
+    pub fn header(mut self, text: impl Into<String>) -> Self {
+        self.opts.header = Some(text.into());
+        self
+    }
+
+    /// Text to put after the files.
+    pub fn footer(mut self, text: impl Into<String>) -> Self {
+        self.opts.footer = Some(text.into());
+        self
+    }
+
+    /// Wrap each markdown file in a `<details>` block.
+    pub fn collapsible(mut self, yes: bool) -> Self {
+        self.opts.collapsible = yes;
+        self
+    }
+
+    /// One markdown heading per top-level directory, with token subtotals.
+    pub fn group_by_dir(mut self, yes: bool) -> Self {
+        self.opts.group_by_dir = yes;
+        self
+    }
+
+    /// Wrap each file's contents in a CDATA section instead of escaping
+    /// them (XML).
+    pub fn xml_cdata(mut self, yes: bool) -> Self {
+        self.opts.xml_cdata = yes;
+        self
+    }
+
+    /// The whole document for `files`, in the order given.
+    pub fn format(&self, files: &[CollectedFile]) -> String {
+        match self.format {
+            OutputFormat::Markdown => format_markdown(files, &self.opts),
+            OutputFormat::Xml => format_xml(files, &self.opts),
+            OutputFormat::Json => format_json(files, &self.opts),
+            OutputFormat::Summary => format_summary(files, &self.opts),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_collector_and_formatter() {
+        let root = crate::test_dir::make_test_dir("api");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("tests")).unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+        fs::write(root.join("tests/it.rs"), "#[test]\nfn t() {}\n").unwrap();
+        fs::write(root.join("README.md"), "# Hi\n").unwrap();
+
+        let collection = Collector::new(&root)
+            .glob("**/*.rs")
+            .no_tests(true)
+            .collect()
+            .unwrap();
+        let paths: Vec<&str> = collection
+            .files
+            .iter()
+            .map(|f| f.relative_path.as_str())
+            .collect();
+        assert_eq!(paths, ["src/lib.rs"]);
+
+        let markdown = Formatter::new(OutputFormat::Markdown)
+            .header("# Context")
+            .format(&collection.files);
+        assert_eq!(
+            markdown,
+            "# Context\n\n## `src/lib.rs`\n\n```rust\npub fn f() {}\n```\n\n"
+        );
+
+        assert!(Collector::new(&root).glob("[").collect().is_err());
+        assert!(Collector::new(&root).package("nope").collect().is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    count_tokens, format_markdown, format_xml, read_candidate, walk_files, CollectOptions,
+    CollectedFile, FormatOptions, PathFilter,
+};
+use std::fmt::Write as FmtWrite;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Median timings of each pipeline stage over several runs.
+pub struct BenchReport {
+    pub runs: usize,
+    pub files: usize,
+    pub bytes: usize,
+    pub stages: Vec<(&'static str, Duration)>,
+}
+
+/// Time walking, filtering, reading, tokenizing and formatting separately,
+/// `runs` times each, keeping the median of every stage.
+pub fn run(root: &Path, opts: &CollectOptions, runs: usize) -> BenchReport {
+    let runs = runs.max(1);
+    let mut samples: Vec<Vec<Duration>> = vec![Vec::new(); 5];
+    let mut files_len = 0;
+    let mut bytes = 0;
+
+    for _ in 0..runs {
+        let start = Instant::now();
+        let paths = walk_files(root, &opts.filter);
+        samples[0].push(start.elapsed());
+
+        let start = Instant::now();
+        let filter = PathFilter::new(root, &opts.filter);
+        let matched: Vec<_> = paths
+            .into_iter()
+            .filter(|(_, rel)| filter.matches(rel))
+            .collect();
+        samples[1].push(start.elapsed());
+
+        let start = Instant::now();
+        let files: Vec<CollectedFile> = matched
+            .into_iter()
+            .filter_map(|(path, rel)| {
+                read_candidate(&path, opts)
+                    .ok()
+                    .map(|content| CollectedFile {
+                        relative_path: rel,
+                        content,
+                    })
+            })
+            .collect();
+        samples[2].push(start.elapsed());
+
+        let start = Instant::now();
+        let tokens: usize = count_tokens(&files).iter().sum();
+        samples[3].push(start.elapsed());
+        std::hint::black_box(tokens);
+
+        let start = Instant::now();
+        let format_opts = FormatOptions::default();
+        std::hint::black_box(format_markdown(&files, &format_opts));
+        std::hint::black_box(format_xml(&files, &format_opts));
+        samples[4].push(start.elapsed());
+
+        files_len = files.len();
+        bytes = files.iter().map(|f| f.content.len()).sum();
+    }
+
+    let names = ["walk", "filter", "read", "tokenize", "format"];
+    let stages = names
+        .iter()
+        .zip(samples)
+        .map(|(name, mut times)| {
+            times.sort();
+            (*name, times[times.len() / 2])
+        })
+        .collect();
+
+    BenchReport {
+        runs,
+        files: files_len,
+        bytes,
+        stages,
+    }
+}
+
+impl BenchReport {
+    pub fn render(&self) -> String {
+        let total: Duration = self.stages.iter().map(|(_, d)| *d).sum();
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} files, {} bytes (median of {} runs)",
+            self.files, self.bytes, self.runs
+        );
+        let _ = writeln!(out);
+        for (name, time) in &self.stages {
+            let pct = if total.is_zero() {
+                0.0
+            } else {
+                time.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+            let _ = writeln!(
+                out,
+                "{name:>10}  {:>10.2} ms  {pct:>5.1}%",
+                time.as_secs_f64() * 1000.0
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{:>10}  {:>10.2} ms",
+            "total",
+            total.as_secs_f64() * 1000.0
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_report_render() {
+        let report = BenchReport {
+            runs: 3,
+            files: 2,
+            bytes: 100,
+            stages: vec![
+                ("walk", Duration::from_millis(3)),
+                ("read", Duration::from_millis(1)),
+            ],
+        };
+        let text = report.render();
+        assert!(text.starts_with("2 files, 100 bytes (median of 3 runs)\n"));
+        assert!(text.contains("      walk        3.00 ms   75.0%"));
+        assert!(text.contains("     total        4.00 ms"));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::cache;
+use std::fmt::Write as FmtWrite;
+
+/// What `--binary` does with files that aren't text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BinaryPolicy {
+    #[default]
+    Skip,
+    /// A one-line placeholder with the file's size
+    Note,
+    /// An `xxd`-style dump: offset, hex bytes and printable characters
+    Hexdump,
+    /// The bytes base64-encoded, 76 characters to a line
+    Base64,
+}
+
+impl std::str::FromStr for BinaryPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(BinaryPolicy::Skip),
+            "note" => Ok(BinaryPolicy::Note),
+            "hexdump" => Ok(BinaryPolicy::Hexdump),
+            "base64" => Ok(BinaryPolicy::Base64),
+            _ => Err(format!(
+                "Unknown binary policy: {s}. Use 'skip', 'note', 'hexdump' or 'base64'."
+            )),
+        }
+    }
+}
+
+/// The placeholder `--binary note` puts in place of a file of `size` bytes,
+/// also used for binaries too large to embed.
+pub fn note(size: u64) -> String {
+    format!("[binary file: {}]\n", cache::format_bytes(size))
+}
+
+/// `bytes` as `policy` renders them, headed by a line saying what follows.
+/// `None` for `skip`.
+pub fn render(policy: BinaryPolicy, bytes: &[u8]) -> Option<String> {
+    let size = cache::format_bytes(bytes.len() as u64);
+    match policy {
+        BinaryPolicy::Skip => None,
+        BinaryPolicy::Note => Some(note(bytes.len() as u64)),
+        BinaryPolicy::Hexdump => Some(format!(
+            "[binary file: {size}, hexdump]\n{}",
+            hexdump(bytes)
+        )),
+        BinaryPolicy::Base64 => {
+            let encoded = base64(bytes);
+            let mut out = format!("[binary file: {size}, base64]\n");
+            for line in encoded.as_bytes().chunks(76) {
+                out.push_str(std::str::from_utf8(line).unwrap_or_default());
+                out.push('\n');
+            }
+            Some(out)
+        }
+    }
+}
+
+/// Sixteen bytes to a line, as `xxd` prints them.
+fn hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x}:", i * 16);
+        for pair in 0..8 {
+            out.push(' ');
+            for b in line.iter().skip(pair * 2).take(2) {
+                let _ = write!(out, "{b:02x}");
+            }
+            if line.len() < pair * 2 + 2 {
+                out.push_str(&"  ".repeat((pair * 2 + 2 - line.len()).min(2)));
+            }
+        }
+        out.push_str("  ");
+        out.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push('\n');
+    }
+    out
+}
+
+/// Standard base64 with padding.
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"x-access-token:abc"), "eC1hY2Nlc3MtdG9rZW46YWJj");
+    }
+
+    #[test]
+    fn test_render() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0";
+        assert_eq!(render(BinaryPolicy::Skip, png), None);
+        assert_eq!(
+            render(BinaryPolicy::Note, png).unwrap(),
+            "[binary file: 18 B]\n"
+        );
+        assert_eq!(
+            render(BinaryPolicy::Hexdump, png).unwrap(),
+            "[binary file: 18 B, hexdump]\n\
+             00000000: 8950 4e47 0d0a 1a0a 0000 000d 4948 4452  .PNG........IHDR\n\
+             00000010: 0000                                     ..\n"
+        );
+        assert_eq!(
+            render(BinaryPolicy::Base64, png).unwrap(),
+            "[binary file: 18 B, base64]\niVBORw0KGgoAAAANSUhEUgAA\n"
+        );
+    }
+}
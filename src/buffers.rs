@@ -0,0 +1,204 @@
+use serde::Deserialize;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// A 1-based position in a buffer.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    #[serde(default)]
+    pub column: Option<usize>,
+}
+
+/// An inclusive, 1-based range of lines shown in the editor.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// One open file, as exported by an editor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Buffer {
+    pub path: String,
+    pub cursor: Option<Position>,
+    pub visible: Option<LineRange>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Path(String),
+    Buffer {
+        path: String,
+        #[serde(default)]
+        cursor: Option<Position>,
+        #[serde(default)]
+        visible: Option<LineRange>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Document {
+    List(Vec<Entry>),
+    Object { buffers: Vec<Entry> },
+}
+
+/// Read a buffer list from `path` (`-` for stdin): either an array or an
+/// object with a `buffers` array, whose entries are a path or
+/// `{"path", "cursor": {"line", "column"}, "visible": {"start", "end"}}`.
+pub fn load(path: &Path) -> Result<Vec<Buffer>, String> {
+    let text = if path == Path::new("-") {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| format!("--buffers-from: failed to read stdin: {e}"))?;
+        text
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?
+    };
+    parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn parse(text: &str) -> Result<Vec<Buffer>, String> {
+    let doc: Document = serde_json::from_str(text)
+        .map_err(|_| "expected a list of paths or {\"path\", \"cursor\", \"visible\"} objects")?;
+    let entries = match doc {
+        Document::List(entries) | Document::Object { buffers: entries } => entries,
+    };
+    Ok(entries
+        .into_iter()
+        .map(|entry| match entry {
+            Entry::Path(path) => Buffer {
+                path,
+                cursor: None,
+                visible: None,
+            },
+            Entry::Buffer {
+                path,
+                cursor,
+                visible,
+            } => Buffer {
+                path,
+                cursor,
+                visible,
+            },
+        })
+        .collect())
+}
+
+/// `path` relative to `root`, or `None` when it lies outside it. Editors
+/// export absolute paths; relative ones are taken as already root-relative.
+pub fn relative_path(root: &Path, path: &str) -> Option<String> {
+    let path = Path::new(path);
+    if path.is_relative() {
+        return Some(path.to_string_lossy().trim_start_matches("./").to_string());
+    }
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    path.strip_prefix(&root)
+        .ok()
+        .map(|rel| rel.to_string_lossy().to_string())
+}
+
+/// The part of `content` around what's on screen: the visible range, or the
+/// cursor's line, widened by `context` lines either side and headed by a
+/// note of where it came from. Buffers without a position, and excerpts that
+/// would cover the whole file anyway, are returned unchanged.
+pub fn excerpt(content: &str, buffer: &Buffer, context: usize) -> String {
+    let (start, end) = match (&buffer.visible, &buffer.cursor) {
+        (Some(visible), _) => (
+            visible.start.min(visible.end),
+            visible.end.max(visible.start),
+        ),
+        (None, Some(cursor)) => (cursor.line, cursor.line),
+        (None, None) => return content.to_string(),
+    };
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let first = start.saturating_sub(context).max(1);
+    let last = end.saturating_add(context).min(lines.len());
+    if first == 1 && last == lines.len() || first > last {
+        return content.to_string();
+    }
+
+    let mut out = format!("[lines {first}-{last} of {}", lines.len());
+    if let Some(cursor) = &buffer.cursor {
+        out.push_str(&format!("; cursor at {}", cursor.line));
+        if let Some(column) = cursor.column {
+            out.push_str(&format!(":{column}"));
+        }
+    }
+    out.push_str("]\n");
+    for line in &lines[first - 1..last] {
+        out.push_str(line);
+    }
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_forms() {
+        let list = parse(r#"["src/a.rs", {"path": "src/b.rs", "cursor": {"line": 3}}]"#).unwrap();
+        assert_eq!(list[0].path, "src/a.rs");
+        assert_eq!(list[0].cursor, None);
+        assert_eq!(
+            list[1].cursor,
+            Some(Position {
+                line: 3,
+                column: None
+            })
+        );
+        let object =
+            parse(r#"{"buffers": [{"path": "x.py", "visible": {"start": 2, "end": 9}}]}"#).unwrap();
+        assert_eq!(object[0].visible, Some(LineRange { start: 2, end: 9 }));
+        assert!(parse(r#"{"files": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_excerpt() {
+        let content: String = (1..=20).map(|n| format!("line {n}\n")).collect();
+        let at = |line: Option<usize>, visible: Option<(usize, usize)>| Buffer {
+            path: "f.txt".to_string(),
+            cursor: line.map(|line| Position {
+                line,
+                column: Some(4),
+            }),
+            visible: visible.map(|(start, end)| LineRange { start, end }),
+        };
+
+        let around_cursor = excerpt(&content, &at(Some(10), None), 2);
+        assert_eq!(
+            around_cursor,
+            "[lines 8-12 of 20; cursor at 10:4]\nline 8\nline 9\nline 10\nline 11\nline 12\n"
+        );
+        let visible = excerpt(&content, &at(None, Some((18, 25))), 1);
+        assert_eq!(
+            visible,
+            "[lines 17-20 of 20]\nline 17\nline 18\nline 19\nline 20\n"
+        );
+        assert_eq!(excerpt(&content, &at(None, None), 2), content);
+        assert_eq!(excerpt(&content, &at(Some(10), None), 50), content);
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let root = Path::new("/work/repo");
+        assert_eq!(
+            relative_path(root, "/work/repo/src/a.rs").as_deref(),
+            Some("src/a.rs")
+        );
+        assert_eq!(
+            relative_path(root, "./src/a.rs").as_deref(),
+            Some("src/a.rs")
+        );
+        assert_eq!(relative_path(root, "/elsewhere/b.rs"), None);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::manifest::sha256_hex;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Scratch directories left behind by a run that exited early are swept
+/// after this long.
+const STALE_SCRATCH_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Root of gather's on-disk cache: `$GATHER_CACHE_DIR`, else the platform
+/// cache directory (`$XDG_CACHE_HOME/gather`, `~/.cache/gather`, or
+/// `%LOCALAPPDATA%\gather` on Windows).
+pub fn cache_root() -> PathBuf {
+    if let Some(dir) = env::var_os("GATHER_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+    if cfg!(windows) {
+        if let Some(dir) = env::var_os("LOCALAPPDATA") {
+            return PathBuf::from(dir).join("gather");
+        }
+    }
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+        return PathBuf::from(dir).join("gather");
+    }
+    if let Some(home) = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+        return PathBuf::from(home).join(".cache").join("gather");
+    }
+    env::temp_dir().join("gather-cache")
+}
+
+/// Cache directory scoped to one repository, named after the directory plus
+/// a hash of its canonical path so same-named checkouts don't collide.
+pub fn repo_cache_dir(root: &Path) -> PathBuf {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let name = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "root".to_string());
+    let hash = sha256_hex(root.to_string_lossy().as_bytes());
+    cache_root()
+        .join("repos")
+        .join(format!("{name}-{}", &hash[..12]))
+}
+
+/// The repository-local state directory, `<root>/.gather`, for the search
+/// index and sessions.
+pub fn local_dir(root: &Path) -> PathBuf {
+    root.join(".gather")
+}
+
+/// Create `local_dir(root)` if needed, with a `.gitignore` that keeps it out
+/// of `git status` without touching the repository's own ignore rules.
+pub fn ensure_local_dir(root: &Path) -> io::Result<PathBuf> {
+    let dir = local_dir(root);
+    fs::create_dir_all(&dir)?;
+    let ignore = dir.join(".gitignore");
+    if !ignore.exists() {
+        fs::write(ignore, "*\n")?;
+    }
+    Ok(dir)
+}
+
+/// A temporary directory under the cache, removed on drop.
+pub struct ScratchDir {
+    /// `<parent>/<unique>/<name>`, so the directory keeps a meaningful name.
+    pub path: PathBuf,
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Some(unique) = self.path.parent() {
+            let _ = fs::remove_dir_all(unique);
+        }
+    }
+}
+
+/// Create an empty scratch directory called `name` under `parent`, first
+/// sweeping stale ones from earlier runs.
+pub fn scratch_dir(parent: &Path, name: &str) -> io::Result<ScratchDir> {
+    if let Ok(entries) = fs::read_dir(parent) {
+        for entry in entries.flatten() {
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.elapsed().ok());
+            if age.is_some_and(|a| a > STALE_SCRATCH_AGE) {
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let path = parent
+        .join(format!("{}-{nanos}", std::process::id()))
+        .join(name);
+    fs::create_dir_all(&path)?;
+    Ok(ScratchDir { path })
+}
+
+/// Total size in bytes and number of files under `path` (0 if missing).
+pub fn dir_usage(path: &Path) -> (u64, usize) {
+    let Ok(entries) = fs::read_dir(path) else {
+        return (0, 0);
+    };
+    let mut bytes = 0;
+    let mut files = 0;
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            let (b, f) = dir_usage(&entry.path());
+            bytes += b;
+            files += f;
+        } else {
+            bytes += meta.len();
+            files += 1;
+        }
+    }
+    (bytes, files)
+}
+
+/// Per-entry usage of the cache: every top-level directory, with `repos/`
+/// broken out by repository. Sorted by path.
+pub fn cache_entries(root: &Path) -> Vec<(String, u64, usize)> {
+    let mut entries = Vec::new();
+    let Ok(children) = fs::read_dir(root) else {
+        return entries;
+    };
+    for child in children.flatten() {
+        let name = child.file_name().to_string_lossy().to_string();
+        let path = child.path();
+        if name == "repos" && path.is_dir() {
+            if let Ok(repos) = fs::read_dir(&path) {
+                for repo in repos.flatten() {
+                    let (bytes, files) = dir_usage(&repo.path());
+                    let label = format!("repos/{}", repo.file_name().to_string_lossy());
+                    entries.push((label, bytes, files));
+                }
+            }
+        } else if path.is_dir() {
+            let (bytes, files) = dir_usage(&path);
+            entries.push((name, bytes, files));
+        } else {
+            let bytes = child.metadata().map(|m| m.len()).unwrap_or(0);
+            entries.push((name, bytes, 1));
+        }
+    }
+    entries.sort();
+    entries
+}
+
+/// The directories gather creates under `cache_root`: per-repo caches, HTTP
+/// responses, remote clones and revision snapshots. The root itself may be a
+/// shared directory (`GATHER_CACHE_DIR=$HOME`), so only these are cleared.
+pub const CACHE_DIRS: &[&str] = &["repos", "http", "clones", "snapshots"];
+
+/// Remove the directories in [`CACHE_DIRS`] from the cache at `root`,
+/// leaving anything else there alone.
+pub fn clear_all(root: &Path) -> io::Result<()> {
+    for dir in CACHE_DIRS {
+        clear(&root.join(dir))?;
+    }
+    Ok(())
+}
+
+/// Remove a cache directory, treating an already-missing one as success.
+pub fn clear(path: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+/// Human-readable byte count, e.g. "1.2 MB".
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+    }
+
+    #[test]
+    fn test_cache_entries_and_clear() {
+        let root = crate::test_dir::make_test_dir("cache");
+        fs::create_dir_all(root.join("repos/app-123/tokens")).unwrap();
+        fs::create_dir_all(root.join("clones")).unwrap();
+        fs::write(root.join("repos/app-123/tokens/a"), "12345").unwrap();
+        fs::write(root.join("repos/app-123/b"), "678").unwrap();
+        fs::write(root.join("clones/c"), "9").unwrap();
+
+        assert_eq!(
+            cache_entries(&root),
+            vec![
+                ("clones".to_string(), 1, 1),
+                ("repos/app-123".to_string(), 8, 2),
+            ]
+        );
+        assert_eq!(dir_usage(&root), (9, 3));
+
+        clear(&root.join("repos/app-123")).unwrap();
+        assert_eq!(dir_usage(&root), (1, 1));
+        // Only gather's own directories go; the root may be shared
+        fs::write(root.join("notes.txt"), "keep").unwrap();
+        clear_all(&root).unwrap();
+        clear_all(&root).unwrap();
+        assert!(!root.join("clones").exists() && !root.join("repos").exists());
+        assert_eq!(fs::read_to_string(root.join("notes.txt")).unwrap(), "keep");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
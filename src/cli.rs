@@ -0,0 +1,2697 @@
+use crate::*;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(
+    name = "gather",
+    about = "Fast context gathering for AI coding agents",
+    long_about = "Gather files from a codebase and format them as structured context \
+                  for AI coding agents. Respects .gitignore, supports glob filtering, \
+                  and estimates token counts.",
+    args_override_self = true
+)]
+struct Cli {
+    /// List every skipped file and the reason it was skipped
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Number of worker threads (default: one per CPU)
+    #[arg(long, global = true, value_name = "N")]
+    threads: Option<usize>,
+
+    /// Run at low CPU and I/O priority to stay polite on shared machines
+    #[arg(long, global = true, visible_alias = "ionice")]
+    nice: bool,
+
+    /// How to estimate tokens: chars (length at a per-language ratio, the
+    /// default), words (word and punctuation boundaries, closer to real
+    /// tokenizers) or bpe (a real tokenizer, cl100k_base unless --tokenizer
+    /// picks another; exact but slower)
+    #[arg(long, global = true, value_name = "ESTIMATOR", default_value = "chars")]
+    estimator: estimate::Estimator,
+
+    /// Count tokens exactly with this BPE encoding: cl100k_base (GPT-4) or
+    /// o200k_base (GPT-4o and later); implies --estimator bpe
+    #[arg(long, global = true, value_name = "ENCODING")]
+    tokenizer: Option<estimate::Tokenizer>,
+
+    /// PEM bundle of CA certificates to trust for remote operations, e.g.
+    /// behind a TLS-intercepting proxy (default: $GATHER_CA_BUNDLE or
+    /// $SSL_CERT_FILE)
+    #[arg(long, global = true, value_name = "PATH")]
+    ca_bundle: Option<PathBuf>,
+
+    /// When nothing matches, write an empty document and exit 0 instead of
+    /// exiting 2
+    #[arg(long, global = true)]
+    allow_empty: bool,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+// Parsed once at startup, so the size of the `collect` variant doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Commands {
+    /// Collect file contents and output as structured context
+    Collect {
+        /// Root directory or git URL to gather from (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        clone: CloneArgs,
+
+        /// Gather the files of a git revision (tag, branch, HEAD~10, ...),
+        /// read from the object database without touching the worktree
+        #[arg(long, value_name = "REV", conflicts_with = "annotate_changes")]
+        at: Option<String>,
+
+        /// Gather the staged versions of files with staged changes, read from
+        /// the index (for pre-commit hooks)
+        #[arg(long, conflicts_with_all = ["at", "annotate_changes"])]
+        staged: bool,
+
+        /// Only gather files changed in the worktree since HEAD: modified or
+        /// added, staged or not, plus untracked files
+        #[arg(long, conflicts_with_all = ["at", "staged", "since", "buffers_from"])]
+        changed: bool,
+
+        /// Only gather files changed since REF (branch, tag or commit),
+        /// committed or not, plus untracked files
+        #[arg(long, value_name = "REF", conflicts_with_all = ["at", "staged", "buffers_from"])]
+        since: Option<String>,
+
+        /// Gather only the files listed in PATH (- reads stdin), one per line
+        /// or NUL-separated, such as the output of `fd`, `git ls-files` or
+        /// `rg -l`; the other filters still apply
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["at", "staged", "changed", "since", "buffers_from"]
+        )]
+        files_from: Option<PathBuf>,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+
+        /// Start from the selection of gather.toml bundles: NAME, or several
+        /// joined with + (e.g. base+api); flags given here add to or override
+        /// it
+        #[arg(long, value_name = "NAME[+NAME...]")]
+        profile: Option<String>,
+
+        /// Ignore the [defaults] table in gather.toml
+        #[arg(long)]
+        no_config: bool,
+
+        /// Output format: markdown (default), xml, json or summary (sizes,
+        /// top-level symbols and the first lines of each file)
+        #[arg(short = 'f', long = "format", default_value = "markdown")]
+        format: OutputFormat,
+
+        /// Show token count estimate in output
+        #[arg(long)]
+        tokens: bool,
+
+        /// Text to emit before the file contents. Supports {repo}, {branch},
+        /// {commit}, {timestamp} and {date} placeholders
+        #[arg(long)]
+        header: Option<String>,
+
+        /// Text to emit after the file contents (same placeholders as --header)
+        #[arg(long)]
+        footer: Option<String>,
+
+        /// XML root element name
+        #[arg(long, default_value = "context", value_name = "NAME", value_parser = parse_xml_name)]
+        xml_root: String,
+
+        /// XML element name for each file
+        #[arg(long, default_value = "file", value_name = "NAME", value_parser = parse_xml_name)]
+        xml_file_element: String,
+
+        /// Replace likely secrets (cloud and API keys, private key blocks,
+        /// bearer tokens, URL passwords, PASSWORD=... style assignments)
+        /// with [REDACTED:kind] placeholders
+        #[arg(long)]
+        redact: bool,
+
+        /// Wrap each file's contents in a CDATA section instead of escaping
+        /// <, > and & (XML output)
+        #[arg(long)]
+        xml_cdata: bool,
+
+        /// Add lang, bytes, tokens, lines, sha256 and mtime attributes to
+        /// each XML file element
+        #[arg(long)]
+        file_meta: bool,
+
+        /// Check XML output against gather's schema before writing it
+        #[arg(long)]
+        validate: bool,
+
+        /// Mark lines added (+) or modified (~) since REF in a gutter, keeping
+        /// whole files for context
+        #[arg(long, value_name = "REF")]
+        annotate_changes: Option<String>,
+
+        /// Embed the import graph between the gathered files as a Mermaid
+        /// block ahead of the file contents
+        #[arg(long)]
+        deps_graph: bool,
+
+        /// Start the output with the directory tree of the selected files,
+        /// as `gather tree` would print it with the same filters
+        #[arg(long)]
+        with_tree: bool,
+
+        /// Markdown fence character: backtick (default) or tilde
+        #[arg(long, default_value = "backtick", value_parser = ["backtick", "tilde"])]
+        fence_char: String,
+
+        /// Markdown fence length (at least 3)
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(3..))]
+        fence_len: u8,
+
+        /// Omit language tags after markdown opening fences
+        #[arg(long)]
+        no_lang_tags: bool,
+
+        /// Wrap each file in a collapsible <details> block (markdown only),
+        /// handy for GitHub issues and PR descriptions
+        #[arg(long)]
+        collapsible: bool,
+
+        /// Group files under one heading per top-level directory, with
+        /// per-directory token subtotals (markdown only)
+        #[arg(long)]
+        group_by_dir: bool,
+
+        /// Lines of each file to show with -f summary
+        #[arg(long, value_name = "N", default_value_t = SUMMARY_LINES)]
+        summary_lines: usize,
+
+        /// Also include the content fetched from URL (can be specified
+        /// multiple times). Responses are cached and revalidated
+        #[arg(long = "url", value_name = "URL")]
+        urls: Vec<String>,
+
+        /// Gather only the files open in an editor, from a JSON list of paths
+        /// or {"path", "cursor": {"line", "column"}, "visible": {"start",
+        /// "end"}} objects (1-based lines; - reads stdin). Files with a
+        /// position are cut down to the lines around it
+        #[arg(long, value_name = "PATH", conflicts_with = "incremental")]
+        buffers_from: Option<PathBuf>,
+
+        /// Gather the files that best match TEXT, best first: as the search
+        /// index (`gather index build`) ranks them, or without one, by BM25
+        /// over the selected files' paths and contents. Repeat for a task with
+        /// several aspects, and the results are merged with duplicates dropped
+        #[arg(long = "query", value_name = "TEXT")]
+        queries: Vec<String>,
+
+        /// Read more --query texts from a file, one per line
+        #[arg(long, value_name = "PATH")]
+        queries_from: Option<PathBuf>,
+
+        /// Put files an agent conversation mentions first, by path or by a
+        /// symbol they define, gathering them even if the filters leave them
+        /// out (plain text, JSON or JSON Lines chat logs)
+        #[arg(long, value_name = "PATH")]
+        transcript: Option<PathBuf>,
+
+        /// Files to retrieve per query
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        query_limit: usize,
+
+        /// Stop adding files once the estimated token total would pass N;
+        /// files that don't fit are reported as skipped
+        #[arg(long, value_name = "N")]
+        max_tokens: Option<usize>,
+
+        /// Which files --max-tokens keeps first: path (alphabetical), size
+        /// (smallest first) or mtime (most recently modified first). By
+        /// default files are taken in output order, which puts --query and
+        /// --transcript matches first
+        #[arg(long, value_name = "ORDER")]
+        budget_order: Option<BudgetOrder>,
+
+        /// Track what this named session has been sent (in
+        /// .gather/sessions/): files already sent and unchanged since are
+        /// listed in one line instead of repeated
+        #[arg(long, value_name = "NAME")]
+        session: Option<String>,
+
+        /// Split the output into parts of at most N estimated tokens. With
+        /// --session the first is written now and `gather next` delivers the
+        /// rest; otherwise each part gets its own file beside --output
+        /// (context-001.md, ...) plus a context-parts.json index
+        #[arg(long, value_name = "N", conflicts_with = "append")]
+        split_tokens: Option<usize>,
+
+        /// Lines to keep either side of a buffer's cursor or visible range
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 40,
+            requires = "buffers_from"
+        )]
+        buffer_context: usize,
+
+        /// Keep only some lines of the files matching PATTERN, given as
+        /// PATTERN:RANGES with 1-based inclusive ranges (src/main.rs:100-250,
+        /// src/lib.rs:1-40,90-). A -g pattern can carry ranges the same way,
+        /// which also selects the file
+        #[arg(long = "lines", value_name = "PATTERN:RANGES", value_parser = lines::parse)]
+        line_specs: Vec<lines::LineSpec>,
+
+        /// Write a JSON manifest of every file considered, whether it was
+        /// included, and why not
+        #[arg(long, value_name = "PATH")]
+        manifest: Option<PathBuf>,
+
+        /// Write a lockfile of the exact file list, sizes, hashes and gather
+        /// version (or, with --verify, check against it)
+        #[arg(long, value_name = "PATH")]
+        lock: Option<PathBuf>,
+
+        /// Fail if the collected files no longer match the --lock file
+        #[arg(long, requires = "lock")]
+        verify: bool,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Add the output to the end of the --output file instead of
+        /// replacing it
+        #[arg(long, requires = "output", conflicts_with = "incremental")]
+        append: bool,
+
+        /// Put the output on the system clipboard instead of stdout (in
+        /// addition to --output)
+        #[arg(long)]
+        copy: bool,
+
+        /// Reuse unchanged files from the existing --output and --manifest,
+        /// re-reading only files whose size or modification time changed
+        #[arg(long, requires_all = ["output", "manifest"])]
+        incremental: bool,
+    },
+
+    /// Show the signatures in the source files: functions, types, and the
+    /// headers of impls, traits, classes and modules, without bodies (Rust,
+    /// Python, Go, JavaScript and TypeScript)
+    Outline {
+        /// Root directory or git URL (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        clone: CloneArgs,
+
+        /// Use the files of a git revision instead of the worktree
+        #[arg(long, value_name = "REV")]
+        at: Option<String>,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+
+        /// Output format: markdown (default), xml, json or summary
+        #[arg(short = 'f', long = "format", default_value = "markdown")]
+        format: OutputFormat,
+
+        /// Show token count estimate in output
+        #[arg(long)]
+        tokens: bool,
+
+        /// Write output to a file instead of stdout
+        #[arg(short = 'o', long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Show a tree view of the directory structure
+    Tree {
+        /// Root directory or git URL (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        clone: CloneArgs,
+
+        /// Use the files of a git revision instead of the worktree
+        #[arg(long, value_name = "REV")]
+        at: Option<String>,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Output format: text (default), mermaid (a flowchart that renders
+        /// inline on GitHub) or csv (depth, bytes, tokens and language per file)
+        #[arg(short = 'f', long = "format", default_value = "text", value_parser = ["text", "mermaid", "csv"])]
+        format: String,
+    },
+
+    /// Estimate token count for files without printing contents
+    Tokens {
+        /// Root directory or git URL (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        clone: CloneArgs,
+
+        /// Use the files of a git revision instead of the worktree
+        #[arg(long, value_name = "REV")]
+        at: Option<String>,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+
+        /// Approximate totals from file sizes alone, without reading contents
+        #[arg(long)]
+        estimate_only: bool,
+
+        /// Output format: table (default) or csv (depth, bytes, tokens and
+        /// language per file)
+        #[arg(short = 'f', long = "format", default_value = "table", value_parser = ["table", "csv"])]
+        format: String,
+
+        /// Order rows by path, or by tokens or bytes with the largest first
+        /// (default: path, or tokens with --by-dir)
+        #[arg(long, value_parser = ["path", "tokens", "bytes"])]
+        sort: Option<String>,
+
+        /// Sum tokens and bytes per directory instead of listing each file
+        #[arg(long)]
+        by_dir: bool,
+
+        /// Directory levels below the root that --by-dir sums at
+        #[arg(long, value_name = "N", default_value_t = 1, requires = "by_dir")]
+        depth: usize,
+
+        /// List only the first N files; the rest are summed in one line
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+
+        /// Also report the exact total from the provider's token-counting API
+        /// (needs ANTHROPIC_API_KEY or OPENAI_API_KEY; falls back to the
+        /// local estimate)
+        #[arg(
+            long,
+            value_name = "PROVIDER",
+            num_args = 0..=1,
+            default_missing_value = "anthropic",
+            value_parser = ["anthropic", "openai"],
+            conflicts_with = "estimate_only"
+        )]
+        count_remote: Option<String>,
+
+        /// Model to count tokens for with --count-remote
+        #[arg(long, value_name = "MODEL", requires = "count_remote")]
+        remote_model: Option<String>,
+    },
+
+    /// Gather per-file patches between two sides: worktree, index, HEAD or
+    /// any revision
+    Diff {
+        /// Directory to limit the comparison to (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Old side: worktree, index, head or a revision
+        #[arg(long, default_value = "head", conflicts_with = "include_stash")]
+        from: diff::Side,
+
+        /// New side: worktree, index, head or a revision
+        #[arg(long, default_value = "worktree", conflicts_with = "include_stash")]
+        to: diff::Side,
+
+        /// What stash entry N changed relative to the commit it was made on,
+        /// without applying it
+        #[arg(long, value_name = "N")]
+        include_stash: Option<usize>,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Output format: markdown (default), xml, json or summary
+        #[arg(short = 'f', long = "format", default_value = "markdown")]
+        format: OutputFormat,
+
+        /// Show token count estimate in output
+        #[arg(long)]
+        tokens: bool,
+    },
+
+    /// Gather the same selection from two directory trees (worktrees,
+    /// upstream and fork) and bundle both versions of the files that differ
+    Compare {
+        /// The first tree
+        left: PathBuf,
+
+        /// The second tree
+        right: PathBuf,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+
+        /// Also include files that are identical in both trees, once each
+        #[arg(long)]
+        all: bool,
+
+        /// Output format: markdown (default), xml, json or summary
+        #[arg(short = 'f', long = "format", default_value = "markdown")]
+        format: OutputFormat,
+
+        /// Show token count estimate in output
+        #[arg(long)]
+        tokens: bool,
+    },
+
+    /// Bundle conflicted files with their base, ours and theirs versions,
+    /// for resolving a merge
+    Conflicts {
+        /// Root directory (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Output format: markdown (default), xml or json
+        #[arg(short = 'f', long = "format", default_value = "markdown")]
+        format: OutputFormat,
+    },
+
+    /// Print the import graph between files in the repository
+    Deps {
+        /// Root directory (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Graph format: mermaid (default) or dot
+        #[arg(short = 'f', long = "format", default_value = "mermaid", value_parser = ["mermaid", "dot"])]
+        format: String,
+    },
+
+    /// Export the gathered files, their metrics and an FTS5 full-text index
+    /// to a SQLite database for other tools to query, or `index build` a
+    /// persistent search index for `gather grep`
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Index {
+        #[command(subcommand)]
+        action: Option<IndexAction>,
+
+        /// Root directory or git URL (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        clone: CloneArgs,
+
+        /// Use the files of a git revision instead of the worktree
+        #[arg(long, value_name = "REV")]
+        at: Option<String>,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+
+        /// Database to write; an existing one is replaced
+        #[arg(long, value_name = "PATH", required = true)]
+        sqlite: Option<PathBuf>,
+
+        /// Also store file contents in the database (they're full-text
+        /// indexed either way)
+        #[arg(long)]
+        content: bool,
+    },
+
+    /// Find files containing a fixed string, or with --query rank them
+    /// against free text, optionally from the `index build` search index
+    Grep {
+        /// Text to look for (a fixed string, not a regex), or the query with
+        /// --query
+        pattern: String,
+
+        /// Root directory (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Read from the index under .gather/ instead of walking the tree
+        #[arg(long)]
+        indexed: bool,
+
+        /// Rank files by relevance to PATTERN as free text (`+must`, `-not`,
+        /// `"a phrase"`); uses the index
+        #[arg(long)]
+        query: bool,
+
+        /// Most files to list with --query
+        #[arg(long, value_name = "N", default_value_t = 20, requires = "query")]
+        limit: usize,
+
+        /// Match case-insensitively
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+
+        /// Print only the paths of matching files
+        #[arg(short = 'l', long, conflicts_with = "query")]
+        files_with_matches: bool,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+
+    /// Compare two manifests written with `collect --manifest`
+    DiffManifest {
+        /// The earlier manifest
+        old: PathBuf,
+
+        /// The later manifest
+        new: PathBuf,
+    },
+
+    /// Time each stage of a gather (walk, filter, read, tokenize, format)
+    Bench {
+        /// Root directory (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+
+        /// Number of runs; the median of each stage is reported
+        #[arg(long, default_value_t = 3)]
+        runs: usize,
+    },
+
+    /// Gather the source of a dependency at the version pinned by the lockfile
+    Dep {
+        /// Crate, npm package or Go module, optionally as NAME@VERSION
+        name: String,
+
+        /// Project whose Cargo.lock, node_modules or go.mod pins the version
+        #[arg(long, default_value = ".", value_name = "PATH")]
+        project: PathBuf,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+
+        /// Output format: markdown (default), xml, json or summary
+        #[arg(short = 'f', long = "format", default_value = "markdown")]
+        format: OutputFormat,
+
+        /// Show token count estimate in output
+        #[arg(long)]
+        tokens: bool,
+
+        /// Keep only declarations and their doc comments
+        #[arg(long)]
+        outline: bool,
+    },
+
+    /// Print the schema for a document gather writes: manifest, lock and
+    /// json output (JSON Schema) or xml output (XSD)
+    Schema {
+        #[arg(default_value = "manifest", value_parser = ["manifest", "lock", "xml", "json"])]
+        document: String,
+    },
+
+    /// Print tool definitions for gather's collect, tree and tokens
+    /// operations, for registering gather as a tool in an agent
+    ToolSchema {
+        /// Provider whose tool format to use
+        #[arg(value_parser = ["openai", "anthropic"])]
+        provider: String,
+    },
+
+    /// Serve gather's collect, tree and tokens tools to agents on demand: as
+    /// a Model Context Protocol server over stdio, or over HTTP
+    Serve {
+        /// Directory to serve (defaults to current directory); tool calls can
+        /// name subdirectories but not leave it
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Speak the Model Context Protocol over stdin/stdout, for MCP
+        /// clients such as Claude Desktop
+        #[arg(long, required_unless_present = "http")]
+        mcp: bool,
+
+        /// Listen for HTTP requests on ADDR (e.g. 127.0.0.1:8377): GET
+        /// /collect, /tree and /tokens, with the tool arguments as query
+        /// parameters
+        #[arg(long, value_name = "ADDR", conflicts_with = "mcp")]
+        http: Option<String>,
+    },
+
+    /// Build a selection interactively: add and remove globs, preview the
+    /// tree and token total, then emit or copy the bundle
+    Repl {
+        /// Root directory or git URL (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        clone: CloneArgs,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+
+        /// Output format: markdown (default), xml, json or summary
+        #[arg(short = 'f', long = "format", default_value = "markdown")]
+        format: OutputFormat,
+    },
+
+    /// Re-run a collect whenever the files it selects change: rewrite its
+    /// --output, or print each fresh bundle to stdout after a separator line
+    Watch {
+        /// Milliseconds without further changes to wait before regenerating
+        #[arg(long, default_value_t = 300, value_name = "MS")]
+        debounce: u64,
+
+        /// Line printed between bundles when writing to stdout
+        #[arg(long, default_value = "<!-- gather: refreshed -->")]
+        separator: String,
+
+        /// Flags as they'd follow `gather collect` (e.g. . -g "src/**" -o
+        /// context.md); watch's own flags go before them
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// Print the next undelivered part of a session's split gather
+    /// (`collect --session NAME --split-tokens N`) and mark it sent
+    Next {
+        /// Session whose parts to deliver
+        #[arg(long, value_name = "NAME")]
+        session: String,
+
+        /// Repository the session belongs to
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+
+        /// Write the part to a file instead of stdout
+        #[arg(short = 'o', long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Put the part on the system clipboard instead of stdout
+        #[arg(long)]
+        copy: bool,
+    },
+
+    /// Gather a named selection saved in gather.toml, or save one
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Bundle {
+        #[command(subcommand)]
+        action: Option<BundleAction>,
+
+        /// Bundle to gather
+        #[arg(required = true)]
+        name: Option<String>,
+
+        /// Repository whose gather.toml defines the bundle, and the root to
+        /// gather from
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+
+        /// More `gather collect` flags (e.g. -o context.md), applied after
+        /// the bundle's own
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// Start a gather.toml from another tool's config: a repomix
+    /// repomix.config.json or a code2prompt .c2pconfig
+    Init {
+        /// Config file to translate
+        #[arg(long, value_name = "PATH")]
+        from: PathBuf,
+
+        /// Bundle to save the translated selection as (default: the tool's
+        /// name)
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+
+        /// Repository whose gather.toml to write (created if missing)
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+    },
+
+    /// Inspect gather's layered configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Inspect or reset the per-session state kept by `collect --session`
+    Session {
+        #[command(subcommand)]
+        action: SessionAction,
+    },
+
+    /// Inspect or clean gather's on-disk cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionAction {
+    /// List sessions with their files sent, gathers and tokens used
+    List {
+        /// Repository whose sessions to list
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+    },
+
+    /// Show each gather of a session and the files it has been sent
+    Show {
+        name: String,
+
+        /// Repository the session belongs to
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+    },
+
+    /// Forget what a session has been sent, so the next gather sends
+    /// everything again
+    Reset {
+        name: String,
+
+        /// Repository the session belongs to
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum BundleAction {
+    /// Save selection flags as `[bundles.NAME]` in gather.toml, replacing
+    /// any bundle of that name
+    Save {
+        /// Name to save the bundle under
+        name: String,
+
+        /// Repository whose gather.toml to write (created if missing)
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Maximum file size in bytes to include
+        #[arg(long)]
+        max_size: Option<u64>,
+
+        /// Order files by relevance to TEXT (can be specified multiple times)
+        #[arg(long = "query", value_name = "TEXT")]
+        queries: Vec<String>,
+
+        /// Token budget for the gathered files
+        #[arg(long, value_name = "N")]
+        max_tokens: Option<usize>,
+
+        /// Which files the token budget keeps first: path, size or mtime
+        #[arg(long, value_name = "ORDER", value_parser = ["path", "size", "mtime"])]
+        budget_order: Option<String>,
+
+        /// Output format: markdown, xml or json
+        #[arg(short = 'f', long = "format", value_parser = ["markdown", "xml", "json"])]
+        format: Option<String>,
+
+        /// Replace likely secrets with placeholders
+        #[arg(long)]
+        redact: bool,
+
+        /// Build on another bundle's selection
+        #[arg(long, value_name = "NAME")]
+        extends: Option<String>,
+
+        /// What the bundle is for, shown by `gather bundle list`
+        #[arg(short = 'd', long, value_name = "TEXT")]
+        description: Option<String>,
+
+        /// Expected size noted for `gather bundle list` (default: estimated
+        /// now from the bundle's files, unless it uses --query)
+        #[arg(long, value_name = "N")]
+        expected_tokens: Option<usize>,
+    },
+
+    /// List the bundles in gather.toml with their descriptions, sizes and
+    /// flags
+    List {
+        /// Repository whose gather.toml to read
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print every `gather collect` setting the given flags resolve to, and
+    /// where each value comes from (default, env, gather.toml [defaults],
+    /// --profile or command line)
+    Show {
+        /// Flags as they'd follow `gather collect` (e.g. . --profile api -g
+        /// "src/**")
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// Validate gather.toml, reporting each problem with its line and
+    /// column; exits non-zero if there are any
+    Check {
+        /// Repository whose gather.toml to check
+        #[arg(long, value_name = "PATH", default_value = ".")]
+        repo: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Show cache size, broken down by entry
+    Status {
+        /// Only report the cache for this repository
+        #[arg(long, value_name = "PATH")]
+        repo: Option<PathBuf>,
+    },
+
+    /// Delete cached data: the directories gather creates in the cache
+    /// directory, never the directory itself
+    Clear {
+        /// Only clear the cache for this repository
+        #[arg(long, value_name = "PATH")]
+        repo: Option<PathBuf>,
+    },
+
+    /// Print the cache directory
+    Path {
+        /// Print the directory for this repository
+        #[arg(long, value_name = "PATH")]
+        repo: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IndexAction {
+    /// Build (or rebuild) the search index under .gather/ used by
+    /// `gather grep --indexed` and `--query`
+    Build {
+        /// Root directory (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        #[command(flatten)]
+        opts: CollectOptions,
+    },
+}
+
+fn run_session(action: SessionAction) {
+    match action {
+        SessionAction::List { repo } => {
+            let sessions = session::list(&repo);
+            if sessions.is_empty() {
+                println!("No sessions in {}", session::sessions_dir(&repo).display());
+                return;
+            }
+            for (name, state) in &sessions {
+                println!(
+                    "{name:<24} {:>5} files  {:>4} gathers  {:>8} tokens  last {}",
+                    state.files.len(),
+                    state.runs.len(),
+                    state.tokens_sent(),
+                    state.last_used().unwrap_or("never")
+                );
+            }
+        }
+        SessionAction::Show { name, repo } => {
+            let state = match session::Session::load(&repo, &name) {
+                Ok(state) if state.runs.is_empty() => {
+                    eprintln!("No session named {name}");
+                    std::process::exit(status::ERROR);
+                }
+                Ok(state) => state,
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                }
+            };
+            println!("Session {name}: {} tokens sent", state.tokens_sent());
+            println!();
+            for run in &state.runs {
+                println!(
+                    "  {}  {:>4} sent  {:>4} unchanged  {:>8} tokens",
+                    run.at, run.sent, run.unchanged, run.tokens
+                );
+            }
+            if let Some(part) = state.queue.first() {
+                println!(
+                    "  parts {}-{} of {} waiting for `gather next`",
+                    part.number, part.total, part.total
+                );
+            }
+            println!();
+            println!("Files sent ({}):", state.files.len());
+            for path in state.files.keys() {
+                println!("  {path}");
+            }
+        }
+        SessionAction::Reset { name, repo } => match session::reset(&repo, &name) {
+            Ok(true) => println!("Reset session {name}"),
+            Ok(false) => println!("No session named {name}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(status::ERROR);
+            }
+        },
+    }
+}
+
+/// Print a command-line error and exit with `status::ERROR` (clap would use
+/// 2, which means "no matches" here). Help and version output exit 0.
+fn exit_parse_error(e: clap::Error) -> ! {
+    if !e.use_stderr() {
+        e.exit();
+    }
+    let _ = e.print();
+    std::process::exit(status::ERROR)
+}
+
+/// Deliver a rendered bundle: to `output` if given (replacing it, or with
+/// `append` adding to it), otherwise to stdout, and with `copy` to the
+/// clipboard instead of stdout. When the clipboard can't be set the bundle goes
+/// to stdout after all, with a warning.
+fn emit(rendered: &str, output: Option<&Path>, append: bool, copy: bool) {
+    if let Some(path) = output {
+        if let Err(e) = output::write(path, rendered, append) {
+            eprintln!("{e}");
+            std::process::exit(status::ERROR);
+        }
+    }
+    if copy {
+        match clipboard::copy(rendered) {
+            Ok(()) => eprintln!("Copied {} bytes to the clipboard", rendered.len()),
+            Err(e) => {
+                status::warn(format!("--copy: {e}"));
+                if output.is_none() {
+                    print!("{rendered}");
+                }
+            }
+        }
+    } else if output.is_none() {
+        print!("{rendered}");
+    }
+}
+
+/// Report that the selection came up empty and exit with
+/// `status::NO_MATCHES`, unless `--allow-empty` asked for an empty document.
+fn no_matches(message: &str, allow_empty: bool) {
+    if !allow_empty {
+        eprintln!("{message}");
+        std::process::exit(status::NO_MATCHES);
+    }
+}
+
+/// The `gather collect` command `args` stand for with `config_root`'s
+/// gather.toml applied: the flags of its `[defaults]` (unless `args` include
+/// `--no-config`), then those of the bundles `spec` names (see
+/// `config::Config::resolve`), then `args`.
+fn collect_command(spec: Option<&str>, config_root: &Path, args: Vec<String>) -> Commands {
+    let fail = |e: String| -> ! {
+        let file =
+            config::find(config_root).unwrap_or_else(|| config_root.join(config::FILE_NAMES[0]));
+        eprintln!("{}: {e}", file.display());
+        std::process::exit(status::ERROR);
+    };
+    let config = config::load(config_root).unwrap_or_else(|e| fail(e));
+    let mut argv = vec!["gather".to_string(), "collect".to_string()];
+    if !args.iter().any(|arg| arg == "--no-config") {
+        argv.extend(config.defaults.to_args());
+    }
+    if let Some(spec) = spec {
+        let bundle = config.resolve(spec).unwrap_or_else(|e| fail(e));
+        argv.extend(bundle.to_args());
+    }
+    argv.extend(args);
+    match Cli::try_parse_from(argv) {
+        Ok(cli) => cli.command,
+        Err(e) => exit_parse_error(e),
+    }
+}
+
+/// `collect [--profile SPEC] ...` as the plain collect command it stands
+/// for: gather.toml's defaults, the profile's flags, then every flag given on
+/// the command line.
+fn expand_profile(spec: Option<&str>, path: &Path) -> Commands {
+    let argv: Vec<String> = std::env::args().collect();
+    let start = argv
+        .iter()
+        .position(|arg| arg == "collect")
+        .map_or(argv.len(), |i| i + 1);
+    let mut args = Vec::new();
+    let mut rest = argv[start..].iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--profile" {
+            rest.next();
+        } else if !arg.starts_with("--profile=") {
+            args.push(arg.clone());
+        }
+    }
+    let config_root = if path.is_dir() { path } else { Path::new(".") };
+    collect_command(spec, config_root, args)
+}
+
+fn run_bundle(action: BundleAction) {
+    match action {
+        BundleAction::Save {
+            name,
+            repo,
+            filter,
+            max_size,
+            queries,
+            max_tokens,
+            budget_order,
+            format,
+            redact,
+            extends,
+            description,
+            expected_tokens,
+        } => {
+            validate_filter(&repo, &filter);
+            let mut bundle = config::Bundle {
+                description,
+                expected_tokens,
+                extends,
+                globs: filter.globs,
+                excludes: filter.excludes,
+                prunes: filter.prunes,
+                max_depth: filter.max_depth,
+                no_tests: filter.no_tests,
+                langs: filter
+                    .langs
+                    .iter()
+                    .map(|lang| lang.name.to_string())
+                    .collect(),
+                submodules: filter.submodules,
+                follow_symlinks: filter.follow_symlinks,
+                hidden: filter.hidden,
+                no_ignore: filter.no_ignore,
+                no_ignore_vcs: filter.no_ignore_vcs,
+                natural_sort: filter.natural_sort,
+                package: filter.package,
+                include_packages: filter.include_package,
+                max_size,
+                queries,
+                max_tokens,
+                budget_order,
+                format,
+                redact,
+            };
+            let mut selection = match &bundle.extends {
+                Some(parent) => config::load(&repo)
+                    .and_then(|config| config.resolve(parent))
+                    .unwrap_or_else(|e| {
+                        eprintln!("--extends: {e}");
+                        std::process::exit(status::ERROR);
+                    }),
+                None => config::Bundle::default(),
+            };
+            selection.merge(&bundle);
+            if bundle.expected_tokens.is_none() && selection.queries.is_empty() {
+                let opts = CollectOptions {
+                    filter: FilterArgs {
+                        globs: selection.globs,
+                        excludes: selection.excludes,
+                        prunes: selection.prunes,
+                        max_depth: selection.max_depth,
+                        no_tests: selection.no_tests,
+                        // `gather config check` reports unknown languages
+                        langs: selection
+                            .langs
+                            .iter()
+                            .filter_map(|name| lang::parse(name).ok())
+                            .collect(),
+                        submodules: selection.submodules,
+                        follow_symlinks: selection.follow_symlinks,
+                        hidden: selection.hidden,
+                        no_ignore: selection.no_ignore,
+                        no_ignore_vcs: selection.no_ignore_vcs,
+                        natural_sort: selection.natural_sort,
+                        package: selection.package,
+                        include_package: selection.include_packages,
+                        paths: None,
+                    },
+                    max_size: selection.max_size.unwrap_or(DEFAULT_MAX_SIZE),
+                    ..Default::default()
+                };
+                let files = collect_files(&repo, &opts).files;
+                let total: usize = count_tokens(&files).iter().sum();
+                bundle.expected_tokens = Some(
+                    selection
+                        .max_tokens
+                        .map_or(total, |budget| total.min(budget)),
+                );
+            }
+            match config::save_bundle(&repo, &name, &bundle) {
+                Ok((path, replaced)) => println!(
+                    "{} bundle {name} in {}",
+                    if replaced { "Updated" } else { "Saved" },
+                    path.display()
+                ),
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                }
+            }
+        }
+        BundleAction::List { repo } => {
+            let config = config::load(&repo).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(status::ERROR);
+            });
+            if config.bundles.is_empty() {
+                println!(
+                    "No bundles in {}; save one with `gather bundle save NAME`",
+                    repo.join(config::FILE_NAMES[0]).display()
+                );
+                return;
+            }
+            let width = config.bundles.keys().map(String::len).max().unwrap_or(0);
+            for (name, bundle) in &config.bundles {
+                let tokens = bundle
+                    .expected_tokens
+                    .map_or_else(|| "?".to_string(), |n| format!("~{n}"));
+                let line = format!(
+                    "{name:<width$}  {tokens:>8} tokens  {}",
+                    bundle.description.as_deref().unwrap_or("")
+                );
+                println!("{}", line.trim_end());
+                let mut args = bundle.to_args();
+                if let Some(parent) = &bundle.extends {
+                    args.insert(0, format!("(extends {parent})"));
+                }
+                if !args.is_empty() {
+                    println!("{:width$}  {}", "", args.join(" "));
+                }
+            }
+        }
+    }
+}
+
+/// Every `gather collect` setting that `args` (flags as typed after
+/// `gather collect`) resolve to, layered defaults <- env <- gather.toml's
+/// [defaults] <- --profile from gather.toml <- command line, with the layer
+/// each value came from.
+fn effective_settings(args: &[String]) -> Result<Vec<config::Setting>, String> {
+    let parse = |args: &[String]| -> Result<ArgMatches, String> {
+        let argv = ["gather", "collect"]
+            .into_iter()
+            .map(String::from)
+            .chain(args.iter().cloned());
+        let matches = Cli::command()
+            .try_get_matches_from(argv)
+            .map_err(|e| e.to_string())?;
+        Ok(matches
+            .subcommand_matches("collect")
+            .expect("parsed as collect")
+            .clone())
+    };
+    let given = parse(args)?;
+    let path = given
+        .get_one::<PathBuf>("path")
+        .expect("path has a default");
+    let root = if path.is_dir() {
+        path.as_path()
+    } else {
+        Path::new(".")
+    };
+    let file = config::find(root).unwrap_or_else(|| root.join(config::FILE_NAMES[0]));
+    let config = config::load(root).map_err(|e| format!("{}: {e}", file.display()))?;
+
+    // Each gather.toml layer as a label and the flags it contributes
+    let mut layers = Vec::new();
+    if !given.get_flag("no_config") && config.defaults != config::Bundle::default() {
+        layers.push((
+            format!("[defaults] in {}", file.display()),
+            config.defaults.to_args(),
+        ));
+    }
+    if let Some(spec) = given.get_one::<String>("profile") {
+        let bundle = config
+            .resolve(spec)
+            .map_err(|e| format!("{}: {e}", file.display()))?;
+        layers.push((
+            format!("profile {spec} in {}", file.display()),
+            bundle.to_args(),
+        ));
+    }
+    let from_layers = layers
+        .iter()
+        .map(|(label, layer_args)| Ok((label, parse(layer_args)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut all_args: Vec<String> = layers.iter().flat_map(|(_, a)| a.clone()).collect();
+    all_args.extend_from_slice(args);
+    let effective = parse(&all_args)?;
+
+    let mut command = Cli::command();
+    command.build();
+    let collect = command
+        .find_subcommand("collect")
+        .expect("collect is a subcommand");
+    let mut settings = Vec::new();
+    for arg in collect.get_arguments() {
+        let id = arg.get_id().as_str();
+        let name = arg.get_long().unwrap_or(id).to_string();
+        let on_command_line = given.value_source(id) == Some(ValueSource::CommandLine);
+        let set_by: Vec<&str> = from_layers
+            .iter()
+            .filter(|(_, matches)| matches.value_source(id) == Some(ValueSource::CommandLine))
+            .map(|(label, _)| label.as_str())
+            .collect();
+        let env = (id == "ca_bundle" && !on_command_line)
+            .then(|| {
+                ["GATHER_CA_BUNDLE", "SSL_CERT_FILE"]
+                    .into_iter()
+                    .find_map(|var| Some((var, std::env::var(var).ok().filter(|v| !v.is_empty())?)))
+            })
+            .flatten();
+        let Some(values) = effective.get_raw(id) else {
+            if let Some((var, value)) = env {
+                settings.push(config::Setting {
+                    name,
+                    value,
+                    source: format!("env {var}"),
+                });
+            }
+            continue;
+        };
+        let value = values
+            .map(|v| v.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        // Lists add up across layers; single values come from the last one
+        let appends = matches!(arg.get_action(), ArgAction::Append);
+        let source = match (set_by.last(), on_command_line) {
+            (Some(_), true) if appends => format!("{} + command line", set_by.join(" + ")),
+            (Some(layer), true) => format!("command line (overrides {layer})"),
+            (Some(_), false) if appends => set_by.join(" + "),
+            (Some(layer), false) => layer.to_string(),
+            (None, true) => "command line".to_string(),
+            (None, false) => "default".to_string(),
+        };
+        settings.push(config::Setting {
+            name,
+            value,
+            source,
+        });
+    }
+    let cache_source = if std::env::var_os("GATHER_CACHE_DIR").is_some() {
+        "env GATHER_CACHE_DIR"
+    } else {
+        "default"
+    };
+    settings.push(config::Setting {
+        name: "cache-dir".to_string(),
+        value: cache::cache_root().display().to_string(),
+        source: cache_source.to_string(),
+    });
+    Ok(settings)
+}
+
+/// `gather watch`: parse `args` as a collect to learn what to watch, then
+/// run `gather collect` with them (and the global flags this run was given)
+/// as a child process each time the selection changes, so a failed or empty
+/// gather doesn't end the watch.
+fn run_watch(debounce: u64, separator: &str, args: Vec<String>) {
+    let argv = ["gather", "collect"]
+        .into_iter()
+        .map(String::from)
+        .chain(args.iter().cloned());
+    let cli = Cli::try_parse_from(argv).unwrap_or_else(|e| exit_parse_error(e));
+    let command = match cli.command {
+        Commands::Collect {
+            profile,
+            path,
+            no_config,
+            ..
+        } if profile.is_some() || (!no_config && config::find(&path).is_some()) => {
+            let config_root = if path.is_dir() { &path } else { Path::new(".") };
+            collect_command(profile.as_deref(), config_root, args.clone())
+        }
+        command => command,
+    };
+    let Commands::Collect {
+        path,
+        opts,
+        output,
+        manifest,
+        lock,
+        split_tokens,
+        ..
+    } = command
+    else {
+        unreachable!("parsed as collect");
+    };
+    if !path.is_dir() {
+        eprintln!(
+            "gather watch needs a local directory, not {}",
+            path.display()
+        );
+        std::process::exit(status::ERROR);
+    }
+    let mut opts = opts;
+    if let Err(e) = lines::take_from_globs(&mut opts.filter.globs) {
+        eprintln!("{e}");
+        std::process::exit(status::ERROR);
+    }
+    validate_filter(&path, &opts.filter);
+
+    let globals: Vec<String> = std::env::args()
+        .skip(1)
+        .take_while(|arg| arg != "watch")
+        .collect();
+    let exe = std::env::current_exe().unwrap_or_else(|e| {
+        eprintln!("Failed to locate the gather executable: {e}");
+        std::process::exit(status::ERROR);
+    });
+    let written: Vec<PathBuf> = [&output, &manifest, &lock]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    eprintln!("Watching {} (Ctrl-C to stop)", path.display());
+    let mut first = true;
+    let result = watch::watch(
+        &path,
+        &opts.filter,
+        &written,
+        std::time::Duration::from_millis(debounce),
+        || {
+            if !first && output.is_none() {
+                println!("{separator}");
+            }
+            first = false;
+            let run = std::process::Command::new(&exe)
+                .args(&globals)
+                .arg("collect")
+                .args(&args)
+                .status();
+            match (run, &output) {
+                (Ok(code), Some(output)) if code.success() && split_tokens.is_none() => {
+                    eprintln!("Wrote {}", output.display());
+                }
+                (Ok(_), _) => {}
+                (Err(e), _) => status::warn(format!("failed to run gather collect: {e}")),
+            }
+        },
+    );
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(status::ERROR);
+    }
+}
+
+fn run_config(action: ConfigAction) {
+    match action {
+        ConfigAction::Show { args } => match effective_settings(&args) {
+            Ok(settings) => print!("{}", config::render_settings(&settings)),
+            Err(e) => {
+                eprintln!("{}", e.trim_end());
+                std::process::exit(status::ERROR);
+            }
+        },
+        ConfigAction::Check { repo } => {
+            let Some(path) = config::find(&repo) else {
+                eprintln!("No gather.toml or .gather.toml in {}", repo.display());
+                std::process::exit(status::ERROR);
+            };
+            let text = fs::read_to_string(&path).unwrap_or_else(|e| {
+                eprintln!("{}: {e}", path.display());
+                std::process::exit(status::ERROR);
+            });
+            let problems = config::check(&text);
+            if problems.is_empty() {
+                println!("{}: ok", path.display());
+                return;
+            }
+            for problem in &problems {
+                eprintln!(
+                    "{}:{}:{}: {}",
+                    path.display(),
+                    problem.line,
+                    problem.column,
+                    problem.message
+                );
+            }
+            eprintln!(
+                "{} problem{} in {}",
+                problems.len(),
+                if problems.len() == 1 { "" } else { "s" },
+                path.display()
+            );
+            std::process::exit(status::ERROR);
+        }
+    }
+}
+
+fn run_cache(action: CacheAction) {
+    match action {
+        CacheAction::Status { repo } => {
+            let (dir, entries) = match repo {
+                Some(repo) => {
+                    let dir = cache::repo_cache_dir(&repo);
+                    let (bytes, files) = cache::dir_usage(&dir);
+                    let entries = if files > 0 {
+                        vec![(".".to_string(), bytes, files)]
+                    } else {
+                        Vec::new()
+                    };
+                    (dir, entries)
+                }
+                None => {
+                    let dir = cache::cache_root();
+                    let entries = cache::cache_entries(&dir);
+                    (dir, entries)
+                }
+            };
+
+            println!("Cache: {}", dir.display());
+            let mut total_bytes = 0;
+            let mut total_files = 0;
+            for (name, bytes, files) in &entries {
+                total_bytes += bytes;
+                total_files += files;
+                println!(
+                    "  {:>10}  {:>6} files  {name}",
+                    cache::format_bytes(*bytes),
+                    files
+                );
+            }
+            println!(
+                "Total: {} in {total_files} files",
+                cache::format_bytes(total_bytes)
+            );
+        }
+        CacheAction::Clear { repo } => {
+            let (dir, bytes, cleared) = match repo {
+                Some(repo) => {
+                    let dir = cache::repo_cache_dir(&repo);
+                    let (bytes, _) = cache::dir_usage(&dir);
+                    let cleared = cache::clear(&dir);
+                    (dir, bytes, cleared)
+                }
+                None => {
+                    let dir = cache::cache_root();
+                    let bytes = cache::CACHE_DIRS
+                        .iter()
+                        .map(|sub| cache::dir_usage(&dir.join(sub)).0)
+                        .sum();
+                    let cleared = cache::clear_all(&dir);
+                    (dir, bytes, cleared)
+                }
+            };
+            if let Err(e) = cleared {
+                eprintln!("Failed to clear {}: {e}", dir.display());
+                std::process::exit(status::ERROR);
+            }
+            println!("Cleared {} ({})", dir.display(), cache::format_bytes(bytes));
+        }
+        CacheAction::Path { repo } => {
+            let dir = match repo {
+                Some(repo) => cache::repo_cache_dir(&repo),
+                None => cache::cache_root(),
+            };
+            println!("{}", dir.display());
+        }
+    }
+}
+
+/// Parse the command line and run it. Exits the process with a code from
+/// `status` instead of returning when it ends with warnings or fails.
+pub fn run() {
+    let cli = Cli::try_parse().unwrap_or_else(|e| exit_parse_error(e));
+    let allow_empty = cli.allow_empty;
+
+    estimate::configure(match cli.tokenizer {
+        Some(tokenizer) => estimate::Estimator::Bpe(tokenizer),
+        None => cli.estimator,
+    });
+    // Before any worker thread exists: on Linux the nice value and I/O class
+    // apply to the calling thread, and threads only inherit them when spawned
+    if cli.nice {
+        if let Err(e) = priority::lower_priority() {
+            status::warn(format!("could not lower process priority: {e}"));
+        }
+    }
+    if let Some(threads) = cli.threads {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+        {
+            eprintln!("Failed to configure {threads} threads: {e}");
+            std::process::exit(status::ERROR);
+        }
+    }
+    let ca_bundle = cli.ca_bundle.clone().or_else(|| {
+        ["GATHER_CA_BUNDLE", "SSL_CERT_FILE"]
+            .iter()
+            .find_map(|var| std::env::var_os(var).filter(|v| !v.is_empty()))
+            .map(PathBuf::from)
+    });
+    if let Some(path) = ca_bundle {
+        http::configure_ca_bundle(path);
+    }
+
+    let command = match cli.command {
+        Commands::Bundle {
+            action: None,
+            name: Some(name),
+            repo,
+            args,
+        } => {
+            let mut collect_args = vec![repo.to_string_lossy().to_string()];
+            collect_args.extend(args);
+            collect_command(Some(&name), &repo, collect_args)
+        }
+        Commands::Collect {
+            profile,
+            path,
+            no_config,
+            ..
+        } if profile.is_some() || (!no_config && config::find(&path).is_some()) => {
+            expand_profile(profile.as_deref(), &path)
+        }
+        command => command,
+    };
+
+    match command {
+        Commands::Collect {
+            path,
+            clone,
+            at,
+            staged,
+            changed,
+            since,
+            files_from,
+            opts: collect_opts,
+            profile: _,
+            no_config: _,
+            format,
+            tokens,
+            header,
+            footer,
+            collapsible,
+            group_by_dir,
+            summary_lines,
+            fence_char,
+            fence_len,
+            no_lang_tags,
+            xml_root,
+            xml_file_element,
+            redact,
+            xml_cdata,
+            file_meta,
+            validate,
+            annotate_changes,
+            deps_graph,
+            with_tree,
+            urls,
+            buffers_from,
+            buffer_context,
+            line_specs,
+            queries,
+            queries_from,
+            query_limit,
+            transcript,
+            max_tokens,
+            budget_order,
+            session,
+            split_tokens,
+            manifest,
+            lock,
+            verify,
+            output,
+            append,
+            copy,
+            incremental,
+        } => {
+            if session.is_some() && remote::is_remote(&path.to_string_lossy()) {
+                eprintln!("--session keeps state in the repository; use it with a local path");
+                std::process::exit(status::ERROR);
+            }
+            // `-g src/main.rs:100-250` selects the file and cuts it down
+            let mut collect_opts = collect_opts;
+            let mut line_specs = line_specs;
+            line_specs.extend(
+                lines::take_from_globs(&mut collect_opts.filter.globs).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                }),
+            );
+            let source = resolve_root(
+                path,
+                &collect_opts.filter,
+                &clone,
+                Snapshot::from_flags(at, staged),
+                Some(&collect_opts),
+            );
+            let root = source.root.clone();
+            validate_filter(&root, &collect_opts.filter);
+            let mut session_state = session.as_ref().map(|name| {
+                session::Session::load(source.repo(), name).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                })
+            });
+            if let Some(base) = since.as_deref().or(changed.then_some("HEAD")) {
+                let changed = git::changed_since(&root, base).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                });
+                collect_opts.filter.paths = Some(changed);
+            }
+            if let Some(ref path) = files_from {
+                let listed = load_file_list(&root, path).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                });
+                collect_opts.filter.paths = Some(listed);
+            }
+            let buffers = buffers_from.map(|path| {
+                let buffers = buffers::load(&path).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                });
+                let buffers = open_buffers(&root, buffers);
+                collect_opts.filter.paths = Some(buffers.iter().map(|b| b.path.clone()).collect());
+                buffers
+            });
+            let mut queries = queries;
+            if let Some(ref path) = queries_from {
+                match search::read_queries(path) {
+                    Ok(more) => queries.extend(more),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(status::ERROR);
+                    }
+                }
+            }
+            let retrieved = (!queries.is_empty()).then(|| {
+                let retrieved = if search::exists(&root) {
+                    search::SearchIndex::open(&root)
+                        .and_then(|index| search::retrieve(&index, &queries, query_limit))
+                        .unwrap_or_else(|e| {
+                            eprintln!("{e}");
+                            std::process::exit(status::ERROR);
+                        })
+                } else {
+                    // Without an index, rank what the filters select in memory
+                    let filter = FilterArgs {
+                        paths: None,
+                        ..collect_opts.filter.clone()
+                    };
+                    let paths = tree_paths(&root, &filter);
+                    let ranker = rank::Ranker::read(&root, &paths, collect_opts.max_size);
+                    rank::retrieve(&ranker, &queries, query_limit)
+                };
+                // Buffers and queries together gather what either selects
+                let mut paths = collect_opts.filter.paths.take().unwrap_or_default();
+                paths.extend(retrieved.iter().cloned());
+                collect_opts.filter.paths = Some(paths);
+                retrieved
+            });
+            if split_tokens.is_some() && session.is_none() && output.is_none() {
+                eprintln!("--split-tokens writes its parts beside --output; give -o or --session");
+                std::process::exit(status::ERROR);
+            }
+            if session.is_some() && matches!(format, OutputFormat::Summary) {
+                eprintln!(
+                    "--session records files as sent, but -f summary sends only part of them"
+                );
+                std::process::exit(status::ERROR);
+            }
+            if validate && !matches!(format, OutputFormat::Xml) {
+                eprintln!("--validate checks XML output; use it with -f xml");
+                std::process::exit(status::ERROR);
+            }
+
+            let previous = match (&manifest, &output) {
+                (Some(manifest_path), Some(output_path)) if incremental => {
+                    PreviousRun::load(manifest_path, output_path, &format)
+                }
+                _ => None,
+            };
+            let mut collection = collect_files_with(&root, &collect_opts, previous.as_ref());
+            // Don't read back what an earlier run of this command wrote
+            let written: Vec<PathBuf> = [&output, &manifest, &lock]
+                .into_iter()
+                .flatten()
+                .filter_map(|path| output::resolve(path))
+                .collect();
+            if let (false, Ok(canonical_root)) = (written.is_empty(), root.canonicalize()) {
+                collection.files.retain(|f| {
+                    let path = canonical_root.join(&f.relative_path);
+                    !written
+                        .iter()
+                        .any(|w| path == *w || split::is_part(w, &path))
+                });
+            }
+            if !urls.is_empty() {
+                collection.files.extend(fetch_urls(&urls));
+            }
+            if let Some(ref retrieved) = retrieved {
+                order_by_paths(&mut collection.files, retrieved);
+            }
+            if let Some(ref path) = transcript {
+                let mentioned = transcript_files(&root, path, &collect_opts);
+                force_include(&root, &mut collection, &mentioned, &collect_opts);
+                order_by_paths(&mut collection.files, &mentioned);
+            }
+            if let Some(ref buffers) = buffers {
+                collection.files = focus_buffers(collection.files, buffers, buffer_context);
+            }
+            if !line_specs.is_empty() {
+                lines::apply(&mut collection.files, &line_specs);
+            }
+            if redact {
+                let redacted = redact::redact_files(&mut collection.files);
+                redact::report(&redacted, cli.verbose);
+            }
+            if let Some(budget) = max_tokens {
+                apply_token_budget(
+                    &root,
+                    &mut collection,
+                    budget,
+                    budget_order,
+                    &collect_opts.filter,
+                );
+            }
+            report_skipped(&collection.skipped, cli.verbose);
+            if let Some(ref previous) = previous {
+                eprintln!(
+                    "Incremental: reused {} of {} files",
+                    previous.reused_count(),
+                    collection.files.len()
+                );
+            }
+
+            if let Some(ref manifest_path) = manifest {
+                let doc = Manifest::from_collection(&root, &collection);
+                if let Err(e) = doc.write(manifest_path) {
+                    eprintln!("Failed to write manifest {}: {e}", manifest_path.display());
+                    std::process::exit(status::ERROR);
+                }
+            }
+
+            let mut files = collection.files;
+            if let Some(ref base) = annotate_changes {
+                match annotate::annotate_changes(&root, base, &mut files) {
+                    Ok(n) => eprintln!("Annotated {n} files changed since {base}"),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(status::ERROR);
+                    }
+                }
+            }
+
+            if let Some(ref lock_path) = lock {
+                let current = Lockfile::from_files(&files);
+                if verify {
+                    verify_lock(lock_path, &current);
+                } else if let Err(e) = current.write(lock_path) {
+                    eprintln!("Failed to write lockfile {}: {e}", lock_path.display());
+                    std::process::exit(status::ERROR);
+                }
+            }
+
+            if files.is_empty() {
+                no_matches("No files found matching the given criteria.", allow_empty);
+            }
+
+            let mut opts = FormatOptions {
+                show_tokens: tokens,
+                collapsible,
+                group_by_dir,
+                fence: Some(
+                    (if fence_char == "tilde" { "~" } else { "`" }).repeat(fence_len.into()),
+                ),
+                no_lang_tags,
+                xml_root: Some(xml_root),
+                xml_file_element: Some(xml_file_element),
+                xml_cdata,
+                file_meta,
+                root: root.clone(),
+                summary_lines: Some(summary_lines),
+                ..Default::default()
+            };
+            if with_tree {
+                let name = root
+                    .file_name()
+                    .unwrap_or(root.as_os_str())
+                    .to_string_lossy();
+                opts.tree = Some(tree::text(&name, &tree_paths(&root, &collect_opts.filter)));
+            }
+            if deps_graph {
+                let paths: Vec<String> = files.iter().map(|f| f.relative_path.clone()).collect();
+                let graph = deps::graph(&root, &paths);
+                if graph.edges.is_empty() {
+                    status::warn("no imports between the gathered files; omitting --deps-graph");
+                } else {
+                    opts.graph = Some(graph.to_mermaid());
+                }
+            }
+            if header.is_some() || footer.is_some() {
+                let vars = source.template_vars();
+                opts.header = header.map(|h| vars.render(&h));
+                opts.footer = footer.map(|f| vars.render(&f));
+            }
+            let mut unchanged_count = 0;
+            if let (Some(name), Some(state)) = (&session, &session_state) {
+                let (fresh, unchanged) = state.split(files);
+                files = fresh;
+                if files.is_empty() {
+                    eprintln!("Nothing new or changed since the last gather in session {name}");
+                }
+                unchanged_count = unchanged.len();
+                opts.unchanged = Some((name.clone(), unchanged));
+            }
+
+            let parts = match split_tokens {
+                Some(limit) => partition(files, limit),
+                None => vec![files],
+            };
+            let base_header = opts.header.clone();
+            let mut rendered_parts = Vec::new();
+            for (i, part) in parts.iter().enumerate() {
+                if parts.len() > 1 {
+                    let label = format!("Part {} of {}", i + 1, parts.len());
+                    opts.header = Some(match base_header {
+                        Some(ref header) => format!("{header}\n\n{label}"),
+                        None => label,
+                    });
+                }
+                let rendered = match format {
+                    OutputFormat::Markdown => format_markdown(part, &opts),
+                    OutputFormat::Xml => format_xml(part, &opts),
+                    OutputFormat::Json => format_json(part, &opts),
+                    OutputFormat::Summary => format_summary(part, &opts),
+                };
+                if validate {
+                    let errors = validate::validate_xml(
+                        &rendered,
+                        opts.xml_root.as_deref().unwrap_or("context"),
+                        opts.xml_file_element.as_deref().unwrap_or("file"),
+                    );
+                    if !errors.is_empty() {
+                        eprintln!("XML output failed validation:");
+                        for e in &errors {
+                            eprintln!("  {e}");
+                        }
+                        std::process::exit(status::ERROR);
+                    }
+                }
+                rendered_parts.push(rendered);
+                // The tree, graph and unchanged list lead the first part only
+                opts.tree = None;
+                opts.graph = None;
+                opts.unchanged = None;
+            }
+            if let (None, Some(_), Some(output_path)) = (&session, split_tokens, &output) {
+                match split::write_parts(output_path, &rendered_parts, &parts) {
+                    Ok(manifest) => eprintln!(
+                        "Wrote {} parts; the order and contents are in {}",
+                        parts.len(),
+                        manifest.display()
+                    ),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(status::ERROR);
+                    }
+                }
+                if copy {
+                    emit(&rendered_parts[0], None, false, true);
+                }
+            } else {
+                let rendered = rendered_parts.remove(0);
+                emit(&rendered, output.as_deref(), append, copy);
+            }
+
+            if let (Some(name), Some(state)) = (&session, &mut session_state) {
+                let tokens = count_tokens(&parts[0]).iter().sum();
+                state.record(&parts[0], unchanged_count, tokens);
+                let queued: Vec<(String, &[CollectedFile], usize)> = rendered_parts
+                    .into_iter()
+                    .zip(&parts[1..])
+                    .map(|(text, files)| (text, files.as_slice(), count_tokens(files).iter().sum()))
+                    .collect();
+                if !state.queue.is_empty() {
+                    status::warn(format!(
+                        "discarding {} undelivered parts from the last gather in session {name}",
+                        state.queue.len()
+                    ));
+                }
+                if !queued.is_empty() {
+                    eprintln!(
+                        "Part 1 of {}; run `gather next --session {name}` for the next",
+                        parts.len()
+                    );
+                }
+                let result = state
+                    .queue_parts(source.repo(), name, queued, 2, parts.len())
+                    .and_then(|()| state.save(source.repo(), name));
+                if let Err(e) = result {
+                    eprintln!("Failed to save session {name}: {e}");
+                    std::process::exit(status::ERROR);
+                }
+            }
+        }
+
+        Commands::Tree {
+            path,
+            clone,
+            at,
+            filter,
+            format,
+        } => {
+            let source = resolve_root(path, &filter, &clone, at.map(Snapshot::Rev), None);
+            let root = source.root.clone();
+            validate_filter(&root, &filter);
+            let paths = tree_paths(&root, &filter);
+            let name = root
+                .file_name()
+                .unwrap_or(root.as_os_str())
+                .to_string_lossy();
+            match format.as_str() {
+                "mermaid" => print!("{}", tree::mermaid(&name, &paths)),
+                "csv" => {
+                    let rows: Vec<(String, usize, usize)> = paths
+                        .into_iter()
+                        .map(|rel| {
+                            let bytes = fs::read(root.join(&rel)).unwrap_or_default();
+                            let tokens = std::str::from_utf8(&bytes)
+                                .map_or(0, |text| estimate_tokens(Path::new(&rel), text));
+                            (rel, tokens, bytes.len())
+                        })
+                        .collect();
+                    print!("{}", csv::path_metrics(&rows));
+                }
+                _ => print!("{}", tree::text(&name, &paths)),
+            }
+        }
+
+        Commands::Outline {
+            path,
+            clone,
+            at,
+            opts: collect_opts,
+            format,
+            tokens,
+            output,
+        } => {
+            let source = resolve_root(
+                path,
+                &collect_opts.filter,
+                &clone,
+                at.map(Snapshot::Rev),
+                Some(&collect_opts),
+            );
+            let root = source.root.clone();
+            validate_filter(&root, &collect_opts.filter);
+            let collection = collect_files(&root, &collect_opts);
+            report_skipped(&collection.skipped, cli.verbose);
+            let (mut files, others): (Vec<_>, Vec<_>) = collection
+                .files
+                .into_iter()
+                .partition(|f| outline::supports(Path::new(&f.relative_path)));
+            if files.is_empty() {
+                no_matches("No source files to outline.", allow_empty);
+            }
+            let before: usize = count_tokens(&files).iter().sum();
+            files.par_iter_mut().for_each(|f| {
+                f.content = outline::outline(Path::new(&f.relative_path), &f.content);
+            });
+            let after: usize = count_tokens(&files).iter().sum();
+            let noun = if files.len() == 1 { "file" } else { "files" };
+            eprintln!(
+                "Outlined {} {noun}: ~{after} tokens, down from ~{before}",
+                files.len()
+            );
+            if !others.is_empty() {
+                let noun = if others.len() == 1 { "file" } else { "files" };
+                eprintln!(
+                    "Left out {} {noun} in languages outline doesn't parse",
+                    others.len()
+                );
+            }
+
+            let opts = FormatOptions {
+                show_tokens: tokens,
+                ..Default::default()
+            };
+            let rendered = match format {
+                OutputFormat::Markdown => format_markdown(&files, &opts),
+                OutputFormat::Xml => format_xml(&files, &opts),
+                OutputFormat::Json => format_json(&files, &opts),
+                OutputFormat::Summary => format_summary(&files, &opts),
+            };
+            emit(&rendered, output.as_deref(), false, false);
+        }
+
+        Commands::Watch {
+            debounce,
+            separator,
+            args,
+        } => run_watch(debounce, &separator, args),
+
+        Commands::Repl {
+            path,
+            clone,
+            opts,
+            format,
+        } => {
+            let source = resolve_root(path, &opts.filter, &clone, None, None);
+            validate_filter(&source.root, &opts.filter);
+            repl::Repl::new(source.root.clone(), opts, format).run();
+        }
+
+        Commands::Tokens {
+            path,
+            clone,
+            at,
+            opts,
+            estimate_only,
+            format,
+            sort,
+            by_dir,
+            depth,
+            top,
+            count_remote,
+            remote_model,
+        } => {
+            if count_remote.is_some() && format == "csv" {
+                eprintln!("--count-remote adds a total line; use it with the table format");
+                std::process::exit(status::ERROR);
+            }
+            if by_dir && format == "csv" {
+                eprintln!("--by-dir prints a table of directories; use it with the table format");
+                std::process::exit(status::ERROR);
+            }
+            let source = resolve_root(
+                path,
+                &opts.filter,
+                &clone,
+                at.map(Snapshot::Rev),
+                Some(&opts),
+            );
+            let root = source.root.clone();
+            validate_filter(&root, &opts.filter);
+
+            let mut exact_text = None;
+            let (mut rows, skipped, note) = if estimate_only {
+                let (sizes, skipped) = estimate_sizes(&root, &opts);
+                let rows: Vec<(String, usize, usize)> = sizes
+                    .into_iter()
+                    .map(|(rel, bytes)| {
+                        let tokens = estimate::from_size(bytes, lang_tag(Path::new(&rel)));
+                        (rel, tokens, bytes as usize)
+                    })
+                    .collect();
+                (rows, skipped, ", estimated from file sizes")
+            } else {
+                let Collection { files, skipped } = collect_files(&root, &opts);
+                if count_remote.is_some() {
+                    exact_text = Some(format_markdown(&files, &FormatOptions::default()));
+                }
+                let tokens = count_tokens(&files);
+                let rows = files
+                    .into_iter()
+                    .zip(tokens)
+                    .map(|(f, tokens)| {
+                        let bytes = f.content.len();
+                        (f.relative_path, tokens, bytes)
+                    })
+                    .collect();
+                (rows, skipped, "")
+            };
+            report_skipped(&skipped, cli.verbose);
+
+            if rows.is_empty() {
+                no_matches("No files found matching the given criteria.", allow_empty);
+            }
+
+            if by_dir {
+                let mut dirs = dir_rows(&rows, depth);
+                sort_token_rows(&mut dirs, sort.as_deref().unwrap_or("tokens"));
+                let noun = ["directory", "directories"];
+                print!("{}", token_table(&dirs, top, noun, rows.len(), note));
+            } else {
+                sort_token_rows(&mut rows, sort.as_deref().unwrap_or("path"));
+                if format == "csv" {
+                    let shown = top.unwrap_or(rows.len()).min(rows.len());
+                    print!("{}", csv::path_metrics(&rows[..shown]));
+                } else {
+                    print!(
+                        "{}",
+                        token_table(&rows, top, ["file", "files"], rows.len(), note)
+                    );
+                }
+            }
+
+            if let (Some(provider), Some(text)) = (count_remote, exact_text) {
+                let counted = remote_count::RemoteCounter::new(&provider, remote_model)
+                    .and_then(|counter| Ok((counter.count(&text)?, counter.describe())));
+                match counted {
+                    Ok((exact, model)) => {
+                        println!("{exact:>8} tokens  exact for the collected markdown ({model})")
+                    }
+                    Err(e) => status::warn(format!(
+                        "remote token count unavailable ({e}); showing local estimates"
+                    )),
+                }
+            }
+        }
+
+        Commands::Diff {
+            path,
+            from,
+            to,
+            include_stash,
+            filter,
+            format,
+            tokens,
+        } => {
+            let root = path.canonicalize().unwrap_or(path);
+            validate_filter(&root, &filter);
+            let patches = match include_stash {
+                Some(n) => diff::stash_changes(&root, n),
+                None => diff::changes(&root, &from, &to),
+            };
+            let patches = patches.unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(status::ERROR);
+            });
+            let path_filter = PathFilter::new(&root, &filter);
+            let files: Vec<CollectedFile> = patches
+                .into_iter()
+                .filter(|p| path_filter.matches(&p.path))
+                .map(|p| CollectedFile {
+                    relative_path: p.path,
+                    content: p.patch,
+                })
+                .collect();
+            if files.is_empty() {
+                no_matches("No changes between the given sides.", allow_empty);
+            }
+
+            let opts = FormatOptions {
+                show_tokens: tokens,
+                lang: Some("diff"),
+                root,
+                ..Default::default()
+            };
+            match format {
+                OutputFormat::Markdown => print!("{}", format_markdown(&files, &opts)),
+                OutputFormat::Xml => print!("{}", format_xml(&files, &opts)),
+                OutputFormat::Json => print!("{}", format_json(&files, &opts)),
+                OutputFormat::Summary => print!("{}", format_summary(&files, &opts)),
+            }
+        }
+
+        Commands::Compare {
+            left,
+            right,
+            opts: collect_opts,
+            all,
+            format,
+            tokens,
+        } => {
+            let mut sides = Vec::new();
+            for path in [left, right] {
+                if !path.is_dir() {
+                    eprintln!("Not a directory: {}", path.display());
+                    std::process::exit(status::ERROR);
+                }
+                let root = path.canonicalize().unwrap_or(path);
+                validate_filter(&root, &collect_opts.filter);
+                let collection = collect_files(&root, &collect_opts);
+                report_skipped(&collection.skipped, cli.verbose);
+                sides.push((root, collection.files));
+            }
+            let [label_a, label_b] = compare::labels(&sides[0].0, &sides[1].0);
+            let labels = [label_a.as_str(), label_b.as_str()];
+            let pairs = compare::pair(&sides[0].1, &sides[1].1);
+            let files = compare::bundle(&pairs, labels, all);
+            if files.is_empty() {
+                no_matches("The two trees have the same files.", allow_empty);
+            }
+
+            let opts = FormatOptions {
+                show_tokens: tokens,
+                header: Some(compare::summary(&pairs, labels)),
+                ..Default::default()
+            };
+            match format {
+                OutputFormat::Markdown => print!("{}", format_markdown(&files, &opts)),
+                OutputFormat::Xml => print!("{}", format_xml(&files, &opts)),
+                OutputFormat::Json => print!("{}", format_json(&files, &opts)),
+                OutputFormat::Summary => print!("{}", format_summary(&files, &opts)),
+            }
+        }
+
+        Commands::Conflicts {
+            path,
+            filter,
+            format,
+        } => {
+            let root = path.canonicalize().unwrap_or(path);
+            validate_filter(&root, &filter);
+            let path_filter = PathFilter::new(&root, &filter);
+            let mut conflicts: Vec<conflicts::Conflict> = conflicts::unmerged(&root)
+                .into_iter()
+                .filter(|c| path_filter.matches(&c.path))
+                .collect();
+            // Outside git, or after the index was resolved, look for markers
+            if conflicts.is_empty() {
+                conflicts = walk_files(&root, &filter)
+                    .into_iter()
+                    .filter(|(_, rel)| path_filter.matches(rel))
+                    .filter_map(|(path, rel)| {
+                        let content = fs::read_to_string(path).ok()?;
+                        conflicts::has_markers(&content).then(|| conflicts::Conflict {
+                            path: rel,
+                            working: Some(content),
+                            ..Default::default()
+                        })
+                    })
+                    .collect();
+                conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+            }
+            if conflicts.is_empty() {
+                no_matches("No merge conflicts found.", allow_empty);
+            }
+
+            let labels = conflicts::Labels::detect(&root);
+            match format {
+                OutputFormat::Markdown => {
+                    print!("{}", conflicts::render_markdown(&conflicts, &labels))
+                }
+                OutputFormat::Xml => print!("{}", conflicts::render_xml(&conflicts, &labels)),
+                OutputFormat::Json => print!("{}", conflicts::render_json(&conflicts, &labels)),
+                OutputFormat::Summary => {
+                    eprintln!("gather conflicts writes markdown, xml or json, not a summary");
+                    std::process::exit(status::ERROR);
+                }
+            }
+        }
+
+        Commands::Deps {
+            path,
+            filter,
+            format,
+        } => {
+            let root = path.canonicalize().unwrap_or(path);
+            validate_filter(&root, &filter);
+            let path_filter = PathFilter::new(&root, &filter);
+            let mut paths: Vec<String> = walk_files(&root, &filter)
+                .into_iter()
+                .map(|(_, rel)| rel.replace('\\', "/"))
+                .filter(|rel| path_filter.matches(rel))
+                .collect();
+            paths.sort();
+            let graph = deps::graph(&root, &paths);
+            if graph.edges.is_empty() {
+                no_matches("No imports between files found.", allow_empty);
+            }
+            match format.as_str() {
+                "dot" => print!("{}", graph.to_dot()),
+                _ => print!("{}", graph.to_mermaid()),
+            }
+        }
+
+        Commands::Index {
+            action: Some(IndexAction::Build { path, opts }),
+            ..
+        } => {
+            let root = path.canonicalize().unwrap_or(path);
+            validate_filter(&root, &opts.filter);
+            let Collection { files, skipped } = collect_files(&root, &opts);
+            report_skipped(&skipped, cli.verbose);
+            if let Err(e) = search::build(&root, &files) {
+                eprintln!("Failed to build search index {e}");
+                std::process::exit(status::ERROR);
+            }
+            eprintln!(
+                "Indexed {} files into {}",
+                files.len(),
+                search::index_dir(&root).display()
+            );
+        }
+
+        Commands::Index {
+            action: None,
+            path,
+            clone,
+            at,
+            opts,
+            sqlite,
+            content,
+        } => {
+            let sqlite = sqlite.expect("--sqlite is required without a subcommand");
+            let source = resolve_root(
+                path,
+                &opts.filter,
+                &clone,
+                at.map(Snapshot::Rev),
+                Some(&opts),
+            );
+            let root = source.root.clone();
+            validate_filter(&root, &opts.filter);
+            let Collection { files, skipped } = collect_files(&root, &opts);
+            report_skipped(&skipped, cli.verbose);
+            if files.is_empty() {
+                no_matches("No files found matching the given criteria.", allow_empty);
+            }
+            let tokens = count_tokens(&files);
+            if let Err(e) = sqlite::export(&sqlite, &root, &files, &tokens, content) {
+                eprintln!("Failed to write index {e}");
+                std::process::exit(status::ERROR);
+            }
+            eprintln!(
+                "Indexed {} files ({} tokens) into {}",
+                files.len(),
+                tokens.iter().sum::<usize>(),
+                sqlite.display()
+            );
+        }
+
+        Commands::Grep {
+            pattern,
+            path,
+            indexed,
+            query,
+            limit,
+            ignore_case,
+            files_with_matches,
+            filter,
+        } => {
+            let root = path.canonicalize().unwrap_or(path);
+            validate_filter(&root, &filter);
+            let path_filter = PathFilter::new(&root, &filter);
+            let open_index = || {
+                search::SearchIndex::open(&root).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                })
+            };
+
+            if query {
+                let hits = open_index().query(&pattern, limit).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                });
+                let hits: Vec<_> = hits
+                    .into_iter()
+                    .filter(|hit| path_filter.matches(&hit.path))
+                    .collect();
+                if hits.is_empty() {
+                    std::process::exit(status::NO_MATCHES);
+                }
+                for hit in hits {
+                    println!("{:.2}\t{}", hit.score, hit.path);
+                }
+                return;
+            }
+
+            let files: Vec<CollectedFile> = if indexed {
+                let hits = open_index().candidates(&pattern).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                });
+                hits.into_iter()
+                    .filter(|hit| path_filter.matches(&hit.path))
+                    .map(|hit| CollectedFile {
+                        relative_path: hit.path,
+                        content: hit.content,
+                    })
+                    .collect()
+            } else {
+                let opts = CollectOptions {
+                    filter,
+                    ..Default::default()
+                };
+                let collection = collect_files(&root, &opts);
+                report_skipped(&collection.skipped, cli.verbose);
+                collection.files
+            };
+            let out = grep_lines(&files, &pattern, ignore_case, files_with_matches);
+            if out.is_empty() {
+                std::process::exit(status::NO_MATCHES);
+            }
+            print!("{out}");
+        }
+
+        Commands::DiffManifest { old, new } => {
+            let load = |path: &Path| {
+                Manifest::load(path).unwrap_or_else(|e| {
+                    eprintln!("Failed to read manifest {}: {e}", path.display());
+                    std::process::exit(status::ERROR);
+                })
+            };
+            let diff = ManifestDiff::between(&load(&old), &load(&new));
+            print!("{}", diff.render());
+        }
+
+        Commands::Bench { path, opts, runs } => {
+            let root = path.canonicalize().unwrap_or(path);
+            validate_filter(&root, &opts.filter);
+            print!("{}", bench::run(&root, &opts, runs).render());
+        }
+
+        Commands::Dep {
+            name,
+            project,
+            opts: collect_opts,
+            format,
+            tokens,
+            outline,
+        } => {
+            let dep = dependency::locate(&project, &name).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(status::ERROR);
+            });
+            eprintln!(
+                "{} {} ({}) at {}",
+                dep.name,
+                dep.version,
+                dep.ecosystem,
+                dep.dir.display()
+            );
+
+            let collection = collect_files(&dep.dir, &collect_opts);
+            report_skipped(&collection.skipped, cli.verbose);
+            let mut files = collection.files;
+            if files.is_empty() {
+                no_matches("No files found matching the given criteria.", allow_empty);
+            }
+            if outline {
+                for f in &mut files {
+                    f.content = outline::outline(Path::new(&f.relative_path), &f.content);
+                }
+            }
+
+            let opts = FormatOptions {
+                show_tokens: tokens,
+                header: Some(format!(
+                    "Source of {} {} ({})",
+                    dep.name, dep.version, dep.ecosystem
+                )),
+                ..Default::default()
+            };
+            let rendered = match format {
+                OutputFormat::Markdown => format_markdown(&files, &opts),
+                OutputFormat::Xml => format_xml(&files, &opts),
+                OutputFormat::Json => format_json(&files, &opts),
+                OutputFormat::Summary => format_summary(&files, &opts),
+            };
+            print!("{rendered}");
+        }
+
+        Commands::Schema { document } => {
+            // The value parser only admits documents that have a schema
+            print!("{}", schema::get(&document).unwrap_or_default());
+        }
+
+        Commands::ToolSchema { provider } => {
+            print!("{}", tool_schema::render(&provider).unwrap_or_default());
+        }
+
+        Commands::Serve { path, mcp: _, http } => {
+            let root = path.canonicalize().unwrap_or_else(|e| {
+                eprintln!("Cannot read {}: {e}", path.display());
+                std::process::exit(status::ERROR);
+            });
+            match http {
+                Some(addr) => {
+                    if let Err(e) = serve::http(&root, &addr) {
+                        eprintln!("{e}");
+                        std::process::exit(status::ERROR);
+                    }
+                }
+                None => serve::mcp(&root),
+            }
+        }
+
+        Commands::Next {
+            session,
+            repo,
+            output,
+            copy,
+        } => {
+            let mut state = session::Session::load(&repo, &session).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(status::ERROR);
+            });
+            let part = match state.next_part(&repo, &session) {
+                Ok(Some(part)) => part,
+                Ok(None) => {
+                    eprintln!("No parts left to deliver in session {session}");
+                    std::process::exit(status::ERROR);
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                }
+            };
+            let (part, rendered) = part;
+            emit(&rendered, output.as_deref(), false, copy);
+            if let Err(e) = state.save(&repo, &session) {
+                eprintln!("Failed to save session {session}: {e}");
+                std::process::exit(status::ERROR);
+            }
+            eprintln!(
+                "Part {} of {} ({} left)",
+                part.number,
+                part.total,
+                state.queue.len()
+            );
+        }
+
+        Commands::Bundle { action, .. } => {
+            if let Some(action) = action {
+                run_bundle(action);
+            }
+        }
+        Commands::Init { from, name, repo } => {
+            let imported = migrate::import(&from).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                std::process::exit(status::ERROR);
+            });
+            let name = name.unwrap_or_else(|| imported.tool.to_string());
+            match config::save_bundle(&repo, &name, &imported.bundle) {
+                Ok((path, replaced)) => println!(
+                    "{} bundle {name} in {} from {}; gather it with `gather bundle {name}`",
+                    if replaced { "Updated" } else { "Saved" },
+                    path.display(),
+                    from.display()
+                ),
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(status::ERROR);
+                }
+            }
+            if !imported.untranslated.is_empty() {
+                eprintln!("Not translated (no gather equivalent):");
+                for key in &imported.untranslated {
+                    eprintln!("  {key}");
+                }
+            }
+        }
+
+        Commands::Config { action } => run_config(action),
+        Commands::Session { action } => run_session(action),
+        Commands::Cache { action } => run_cache(action),
+    }
+    std::process::exit(status::code());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_settings_sources() {
+        let args = ["-g", "src/**", "--max-size", "5000"].map(String::from);
+        let settings = effective_settings(&args).unwrap();
+        let find = |name: &str| {
+            settings
+                .iter()
+                .find(|s| s.name == name)
+                .map(|s| (s.value.as_str(), s.source.as_str()))
+        };
+        assert_eq!(find("glob"), Some(("src/**", "command line")));
+        assert_eq!(find("max-size"), Some(("5000", "command line")));
+        assert_eq!(find("format"), Some(("markdown", "default")));
+        assert_eq!(find("output"), None);
+        assert!(effective_settings(&["--no-such-flag".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_effective_settings_config_layers() {
+        let root = crate::test_dir::make_test_dir("layers");
+        std::fs::write(
+            root.join("gather.toml"),
+            "[defaults]\nexcludes = [\"**/fixtures/**\"]\nmax_size = 5000\nformat = \"xml\"\n\n\
+             [bundles.api]\nexcludes = [\"**/*_test.rs\"]\nmax_size = 8000\n",
+        )
+        .unwrap();
+        let file = root.join("gather.toml").display().to_string();
+        let settings = |args: &[&str]| {
+            let mut argv = vec![root.display().to_string()];
+            argv.extend(args.iter().map(|a| a.to_string()));
+            effective_settings(&argv).unwrap()
+        };
+        let find = |settings: &[config::Setting], name: &str| {
+            settings
+                .iter()
+                .find(|s| s.name == name)
+                .map(|s| (s.value.clone(), s.source.clone()))
+        };
+
+        let layered = settings(&["--profile", "api", "-f", "json"]);
+        assert_eq!(
+            find(&layered, "exclude"),
+            Some((
+                "**/fixtures/**, **/*_test.rs".to_string(),
+                format!("[defaults] in {file} + profile api in {file}")
+            ))
+        );
+        assert_eq!(
+            find(&layered, "max-size"),
+            Some(("8000".to_string(), format!("profile api in {file}")))
+        );
+        assert_eq!(
+            find(&layered, "format"),
+            Some((
+                "json".to_string(),
+                format!("command line (overrides [defaults] in {file})")
+            ))
+        );
+
+        let plain = settings(&["--no-config"]);
+        assert_eq!(
+            find(&plain, "max-size"),
+            Some(("102400".to_string(), "default".to_string()))
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
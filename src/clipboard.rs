@@ -0,0 +1,10 @@
+use arboard::Clipboard;
+
+/// Put `text` on the system clipboard. On Linux the text is handed to the
+/// clipboard manager when gather exits, so it outlives the process.
+pub fn copy(text: &str) -> Result<(), String> {
+    let mut clipboard = Clipboard::new().map_err(|e| format!("cannot open the clipboard: {e}"))?;
+    clipboard
+        .set_text(text)
+        .map_err(|e| format!("cannot set the clipboard: {e}"))
+}
@@ -0,0 +1,201 @@
+use crate::CollectedFile;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// How a path compares between the two trees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Same,
+    Changed,
+    OnlyLeft,
+    OnlyRight,
+}
+
+/// One path in either tree with its content on each side.
+#[derive(Debug)]
+pub struct Pair<'a> {
+    pub path: &'a str,
+    pub status: Status,
+    left: Option<&'a str>,
+    right: Option<&'a str>,
+}
+
+/// Match the files gathered from each tree by path, sorted by path.
+pub fn pair<'a>(left: &'a [CollectedFile], right: &'a [CollectedFile]) -> Vec<Pair<'a>> {
+    let mut sides: BTreeMap<&str, (Option<&str>, Option<&str>)> = BTreeMap::new();
+    for f in left {
+        sides.entry(&f.relative_path).or_default().0 = Some(&f.content);
+    }
+    for f in right {
+        sides.entry(&f.relative_path).or_default().1 = Some(&f.content);
+    }
+    sides
+        .into_iter()
+        .map(|(path, (left, right))| {
+            let status = match (left, right) {
+                (Some(a), Some(b)) if a == b => Status::Same,
+                (Some(_), Some(_)) => Status::Changed,
+                (Some(_), None) => Status::OnlyLeft,
+                _ => Status::OnlyRight,
+            };
+            Pair {
+                path,
+                status,
+                left,
+                right,
+            }
+        })
+        .collect()
+}
+
+/// The paired bundle: both versions of each changed file, one after the
+/// other under the side labels, and files present on one side once under
+/// that side's label. Identical files are left out unless `all`, in which
+/// case they appear once under their plain path.
+pub fn bundle(pairs: &[Pair], labels: [&str; 2], all: bool) -> Vec<CollectedFile> {
+    let mut files = Vec::new();
+    let mut push = |label: &str, path: &str, content: &str| {
+        files.push(CollectedFile {
+            relative_path: if label.is_empty() {
+                path.to_string()
+            } else {
+                format!("{label}/{path}")
+            },
+            content: content.to_string(),
+        });
+    };
+    for p in pairs {
+        match p.status {
+            Status::Same if all => push("", p.path, p.left.unwrap_or_default()),
+            Status::Same => {}
+            _ => {
+                if let Some(content) = p.left {
+                    push(labels[0], p.path, content);
+                }
+                if let Some(content) = p.right {
+                    push(labels[1], p.path, content);
+                }
+            }
+        }
+    }
+    files
+}
+
+/// A header listing what differs, for the top of the bundle.
+pub fn summary(pairs: &[Pair], labels: [&str; 2]) -> String {
+    let count = |status| pairs.iter().filter(|p| p.status == status).count();
+    let mut out = format!(
+        "Comparing {} with {}: {} changed, {} only in {}, {} only in {}, {} identical",
+        labels[0],
+        labels[1],
+        count(Status::Changed),
+        count(Status::OnlyLeft),
+        labels[0],
+        count(Status::OnlyRight),
+        labels[1],
+        count(Status::Same),
+    );
+    let sections = [
+        (Status::Changed, "Changed".to_string()),
+        (Status::OnlyLeft, format!("Only in {}", labels[0])),
+        (Status::OnlyRight, format!("Only in {}", labels[1])),
+    ];
+    for (status, title) in sections {
+        let paths: Vec<&str> = pairs
+            .iter()
+            .filter(|p| p.status == status)
+            .map(|p| p.path)
+            .collect();
+        if !paths.is_empty() {
+            let _ = write!(out, "\n\n{title}:");
+            for path in paths {
+                let _ = write!(out, "\n- {path}");
+            }
+        }
+    }
+    out
+}
+
+/// Short names for the two trees: their directory names, or `a` and `b`
+/// when those are the same.
+pub fn labels(left: &Path, right: &Path) -> [String; 2] {
+    let name = |p: &Path| {
+        p.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let (a, b) = (name(left), name(right));
+    if a.is_empty() || b.is_empty() || a == b {
+        ["a".to_string(), "b".to_string()]
+    } else {
+        [a, b]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, content: &str) -> CollectedFile {
+        CollectedFile {
+            relative_path: path.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let left = [
+            file("README.md", "# Hi\n"),
+            file("src/lib.rs", "fn a() {}\n"),
+            file("src/old.rs", "gone\n"),
+        ];
+        let right = [
+            file("README.md", "# Hi\n"),
+            file("src/lib.rs", "fn b() {}\n"),
+            file("src/new.rs", "added\n"),
+        ];
+        let pairs = pair(&left, &right);
+        let statuses: Vec<(&str, Status)> = pairs.iter().map(|p| (p.path, p.status)).collect();
+        assert_eq!(
+            statuses,
+            [
+                ("README.md", Status::Same),
+                ("src/lib.rs", Status::Changed),
+                ("src/new.rs", Status::OnlyRight),
+                ("src/old.rs", Status::OnlyLeft),
+            ]
+        );
+
+        let labels = ["upstream", "fork"];
+        let paths = |files: Vec<CollectedFile>| -> Vec<String> {
+            files.into_iter().map(|f| f.relative_path).collect()
+        };
+        assert_eq!(
+            paths(bundle(&pairs, labels, false)),
+            [
+                "upstream/src/lib.rs",
+                "fork/src/lib.rs",
+                "fork/src/new.rs",
+                "upstream/src/old.rs"
+            ]
+        );
+        assert_eq!(paths(bundle(&pairs, labels, true))[0], "README.md");
+        assert_eq!(
+            summary(&pairs, labels),
+            "Comparing upstream with fork: 1 changed, 1 only in upstream, 1 only in fork, \
+             1 identical\n\nChanged:\n- src/lib.rs\n\nOnly in upstream:\n- src/old.rs\n\n\
+             Only in fork:\n- src/new.rs"
+        );
+
+        assert_eq!(
+            super::labels(Path::new("/w/upstream"), Path::new("/w/fork")),
+            ["upstream", "fork"]
+        );
+        assert_eq!(
+            super::labels(Path::new("/a/repo"), Path::new("/b/repo")),
+            ["a", "b"]
+        );
+    }
+}
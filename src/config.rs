@@ -0,0 +1,703 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Config file names looked for in a repository root, in order.
+pub const FILE_NAMES: &[&str] = &["gather.toml", ".gather.toml"];
+
+/// A repository's `gather.toml`.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Selection every `gather collect` in the repository starts from,
+    /// unless `--no-config` is given.
+    #[serde(default)]
+    pub defaults: Bundle,
+    /// Named selections for `gather bundle NAME` and `--profile NAME`.
+    #[serde(default)]
+    pub bundles: BTreeMap<String, Bundle>,
+}
+
+/// A saved selection: the collect flags that pick and order files, plus
+/// notes for `gather bundle list`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bundle {
+    /// What the bundle is for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Roughly how many tokens the bundle comes to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_tokens: Option<usize>,
+    /// Bundle whose selection this one builds on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub globs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excludes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prunes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_tests: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub langs: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub submodules: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub follow_symlinks: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub hidden: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_ignore: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_ignore_vcs: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub natural_sort: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_packages: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queries: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_order: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub redact: bool,
+}
+
+/// Keys a `[bundles.NAME]` table may have (the fields of [`Bundle`]).
+const BUNDLE_KEYS: &[&str] = &[
+    "description",
+    "expected_tokens",
+    "extends",
+    "globs",
+    "excludes",
+    "prunes",
+    "max_depth",
+    "no_tests",
+    "langs",
+    "submodules",
+    "follow_symlinks",
+    "hidden",
+    "no_ignore",
+    "no_ignore_vcs",
+    "natural_sort",
+    "package",
+    "include_packages",
+    "max_size",
+    "queries",
+    "max_tokens",
+    "budget_order",
+    "format",
+    "redact",
+];
+
+/// Bundle keys that only make sense on a named bundle, not in `[defaults]`.
+const BUNDLE_ONLY_KEYS: &[&str] = &["description", "expected_tokens", "extends"];
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl Config {
+    /// The selection `spec` stands for: a bundle name, or several joined
+    /// with `+` (`base+api`), each with its `extends` chain applied first.
+    pub fn resolve(&self, spec: &str) -> Result<Bundle, String> {
+        if !spec.contains('+') {
+            return self.resolve_one(spec, &mut Vec::new());
+        }
+        let mut resolved = Bundle::default();
+        for name in spec.split('+') {
+            resolved.merge(&self.resolve_one(name, &mut Vec::new())?);
+        }
+        Ok(resolved)
+    }
+
+    fn resolve_one(&self, name: &str, chain: &mut Vec<String>) -> Result<Bundle, String> {
+        if chain.iter().any(|n| n == name) {
+            chain.push(name.to_string());
+            return Err(format!("bundles extend in a cycle: {}", chain.join(" -> ")));
+        }
+        let Some(bundle) = self.bundles.get(name) else {
+            return Err(match chain.last() {
+                Some(child) => format!("bundle {child} extends unknown bundle {name}"),
+                None => format!("No bundle named {name}"),
+            });
+        };
+        chain.push(name.to_string());
+        let mut resolved = match &bundle.extends {
+            Some(parent) => self.resolve_one(parent, chain)?,
+            None => Bundle::default(),
+        };
+        resolved.merge(bundle);
+        resolved.description = bundle.description.clone();
+        resolved.expected_tokens = bundle.expected_tokens;
+        Ok(resolved)
+    }
+}
+
+impl Bundle {
+    /// Layer `other` over this selection: lists (globs, excludes, prunes,
+    /// languages, queries, packages to include) are combined, switches stay on
+    /// once set, and `other`'s single values (package, max_depth, max_size,
+    /// max_tokens, budget_order, format) replace these. Notes are left to the caller.
+    pub fn merge(&mut self, other: &Bundle) {
+        fn extend(list: &mut Vec<String>, more: &[String]) {
+            for item in more {
+                if !list.contains(item) {
+                    list.push(item.clone());
+                }
+            }
+        }
+        extend(&mut self.globs, &other.globs);
+        extend(&mut self.excludes, &other.excludes);
+        extend(&mut self.prunes, &other.prunes);
+        extend(&mut self.langs, &other.langs);
+        extend(&mut self.include_packages, &other.include_packages);
+        extend(&mut self.queries, &other.queries);
+        self.no_tests |= other.no_tests;
+        self.submodules |= other.submodules;
+        self.follow_symlinks |= other.follow_symlinks;
+        self.hidden |= other.hidden;
+        self.no_ignore |= other.no_ignore;
+        self.no_ignore_vcs |= other.no_ignore_vcs;
+        self.natural_sort |= other.natural_sort;
+        self.redact |= other.redact;
+        if other.package.is_some() {
+            self.package.clone_from(&other.package);
+        }
+        self.max_depth = other.max_depth.or(self.max_depth);
+        self.max_size = other.max_size.or(self.max_size);
+        self.max_tokens = other.max_tokens.or(self.max_tokens);
+        if other.budget_order.is_some() {
+            self.budget_order.clone_from(&other.budget_order);
+        }
+        if other.format.is_some() {
+            self.format.clone_from(&other.format);
+        }
+    }
+
+    /// The `gather collect` flags that reproduce this selection (ignoring
+    /// `extends`; see [`Config::resolve`]).
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut flag = |name: &str, value: &dyn ToString| {
+            args.push(name.to_string());
+            args.push(value.to_string());
+        };
+        for glob in &self.globs {
+            flag("--glob", glob);
+        }
+        for exclude in &self.excludes {
+            flag("--exclude", exclude);
+        }
+        for prune in &self.prunes {
+            flag("--prune", prune);
+        }
+        if let Some(max_depth) = self.max_depth {
+            flag("--max-depth", &max_depth);
+        }
+        for lang in &self.langs {
+            flag("--lang", lang);
+        }
+        if let Some(package) = &self.package {
+            flag("--package", package);
+        }
+        for package in &self.include_packages {
+            flag("--include-package", package);
+        }
+        if let Some(max_size) = self.max_size {
+            flag("--max-size", &max_size);
+        }
+        for query in &self.queries {
+            flag("--query", query);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            flag("--max-tokens", &max_tokens);
+        }
+        if let Some(order) = &self.budget_order {
+            flag("--budget-order", order);
+        }
+        if let Some(format) = &self.format {
+            flag("--format", format);
+        }
+        if self.no_tests {
+            args.push("--no-tests".to_string());
+        }
+        if self.submodules {
+            args.push("--submodules".to_string());
+        }
+        if self.follow_symlinks {
+            args.push("--follow-symlinks".to_string());
+        }
+        if self.hidden {
+            args.push("--hidden".to_string());
+        }
+        if self.no_ignore {
+            args.push("--no-ignore".to_string());
+        }
+        if self.no_ignore_vcs {
+            args.push("--no-ignore-vcs".to_string());
+        }
+        if self.natural_sort {
+            args.push("--natural-sort".to_string());
+        }
+        if self.redact {
+            args.push("--redact".to_string());
+        }
+        args
+    }
+}
+
+/// A problem `gather config check` found, at a 1-based line and column.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// Every problem with the config file `text`: syntax errors, unknown keys,
+/// values of the wrong type, invalid globs, bundles extending missing
+/// bundles or each other in a cycle, patterns both included and excluded,
+/// and out-of-range values.
+pub fn check(text: &str) -> Vec<Diagnostic> {
+    let at = |span: Option<std::ops::Range<usize>>, message: String| {
+        let offset = span.map_or(0, |s| s.start);
+        let before = &text[..offset.min(text.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        Diagnostic {
+            line,
+            column,
+            message,
+        }
+    };
+    let doc = match toml_edit::Document::parse(text) {
+        Ok(doc) => doc,
+        Err(e) => return vec![at(e.span(), e.message().to_string())],
+    };
+
+    let mut found = Vec::new();
+    let root = doc.as_table();
+    for (key, _) in root.iter() {
+        if key != "defaults" && key != "bundles" {
+            found.push(at(
+                root.key(key).and_then(|k| k.span()),
+                format!("unknown key `{key}` (expected `defaults` or `bundles`)"),
+            ));
+        }
+    }
+    let defaults = root.get("defaults").and_then(|item| item.as_table_like());
+    for (key, _) in defaults.iter().flat_map(|t| t.iter()) {
+        if !BUNDLE_KEYS.contains(&key) || BUNDLE_ONLY_KEYS.contains(&key) {
+            found.push(at(
+                defaults.and_then(|t| t.key(key)).and_then(|k| k.span()),
+                format!("unknown key `{key}` in [defaults]"),
+            ));
+        }
+    }
+    let bundles = root.get("bundles").and_then(|item| item.as_table_like());
+    for (name, item) in bundles.iter().flat_map(|t| t.iter()) {
+        let Some(bundle) = item.as_table_like() else {
+            continue;
+        };
+        for (key, _) in bundle.iter() {
+            if !BUNDLE_KEYS.contains(&key) {
+                found.push(at(
+                    bundle.key(key).and_then(|k| k.span()),
+                    format!("unknown key `{key}` in bundle {name}"),
+                ));
+            }
+        }
+    }
+    if !found.is_empty() {
+        return found;
+    }
+    // Types are left to serde, which stops at the first mismatch
+    let config: Config = match toml::from_str(text) {
+        Ok(config) => config,
+        Err(e) => return vec![at(e.span(), e.message().trim_end().to_string())],
+    };
+
+    let sections = defaults
+        .map(|table| ("[defaults]".to_string(), table, &config.defaults))
+        .into_iter()
+        .chain(config.bundles.iter().filter_map(|(name, bundle)| {
+            let table = bundles?.get(name)?.as_table_like()?;
+            Some((format!("bundle {name}"), table, bundle))
+        }));
+    for (name, table, bundle) in sections {
+        let value_span = |key: &str, index: Option<usize>| {
+            let item = table.get(key)?;
+            match index {
+                Some(i) => item.as_array()?.get(i)?.span(),
+                None => item.span(),
+            }
+        };
+        for (key, patterns) in [
+            ("globs", &bundle.globs),
+            ("excludes", &bundle.excludes),
+            ("prunes", &bundle.prunes),
+        ] {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if let Err(e) = globset::Glob::new(pattern) {
+                    found.push(at(
+                        value_span(key, Some(i)),
+                        format!("invalid glob in {name}: {e}"),
+                    ));
+                }
+            }
+        }
+        for (i, package) in bundle.include_packages.iter().enumerate() {
+            if !crate::is_package_name(package) {
+                found.push(at(
+                    value_span("include_packages", Some(i)),
+                    format!("`{package}` in {name} is not an npm package name"),
+                ));
+            }
+        }
+        for (i, lang) in bundle.langs.iter().enumerate() {
+            if let Err(e) = crate::lang::parse(lang) {
+                found.push(at(value_span("langs", Some(i)), format!("{e} in {name}")));
+            }
+        }
+        for (i, pattern) in bundle.globs.iter().enumerate() {
+            if bundle.excludes.contains(pattern) {
+                found.push(at(
+                    value_span("globs", Some(i)),
+                    format!("`{pattern}` is both a glob and an exclude in {name}, so it matches nothing"),
+                ));
+            }
+        }
+        if let (Some(bundle_name), Some(_)) = (name.strip_prefix("bundle "), &bundle.extends) {
+            if let Err(e) = config.resolve(bundle_name) {
+                found.push(at(value_span("extends", None), e));
+            }
+        }
+        if let Some(format) = &bundle.format {
+            if !["markdown", "md", "xml", "json", "summary"]
+                .contains(&format.to_lowercase().as_str())
+            {
+                found.push(at(
+                    value_span("format", None),
+                    format!("unknown format `{format}` in {name} (expected markdown, xml, json or summary)"),
+                ));
+            }
+        }
+        if let Some(order) = &bundle.budget_order {
+            if !["path", "size", "mtime"].contains(&order.as_str()) {
+                found.push(at(
+                    value_span("budget_order", None),
+                    format!(
+                        "unknown budget_order `{order}` in {name} (expected path, size or mtime)"
+                    ),
+                ));
+            }
+        }
+        for (key, value) in [
+            ("max_size", bundle.max_size.map(|n| n as usize)),
+            ("max_tokens", bundle.max_tokens),
+            ("max_depth", bundle.max_depth),
+        ] {
+            if value == Some(0) {
+                found.push(at(
+                    value_span(key, None),
+                    format!("`{key}` of 0 in {name} leaves nothing to gather"),
+                ));
+            }
+        }
+    }
+    found
+}
+
+/// One resolved setting for `gather config show`.
+#[derive(Debug, PartialEq)]
+pub struct Setting {
+    pub name: String,
+    pub value: String,
+    /// Where the value came from: default, env, a profile or the command line.
+    pub source: String,
+}
+
+/// `settings` as aligned `name = value  # source` lines.
+pub fn render_settings(settings: &[Setting]) -> String {
+    let name_width = settings.iter().map(|s| s.name.len()).max().unwrap_or(0);
+    let value_width = settings
+        .iter()
+        .map(|s| s.value.len())
+        .max()
+        .unwrap_or(0)
+        .min(40);
+    settings
+        .iter()
+        .map(|s| {
+            format!(
+                "{:name_width$} = {:value_width$}  # {}\n",
+                s.name, s.value, s.source
+            )
+        })
+        .collect()
+}
+
+/// The config file in `root`, if there is one.
+pub fn find(root: &Path) -> Option<PathBuf> {
+    FILE_NAMES
+        .iter()
+        .map(|name| root.join(name))
+        .find(|path| path.is_file())
+}
+
+/// The config in `root`, empty if there is no config file.
+pub fn load(root: &Path) -> Result<Config, String> {
+    let Some(path) = find(root) else {
+        return Ok(Config::default());
+    };
+    let text = fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+    toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Store `bundle` as `[bundles.NAME]` in the config in `root` (creating
+/// `gather.toml` if needed), keeping the rest of the file and its comments
+/// as they are. Returns the file written and whether the bundle replaced an
+/// existing one.
+pub fn save_bundle(root: &Path, name: &str, bundle: &Bundle) -> Result<(PathBuf, bool), String> {
+    let path = find(root).unwrap_or_else(|| root.join(FILE_NAMES[0]));
+    let fail = |e: &dyn std::fmt::Display| format!("{}: {e}", path.display());
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(fail(&e)),
+    };
+    let mut doc: toml_edit::DocumentMut = text.parse().map_err(|e| fail(&e))?;
+    let entry: toml_edit::DocumentMut = toml::to_string(bundle)
+        .map_err(|e| fail(&e))?
+        .parse()
+        .map_err(|e| fail(&e))?;
+
+    let bundles = doc
+        .entry("bundles")
+        .or_insert_with(|| {
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            toml_edit::Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| fail(&"`bundles` is not a table"))?;
+    let mut table = entry.as_table().clone();
+    // Comments above a replaced bundle belong to it; keep them
+    let replaced = match bundles.get(name).and_then(|item| item.as_table()) {
+        Some(old) => {
+            *table.decor_mut() = old.decor().clone();
+            if let Some(position) = old.position() {
+                table.set_position(Some(position));
+            }
+            true
+        }
+        None => false,
+    };
+    bundles.insert(name, toml_edit::Item::Table(table));
+    fs::write(&path, doc.to_string()).map_err(|e| fail(&e))?;
+    Ok((path, replaced))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_bundle() {
+        let root = crate::test_dir::make_test_dir("config");
+        assert_eq!(load(&root).unwrap(), Config::default());
+
+        let bundle = Bundle {
+            globs: vec!["src/api/**".to_string()],
+            excludes: vec!["**/*_test.rs".to_string()],
+            max_tokens: Some(40000),
+            ..Default::default()
+        };
+        let (path, replaced) = save_bundle(&root, "api", &bundle).unwrap();
+        assert_eq!(path, root.join("gather.toml"));
+        assert!(!replaced);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[bundles.api]\nglobs = [\"src/api/**\"]\nexcludes = [\"**/*_test.rs\"]\nmax_tokens = 40000\n"
+        );
+
+        // Other bundles and comments survive a save
+        let edited = format!(
+            "# Shared context sets\n{}",
+            fs::read_to_string(&path).unwrap()
+        );
+        fs::write(&path, edited).unwrap();
+        let auth = Bundle {
+            description: Some("Login, sessions and tokens".to_string()),
+            expected_tokens: Some(12000),
+            globs: vec!["src/auth/**".to_string()],
+            no_tests: true,
+            ..Default::default()
+        };
+        save_bundle(&root, "auth", &auth).unwrap();
+        let (_, replaced) = save_bundle(&root, "api", &bundle).unwrap();
+        assert!(replaced);
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# Shared context sets\n[bundles.api]"));
+
+        let config = load(&root).unwrap();
+        assert_eq!(config.bundles["api"], bundle);
+        assert_eq!(config.bundles["auth"], auth);
+        assert_eq!(
+            config.bundles["api"].to_args(),
+            [
+                "--glob",
+                "src/api/**",
+                "--exclude",
+                "**/*_test.rs",
+                "--max-tokens",
+                "40000"
+            ]
+        );
+
+        assert_eq!(
+            config.bundles["auth"].to_args(),
+            ["--glob", "src/auth/**", "--no-tests"]
+        );
+
+        fs::write(&path, "[bundles.x]\nglob = [\"a\"]\n").unwrap();
+        assert!(load(&root).unwrap_err().contains("unknown field `glob`"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_extends_and_composition() {
+        let config: Config = toml::from_str(
+            r#"
+            [bundles.base]
+            excludes = ["**/fixtures/**"]
+            no_tests = true
+            max_tokens = 50000
+
+            [bundles.api]
+            extends = "base"
+            description = "HTTP handlers"
+            globs = ["src/api/**"]
+            max_tokens = 30000
+
+            [bundles.docs]
+            globs = ["docs/**"]
+            excludes = ["docs/archive/**"]
+
+            [bundles.loop]
+            extends = "cycle"
+            [bundles.cycle]
+            extends = "loop"
+            "#,
+        )
+        .unwrap();
+
+        let api = config.resolve("api").unwrap();
+        assert_eq!(api.globs, ["src/api/**"]);
+        assert_eq!(api.excludes, ["**/fixtures/**"]);
+        assert!(api.no_tests);
+        assert_eq!(api.max_tokens, Some(30000));
+        assert_eq!(api.description.as_deref(), Some("HTTP handlers"));
+
+        let both = config.resolve("api+docs").unwrap();
+        assert_eq!(both.globs, ["src/api/**", "docs/**"]);
+        assert_eq!(both.excludes, ["**/fixtures/**", "docs/archive/**"]);
+        assert_eq!(both.max_tokens, Some(30000));
+        assert_eq!(config.resolve("docs+base").unwrap().max_tokens, Some(50000));
+
+        assert_eq!(
+            config.resolve("loop").unwrap_err(),
+            "bundles extend in a cycle: loop -> cycle -> loop"
+        );
+        assert_eq!(
+            config.resolve("api+nope").unwrap_err(),
+            "No bundle named nope"
+        );
+    }
+
+    #[test]
+    fn test_render_settings() {
+        let setting = |name: &str, value: &str, source: &str| Setting {
+            name: name.to_string(),
+            value: value.to_string(),
+            source: source.to_string(),
+        };
+        assert_eq!(
+            render_settings(&[
+                setting("glob", "src/**", "command line"),
+                setting("max-size", "102400", "default"),
+            ]),
+            "glob     = src/**  # command line\nmax-size = 102400  # default\n"
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let problems = |text: &str| {
+            check(text)
+                .into_iter()
+                .map(|d| format!("{}:{}: {}", d.line, d.column, d.message))
+                .collect::<Vec<_>>()
+        };
+        assert!(problems("[bundles.api]\nglobs = [\"src/**\"]\n").is_empty());
+        assert_eq!(
+            problems("[bundles.api]\nglobs = [\"src/**\"\n"),
+            ["2:18: unclosed array, expected `]`"]
+        );
+        assert_eq!(
+            problems("color = true\n[bundles.api]\nglob = [\"a\"]\nmax_tokens = 5\n"),
+            [
+                "1:1: unknown key `color` (expected `defaults` or `bundles`)",
+                "3:1: unknown key `glob` in bundle api"
+            ]
+        );
+        assert_eq!(
+            problems("[bundles.api]\nmax_tokens = \"lots\"\n"),
+            ["2:14: invalid type: string \"lots\", expected usize"]
+        );
+        assert_eq!(
+            problems(
+                "[bundles.api]\nextends = \"base\"\nglobs = [\"src/**\", \"a[\"]\n\
+                 excludes = [\"src/**\"]\nformat = \"html\"\nmax_tokens = 0\n"
+            ),
+            [
+                "3:20: invalid glob in bundle api: error parsing glob 'a[': unclosed character class; missing ']'",
+                "3:10: `src/**` is both a glob and an exclude in bundle api, so it matches nothing",
+                "2:11: bundle api extends unknown bundle base",
+                "5:10: unknown format `html` in bundle api (expected markdown, xml, json or summary)",
+                "6:14: `max_tokens` of 0 in bundle api leaves nothing to gather"
+            ]
+        );
+        let langs = problems("[bundles.api]\nlangs = [\"rust\", \"cobol\"]\n");
+        assert!(langs[0].starts_with("2:18: unknown language 'cobol' (known: rust, python,"));
+        assert!(langs[0].ends_with(") in bundle api"));
+        assert_eq!(
+            problems("[defaults]\ninclude_packages = [\"lodash\", \"../../home\"]\n"),
+            ["2:31: `../../home` in [defaults] is not an npm package name"]
+        );
+        assert_eq!(
+            problems("[defaults]\nexcludes = [\"b[\"]\nextends = \"base\"\n"),
+            ["3:1: unknown key `extends` in [defaults]"]
+        );
+        assert_eq!(
+            problems("[defaults]\nexcludes = [\"b[\"]\nformat = \"html\"\n"),
+            [
+                "2:13: invalid glob in [defaults]: error parsing glob 'b[': unclosed character class; missing ']'",
+                "3:10: unknown format `html` in [defaults] (expected markdown, xml, json or summary)"
+            ]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::git::git_output;
+use crate::{fence_for, lang_tag, xml_attr_escape, xml_escape, DEFAULT_FENCE};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// One conflicted file: the three index stages and the working copy. A side
+/// is `None` when the file doesn't exist there (added or deleted on one side).
+#[derive(Debug, Default, PartialEq)]
+pub struct Conflict {
+    pub path: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    pub working: Option<String>,
+}
+
+/// What "ours" and "theirs" refer to in the operation in progress.
+pub struct Labels {
+    pub ours: String,
+    pub theirs: String,
+}
+
+impl Labels {
+    /// Describe HEAD and whichever of MERGE_HEAD, CHERRY_PICK_HEAD,
+    /// REVERT_HEAD or REBASE_HEAD is set.
+    pub fn detect(root: &Path) -> Self {
+        let describe = |rev: &str| {
+            git_output(root, &["rev-parse", "--verify", "--quiet", rev])?;
+            let name = git_output(root, &["name-rev", "--name-only", "--always", rev])?;
+            Some(format!("{rev}: {name}"))
+        };
+        let theirs = [
+            "MERGE_HEAD",
+            "CHERRY_PICK_HEAD",
+            "REVERT_HEAD",
+            "REBASE_HEAD",
+        ]
+        .iter()
+        .find_map(|rev| describe(rev));
+        Labels {
+            ours: describe("HEAD").unwrap_or_else(|| "HEAD".to_string()),
+            theirs: theirs.unwrap_or_else(|| "incoming".to_string()),
+        }
+    }
+}
+
+/// Unmerged paths under `root` from the git index, with each stage's content.
+/// Empty outside a repository or when nothing is unmerged.
+pub fn unmerged(root: &Path) -> Vec<Conflict> {
+    let Ok(output) = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-files", "--unmerged", "-z"])
+        .output()
+    else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+
+    let mut conflicts: BTreeMap<String, Conflict> = BTreeMap::new();
+    for (stage, oid, path) in parse_unmerged(&String::from_utf8_lossy(&output.stdout)) {
+        let conflict = conflicts.entry(path.clone()).or_insert_with(|| Conflict {
+            working: fs::read(root.join(&path)).ok().map(text_or_placeholder),
+            path,
+            ..Default::default()
+        });
+        let content = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["cat-file", "blob", &oid])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| text_or_placeholder(o.stdout));
+        match stage {
+            1 => conflict.base = content,
+            2 => conflict.ours = content,
+            _ => conflict.theirs = content,
+        }
+    }
+    conflicts.into_values().collect()
+}
+
+/// Parse `git ls-files --unmerged -z` into (stage, object id, path).
+fn parse_unmerged(listing: &str) -> Vec<(u8, String, String)> {
+    listing
+        .split('\0')
+        .filter_map(|entry| {
+            let (meta, path) = entry.split_once('\t')?;
+            let mut fields = meta.split(' ');
+            let _mode = fields.next()?;
+            let oid = fields.next()?;
+            let stage = fields.next()?.parse().ok()?;
+            Some((stage, oid.to_string(), path.to_string()))
+        })
+        .collect()
+}
+
+fn text_or_placeholder(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|_| "[binary content]\n".to_string())
+}
+
+/// Whether `content` has a complete set of conflict markers.
+pub fn has_markers(content: &str) -> bool {
+    let mut lines = content.lines();
+    lines.any(|l| l.starts_with("<<<<<<< ") || l == "<<<<<<<")
+        && lines.any(|l| l == "=======")
+        && lines.any(|l| l.starts_with(">>>>>>> ") || l == ">>>>>>>")
+}
+
+/// One section per conflicted file, with each side under its own heading.
+pub fn render_markdown(conflicts: &[Conflict], labels: &Labels) -> String {
+    let mut out = String::new();
+    for conflict in conflicts {
+        let lang = lang_tag(Path::new(&conflict.path));
+        let _ = writeln!(out, "## `{}`\n", conflict.path);
+        for (heading, content) in sides(conflict, labels) {
+            let _ = writeln!(out, "### {heading}\n");
+            match content {
+                Some(text) => {
+                    let fence = fence_for(DEFAULT_FENCE, text);
+                    let _ = writeln!(out, "{fence}{lang}");
+                    let _ = write!(out, "{text}");
+                    if !text.ends_with('\n') {
+                        let _ = writeln!(out);
+                    }
+                    let _ = writeln!(out, "{fence}\n");
+                }
+                None => {
+                    let _ = writeln!(out, "_(not present on this side)_\n");
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `<conflicts>` with a `<conflict>` per file and an element per side that
+/// exists; `ours`/`theirs` carry the ref they came from.
+pub fn render_xml(conflicts: &[Conflict], labels: &Labels) -> String {
+    let mut out = String::from("<conflicts>\n");
+    for conflict in conflicts {
+        let _ = writeln!(
+            out,
+            "  <conflict path=\"{}\">",
+            xml_attr_escape(&conflict.path)
+        );
+        let elements = [
+            ("base", None, &conflict.base),
+            ("ours", Some(&labels.ours), &conflict.ours),
+            ("theirs", Some(&labels.theirs), &conflict.theirs),
+            ("working", None, &conflict.working),
+        ];
+        for (name, label, content) in elements {
+            let Some(text) = content else {
+                continue;
+            };
+            let _ = match label {
+                Some(label) => writeln!(out, "    <{name} ref=\"{}\">", xml_attr_escape(label)),
+                None => writeln!(out, "    <{name}>"),
+            };
+            let escaped = xml_escape(text);
+            let _ = write!(out, "{escaped}");
+            if !escaped.ends_with('\n') {
+                let _ = writeln!(out);
+            }
+            let _ = writeln!(out, "    </{name}>");
+        }
+        let _ = writeln!(out, "  </conflict>");
+    }
+    out.push_str("</conflicts>\n");
+    out
+}
+
+/// Headings and contents in the order they're presented. Files found only by
+/// their markers have just a working copy.
+fn sides<'a>(conflict: &'a Conflict, labels: &Labels) -> Vec<(String, Option<&'a String>)> {
+    let mut sides = Vec::new();
+    let staged = conflict.base.is_some() || conflict.ours.is_some() || conflict.theirs.is_some();
+    if staged {
+        sides.push(("Base (common ancestor)".to_string(), conflict.base.as_ref()));
+        sides.push((format!("Ours ({})", labels.ours), conflict.ours.as_ref()));
+        sides.push((
+            format!("Theirs ({})", labels.theirs),
+            conflict.theirs.as_ref(),
+        ));
+    }
+    sides.push((
+        "Working copy (with conflict markers)".to_string(),
+        conflict.working.as_ref(),
+    ));
+    sides
+}
+
+/// `{"conflicts": [...]}` with an object per file; each side is its content,
+/// `ours`/`theirs` as `{"ref", "content"}`, or null where the file doesn't
+/// exist.
+pub fn render_json(conflicts: &[Conflict], labels: &Labels) -> String {
+    let labelled = |label: &str, content: &Option<String>| {
+        content
+            .as_ref()
+            .map(|text| json!({"ref": label, "content": text}))
+    };
+    let items: Vec<Value> = conflicts
+        .iter()
+        .map(|conflict| {
+            let lang = lang_tag(Path::new(&conflict.path));
+            json!({
+                "path": conflict.path,
+                "language": (!lang.is_empty()).then_some(lang),
+                "base": conflict.base,
+                "ours": labelled(&labels.ours, &conflict.ours),
+                "theirs": labelled(&labels.theirs, &conflict.theirs),
+                "working": conflict.working,
+            })
+        })
+        .collect();
+    let mut out = serde_json::to_string_pretty(&json!({ "conflicts": items })).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_unmerged_and_markers() {
+        let listing = [
+            "100644 aaa 1\tsrc/a.rs",
+            "100644 bbb 2\tsrc/a.rs",
+            "100644 ccc 3\tsrc/a.rs",
+        ]
+        .join("\0");
+        assert_eq!(
+            parse_unmerged(&listing),
+            vec![
+                (1, "aaa".to_string(), "src/a.rs".to_string()),
+                (2, "bbb".to_string(), "src/a.rs".to_string()),
+                (3, "ccc".to_string(), "src/a.rs".to_string()),
+            ]
+        );
+        assert!(has_markers(
+            "<<<<<<< HEAD\na\n=======\nb\n>>>>>>> feature\n"
+        ));
+        assert!(!has_markers("=======\nnot a conflict\n"));
+    }
+
+    #[test]
+    fn test_unmerged_from_real_merge() {
+        let repo = crate::test_dir::make_test_dir("conflicts");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        };
+        if !git(&["init", "-q", "-b", "main"]) {
+            return; // git unavailable
+        }
+        fs::write(repo.join("a.txt"), "base\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-qm", "base"]);
+        git(&["checkout", "-qb", "feature"]);
+        fs::write(repo.join("a.txt"), "theirs\n").unwrap();
+        git(&["commit", "-qam", "feature"]);
+        git(&["checkout", "-q", "main"]);
+        fs::write(repo.join("a.txt"), "ours\n").unwrap();
+        git(&["commit", "-qam", "main"]);
+        git(&["merge", "-q", "feature"]);
+
+        let conflicts = unmerged(&repo);
+        assert_eq!(conflicts.len(), 1);
+        let c = &conflicts[0];
+        assert_eq!(c.path, "a.txt");
+        assert_eq!(c.base.as_deref(), Some("base\n"));
+        assert_eq!(c.ours.as_deref(), Some("ours\n"));
+        assert_eq!(c.theirs.as_deref(), Some("theirs\n"));
+        assert!(has_markers(c.working.as_deref().unwrap()));
+
+        let labels = Labels::detect(&repo);
+        assert_eq!(labels.ours, "HEAD: main");
+        assert_eq!(labels.theirs, "MERGE_HEAD: feature");
+        let md = render_markdown(&conflicts, &labels);
+        assert!(md.contains("### Theirs (MERGE_HEAD: feature)\n\n```\ntheirs\n```"));
+        // A side with its own code block gets a longer fence
+        let readme = Conflict {
+            path: "README.md".to_string(),
+            ours: Some("```sh\nmake\n```\n".to_string()),
+            ..Conflict::default()
+        };
+        let md = render_markdown(&[readme], &labels);
+        assert!(md.contains("### Ours (HEAD: main)\n\n````markdown\n```sh\nmake\n```\n````\n"));
+        let xml = render_xml(&conflicts, &labels);
+        assert!(xml.contains("    <ours ref=\"HEAD: main\">\nours\n    </ours>"));
+
+        fs::remove_dir_all(&repo).unwrap();
+    }
+}
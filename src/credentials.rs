@@ -0,0 +1,237 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Forge flavor, which decides the git username and CLI fallback.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    Github,
+    Gitlab,
+}
+
+/// A token for one host and where it came from, for error messages.
+#[derive(Debug, PartialEq)]
+pub struct Credential {
+    pub forge: Forge,
+    pub token: String,
+    pub source: String,
+}
+
+impl Credential {
+    /// The request header that carries this token. GitLab takes personal
+    /// tokens as bearer tokens too, and `Authorization` is the one header
+    /// dropped when a request is redirected to another host.
+    pub fn header(&self) -> (&'static str, String) {
+        ("authorization", format!("Bearer {}", self.token))
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct CredentialsFile {
+    #[serde(default)]
+    hosts: BTreeMap<String, HostEntry>,
+}
+
+#[derive(Deserialize)]
+struct HostEntry {
+    token: String,
+    #[serde(default)]
+    kind: Option<Forge>,
+}
+
+/// `credentials.toml` in gather's config directory (`$XDG_CONFIG_HOME/gather`,
+/// `~/.config/gather`, or `%APPDATA%\gather` on Windows).
+pub fn credentials_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+    }?;
+    Some(dir.join("gather").join("credentials.toml"))
+}
+
+/// Which public forge serves `host`: github.com with its API and
+/// raw-content hosts, or gitlab.com. Only these get tokens from the
+/// environment or the `gh`/`glab` login; any other host, self-hosted
+/// GitLab included, needs its own credentials.toml entry.
+fn forge_of(host: &str) -> Option<Forge> {
+    let under = |domain: &str| host == domain || host.ends_with(&format!(".{domain}"));
+    if under("github.com") || under("githubusercontent.com") {
+        Some(Forge::Github)
+    } else if under("gitlab.com") {
+        Some(Forge::Gitlab)
+    } else {
+        None
+    }
+}
+
+/// The forge `host` most likely runs, for choosing a git username or a
+/// hint; never used to decide whether to send a token.
+fn guess_forge(host: &str) -> Forge {
+    forge_of(host).unwrap_or(if host.split('.').any(|part| part == "gitlab") {
+        Forge::Gitlab
+    } else {
+        Forge::Github
+    })
+}
+
+/// The token to send with a request for `url`, as [`for_host`] finds it.
+/// Always `None` for anything but https, so tokens never cross the network
+/// in the clear.
+pub fn for_url(url: &str) -> Option<Credential> {
+    url.starts_with("https://")
+        .then(|| for_host(crate::http::url_host(url)))
+        .flatten()
+}
+
+/// The token to use for `host`: the credentials file first, then
+/// `GITHUB_TOKEN`/`GH_TOKEN` or `GITLAB_TOKEN`, then the `gh`/`glab` CLI's
+/// stored login. `None` for hosts that aren't GitHub or GitLab unless the
+/// credentials file names them.
+pub fn for_host(host: &str) -> Option<Credential> {
+    let file: CredentialsFile = credentials_path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|t| toml::from_str(&t).ok())
+        .unwrap_or_default();
+    lookup(host, &file, &|var| env::var(var).ok(), &cli_token)
+}
+
+fn lookup(
+    host: &str,
+    file: &CredentialsFile,
+    env_var: &dyn Fn(&str) -> Option<String>,
+    cli: &dyn Fn(Forge, &str) -> Option<String>,
+) -> Option<Credential> {
+    if let Some(entry) = file.hosts.get(host) {
+        return Some(Credential {
+            forge: entry.kind.unwrap_or_else(|| guess_forge(host)),
+            token: entry.token.clone(),
+            source: "credentials.toml".to_string(),
+        });
+    }
+
+    let forge = forge_of(host)?;
+    let vars: &[&str] = match forge {
+        Forge::Github => &["GITHUB_TOKEN", "GH_TOKEN"],
+        Forge::Gitlab => &["GITLAB_TOKEN"],
+    };
+    let from_env = vars.iter().find_map(|var| {
+        env_var(var)
+            .filter(|t| !t.is_empty())
+            .map(|token| (token, format!("${var}")))
+    });
+    let (token, source) = from_env.or_else(|| {
+        let cli_name = match forge {
+            Forge::Github => "gh",
+            Forge::Gitlab => "glab",
+        };
+        cli(forge, host).map(|token| (token, format!("{cli_name} auth")))
+    })?;
+    Some(Credential {
+        forge,
+        token,
+        source,
+    })
+}
+
+/// Ask `gh` or `glab` for the token it has stored for `host`.
+fn cli_token(forge: Forge, host: &str) -> Option<String> {
+    // API and raw-content hosts share the login of the main site
+    let site = match forge {
+        Forge::Github
+            if host.ends_with("github.com") || host.ends_with("githubusercontent.com") =>
+        {
+            "github.com"
+        }
+        _ => host,
+    };
+    let output = match forge {
+        Forge::Github => Command::new("gh")
+            .args(["auth", "token", "--hostname", site])
+            .output(),
+        Forge::Gitlab => Command::new("glab")
+            .args(["config", "get", "token", "--host", site])
+            .output(),
+    }
+    .ok()?;
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !token.is_empty()).then_some(token)
+}
+
+/// Explain a failed status so 404 (missing, or private without access) reads
+/// differently from 401 (bad token) and 403 (token lacks access).
+pub fn status_hint(status: u16, host: &str, credential: Option<&Credential>) -> Option<String> {
+    let setup = match guess_forge(host) {
+        Forge::Gitlab => "set GITLAB_TOKEN, run `glab auth login`, or add it to credentials.toml",
+        _ => "set GITHUB_TOKEN, run `gh auth login`, or add it to credentials.toml",
+    };
+    Some(match (status, credential) {
+        (401, Some(c)) => format!(
+            "authentication failed: the token from {} was rejected",
+            c.source
+        ),
+        (401, None) => format!("authentication required: {setup}"),
+        (403, Some(c)) => format!("forbidden: the token from {} lacks access", c.source),
+        (403, None) => format!("forbidden: {setup}"),
+        (404, Some(c)) => format!("not found (or the token from {} can't see it)", c.source),
+        (404, None) => format!("not found (if it's private, {setup})"),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_order() {
+        let file: CredentialsFile =
+            toml::from_str("[hosts.\"git.corp.example\"]\ntoken = \"corp\"\nkind = \"gitlab\"\n")
+                .unwrap();
+        let env = |var: &str| (var == "GH_TOKEN").then(|| "from-env".to_string());
+        let cli = |_: Forge, _: &str| Some("from-cli".to_string());
+        let no_cli = |_: Forge, _: &str| None;
+
+        let corp = lookup("git.corp.example", &file, &env, &cli).unwrap();
+        assert_eq!(corp.forge, Forge::Gitlab);
+        assert_eq!(corp.header(), ("authorization", "Bearer corp".to_string()));
+
+        let gh = lookup("api.github.com", &file, &env, &cli).unwrap();
+        assert_eq!(gh.token, "from-env");
+        assert_eq!(gh.source, "$GH_TOKEN");
+
+        let gl = lookup("gitlab.com", &file, &env, &cli).unwrap();
+        assert_eq!(gl.source, "glab auth");
+        assert!(lookup("gitlab.com", &file, &env, &no_cli).is_none());
+        assert!(lookup("example.com", &file, &env, &cli).is_none());
+
+        // Lookalike hosts get nothing from the environment or the CLIs
+        assert!(lookup("raw.githubusercontent.com", &file, &env, &cli).is_some());
+        assert!(lookup("evilgithubusercontent.com", &file, &env, &cli).is_none());
+        assert!(lookup("gitlab.attacker.example", &file, &env, &cli).is_none());
+        assert!(for_url("http://github.com/org/repo").is_none());
+    }
+
+    #[test]
+    fn test_status_hint() {
+        assert_eq!(
+            status_hint(404, "github.com", None).unwrap(),
+            "not found (if it's private, set GITHUB_TOKEN, run `gh auth login`, or add it to credentials.toml)"
+        );
+        let cred = Credential {
+            forge: Forge::Github,
+            token: "t".to_string(),
+            source: "$GITHUB_TOKEN".to_string(),
+        };
+        assert!(status_hint(401, "github.com", Some(&cred))
+            .unwrap()
+            .contains("$GITHUB_TOKEN was rejected"));
+        assert!(status_hint(500, "github.com", None).is_none());
+    }
+}
@@ -0,0 +1,45 @@
+use crate::lang_tag;
+use std::fmt::Write as FmtWrite;
+use std::path::Path;
+
+/// Per-path metrics as CSV with a header row: path, depth (0 for files at
+/// the root), bytes, tokens and language tag. Rows are (path, tokens, bytes)
+/// as in the token table.
+pub fn path_metrics(rows: &[(String, usize, usize)]) -> String {
+    let mut out = String::from("path,depth,bytes,tokens,language\n");
+    for (path, tokens, bytes) in rows {
+        let depth = path.matches('/').count();
+        let lang = lang_tag(Path::new(path));
+        let _ = writeln!(out, "{},{depth},{bytes},{tokens},{lang}", field(path));
+    }
+    out
+}
+
+/// Quote a field per RFC 4180 when it contains a delimiter, quote or newline.
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_metrics() {
+        let rows = vec![
+            ("README.md".to_string(), 10, 40),
+            ("src/a,b.rs".to_string(), 3, 12),
+        ];
+        assert_eq!(
+            path_metrics(&rows),
+            "path,depth,bytes,tokens,language\n\
+             README.md,0,40,10,markdown\n\
+             \"src/a,b.rs\",1,12,3,rust\n"
+        );
+        assert_eq!(field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
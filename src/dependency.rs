@@ -0,0 +1,336 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A dependency's source directory, located via the project's lockfile.
+#[derive(Debug)]
+pub struct LocatedDependency {
+    pub ecosystem: &'static str,
+    pub name: String,
+    pub version: String,
+    pub dir: PathBuf,
+}
+
+/// Find the source of `spec` (`name` or `name@version`) for the version pinned
+/// in `root`'s Cargo.lock, package-lock.json / node_modules, or go.mod.
+pub fn locate(root: &Path, spec: &str) -> Result<LocatedDependency, String> {
+    let (name, wanted) = match spec.rsplit_once('@') {
+        // Scoped npm packages start with '@'
+        Some((name, version)) if !name.is_empty() => (name, Some(version)),
+        _ => (spec, None),
+    };
+
+    let mut errors = Vec::new();
+    for locate in [locate_cargo, locate_npm, locate_go] {
+        match locate(root, name, wanted) {
+            Ok(Some(found)) => return Ok(found),
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if errors.is_empty() {
+        Err(format!(
+            "'{name}' is not a dependency in Cargo.lock, node_modules or go.mod under {}",
+            root.display()
+        ))
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+fn locate_cargo(
+    root: &Path,
+    name: &str,
+    wanted: Option<&str>,
+) -> Result<Option<LocatedDependency>, String> {
+    let Ok(text) = fs::read_to_string(root.join("Cargo.lock")) else {
+        return Ok(None);
+    };
+    let lock: toml::Table = toml::from_str(&text).map_err(|e| format!("Cargo.lock: {e}"))?;
+    let packages = lock
+        .get("package")
+        .and_then(|p| p.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut versions: Vec<(String, Option<String>)> = packages
+        .iter()
+        .filter(|p| p.get("name").and_then(|n| n.as_str()) == Some(name))
+        .filter_map(|p| {
+            let version = p.get("version")?.as_str()?.to_string();
+            let source = p.get("source").and_then(|s| s.as_str()).map(String::from);
+            Some((version, source))
+        })
+        .filter(|(v, _)| wanted.is_none_or(|w| w == v))
+        .collect();
+
+    let (version, source) = match versions.len() {
+        0 => return Ok(None),
+        1 => versions.remove(0),
+        _ => {
+            let list: Vec<&str> = versions.iter().map(|(v, _)| v.as_str()).collect();
+            return Err(format!(
+                "{name} is locked at several versions ({}); use {name}@<version>",
+                list.join(", ")
+            ));
+        }
+    };
+
+    let Some(source) = source else {
+        return Err(format!(
+            "{name} {version} is a path dependency; gather its directory directly"
+        ));
+    };
+
+    let cargo_home = env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|h| h.join(".cargo")))
+        .ok_or("cannot determine CARGO_HOME")?;
+
+    let dir = if source.starts_with("git+") {
+        // git+https://host/repo?rev#<commit>
+        let commit = source.rsplit('#').next().unwrap_or("");
+        let short = &commit[..commit.len().min(7)];
+        find_subdir(&cargo_home.join("git").join("checkouts"), |path| {
+            path.file_name()
+                .is_some_and(|n| n.to_string_lossy() == short)
+                && path.join("Cargo.toml").is_file()
+        })
+    } else {
+        let dir_name = format!("{name}-{version}");
+        fs::read_dir(cargo_home.join("registry").join("src"))
+            .ok()
+            .and_then(|registries| {
+                registries
+                    .flatten()
+                    .map(|r| r.path().join(&dir_name))
+                    .find(|p| p.is_dir())
+            })
+    };
+
+    match dir {
+        Some(dir) => Ok(Some(LocatedDependency {
+            ecosystem: "cargo",
+            name: name.to_string(),
+            version,
+            dir,
+        })),
+        None => Err(format!(
+            "{name} {version} is in Cargo.lock but its source isn't in {}; run `cargo fetch`",
+            cargo_home.display()
+        )),
+    }
+}
+
+fn locate_npm(
+    root: &Path,
+    name: &str,
+    wanted: Option<&str>,
+) -> Result<Option<LocatedDependency>, String> {
+    let dir = root.join("node_modules").join(name);
+    let locked = npm_locked_version(root, name)?;
+    let Ok(text) = fs::read_to_string(dir.join("package.json")) else {
+        return match locked {
+            Some(version) => Err(format!(
+                "{name} {version} is in package-lock.json but not in node_modules; run `npm ci`"
+            )),
+            None => Ok(None),
+        };
+    };
+    let manifest: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("{}: {e}", dir.display()))?;
+    let version = manifest
+        .get("version")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    if let Some(locked) = locked.filter(|l| *l != version) {
+        return Err(format!(
+            "node_modules/{name} is version {version} but package-lock.json pins {locked}; \
+             run `npm ci`"
+        ));
+    }
+    if let Some(w) = wanted {
+        if w != version {
+            return Err(format!(
+                "node_modules/{name} is version {version}, not {w}; reinstall to match"
+            ));
+        }
+    }
+
+    Ok(Some(LocatedDependency {
+        ecosystem: "npm",
+        name: name.to_string(),
+        version,
+        dir,
+    }))
+}
+
+/// The version package-lock.json pins for `name`: `packages` keyed by
+/// install path (lockfile v2 and v3), or `dependencies` in v1.
+fn npm_locked_version(root: &Path, name: &str) -> Result<Option<String>, String> {
+    let path = root.join("package-lock.json");
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let lock: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+    let entry = lock
+        .get("packages")
+        .and_then(|p| p.get(format!("node_modules/{name}")))
+        .or_else(|| lock.get("dependencies").and_then(|d| d.get(name)));
+    Ok(entry
+        .and_then(|e| e.get("version"))
+        .and_then(|v| v.as_str())
+        .map(String::from))
+}
+
+fn locate_go(
+    root: &Path,
+    name: &str,
+    wanted: Option<&str>,
+) -> Result<Option<LocatedDependency>, String> {
+    let Ok(text) = fs::read_to_string(root.join("go.mod")) else {
+        return Ok(None);
+    };
+
+    let version = text.lines().find_map(|line| {
+        let line = line.trim().trim_start_matches("require").trim();
+        let mut parts = line.split_whitespace();
+        (parts.next()? == name).then(|| parts.next().map(String::from))?
+    });
+    let Some(version) = version else {
+        return Ok(None);
+    };
+    if wanted.is_some_and(|w| w != version) {
+        return Ok(None);
+    }
+
+    let mod_cache = env::var_os("GOMODCACHE")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("GOPATH").map(|p| PathBuf::from(p).join("pkg").join("mod")))
+        .or_else(|| home_dir().map(|h| h.join("go").join("pkg").join("mod")))
+        .ok_or("cannot determine GOMODCACHE")?;
+    let dir = mod_cache.join(format!("{}@{version}", go_escape(name)));
+
+    if !dir.is_dir() {
+        return Err(format!(
+            "{name} {version} is in go.mod but not in {}; run `go mod download`",
+            mod_cache.display()
+        ));
+    }
+    Ok(Some(LocatedDependency {
+        ecosystem: "go",
+        name: name.to_string(),
+        version,
+        dir,
+    }))
+}
+
+/// Go module cache path escaping: each uppercase letter becomes `!` plus its
+/// lowercase form.
+fn go_escape(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('!');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Depth-first search for the first directory under `base` (two levels deep)
+/// accepted by `pred`.
+fn find_subdir(base: &Path, pred: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    for repo in fs::read_dir(base).ok()?.flatten() {
+        let Ok(checkouts) = fs::read_dir(repo.path()) else {
+            continue;
+        };
+        for checkout in checkouts.flatten() {
+            let path = checkout.path();
+            if pred(&path) {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_go_escape() {
+        assert_eq!(
+            go_escape("github.com/BurntSushi/toml"),
+            "github.com/!burnt!sushi/toml"
+        );
+    }
+
+    #[test]
+    fn test_locate_npm_and_missing() {
+        let root = crate::test_dir::make_test_dir("dep");
+        let pkg = root.join("node_modules/@scope/widget");
+        fs::create_dir_all(&pkg).unwrap();
+        fs::write(
+            pkg.join("package.json"),
+            "{\"name\": \"@scope/widget\", \"version\": \"2.1.0\"}",
+        )
+        .unwrap();
+
+        let found = locate(&root, "@scope/widget").unwrap();
+        assert_eq!(found.ecosystem, "npm");
+        assert_eq!(found.version, "2.1.0");
+        assert_eq!(found.dir, pkg);
+        assert!(locate(&root, "@scope/widget@2.1.0").is_ok());
+        assert!(locate(&root, "@scope/widget@3.0.0").is_err());
+        assert!(locate(&root, "left-pad").is_err());
+
+        // The lockfile's version has to be the one installed
+        let lock = |version: &str| {
+            let packages = format!(
+                "{{\"node_modules/@scope/widget\": {{\"version\": \"{version}\"}}, \
+                 \"node_modules/left-pad\": {{\"version\": \"1.3.0\"}}}}"
+            );
+            let text = format!("{{\"lockfileVersion\": 3, \"packages\": {packages}}}");
+            fs::write(root.join("package-lock.json"), text).unwrap();
+        };
+        lock("2.1.0");
+        assert_eq!(locate(&root, "@scope/widget").unwrap().version, "2.1.0");
+        let err = locate(&root, "left-pad").unwrap_err();
+        assert!(err.contains("1.3.0 is in package-lock.json but not in node_modules"));
+        lock("2.2.0");
+        let err = locate(&root, "@scope/widget").unwrap_err();
+        assert!(err.contains("is version 2.1.0 but package-lock.json pins 2.2.0"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_locate_cargo_multiple_versions() {
+        let root = crate::test_dir::make_test_dir("dep_cargo");
+        fs::write(
+            root.join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"syn\"\nversion = \"1.0.109\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"syn\"\nversion = \"2.0.48\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        )
+        .unwrap();
+
+        let err = locate(&root, "syn").unwrap_err();
+        assert!(err.contains("1.0.109, 2.0.48"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+
+/// Intra-repository imports between files, as (importer, imported) pairs of
+/// root-relative paths. Imports that don't resolve to one of the given files
+/// (the standard library, third-party packages) are dropped.
+#[derive(Debug, Default, PartialEq)]
+pub struct Graph {
+    pub edges: BTreeSet<(String, String)>,
+}
+
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+/// Build the import graph for `files` (root-relative, `/`-separated), reading
+/// each supported source file under `root`.
+pub fn graph(root: &Path, files: &[String]) -> Graph {
+    let known: HashSet<&str> = files.iter().map(String::as_str).collect();
+    let go_modules = go_modules(root, files);
+    let mut graph = Graph::default();
+
+    for file in files {
+        let ext = Path::new(file)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        let targets = match ext {
+            "rs" | "py" | "go" => read(root, file).map(|src| match ext {
+                "rs" => rust_imports(file, &src, &known),
+                "py" => python_imports(file, &src, &known),
+                _ => go_imports(file, &src, &known, &go_modules),
+            }),
+            _ if JS_EXTENSIONS.contains(&ext) => {
+                read(root, file).map(|src| js_imports(file, &src, &known))
+            }
+            _ => None,
+        };
+        for target in targets.unwrap_or_default() {
+            if target != *file {
+                graph.edges.insert((file.clone(), target));
+            }
+        }
+    }
+    graph
+}
+
+fn read(root: &Path, file: &str) -> Option<String> {
+    fs::read_to_string(root.join(file)).ok()
+}
+
+/// `dir/name` without a leading slash when `dir` is the root.
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// Resolve `.` and `..` segments of a relative path against `dir`. `None`
+/// if it climbs above the root.
+fn normalize(dir: &str, relative: &str) -> Option<String> {
+    let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(segment),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn first_known(candidates: Vec<String>, known: &HashSet<&str>) -> Option<String> {
+    candidates.into_iter().find(|c| known.contains(c.as_str()))
+}
+
+/// `mod x;` declarations and `use crate::` / `super::` / `self::` paths.
+fn rust_imports(file: &str, src: &str, known: &HashSet<&str>) -> Vec<String> {
+    let module_dir = rust_module_dir(file);
+    let crate_root = {
+        let mut dir = parent(file);
+        loop {
+            if ["lib.rs", "main.rs"]
+                .iter()
+                .any(|f| known.contains(join(dir, f).as_str()))
+            {
+                break Some(dir.to_string());
+            }
+            if dir.is_empty() {
+                break None;
+            }
+            dir = parent(dir);
+        }
+    };
+    // A module's own path segments resolve under its directory
+    let module = |dir: &str, segments: &[&str]| {
+        (1..=segments.len()).rev().find_map(|n| {
+            let base = join(dir, &segments[..n].join("/"));
+            first_known(vec![format!("{base}.rs"), format!("{base}/mod.rs")], known)
+        })
+    };
+
+    let mut targets = Vec::new();
+    let mut lines = src.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        let line = line.strip_prefix("pub ").unwrap_or(line);
+        let line = line.strip_prefix("pub(crate) ").unwrap_or(line);
+        if let Some(name) = line.strip_prefix("mod ").and_then(|r| r.strip_suffix(';')) {
+            targets.extend(module(&module_dir, &[name.trim()]));
+        } else if let Some(tree) = line.strip_prefix("use ") {
+            // rustfmt spreads long groups over several lines
+            let mut tree = tree.to_string();
+            while !tree.contains(';') {
+                let Some(next) = lines.next() else {
+                    break;
+                };
+                tree.push_str(next.trim());
+            }
+            let tree = tree.split(';').next().unwrap_or_default();
+            for path in expand_use(tree) {
+                let segments: Vec<&str> = path.split("::").map(str::trim).collect();
+                let resolved = match segments.first() {
+                    Some(&"crate") => crate_root
+                        .as_deref()
+                        .and_then(|r| module(r, &segments[1..])),
+                    Some(&"self") => module(&module_dir, &segments[1..]),
+                    Some(&"super") => module(parent(&module_dir), &segments[1..]),
+                    _ => None,
+                };
+                targets.extend(resolved);
+            }
+        }
+    }
+    targets
+}
+
+/// The directory a Rust file's child modules live in.
+fn rust_module_dir(file: &str) -> String {
+    let dir = parent(file);
+    match file.rsplit('/').next() {
+        Some("mod.rs" | "lib.rs" | "main.rs") => dir.to_string(),
+        Some(name) => join(dir, name.trim_end_matches(".rs")),
+        None => dir.to_string(),
+    }
+}
+
+/// Flatten `{...}` groups, nested ones included: `crate::{a, b::{c, d}}`
+/// becomes `crate::a`, `crate::b::c` and `crate::b::d`, and `b::{self}`
+/// becomes `b`.
+fn expand_use(tree: &str) -> Vec<String> {
+    let Some((prefix, rest)) = tree.split_once('{') else {
+        let tree = tree.trim();
+        return vec![tree.strip_suffix("::self").unwrap_or(tree).to_string()];
+    };
+    let (mut depth, mut start, mut items) = (0, 0, Vec::new());
+    for (i, c) in rest.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => {
+                items.push(&rest[start..i]);
+                break;
+            }
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&rest[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .flat_map(|item| expand_use(&format!("{}{item}", prefix.trim())))
+        .collect()
+}
+
+/// `import a.b` and `from a.b import c` (absolute from the root or `src/`,
+/// or relative with leading dots).
+fn python_imports(file: &str, src: &str, known: &HashSet<&str>) -> Vec<String> {
+    let resolve = |base: &str, module: &str| {
+        let path = join(base, &module.replace('.', "/"));
+        first_known(
+            vec![format!("{path}.py"), format!("{path}/__init__.py")],
+            known,
+        )
+    };
+    let absolute = |module: &str| resolve("", module).or_else(|| resolve("src", module));
+
+    let mut targets = Vec::new();
+    for line in src.lines() {
+        let line = line.trim();
+        if let Some(modules) = line.strip_prefix("import ") {
+            for module in modules.split(',') {
+                let name = module.split_whitespace().next().unwrap_or("");
+                targets.extend(absolute(name));
+            }
+        } else if let Some(rest) = line.strip_prefix("from ") {
+            let Some((module, names)) = rest.split_once(" import ") else {
+                continue;
+            };
+            let module = module.trim();
+            let dots = module.chars().take_while(|c| *c == '.').count();
+            let base = if dots > 0 {
+                let mut dir = parent(file);
+                for _ in 1..dots {
+                    dir = parent(dir);
+                }
+                Some(dir)
+            } else {
+                None
+            };
+            let module = &module[dots..];
+            // `from pkg import name` may name a submodule rather than an attribute
+            for name in names.trim_matches(['(', ')', ' ']).split(',') {
+                let name = name.split_whitespace().next().unwrap_or("");
+                if name.is_empty() || name == "*" {
+                    continue;
+                }
+                let sub = if module.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{module}.{name}")
+                };
+                let found = match base {
+                    Some(dir) => resolve(dir, &sub),
+                    None => absolute(&sub),
+                };
+                if found.is_some() {
+                    targets.extend(found);
+                }
+            }
+            if !module.is_empty() {
+                targets.extend(match base {
+                    Some(dir) => resolve(dir, module),
+                    None => absolute(module),
+                });
+            }
+        }
+    }
+    targets
+}
+
+/// Relative `import`/`export ... from`, `require()` and `import()`
+/// specifiers, resolved with the usual extension and `index` fallbacks.
+fn js_imports(file: &str, src: &str, known: &HashSet<&str>) -> Vec<String> {
+    let mut targets = Vec::new();
+    for marker in ["from ", "import ", "require(", "import("] {
+        for (at, _) in src.match_indices(marker) {
+            let rest = src[at + marker.len()..].trim_start();
+            let Some(quote) = rest
+                .chars()
+                .next()
+                .filter(|c| matches!(c, '\'' | '"' | '`'))
+            else {
+                continue;
+            };
+            let Some(spec) = rest[1..].split(quote).next() else {
+                continue;
+            };
+            if !spec.starts_with('.') {
+                continue;
+            }
+            let Some(base) = normalize(parent(file), spec) else {
+                continue;
+            };
+            // TypeScript sources import their compiled `.js` names
+            let stem = [".js", ".jsx", ".mjs", ".cjs"]
+                .iter()
+                .find_map(|e| base.strip_suffix(e))
+                .unwrap_or(&base);
+            let mut candidates = vec![base.clone()];
+            for ext in JS_EXTENSIONS {
+                candidates.push(format!("{stem}.{ext}"));
+            }
+            for ext in JS_EXTENSIONS {
+                candidates.push(format!("{base}/index.{ext}"));
+            }
+            targets.extend(first_known(candidates, known));
+        }
+    }
+    targets
+}
+
+/// Module path declared by each `go.mod` among `files`, keyed by its
+/// directory.
+fn go_modules(root: &Path, files: &[String]) -> BTreeMap<String, String> {
+    files
+        .iter()
+        .filter(|f| *f == "go.mod" || f.ends_with("/go.mod"))
+        .filter_map(|f| {
+            let text = read(root, f)?;
+            let module = text
+                .lines()
+                .find_map(|l| l.trim().strip_prefix("module "))?
+                .trim()
+                .to_string();
+            Some((parent(f).to_string(), module))
+        })
+        .collect()
+}
+
+/// Imports of packages inside the file's own module, linked to every
+/// non-test file of the imported package.
+fn go_imports(
+    file: &str,
+    src: &str,
+    known: &HashSet<&str>,
+    modules: &BTreeMap<String, String>,
+) -> Vec<String> {
+    // The innermost go.mod above the file
+    let Some((mod_dir, module)) = modules
+        .iter()
+        .filter(|(dir, _)| dir.is_empty() || file.starts_with(&format!("{dir}/")))
+        .max_by_key(|(dir, _)| dir.len())
+    else {
+        return Vec::new();
+    };
+
+    let mut imports = Vec::new();
+    let mut in_block = false;
+    for line in src.lines() {
+        let line = line.trim();
+        if in_block {
+            if line.starts_with(')') {
+                in_block = false;
+                continue;
+            }
+            imports.extend(line.split('"').nth(1));
+        } else if let Some(rest) = line.strip_prefix("import") {
+            let rest = rest.trim();
+            if rest.starts_with('(') {
+                in_block = true;
+            } else {
+                imports.extend(rest.split('"').nth(1));
+            }
+        }
+    }
+
+    let mut targets = Vec::new();
+    for import in imports {
+        let Some(sub) = import
+            .strip_prefix(module.as_str())
+            .filter(|s| s.is_empty() || s.starts_with('/'))
+        else {
+            continue;
+        };
+        let dir = join(mod_dir, sub.trim_start_matches('/'));
+        targets.extend(
+            known
+                .iter()
+                .filter(|k| parent(k) == dir && k.ends_with(".go") && !k.ends_with("_test.go"))
+                .map(|k| k.to_string()),
+        );
+    }
+    targets.sort();
+    targets
+}
+
+impl Graph {
+    /// Files that appear in at least one edge, sorted.
+    fn nodes(&self) -> BTreeSet<&str> {
+        self.edges
+            .iter()
+            .flat_map(|(a, b)| [a.as_str(), b.as_str()])
+            .collect()
+    }
+
+    /// Graphviz DOT.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph deps {\n  rankdir=LR;\n");
+        for (from, to) in &self.edges {
+            let _ = writeln!(out, "  \"{}\" -> \"{}\";", dot_escape(from), dot_escape(to));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// A Mermaid flowchart, with nodes declared once and labeled by path.
+    pub fn to_mermaid(&self) -> String {
+        let nodes = self.nodes();
+        let ids: BTreeMap<&str, usize> = nodes.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+        let mut out = String::from("graph LR\n");
+        for (node, id) in &ids {
+            let _ = writeln!(out, "  n{id}[\"{}\"]", node.replace('"', "#quot;"));
+        }
+        for (from, to) in &self.edges {
+            let _ = writeln!(out, "  n{} --> n{}", ids[from.as_str()], ids[to.as_str()]);
+        }
+        out
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(graph: &Graph) -> Vec<(&str, &str)> {
+        graph
+            .edges
+            .iter()
+            .map(|(a, b)| (a.as_str(), b.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_import_graph() {
+        let files = [
+            ("src/main.rs", "mod cache;\nmod git;\nuse std::fs;\n"),
+            ("src/cache.rs", "use crate::git::git_output;\n"),
+            (
+                "src/git.rs",
+                "use crate::{\n    cache::{self, Cache},\n    net::{http::get, missing},\n};\n",
+            ),
+            ("src/net/mod.rs", "pub mod http;\n"),
+            ("src/net/http.rs", ""),
+            (
+                "web/app.ts",
+                "import { x } from './util.js';\nimport React from 'react';\n",
+            ),
+            ("web/util.ts", "export const x = require(\"./lib\");\n"),
+            ("web/lib/index.js", ""),
+            ("pkg/core.py", "from . import helpers\nimport os\n"),
+            ("pkg/helpers.py", "from pkg.models import User\n"),
+            ("pkg/models.py", ""),
+            ("go.mod", "module example.com/svc\n\ngo 1.22\n"),
+            (
+                "cmd/main.go",
+                "import (\n\t\"fmt\"\n\tstore \"example.com/svc/store\"\n)\n",
+            ),
+            ("store/db.go", ""),
+            ("store/db_test.go", ""),
+        ];
+        let root = crate::test_dir::make_test_dir_with("deps", &files);
+        let names: Vec<String> = files.iter().map(|(p, _)| p.to_string()).collect();
+
+        let graph = graph(&root, &names);
+        assert_eq!(
+            edges(&graph),
+            vec![
+                ("cmd/main.go", "store/db.go"),
+                ("pkg/core.py", "pkg/helpers.py"),
+                ("pkg/helpers.py", "pkg/models.py"),
+                ("src/cache.rs", "src/git.rs"),
+                ("src/git.rs", "src/cache.rs"),
+                ("src/git.rs", "src/net/http.rs"),
+                ("src/git.rs", "src/net/mod.rs"),
+                ("src/main.rs", "src/cache.rs"),
+                ("src/main.rs", "src/git.rs"),
+                ("src/net/mod.rs", "src/net/http.rs"),
+                ("web/app.ts", "web/util.ts"),
+                ("web/util.ts", "web/lib/index.js"),
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_render() {
+        let mut graph = Graph::default();
+        graph
+            .edges
+            .insert(("src/main.rs".to_string(), "src/git.rs".to_string()));
+        assert_eq!(
+            graph.to_dot(),
+            "digraph deps {\n  rankdir=LR;\n  \"src/main.rs\" -> \"src/git.rs\";\n}\n"
+        );
+        assert_eq!(
+            graph.to_mermaid(),
+            "graph LR\n  n0[\"src/git.rs\"]\n  n1[\"src/main.rs\"]\n  n1 --> n0\n"
+        );
+    }
+}
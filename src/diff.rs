@@ -0,0 +1,252 @@
+use crate::git::git_output;
+use std::path::Path;
+use std::process::Command;
+
+/// One side of a comparison.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Side {
+    Worktree,
+    Index,
+    Rev(String),
+}
+
+impl std::str::FromStr for Side {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "worktree" | "working" => Ok(Side::Worktree),
+            "index" | "staged" => Ok(Side::Index),
+            "head" => Ok(Side::Rev("HEAD".to_string())),
+            _ if s.is_empty() => Err("empty side".to_string()),
+            _ => Ok(Side::Rev(s.to_string())),
+        }
+    }
+}
+
+/// One file's patch.
+#[derive(Debug, PartialEq)]
+pub struct FilePatch {
+    pub path: String,
+    pub patch: String,
+}
+
+/// `git diff` arguments comparing `from` to `to`. Git only diffs a commit or
+/// the index against something "newer", so other orders are reversed.
+fn diff_args(from: &Side, to: &Side) -> Result<Vec<String>, String> {
+    let (old, new, reverse) = match (from, to) {
+        (Side::Worktree, Side::Index | Side::Rev(_)) | (Side::Index, Side::Rev(_)) => {
+            (to, from, true)
+        }
+        _ => (from, to, false),
+    };
+    let mut args = Vec::new();
+    if reverse {
+        args.push("-R".to_string());
+    }
+    match (old, new) {
+        (Side::Rev(a), Side::Rev(b)) => args.extend([a.clone(), b.clone()]),
+        (Side::Rev(a), Side::Index) => args.extend(["--cached".to_string(), a.clone()]),
+        (Side::Rev(a), Side::Worktree) => args.push(a.clone()),
+        (Side::Index, Side::Worktree) => {}
+        _ => return Err("--from and --to must be different sides".to_string()),
+    }
+    Ok(args)
+}
+
+/// Per-file patches from `from` to `to` for the part of the repository under
+/// `root`. When `to` is the worktree, untracked (non-ignored) files are
+/// included as additions.
+pub fn changes(root: &Path, from: &Side, to: &Side) -> Result<Vec<FilePatch>, String> {
+    for side in [from, to] {
+        if let Side::Rev(rev) = side {
+            if git_output(
+                root,
+                &["rev-parse", "--verify", &format!("{rev}^{{commit}}")],
+            )
+            .is_none()
+            {
+                return Err(format!("unknown revision '{rev}'"));
+            }
+        }
+    }
+
+    let mut args = vec![
+        "diff".to_string(),
+        "--relative".to_string(),
+        "--no-prefix".to_string(),
+        "--no-color".to_string(),
+        "--no-ext-diff".to_string(),
+    ];
+    args.extend(diff_args(from, to)?);
+    args.push("--".to_string());
+    let mut patches = split_patches(&run_diff(root, &args)?);
+
+    if *to == Side::Worktree {
+        let untracked =
+            git_output(root, &["ls-files", "--others", "--exclude-standard"]).unwrap_or_default();
+        for path in untracked.lines() {
+            let args = [
+                "diff",
+                "--no-index",
+                "--no-prefix",
+                "--no-color",
+                "--",
+                "/dev/null",
+                path,
+            ];
+            patches.extend(split_patches(&run_diff(root, &args)?));
+        }
+        patches.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    Ok(patches)
+}
+
+/// What stash entry `n` changed relative to the commit it was made on,
+/// including untracked files stashed with `--include-untracked`.
+pub fn stash_changes(root: &Path, n: usize) -> Result<Vec<FilePatch>, String> {
+    let stash = format!("stash@{{{n}}}");
+    if git_output(root, &["rev-parse", "--verify", "--quiet", &stash]).is_none() {
+        return Err(format!("no stash entry {n}"));
+    }
+    let mut patches = changes(
+        root,
+        &Side::Rev(format!("{stash}^1")),
+        &Side::Rev(stash.clone()),
+    )?;
+
+    // Untracked files are kept in a parentless third commit
+    let untracked = format!("{stash}^3");
+    if git_output(root, &["rev-parse", "--verify", "--quiet", &untracked]).is_some() {
+        let args = [
+            "diff-tree",
+            "-p",
+            "--root",
+            "--no-commit-id",
+            "--relative",
+            "--no-prefix",
+            "--no-color",
+            &untracked,
+            "--",
+        ];
+        patches.extend(split_patches(&run_diff(root, &args)?));
+        patches.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    Ok(patches)
+}
+
+/// Run a `git diff` command, which exits 1 when `--no-index` finds changes.
+fn run_diff<S: AsRef<std::ffi::OsStr>>(root: &Path, args: &[S]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    if !matches!(output.status.code(), Some(0 | 1)) {
+        return Err(format!(
+            "git diff: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Split `git diff --no-prefix` output into one patch per file, named by the
+/// new path (the old one for deletions).
+fn split_patches(diff: &str) -> Vec<FilePatch> {
+    let mut patches = Vec::new();
+    let mut current = String::new();
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") && !current.is_empty() {
+            patches.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        patches.push(current);
+    }
+
+    patches
+        .into_iter()
+        .map(|patch| FilePatch {
+            path: patch_path(&patch),
+            patch,
+        })
+        .collect()
+}
+
+fn patch_path(patch: &str) -> String {
+    let mut old = None;
+    for line in patch.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            if path != "/dev/null" {
+                return path.to_string();
+            }
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            old = Some(path.to_string());
+        } else if line.starts_with("@@") {
+            break;
+        }
+    }
+    if let Some(path) = old.filter(|p| p != "/dev/null") {
+        return path;
+    }
+    // Binary or mode-only changes have no ---/+++ lines: "diff --git p p"
+    let header = patch.lines().next().unwrap_or("");
+    let both = header.strip_prefix("diff --git ").unwrap_or(header);
+    both[..both.len() / 2].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_args() {
+        let head = Side::Rev("HEAD".to_string());
+        assert_eq!(diff_args(&head, &Side::Worktree).unwrap(), ["HEAD"]);
+        assert_eq!(
+            diff_args(&head, &Side::Index).unwrap(),
+            ["--cached", "HEAD"]
+        );
+        assert!(diff_args(&Side::Index, &Side::Worktree).unwrap().is_empty());
+        assert_eq!(diff_args(&Side::Worktree, &Side::Index).unwrap(), ["-R"]);
+        assert_eq!(
+            diff_args(&Side::Index, &head).unwrap(),
+            ["-R", "--cached", "HEAD"]
+        );
+        let base = Side::Rev("stash@{1}^1".to_string());
+        let stash = Side::Rev("stash@{1}".to_string());
+        assert_eq!(
+            diff_args(&base, &stash).unwrap(),
+            ["stash@{1}^1", "stash@{1}"]
+        );
+        assert!(diff_args(&Side::Index, &Side::Index).is_err());
+        assert_eq!("staged".parse(), Ok(Side::Index));
+        assert_eq!("HEAD".parse(), Ok(head));
+    }
+
+    #[test]
+    fn test_split_patches() {
+        let diff = "diff --git src/a.rs src/a.rs\n\
+                    index 1..2 100644\n\
+                    --- src/a.rs\n\
+                    +++ src/a.rs\n\
+                    @@ -1 +1 @@\n\
+                    -old\n\
+                    +new\n\
+                    diff --git gone.txt gone.txt\n\
+                    deleted file mode 100644\n\
+                    --- gone.txt\n\
+                    +++ /dev/null\n\
+                    @@ -1 +0,0 @@\n\
+                    -bye\n\
+                    diff --git logo.png logo.png\n\
+                    Binary files logo.png and logo.png differ\n";
+        let patches = split_patches(diff);
+        let paths: Vec<&str> = patches.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(paths, ["src/a.rs", "gone.txt", "logo.png"]);
+        assert!(patches[0].patch.ends_with("+new\n"));
+        assert!(patches[1].patch.starts_with("diff --git gone.txt"));
+    }
+}
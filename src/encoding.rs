@@ -0,0 +1,58 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+/// `bytes` as UTF-8: UTF-16 recognized by its byte order mark, and text in a
+/// legacy encoding such as Latin-1 or Shift_JIS guessed from its bytes, are
+/// transcoded. Valid UTF-8, binary data and anything that doesn't decode
+/// cleanly are returned unchanged.
+pub fn to_utf8(bytes: Vec<u8>) -> Vec<u8> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(&bytes) {
+        if encoding == UTF_8 {
+            return bytes;
+        }
+        return decode(encoding, &bytes[bom_len..]).unwrap_or(bytes);
+    }
+    // NUL bytes mean binary; leave those for the binary check
+    if std::str::from_utf8(&bytes).is_ok() || bytes.contains(&0) {
+        return bytes;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(&bytes, true);
+    let encoding = detector.guess(None, true);
+    decode(encoding, &bytes).unwrap_or(bytes)
+}
+
+fn decode(encoding: &'static Encoding, bytes: &[u8]) -> Option<Vec<u8>> {
+    let (text, malformed) = encoding.decode_without_bom_handling(bytes);
+    (!malformed).then(|| text.into_owned().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_utf8() {
+        let utf16le: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("héllo\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(to_utf8(utf16le), "héllo\n".as_bytes());
+        let utf16be: Vec<u8> = [0xFE, 0xFF]
+            .into_iter()
+            .chain("hi".encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        assert_eq!(to_utf8(utf16be), b"hi");
+
+        // Latin-1 (read as windows-1252)
+        assert_eq!(
+            to_utf8(b"caf\xe9 cr\xe8me br\xfbl\xe9e\n".to_vec()),
+            "café crème brûlée\n".as_bytes()
+        );
+
+        // UTF-8, with or without a BOM, and binary data are left alone
+        assert_eq!(to_utf8("ü\n".as_bytes().to_vec()), "ü\n".as_bytes());
+        assert_eq!(to_utf8(b"\xEF\xBB\xBFx".to_vec()), b"\xEF\xBB\xBFx");
+        assert_eq!(to_utf8(b"\x89PNG\0\xff".to_vec()), b"\x89PNG\0\xff");
+    }
+}
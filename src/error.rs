@@ -0,0 +1,49 @@
+use crate::status;
+use std::path::PathBuf;
+
+/// What can go wrong while gathering, with the path or pattern involved.
+/// Bad selections stop the run; unreadable files and directories are left
+/// out and reported, and the run ends with [`Error::exit_code`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The directory to gather from doesn't exist or can't be read.
+    #[error("Cannot read {}: {source}", path.display())]
+    Root {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// A `--glob` or `--exclude` pattern that isn't a valid glob.
+    #[error("Invalid {flag} pattern: {source}")]
+    Pattern {
+        flag: &'static str,
+        pattern: String,
+        #[source]
+        source: globset::Error,
+    },
+    /// A `--package` or `--include-package` that names nothing.
+    #[error("{0}")]
+    Package(String),
+    /// A directory the walk couldn't list, or an ignore file it couldn't
+    /// parse.
+    #[error("{0}")]
+    Walk(#[from] ignore::Error),
+    /// A file that passed the filters but couldn't be read.
+    #[error("{}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+impl Error {
+    /// The exit code for a run that hit this error, as `gather` uses it: 4
+    /// for a bad selection, 3 (a warning) for input that was left out.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Root { .. } | Error::Pattern { .. } | Error::Package(_) => status::ERROR,
+            Error::Walk(_) | Error::Read { .. } => status::WARNINGS,
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+/// How token counts are estimated, chosen once per run with `--estimator`
+/// or `--tokenizer`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Estimator {
+    /// Length at a per-language characters-per-token ratio
+    #[default]
+    Chars,
+    /// Word, number and punctuation boundaries
+    Words,
+    /// A real BPE tokenizer
+    Bpe(Tokenizer),
+}
+
+impl std::str::FromStr for Estimator {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "chars" => Ok(Estimator::Chars),
+            "words" => Ok(Estimator::Words),
+            "bpe" => Ok(Estimator::Bpe(Tokenizer::default())),
+            _ => Err(format!(
+                "Unknown estimator: {s}. Use 'chars', 'words' or 'bpe'."
+            )),
+        }
+    }
+}
+
+/// The BPE encoding `--tokenizer` counts with: cl100k_base (GPT-4,
+/// GPT-3.5) or o200k_base (GPT-4o and later).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Tokenizer {
+    #[default]
+    Cl100kBase,
+    O200kBase,
+}
+
+impl std::str::FromStr for Tokenizer {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cl100k_base" | "cl100k" => Ok(Tokenizer::Cl100kBase),
+            "o200k_base" | "o200k" => Ok(Tokenizer::O200kBase),
+            _ => Err(format!(
+                "Unknown tokenizer: {s}. Use 'cl100k_base' or 'o200k_base'."
+            )),
+        }
+    }
+}
+
+impl Tokenizer {
+    /// The encoding, loaded on first use.
+    fn bpe(self) -> &'static CoreBPE {
+        match self {
+            Tokenizer::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+            Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        }
+    }
+}
+
+static ESTIMATOR: OnceLock<Estimator> = OnceLock::new();
+
+/// Use `estimator` for every estimate in this run. Only the first call has
+/// an effect.
+pub fn configure(estimator: Estimator) {
+    let _ = ESTIMATOR.set(estimator);
+}
+
+/// Estimated tokens in `text` written in `lang`, by the configured
+/// estimator.
+pub fn estimate(text: &str, lang: &str) -> usize {
+    match ESTIMATOR.get().copied().unwrap_or_default() {
+        Estimator::Chars => calibrated(text, lang),
+        Estimator::Words => words(text),
+        Estimator::Bpe(tokenizer) => tokenizer.bpe().encode_ordinary(text).len(),
+    }
+}
+
+/// Average characters per token for text in `lang` (a `lang_tag` name, empty
+/// for prose and unknown files), measured against cl100k_base.
+/// Punctuation-dense formats split into more tokens than prose: JSON runs
+/// about 3 characters per token, English text about 4.
+pub fn chars_per_token(lang: &str) -> f64 {
+    match lang {
+        "json" => 3.0,
+        "html" | "xml" | "css" | "scss" => 3.4,
+        "bash" | "zsh" | "fish" | "dockerfile" | "hcl" | "nix" | "toml" | "yaml" | "sql" => 3.5,
+        "rust" | "go" | "c" | "cpp" | "java" | "kotlin" | "swift" | "javascript" | "typescript"
+        | "tsx" | "jsx" | "lua" | "zig" | "r" => 3.8,
+        _ => 4.0,
+    }
+}
+
+/// Estimated tokens in `text` written in `lang`: ASCII at the language's
+/// ratio, CJK ideographs, kana and hangul at about one token each, and other
+/// non-ASCII characters (accented Latin, Cyrillic, emoji) at about two
+/// characters per token.
+pub fn calibrated(text: &str, lang: &str) -> usize {
+    let (mut ascii, mut cjk, mut other) = (0usize, 0usize, 0usize);
+    for c in text.chars() {
+        if c.is_ascii() {
+            ascii += 1;
+        } else if is_cjk(c) {
+            cjk += 1;
+        } else {
+            other += 1;
+        }
+    }
+    let tokens = ascii as f64 / chars_per_token(lang) + cjk as f64 + other as f64 / 2.0;
+    tokens.ceil() as usize
+}
+
+/// Estimated tokens in `text` from its word, number and punctuation
+/// boundaries, the way BPE tokenizers split it: a word is a token (long
+/// ones and each camelCase part may take more), digits go in groups of
+/// three, punctuation runs pair up, a line break and the indentation after
+/// it cost one, and CJK characters one each.
+pub fn words(text: &str) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if is_cjk(c) {
+            tokens += 1;
+            i += 1;
+        } else if c.is_alphabetic() {
+            let mut piece = i;
+            while i < chars.len() && chars[i].is_alphabetic() && !is_cjk(chars[i]) {
+                // A lowercase-to-uppercase step starts a new piece
+                if i > piece && chars[i].is_uppercase() && chars[i - 1].is_lowercase() {
+                    tokens += word_tokens(i - piece);
+                    piece = i;
+                }
+                i += 1;
+            }
+            tokens += word_tokens(i - piece);
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            tokens += (i - start).div_ceil(3);
+        } else if c == '\n' || c == '\r' {
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            tokens += 1;
+        } else if c.is_whitespace() {
+            while i < chars.len() && chars[i].is_whitespace() && chars[i] != '\n' {
+                i += 1;
+            }
+            // A single space rides along with the next word
+            if i - start > 1 {
+                tokens += 1;
+            }
+        } else if c == '_' {
+            // Joins identifier pieces without a token of its own
+            i += 1;
+        } else {
+            while i < chars.len() && is_punctuation(chars[i]) {
+                i += 1;
+            }
+            let run = (i - start).max(1);
+            i = start + run;
+            tokens += run.div_ceil(3);
+        }
+    }
+    tokens
+}
+
+fn word_tokens(len: usize) -> usize {
+    1 + len.saturating_sub(1) / 10
+}
+
+fn is_punctuation(c: char) -> bool {
+    !(c.is_alphanumeric() || c.is_whitespace() || c == '_')
+}
+
+/// Estimated tokens in a file of `bytes` bytes in `lang`, without reading
+/// it (assumes mostly ASCII content).
+pub fn from_size(bytes: u64, lang: &str) -> usize {
+    (bytes as f64 / chars_per_token(lang)).ceil() as usize
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF       // Hiragana, Katakana
+        | 0x3400..=0x4DBF     // CJK Extension A
+        | 0x4E00..=0x9FFF     // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF     // Hangul syllables
+        | 0xF900..=0xFAFF     // CJK Compatibility Ideographs
+        | 0xFF00..=0xFFEF     // Fullwidth forms
+        | 0x20000..=0x2FA1F   // CJK Extensions B+
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibrated() {
+        assert_eq!(calibrated("", "rust"), 0);
+        assert_eq!(calibrated("abcd", ""), 1);
+        // The same 40 characters cost more as JSON than as prose
+        let text = "x".repeat(40);
+        assert_eq!(calibrated(&text, "markdown"), 10);
+        assert_eq!(calibrated(&text, "rust"), 11);
+        assert_eq!(calibrated(&text, "json"), 14);
+        // Each ideograph is about a token, where bytes/4 would say 1.5
+        assert_eq!(calibrated("日本語のテキスト", "markdown"), 8);
+        assert_eq!(calibrated("café", ""), 2);
+        assert_eq!(from_size(270, "json"), 90);
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words(""), 0);
+        assert_eq!(words("hello world"), 2);
+        assert_eq!(words("fn estimate_tokens(path: &Path) -> usize {"), 12);
+        assert_eq!(words("parseHttpResponse"), 3);
+        assert_eq!(words("1234567"), 3);
+        assert_eq!(words("a\n    b"), 3);
+        assert_eq!(words("日本語"), 3);
+    }
+
+    #[test]
+    fn test_estimators_parse() {
+        assert_eq!("words".parse::<Estimator>(), Ok(Estimator::Words));
+        assert_eq!(
+            "BPE".parse::<Estimator>(),
+            Ok(Estimator::Bpe(Tokenizer::Cl100kBase))
+        );
+        assert!("bytes".parse::<Estimator>().is_err());
+        assert_eq!("o200k_base".parse(), Ok(Tokenizer::O200kBase));
+        assert!("gpt2".parse::<Tokenizer>().is_err());
+    }
+}
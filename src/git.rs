@@ -0,0 +1,462 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Run a git command in `root` and return its trimmed stdout, or `None` if git
+/// is unavailable, the directory isn't a repository, or the command fails.
+pub fn git_output(root: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// A submodule declared in `.gitmodules`.
+pub struct Submodule {
+    /// Path relative to the superproject root, using `/` separators.
+    pub path: String,
+    /// Whether the submodule has been checked out (`git submodule update --init`).
+    pub initialized: bool,
+}
+
+/// Submodules declared in `root/.gitmodules`, in file order. Parsed directly
+/// so it works without git on PATH.
+pub fn submodules(root: &Path) -> Vec<Submodule> {
+    let Ok(text) = fs::read_to_string(root.join(".gitmodules")) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            if key.trim() != "path" {
+                return None;
+            }
+            let path = value.trim().trim_end_matches('/').to_string();
+            let initialized = root.join(&path).join(".git").exists();
+            Some(Submodule { path, initialized })
+        })
+        .collect()
+}
+
+/// Size in bytes of the object a Git LFS pointer file stands for, or `None`
+/// if `bytes` isn't an LFS pointer.
+pub fn lfs_pointer_size(bytes: &[u8]) -> Option<u64> {
+    // Pointers are tiny text files: a version line, then oid and size
+    if bytes.len() > 1024 {
+        return None;
+    }
+    let text = std::str::from_utf8(bytes).ok()?;
+    let mut lines = text.lines();
+    if !lines
+        .next()?
+        .starts_with("version https://git-lfs.github.com/spec/")
+    {
+        return None;
+    }
+    let mut oid = false;
+    let mut size = None;
+    for line in lines {
+        if line.starts_with("oid sha256:") {
+            oid = true;
+        } else if let Some(n) = line.strip_prefix("size ") {
+            size = n.trim().parse().ok();
+        }
+    }
+    size.filter(|_| oid)
+}
+
+/// Fetch the object behind an LFS pointer with `git lfs smudge`, run in
+/// `dir` so it finds the right repository and remote.
+pub fn lfs_smudge(dir: &Path, pointer: &[u8]) -> Option<Vec<u8>> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["lfs", "smudge"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    // Pointers are far smaller than a pipe buffer, so this can't block
+    child.stdin.take()?.write_all(pointer).ok()?;
+    let output = child.wait_with_output().ok()?;
+    output.status.success().then_some(output.stdout)
+}
+
+/// Write the files of `rev` under `dir` (the subtree `dir` covers, when it's
+/// a subdirectory of the repository) into `dest`, reading blobs straight
+/// from the object database so the worktree and index are untouched. Only
+/// paths for which `keep` returns true are written; symlinks and submodule
+/// entries are left out. Blobs for which `unread` (given the path and size)
+/// returns true aren't read: they're written as empty files of their size,
+/// which the size check then skips. Returns the number of files written.
+pub fn export_tree(
+    dir: &Path,
+    rev: &str,
+    dest: &Path,
+    keep: &dyn Fn(&str) -> bool,
+    unread: &dyn Fn(&str, u64) -> bool,
+) -> Result<usize, String> {
+    let commit = git_output(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ],
+    )
+    .ok_or_else(|| format!("--at {rev}: not a revision in {}", dir.display()))?;
+
+    // ls-tree lists paths relative to `dir` and stays within it
+    let listing = git_bytes(dir, &["ls-tree", "-r", "-l", "-z", &commit])?;
+    let mut blobs = Vec::new();
+    let mut stand_ins = 0;
+    for entry in listing.split(|b| *b == 0) {
+        let entry = String::from_utf8_lossy(entry);
+        let Some((meta, path)) = entry.split_once('\t') else {
+            continue;
+        };
+        // The size is padded with spaces to line up
+        let mut fields = meta.split_whitespace();
+        let (Some(mode), Some("blob"), Some(oid), Some(size)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if mode == "120000" || !keep(path) {
+            continue;
+        }
+        match size.parse::<u64>() {
+            Ok(size) if unread(path, size) => {
+                write_stand_in(dest, path, size)?;
+                stand_ins += 1;
+            }
+            _ => blobs.push((oid.to_string(), path.to_string())),
+        }
+    }
+    Ok(stand_ins + write_blobs(dir, &blobs, dest, unread)?)
+}
+
+/// Like [`export_tree`], but write the index versions of the files with
+/// staged changes (added, copied, modified or renamed) under `dir`.
+pub fn export_staged(
+    dir: &Path,
+    dest: &Path,
+    keep: &dyn Fn(&str) -> bool,
+    unread: &dyn Fn(&str, u64) -> bool,
+) -> Result<usize, String> {
+    let names = git_bytes(
+        dir,
+        &[
+            "diff",
+            "--cached",
+            "--relative",
+            "--name-only",
+            "-z",
+            "--diff-filter=ACMR",
+        ],
+    )?;
+    let staged: HashSet<String> = names
+        .split(|b| *b == 0)
+        .filter(|n| !n.is_empty())
+        .map(|n| String::from_utf8_lossy(n).into_owned())
+        .collect();
+
+    let listing = git_bytes(dir, &["ls-files", "--stage", "-z"])?;
+    let mut blobs = Vec::new();
+    for entry in listing.split(|b| *b == 0) {
+        let entry = String::from_utf8_lossy(entry);
+        let Some((meta, path)) = entry.split_once('\t') else {
+            continue;
+        };
+        let mut fields = meta.split(' ');
+        let (Some(mode), Some(oid), Some("0")) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if !matches!(mode, "120000" | "160000") && staged.contains(path) && keep(path) {
+            blobs.push((oid.to_string(), path.to_string()));
+        }
+    }
+    write_blobs(dir, &blobs, dest, unread)
+}
+
+/// Paths under `dir` (relative to it) whose worktree contents differ from
+/// `base`: files added, modified or renamed since that commit, committed or
+/// not, plus untracked files that aren't ignored. Deleted files are left
+/// out. Sorted.
+pub fn changed_since(dir: &Path, base: &str) -> Result<Vec<String>, String> {
+    let commit = git_output(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{base}^{{commit}}"),
+        ],
+    )
+    .ok_or_else(|| format!("{base}: not a revision in {}", dir.display()))?;
+    let diff = git_bytes(
+        dir,
+        &[
+            "diff",
+            "--relative",
+            "--name-only",
+            "-z",
+            "--no-renames",
+            "--diff-filter=ACMT",
+            &commit,
+            "--",
+        ],
+    )?;
+    let untracked = git_bytes(dir, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+    let mut paths: Vec<String> = diff
+        .split(|b| *b == 0)
+        .chain(untracked.split(|b| *b == 0))
+        .filter(|n| !n.is_empty())
+        .map(|n| String::from_utf8_lossy(n).into_owned())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Run git in `dir` and return its raw stdout, or its stderr as the error.
+fn git_bytes(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {}: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Write each (object id, relative path) blob under `dest` with one
+/// `git cat-file --batch` process. Returns the number of files written.
+fn write_blobs(
+    dir: &Path,
+    blobs: &[(String, String)],
+    dest: &Path,
+    unread: &dyn Fn(&str, u64) -> bool,
+) -> Result<usize, String> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    let mut stdin = child.stdin.take().ok_or("git cat-file: no stdin")?;
+    let oids: Vec<String> = blobs.iter().map(|(oid, _)| oid.clone()).collect();
+    // Feed ids from another thread so a full stdout pipe can't deadlock us
+    let writer = thread::spawn(move || {
+        for oid in oids {
+            if writeln!(stdin, "{oid}").is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut out = BufReader::new(child.stdout.take().ok_or("git cat-file: no stdout")?);
+    let mut header = String::new();
+    for (_, path) in blobs {
+        header.clear();
+        out.read_line(&mut header)
+            .map_err(|e| format!("git cat-file: {e}"))?;
+        let size: u64 = header
+            .trim_end()
+            .rsplit(' ')
+            .next()
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| format!("git cat-file: unexpected output {header:?}"))?;
+        // Content is followed by a newline
+        if unread(path, size) {
+            io::copy(&mut (&mut out).take(size + 1), &mut io::sink())
+                .map_err(|e| format!("git cat-file: {e}"))?;
+            write_stand_in(dest, path, size)?;
+            continue;
+        }
+        let mut content = vec![0; size as usize + 1];
+        out.read_exact(&mut content)
+            .map_err(|e| format!("git cat-file: {e}"))?;
+        content.pop();
+
+        let target = dest.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {e}", parent.display()))?;
+        }
+        fs::write(&target, content).map_err(|e| format!("{}: {e}", target.display()))?;
+    }
+    let _ = writer.join();
+    let _ = child.wait();
+    Ok(blobs.len())
+}
+
+/// An empty (sparse) file of `size` bytes at `dest/path`, standing in for a
+/// blob too large to read.
+fn write_stand_in(dest: &Path, path: &str, size: u64) -> Result<(), String> {
+    let target = dest.join(path);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {e}", parent.display()))?;
+    }
+    fs::File::create(&target)
+        .and_then(|file| file.set_len(size))
+        .map_err(|e| format!("{}: {e}", target.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfs_pointer_size() {
+        let pointer = b"version https://git-lfs.github.com/spec/v1\n\
+                        oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n\
+                        size 47185920\n";
+        assert_eq!(lfs_pointer_size(pointer), Some(47_185_920));
+        assert_eq!(lfs_pointer_size(b"version 1\nsize 3\n"), None);
+        assert_eq!(
+            lfs_pointer_size(b"version https://git-lfs.github.com/spec/v1\nsize 3\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_export_tree_at_revision() {
+        let base = crate::test_dir::make_test_dir("export");
+        let repo = base.join("repo");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        };
+        if !git(&["init", "-q"]) {
+            return; // git unavailable
+        }
+        fs::write(repo.join("src/lib.rs"), "pub fn old() {}\n").unwrap();
+        fs::write(repo.join("src/skip.txt"), "x").unwrap();
+        fs::write(repo.join("README.md"), "# r\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-qm", "first"]);
+        fs::write(repo.join("src/lib.rs"), "pub fn new() {}\n").unwrap();
+        git(&["commit", "-qam", "second"]);
+
+        let dest = base.join("out");
+        let keep = |p: &str| !p.ends_with(".txt");
+        let n = export_tree(&repo.join("src"), "HEAD~1", &dest, &keep, &|_, _| false).unwrap();
+        assert_eq!(n, 1);
+        assert_eq!(
+            fs::read_to_string(dest.join("lib.rs")).unwrap(),
+            "pub fn old() {}\n"
+        );
+        assert!(!dest.join("README.md").exists());
+        assert!(export_tree(&repo, "no-such-rev", &dest, &keep, &|_, _| false).is_err());
+        // A blob over the limit is stood in for by a file of its size
+        let sized = base.join("sized");
+        assert_eq!(
+            export_tree(&repo, "HEAD", &sized, &|_| true, &|_, size| size > 4).unwrap(),
+            3
+        );
+        assert_eq!(fs::read(sized.join("src/lib.rs")).unwrap(), [0; 16]);
+        assert_eq!(
+            fs::read_to_string(sized.join("README.md")).unwrap(),
+            "# r\n"
+        );
+        // The worktree is untouched
+        assert_eq!(
+            fs::read_to_string(repo.join("src/lib.rs")).unwrap(),
+            "pub fn new() {}\n"
+        );
+
+        // Staged content wins over later worktree edits; unchanged files are left out
+        fs::write(repo.join("src/lib.rs"), "pub fn staged() {}\n").unwrap();
+        git(&["add", "src/lib.rs"]);
+        fs::write(repo.join("src/lib.rs"), "pub fn unstaged() {}\n").unwrap();
+        let staged = base.join("staged");
+        assert_eq!(
+            export_staged(&repo, &staged, &|_| true, &|_, _| false).unwrap(),
+            1
+        );
+        assert_eq!(
+            fs::read_to_string(staged.join("src/lib.rs")).unwrap(),
+            "pub fn staged() {}\n"
+        );
+        assert!(!staged.join("README.md").exists());
+
+        // Staged, unstaged and untracked changes all count; deletions don't
+        fs::write(repo.join("notes.md"), "new\n").unwrap();
+        fs::remove_file(repo.join("README.md")).unwrap();
+        assert_eq!(
+            changed_since(&repo, "HEAD").unwrap(),
+            ["notes.md", "src/lib.rs"]
+        );
+        assert_eq!(
+            changed_since(&repo, "HEAD~1").unwrap(),
+            ["notes.md", "src/lib.rs"]
+        );
+        assert_eq!(
+            changed_since(&repo.join("src"), "HEAD").unwrap(),
+            ["lib.rs"]
+        );
+        assert!(changed_since(&repo, "no-such-rev").is_err());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_submodules_parses_gitmodules() {
+        let dir = crate::test_dir::make_test_dir("gitmodules");
+        fs::create_dir_all(dir.join("vendor/lib")).unwrap();
+        fs::write(
+            dir.join("vendor/lib/.git"),
+            "gitdir: ../../.git/modules/lib\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join(".gitmodules"),
+            "[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = https://example.com/lib.git\n\
+             [submodule \"docs\"]\n\tpath = docs/\n\turl = ../docs.git\n",
+        )
+        .unwrap();
+
+        let subs = submodules(&dir);
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].path, "vendor/lib");
+        assert!(subs[0].initialized);
+        assert_eq!(subs[1].path, "docs");
+        assert!(!subs[1].initialized);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+use crate::credentials;
+use crate::manifest::sha256_hex;
+use crate::status;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ureq::tls::{Certificate, PemItem, RootCerts, TlsConfig};
+
+/// The `--ca-bundle` path, set once at startup and loaded on first use so
+/// commands that never touch the network don't fail on a bad bundle.
+static CA_BUNDLE_PATH: OnceLock<PathBuf> = OnceLock::new();
+static CA_BUNDLE: OnceLock<Result<Vec<Certificate<'static>>, String>> = OnceLock::new();
+
+/// Trust the PEM certificates in `path` instead of the built-in roots for
+/// all remote operations (for TLS-intercepting corporate proxies).
+pub fn configure_ca_bundle(path: PathBuf) {
+    let _ = CA_BUNDLE_PATH.set(path);
+}
+
+/// The configured CA bundle path, for handing to child processes like git.
+pub fn ca_bundle_path() -> Option<&'static Path> {
+    CA_BUNDLE_PATH.get().map(PathBuf::as_path)
+}
+
+fn load_pem_certs(path: &Path) -> Result<Vec<Certificate<'static>>, String> {
+    let pem = fs::read(path).map_err(|e| format!("CA bundle {}: {e}", path.display()))?;
+    let mut certs = Vec::new();
+    for item in ureq::tls::parse_pem(&pem) {
+        match item {
+            Ok(PemItem::Certificate(cert)) => certs.push(cert.to_owned()),
+            Ok(_) => {}
+            Err(e) => return Err(format!("CA bundle {}: {e}", path.display())),
+        }
+    }
+    if certs.is_empty() {
+        return Err(format!(
+            "CA bundle {}: no PEM certificates found",
+            path.display()
+        ));
+    }
+    Ok(certs)
+}
+
+/// The HTTP agent every network feature goes through. Proxies come from
+/// `HTTPS_PROXY` / `HTTP_PROXY` / `ALL_PROXY` (upper or lower case), with
+/// `NO_PROXY` exclusions; TLS trusts `--ca-bundle` when one is configured.
+pub fn agent() -> Result<ureq::Agent, String> {
+    let mut config = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(60)))
+        .http_status_as_error(false)
+        .user_agent(concat!("gather/", env!("CARGO_PKG_VERSION")))
+        .proxy(ureq::Proxy::try_from_env());
+    if let Some(path) = CA_BUNDLE_PATH.get() {
+        let certs = CA_BUNDLE.get_or_init(|| load_pem_certs(path)).clone()?;
+        config = config.tls_config(
+            TlsConfig::builder()
+                .root_certs(RootCerts::new_with_certs(&certs))
+                .build(),
+        );
+    }
+    Ok(config.build().into())
+}
+
+/// Validators for a cached response body.
+#[derive(Serialize, Deserialize)]
+struct CacheMeta {
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+/// When a host's rate limit resets, in seconds since the Unix epoch.
+#[derive(Serialize, Deserialize)]
+struct RateLimit {
+    reset: u64,
+}
+
+/// On-disk cache of GET responses under `<cache>/http`, revalidated with
+/// ETag / If-Modified-Since. Hosts that report an exhausted rate limit are
+/// not contacted again until it resets; cached bodies are served instead.
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+impl HttpCache {
+    pub fn new() -> Self {
+        HttpCache {
+            dir: crate::cache::cache_root().join("http"),
+        }
+    }
+
+    /// Fetch `url`, authenticating to GitHub/GitLab hosts with the configured
+    /// token, and return the response body. Falls back to a stale cached body
+    /// when the host is rate limited or unreachable.
+    pub fn get(&self, url: &str) -> Result<Vec<u8>, String> {
+        let key = &sha256_hex(url.as_bytes())[..32];
+        let meta_path = self.dir.join(format!("{key}.json"));
+        let body_path = self.dir.join(format!("{key}.body"));
+        let host = host_of(url);
+        let limit_path = self.dir.join(format!("ratelimit-{host}.json"));
+
+        let cached_meta: Option<CacheMeta> = fs::read_to_string(&meta_path)
+            .ok()
+            .and_then(|t| serde_json::from_str(&t).ok());
+        let cached = cached_meta.as_ref().and(fs::read(&body_path).ok());
+        let stale = |why: String| match &cached {
+            Some(body) => {
+                status::warn(format!("{why}; using cached {url}"));
+                Ok(body.clone())
+            }
+            None => Err(why),
+        };
+
+        let now = unix_now();
+        let limit: Option<RateLimit> = fs::read_to_string(&limit_path)
+            .ok()
+            .and_then(|t| serde_json::from_str(&t).ok());
+        if let Some(limit) = limit.filter(|l| l.reset > now) {
+            return stale(format!(
+                "{host} rate limit exhausted until {}",
+                crate::template::format_utc_timestamp(limit.reset)
+            ));
+        }
+
+        let credential = credentials::for_url(url);
+        let mut request = agent()?.get(url);
+        if let Some(ref credential) = credential {
+            let (name, value) = credential.header();
+            request = request.header(name, &value);
+        }
+        if cached.is_some() {
+            if let Some(meta) = &cached_meta {
+                if let Some(etag) = &meta.etag {
+                    request = request.header("if-none-match", etag);
+                }
+                if let Some(modified) = &meta.last_modified {
+                    request = request.header("if-modified-since", modified);
+                }
+            }
+        }
+
+        let mut response = match request.call() {
+            Ok(response) => response,
+            Err(e) => return stale(e.to_string()),
+        };
+        let status = response.status().as_u16();
+        let response_headers = response.headers().clone();
+        let header = |name: &str| {
+            response_headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+
+        if let Some(reset) = rate_limit_reset(status, &header, now) {
+            let _ = fs::create_dir_all(&self.dir);
+            let _ = fs::write(
+                &limit_path,
+                serde_json::to_string(&RateLimit { reset }).unwrap_or_default(),
+            );
+            if status == 403 || status == 429 {
+                return stale(format!(
+                    "{host} rate limit exhausted until {}",
+                    crate::template::format_utc_timestamp(reset)
+                ));
+            }
+        }
+
+        match status {
+            304 => cached.ok_or_else(|| format!("{url}: 304 Not Modified but nothing cached")),
+            200..=299 => {
+                let meta = CacheMeta {
+                    url: url.to_string(),
+                    etag: header("etag"),
+                    last_modified: header("last-modified"),
+                };
+                let body = response
+                    .body_mut()
+                    .with_config()
+                    .limit(64 * 1024 * 1024)
+                    .read_to_vec()
+                    .map_err(|e| e.to_string())?;
+                if meta.etag.is_some() || meta.last_modified.is_some() {
+                    let _ = fs::create_dir_all(&self.dir);
+                    let _ = fs::write(&body_path, &body);
+                    let _ = fs::write(&meta_path, serde_json::to_string(&meta).unwrap_or_default());
+                }
+                Ok(body)
+            }
+            _ => Err(
+                match credentials::status_hint(status, url_host(url), credential.as_ref()) {
+                    Some(hint) => format!("{url}: HTTP {status}, {hint}"),
+                    None => format!("{url}: HTTP {status}"),
+                },
+            ),
+        }
+    }
+}
+
+/// When the host's rate limit resets, if this response says it is exhausted:
+/// `Retry-After` on 403/429, or a zero `X-RateLimit-Remaining` (GitHub) /
+/// `RateLimit-Remaining` (GitLab) with its reset time.
+fn rate_limit_reset(status: u16, header: &dyn Fn(&str) -> Option<String>, now: u64) -> Option<u64> {
+    let number = |name: &str| header(name).and_then(|v| v.trim().parse::<u64>().ok());
+
+    if status == 403 || status == 429 {
+        if let Some(secs) = number("retry-after") {
+            return Some(now + secs);
+        }
+    }
+    let remaining = number("x-ratelimit-remaining").or_else(|| number("ratelimit-remaining"))?;
+    if remaining > 0 {
+        return None;
+    }
+    // Reset is an epoch timestamp on GitHub and GitLab; default to a minute
+    let reset = number("x-ratelimit-reset")
+        .or_else(|| number("ratelimit-reset"))
+        .unwrap_or(now + 60);
+    Some(reset.max(now))
+}
+
+/// The host part of `url`, without credentials or port.
+pub fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, r)| r);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    host.split(':').next().unwrap_or(host)
+}
+
+/// `url`'s host and port, made safe for a file name.
+fn host_of(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, r)| r);
+    rest.split(['/', '?', '#'])
+        .next()
+        .unwrap_or("")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn headers(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| map.get(name).cloned()
+    }
+
+    #[test]
+    fn test_rate_limit_reset() {
+        let now = 1_000;
+        let github = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1500"),
+        ]);
+        assert_eq!(rate_limit_reset(200, &github, now), Some(1500));
+
+        let gitlab = headers(&[("ratelimit-remaining", "12"), ("ratelimit-reset", "1500")]);
+        assert_eq!(rate_limit_reset(200, &gitlab, now), None);
+
+        let retry = headers(&[("retry-after", "30")]);
+        assert_eq!(rate_limit_reset(429, &retry, now), Some(1030));
+        assert_eq!(rate_limit_reset(200, &retry, now), None);
+    }
+
+    #[test]
+    fn test_load_pem_certs_errors() {
+        let dir = crate::test_dir::make_test_dir("ca");
+        let empty = dir.join("empty.pem");
+        fs::write(&empty, "not a certificate\n").unwrap();
+
+        assert!(load_pem_certs(&empty)
+            .unwrap_err()
+            .contains("no PEM certificates found"));
+        assert!(load_pem_certs(&dir.join("missing.pem")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(
+            host_of("https://api.github.com/repos/a/b"),
+            "api.github.com"
+        );
+        assert_eq!(host_of("http://localhost:8080/x"), "localhost_8080");
+        assert_eq!(url_host("https://user@gitlab.corp:8443/g/p"), "gitlab.corp");
+    }
+}
@@ -0,0 +1,315 @@
+use crate::manifest::{mtime_ns, sha256_hex, Manifest};
+use crate::{JsonDocument, OutputFormat};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// File contents recovered from a previous `--output` / `--manifest` pair,
+/// keyed by relative path. Only files whose recovered content matches the
+/// manifest's SHA-256 are kept, so a mis-parsed section is never reused.
+pub struct PreviousRun {
+    files: HashMap<String, ReusableFile>,
+    reused: AtomicUsize,
+}
+
+struct ReusableFile {
+    bytes: u64,
+    mtime_ns: u64,
+    content: String,
+}
+
+impl PreviousRun {
+    /// Load the previous run, or `None` if either file is missing or unreadable.
+    pub fn load(manifest_path: &Path, output_path: &Path, format: &OutputFormat) -> Option<Self> {
+        let manifest = Manifest::load(manifest_path).ok()?;
+        let output = fs::read_to_string(output_path).ok()?;
+
+        let sections = match format {
+            OutputFormat::Markdown => markdown_sections(&output),
+            OutputFormat::Xml => xml_sections(&output),
+            OutputFormat::Json => json_sections(&output),
+            // Summaries don't carry whole files to reuse
+            OutputFormat::Summary => return None,
+        };
+
+        let mut files = HashMap::new();
+        for entry in manifest.files.into_iter().filter(|e| e.included) {
+            let (Some(bytes), Some(mtime_ns), Some(sha)) =
+                (entry.bytes, entry.mtime_ns, entry.sha256)
+            else {
+                continue;
+            };
+            let Some(section) = sections.get(&entry.path) else {
+                continue;
+            };
+            let Some(content) = restore_content(section, bytes, format) else {
+                continue;
+            };
+            if sha256_hex(content.as_bytes()) == sha {
+                files.insert(
+                    entry.path,
+                    ReusableFile {
+                        bytes,
+                        mtime_ns,
+                        content,
+                    },
+                );
+            }
+        }
+
+        Some(PreviousRun {
+            files,
+            reused: AtomicUsize::new(0),
+        })
+    }
+
+    /// Previous content for `rel` if the file on disk still has the same size
+    /// and modification time.
+    pub fn reuse(&self, rel: &str, path: &Path) -> Option<String> {
+        let prev = self.files.get(rel)?;
+        let meta = fs::metadata(path).ok()?;
+        if meta.len() != prev.bytes || mtime_ns(&meta) != Some(prev.mtime_ns) {
+            return None;
+        }
+        self.reused.fetch_add(1, Ordering::Relaxed);
+        Some(prev.content.clone())
+    }
+
+    /// How many files `reuse` has served so far.
+    pub fn reused_count(&self) -> usize {
+        self.reused.load(Ordering::Relaxed)
+    }
+}
+
+/// Undo the formatter's trailing-newline handling: markdown trims trailing
+/// newlines inside the fence, XML appends one if the content lacked it. The
+/// manifest's byte count tells us which applies.
+fn restore_content(section: &str, bytes: u64, format: &OutputFormat) -> Option<String> {
+    let bytes = usize::try_from(bytes).ok()?;
+    let mut content = match format {
+        OutputFormat::Markdown => section.to_string(),
+        OutputFormat::Xml => xml_unescape(section),
+        OutputFormat::Json => section.to_string(),
+        OutputFormat::Summary => return None,
+    };
+    if content.len() == bytes + 1 && content.ends_with('\n') {
+        content.pop();
+    }
+    while content.len() < bytes {
+        content.push('\n');
+    }
+    Some(content)
+}
+
+/// File contents keyed by path from a `--format json` document, which
+/// holds them verbatim.
+fn json_sections(output: &str) -> HashMap<String, String> {
+    serde_json::from_str::<JsonDocument>(output)
+        .map(|doc| doc.files.into_iter().map(|f| (f.path, f.content)).collect())
+        .unwrap_or_default()
+}
+
+/// Split a markdown bundle into raw fenced contents keyed by path. Handles
+/// both `## `path`` headings and `--collapsible` `<summary>` blocks, with any
+/// backtick or tilde fence.
+fn markdown_sections(output: &str) -> HashMap<String, String> {
+    let mut sections = HashMap::new();
+    let mut rest = output;
+
+    loop {
+        let heading = rest.find("## `");
+        let summary = rest.find("<summary>");
+        let path = match (heading, summary) {
+            (Some(h), s) if s.is_none_or(|s| h < s) => {
+                rest = &rest[h + 4..];
+                let Some(end) = rest.find("`\n\n") else {
+                    break;
+                };
+                let path = rest[..end].to_string();
+                rest = &rest[end + 3..];
+                path
+            }
+            (_, Some(s)) => {
+                rest = &rest[s + "<summary>".len()..];
+                let Some(end) = rest.find("</summary>\n\n") else {
+                    break;
+                };
+                // Drop the " (N tokens)" suffix
+                let label = &rest[..end];
+                let path = xml_unescape(&label[..label.rfind(" (").unwrap_or(label.len())]);
+                rest = &rest[end + "</summary>\n\n".len()..];
+                path
+            }
+            _ => break,
+        };
+
+        // The opening fence line: the fence, then an optional language tag
+        let fence_len = rest
+            .find(|c: char| c != '`' && c != '~')
+            .unwrap_or(rest.len());
+        if fence_len < 3 {
+            continue;
+        }
+        let fence = &rest[..fence_len];
+        let Some(body_start) = rest.find('\n') else {
+            break;
+        };
+        rest = &rest[body_start + 1..];
+
+        // The closing fence is the one followed by the next section header
+        // or the end of a <details> block, or the last fence in the document.
+        let close = format!("\n{fence}\n");
+        let body_end = [
+            rest.find(&format!("{close}\n## `")),
+            rest.find(&format!("{close}\n</details>\n")),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .or_else(|| rest.rfind(&close));
+        let Some(body_end) = body_end else {
+            break;
+        };
+        sections.insert(path, rest[..body_end].to_string());
+        rest = &rest[body_end + close.len()..];
+    }
+
+    sections
+}
+
+/// Split an XML bundle into escaped file contents keyed by path.
+fn xml_sections(output: &str) -> HashMap<String, String> {
+    const PATH_ATTR: &str = " path=\"";
+    let mut sections = HashMap::new();
+    let mut rest = output;
+
+    // File elements open on their own line as `  <NAME path="..." ...>`.
+    // Content is escaped, so a raw `<` only ever starts markup.
+    while let Some(start) = rest.find(PATH_ATTR) {
+        let line_start = rest[..start].rfind('\n').map_or(0, |i| i + 1);
+        let name = rest[line_start..start]
+            .strip_prefix("  <")
+            .filter(|name| !name.is_empty() && !name.contains(char::is_whitespace));
+        rest = &rest[start + PATH_ATTR.len()..];
+        let Some(name) = name else {
+            continue;
+        };
+        let close = format!("  </{name}>\n");
+
+        let Some(end) = rest.find('"') else {
+            break;
+        };
+        let path = xml_unescape(&rest[..end]);
+        let Some(open_end) = rest.find(">\n") else {
+            break;
+        };
+        rest = &rest[open_end + 2..];
+        let Some(body_end) = rest.find(&close) else {
+            break;
+        };
+        sections.insert(path, rest[..body_end].to_string());
+        rest = &rest[body_end + close.len()..];
+    }
+
+    sections
+}
+
+/// The text of an escaped section, or of a `--xml-cdata` one with its CDATA
+/// splits undone.
+fn xml_unescape(text: &str) -> String {
+    if let Some(cdata) = text.strip_prefix("<![CDATA[") {
+        let cdata = cdata.strip_suffix('\n').unwrap_or(cdata);
+        let cdata = cdata.strip_suffix("]]>").unwrap_or(cdata);
+        return cdata.replace("]]]]><![CDATA[>", "]]>");
+    }
+    text.replace("&#9;", "\t")
+        .replace("&#10;", "\n")
+        .replace("&#13;", "\r")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format_json, format_markdown, format_xml, CollectedFile, FormatOptions};
+
+    fn sample_files() -> Vec<CollectedFile> {
+        vec![
+            CollectedFile {
+                relative_path: "README.md".to_string(),
+                content: "# Title\n\n```sh\nmake\n```\n\n".to_string(),
+            },
+            CollectedFile {
+                relative_path: "src/a&b \"c\".rs".to_string(),
+                content: "if a < b && c > d {}".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_markdown_sections_roundtrip() {
+        let files = sample_files();
+        for (collapsible, fence) in [(false, None), (true, Some("~~~~".to_string()))] {
+            let opts = FormatOptions {
+                collapsible,
+                fence,
+                ..Default::default()
+            };
+            let sections = markdown_sections(&format_markdown(&files, &opts));
+            for file in &files {
+                let restored = restore_content(
+                    &sections[&file.relative_path],
+                    file.content.len() as u64,
+                    &OutputFormat::Markdown,
+                );
+                assert_eq!(restored.as_deref(), Some(file.content.as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_xml_sections_roundtrip() {
+        let mut files = sample_files();
+        files.push(CollectedFile {
+            relative_path: "odd\tname.txt".to_string(),
+            content: "a ]]> b".to_string(),
+        });
+        let variants = [(None, false), (Some("document".to_string()), true)];
+        for (element, xml_cdata) in variants {
+            let opts = FormatOptions {
+                xml_file_element: element,
+                xml_cdata,
+                ..Default::default()
+            };
+            let sections = xml_sections(&format_xml(&files, &opts));
+            for file in &files {
+                let restored = restore_content(
+                    &sections[&file.relative_path],
+                    file.content.len() as u64,
+                    &OutputFormat::Xml,
+                );
+                assert_eq!(restored.as_deref(), Some(file.content.as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_sections_roundtrip() {
+        let files = sample_files();
+        let sections = json_sections(&format_json(&files, &FormatOptions::default()));
+        for file in &files {
+            let restored = restore_content(
+                &sections[&file.relative_path],
+                file.content.len() as u64,
+                &OutputFormat::Json,
+            );
+            assert_eq!(restored.as_deref(), Some(file.content.as_str()));
+        }
+        assert!(json_sections("not json").is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+/// A language `gather` recognizes: the tag its code blocks get, and the
+/// files that are written in it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Language {
+    /// Markdown language tag, also the name `--lang` takes
+    pub name: &'static str,
+    /// File extensions, without the dot
+    pub extensions: &'static [&'static str],
+    /// Whole file names, for files without a telling extension
+    pub filenames: &'static [&'static str],
+    /// Other names `--lang` accepts
+    pub aliases: &'static [&'static str],
+}
+
+const fn language(
+    name: &'static str,
+    extensions: &'static [&'static str],
+    aliases: &'static [&'static str],
+) -> Language {
+    Language {
+        name,
+        extensions,
+        filenames: &[],
+        aliases,
+    }
+}
+
+pub const LANGUAGES: &[Language] = &[
+    language("rust", &["rs"], &["rs"]),
+    language("python", &["py", "pyi"], &["py"]),
+    language("javascript", &["js", "mjs", "cjs"], &["js"]),
+    language("typescript", &["ts", "mts", "cts"], &["ts"]),
+    language("tsx", &["tsx"], &[]),
+    language("jsx", &["jsx"], &[]),
+    language("go", &["go"], &["golang"]),
+    language("ruby", &["rb"], &["rb"]),
+    language("java", &["java"], &[]),
+    language("c", &["c"], &[]),
+    language("cpp", &["cpp", "cc", "cxx", "h", "hpp"], &["c++"]),
+    language("bash", &["sh", "bash"], &["sh", "shell"]),
+    language("zsh", &["zsh"], &[]),
+    language("fish", &["fish"], &[]),
+    language("json", &["json"], &[]),
+    language("yaml", &["yaml", "yml"], &["yml"]),
+    language("toml", &["toml"], &[]),
+    language("xml", &["xml"], &[]),
+    language("html", &["html", "htm"], &[]),
+    language("css", &["css"], &[]),
+    language("scss", &["scss"], &[]),
+    language("sql", &["sql"], &[]),
+    language("markdown", &["md"], &["md"]),
+    Language {
+        name: "dockerfile",
+        extensions: &["dockerfile"],
+        filenames: &["Dockerfile", "Containerfile"],
+        aliases: &["docker"],
+    },
+    language("hcl", &["tf"], &["terraform", "tf"]),
+    language("swift", &["swift"], &[]),
+    language("kotlin", &["kt", "kts"], &["kt"]),
+    language("r", &["r"], &[]),
+    language("lua", &["lua"], &[]),
+    language("zig", &["zig"], &[]),
+    language("nix", &["nix"], &[]),
+];
+
+impl Language {
+    /// Whether `path` is written in this language, by its extension or name.
+    pub fn matches(&self, path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        self.extensions.contains(&ext) || self.filenames.contains(&name)
+    }
+}
+
+/// The language `path` is written in, if `gather` knows it.
+pub fn of(path: &Path) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|lang| lang.matches(path))
+}
+
+/// Look up a `--lang` value by name or alias, ignoring case.
+pub fn parse(name: &str) -> Result<&'static Language, String> {
+    let name = name.trim().to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.name == name || lang.aliases.contains(&name.as_str()))
+        .ok_or_else(|| {
+            let known: Vec<&str> = LANGUAGES.iter().map(|lang| lang.name).collect();
+            format!("unknown language '{name}' (known: {})", known.join(", "))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_languages() {
+        assert_eq!(of(Path::new("src/main.rs")).unwrap().name, "rust");
+        assert_eq!(of(Path::new("include/a.h")).unwrap().name, "cpp");
+        assert_eq!(
+            of(Path::new("docker/Dockerfile")).unwrap().name,
+            "dockerfile"
+        );
+        assert!(of(Path::new("Makefile")).is_none());
+
+        assert_eq!(parse("Rust").unwrap().name, "rust");
+        assert_eq!(parse("yml").unwrap().name, "yaml");
+        assert!(parse("cobol")
+            .unwrap_err()
+            .starts_with("unknown language 'cobol'"));
+
+        // Every extension, name and alias belongs to one language
+        let mut seen = std::collections::HashSet::new();
+        for lang in LANGUAGES {
+            for ext in lang.extensions {
+                assert!(seen.insert(format!(".{ext}")), "extension {ext} repeated");
+            }
+            for name in std::iter::once(&lang.name).chain(lang.aliases) {
+                assert!(seen.insert(name.to_string()), "name {name} repeated");
+            }
+        }
+    }
+}
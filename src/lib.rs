@@ -0,0 +1,3281 @@
+//! Gather files from a codebase and format them as context for AI coding
+//! agents. [`Collector`] walks and filters a tree the way `gather collect`
+//! does, [`Formatter`] renders the result as markdown, XML or JSON, and
+//! [`estimate`] holds the token estimators.
+
+use clap::Args;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::{WalkBuilder, WalkState};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+mod annotate;
+mod api;
+mod bench;
+mod binary;
+mod buffers;
+mod cache;
+pub mod cli;
+mod clipboard;
+mod compare;
+mod config;
+mod conflicts;
+mod credentials;
+mod csv;
+mod dependency;
+mod deps;
+mod diff;
+mod encoding;
+mod error;
+pub mod estimate;
+mod git;
+mod http;
+mod incremental;
+mod lang;
+mod lines;
+mod lock;
+mod manifest;
+mod migrate;
+mod natural;
+mod outline;
+mod output;
+mod priority;
+mod rank;
+mod redact;
+mod remote;
+mod remote_count;
+mod repl;
+mod schema;
+mod search;
+mod serve;
+mod session;
+mod split;
+mod sqlite;
+mod status;
+mod summarize;
+mod template;
+#[cfg(test)]
+mod test_dir;
+mod tool_schema;
+mod transcript;
+mod tree;
+mod validate;
+mod watch;
+mod workspace;
+
+pub use api::{Collector, Formatter};
+use binary::BinaryPolicy;
+pub use error::Error;
+use incremental::PreviousRun;
+use lock::Lockfile;
+use manifest::{Manifest, ManifestDiff};
+use serde::{Deserialize, Serialize};
+use summarize::{OversizeStrategy, VendoredRole};
+use template::TemplateVars;
+
+/// Selection flags shared by every subcommand that walks the tree.
+#[derive(Args, Clone, Default)]
+struct FilterArgs {
+    /// Glob patterns to include (can be specified multiple times)
+    #[arg(short = 'g', long = "glob")]
+    globs: Vec<String>,
+
+    /// Glob patterns to exclude (can be specified multiple times)
+    #[arg(short = 'e', long = "exclude")]
+    excludes: Vec<String>,
+
+    /// Don't descend into directories matching this glob, by path or by name
+    /// (e.g. node_modules or "packages/*/dist"); can be specified multiple
+    /// times
+    #[arg(long = "prune", value_name = "GLOB")]
+    prunes: Vec<String>,
+
+    /// Descend at most N levels below the root (1 keeps only the files
+    /// directly in it)
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Exclude test directories and test-named files (tests/, __tests__/,
+    /// *_test.go, *.spec.ts, test_*.py, ...)
+    #[arg(long, visible_alias = "exclude-tests")]
+    no_tests: bool,
+
+    /// Only include files in these languages, by name or alias (e.g.
+    /// rust,toml); comma-separated or repeated
+    #[arg(long = "lang", value_name = "LANG", value_delimiter = ',', value_parser = lang::parse)]
+    langs: Vec<&'static lang::Language>,
+
+    /// Descend into initialized git submodules (skipped by default)
+    #[arg(long)]
+    submodules: bool,
+
+    /// Descend into symlinked directories (skipped by default). Files
+    /// reachable through several links are gathered once, and links back into
+    /// an enclosing directory are not followed
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Include hidden files and directories, such as .github/ and
+    /// .env.example (.git/ is still left out)
+    #[arg(long)]
+    hidden: bool,
+
+    /// Don't respect ignore files: .gitignore, .ignore, .gatherignore, the
+    /// global gitignore and .git/info/exclude
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// Don't respect the git ignore rules (.gitignore, the global gitignore
+    /// and .git/info/exclude), but keep .ignore and .gatherignore
+    #[arg(long)]
+    no_ignore_vcs: bool,
+
+    /// Only gather one Cargo/npm/pnpm/Go workspace member (by package name or
+    /// directory), plus the workspace's root manifests
+    #[arg(long, value_name = "NAME")]
+    package: Option<String>,
+
+    /// Include an installed package's source from node_modules/ even though
+    /// node_modules is ignored (can be specified multiple times)
+    #[arg(long, value_name = "NAME")]
+    include_package: Vec<String>,
+
+    /// Order paths with numbers by value (part2 before part10) instead of
+    /// character by character
+    #[arg(long)]
+    natural_sort: bool,
+
+    /// Gather exactly these root-relative paths instead of walking the tree
+    #[arg(skip)]
+    paths: Option<Vec<String>>,
+}
+
+impl FilterArgs {
+    /// The order paths are listed in: natural with --natural-sort, otherwise
+    /// plain string order.
+    fn cmp_paths(&self, a: &str, b: &str) -> std::cmp::Ordering {
+        if self.natural_sort {
+            natural::cmp(a, b)
+        } else {
+            a.cmp(b)
+        }
+    }
+}
+
+/// How much to fetch when the path is a git URL.
+#[derive(Args, Clone)]
+struct CloneArgs {
+    /// Commits of history to fetch; 0 for full history (needed by features
+    /// that read blame or log)
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    clone_depth: u32,
+
+    /// Branch, tag or commit SHA to check out instead of the default branch
+    #[arg(long = "ref", value_name = "REF")]
+    git_ref: Option<String>,
+
+    /// Don't fetch tags
+    #[arg(long)]
+    no_tags: bool,
+}
+
+impl CloneArgs {
+    /// Whether any clone flag differs from its default.
+    fn is_set(&self) -> bool {
+        self.git_ref.is_some() || self.clone_depth != 1 || self.no_tags
+    }
+
+    fn to_options(&self) -> remote::CloneOptions {
+        remote::CloneOptions {
+            depth: self.clone_depth,
+            reference: self.git_ref.clone(),
+            tags: !self.no_tags,
+        }
+    }
+}
+
+/// How a bundle is rendered.
+#[derive(Clone, Debug, Default)]
+pub enum OutputFormat {
+    #[default]
+    Markdown,
+    Xml,
+    Json,
+    /// Sizes, top-level symbols and the first lines of each file
+    Summary,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "xml" => Ok(OutputFormat::Xml),
+            "json" => Ok(OutputFormat::Json),
+            "summary" => Ok(OutputFormat::Summary),
+            _ => Err(format!(
+                "Unknown format: {s}. Use 'markdown', 'xml', 'json' or 'summary'."
+            )),
+        }
+    }
+}
+
+/// Which files `--max-tokens` keeps first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BudgetOrder {
+    Path,
+    Size,
+    Mtime,
+}
+
+impl std::str::FromStr for BudgetOrder {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "path" => Ok(BudgetOrder::Path),
+            "size" => Ok(BudgetOrder::Size),
+            "mtime" => Ok(BudgetOrder::Mtime),
+            _ => Err(format!(
+                "Unknown budget order: {s}. Use 'path', 'size' or 'mtime'."
+            )),
+        }
+    }
+}
+
+/// Estimate the token count of a file's text with the `--estimator` in
+/// effect; by default from its length, at the characters-per-token ratio of
+/// its language (inferred from `path`).
+pub fn estimate_tokens(path: &Path, text: &str) -> usize {
+    estimate::estimate(text, lang_tag(path))
+}
+
+/// Token estimates for each file, computed across a rayon thread pool since
+/// files are independent.
+fn count_tokens(files: &[CollectedFile]) -> Vec<usize> {
+    files
+        .par_iter()
+        .map(|f| estimate_tokens(Path::new(&f.relative_path), &f.content))
+        .collect()
+}
+
+/// Check if file contents are likely binary by looking for NUL bytes in a
+/// small sample.
+fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(8192)];
+    sample.contains(&0)
+}
+
+/// `is_binary` on the first 8KB of the file at `path`, for files too large to
+/// read whole.
+fn sniff_binary(path: &Path) -> bool {
+    use std::io::Read;
+    let mut sample = Vec::new();
+    fs::File::open(path)
+        .and_then(|f| f.take(8192).read_to_end(&mut sample))
+        .is_ok_and(|_| is_binary(&sample))
+}
+
+/// Average line length (in bytes) above which a script or stylesheet is
+/// considered minified.
+const MINIFIED_AVG_LINE_LEN: usize = 300;
+
+/// Detect minified or bundled build output: `.min.` names, source maps,
+/// sourcemap pointers, and JS/CSS whose lines are implausibly long.
+fn is_minified(path: &Path, content: &str) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    if name.contains(".min.") || name.ends_with(".map") {
+        return true;
+    }
+
+    let has_sourcemap_pointer = content.lines().rev().take(3).any(|line| {
+        let line = line.trim_start();
+        line.starts_with("//# sourceMappingURL=") || line.starts_with("/*# sourceMappingURL=")
+    });
+    if has_sourcemap_pointer {
+        return true;
+    }
+
+    let is_script_or_style = matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("js" | "mjs" | "cjs" | "css")
+    );
+    if is_script_or_style && content.len() >= 1024 {
+        let lines = content.lines().count().max(1);
+        return content.len() / lines > MINIFIED_AVG_LINE_LEN;
+    }
+
+    false
+}
+
+/// Infer a markdown language tag from a file's extension or name.
+fn lang_tag(path: &Path) -> &'static str {
+    lang::of(path).map_or("", |lang| lang.name)
+}
+
+/// Options controlling how collected files are rendered.
+#[derive(Default)]
+struct FormatOptions {
+    show_tokens: bool,
+    header: Option<String>,
+    footer: Option<String>,
+    /// Wrap each markdown file in a `<details>` block
+    collapsible: bool,
+    /// One markdown heading per top-level directory, with token subtotals
+    group_by_dir: bool,
+    /// Markdown code fence (default: three backticks)
+    fence: Option<String>,
+    /// Omit the language tag after opening fences
+    no_lang_tags: bool,
+    /// Language tag for every file instead of one inferred from its path
+    /// (e.g. `diff` for patches)
+    lang: Option<&'static str>,
+    /// XML root element name (default: context)
+    xml_root: Option<String>,
+    /// XML per-file element name (default: file)
+    xml_file_element: Option<String>,
+    /// Wrap XML file contents in CDATA sections instead of escaping them
+    xml_cdata: bool,
+    /// Add lang/bytes/tokens/lines/sha256/mtime attributes to XML file
+    /// elements; mtimes are read from files under `root`
+    file_meta: bool,
+    root: PathBuf,
+    /// Directory tree of the files to place ahead of them
+    tree: Option<String>,
+    /// Mermaid import graph to place ahead of the files
+    graph: Option<String>,
+    /// Session name and the paths it already has unchanged, listed in one
+    /// line instead of being sent again
+    unchanged: Option<(String, Vec<String>)>,
+    /// Lines of each file `--format summary` shows (default: 10)
+    summary_lines: Option<usize>,
+}
+
+/// A gathered file: its path relative to the root, with `/` separators, and
+/// its text.
+pub struct CollectedFile {
+    pub relative_path: String,
+    pub content: String,
+}
+
+/// Why `collect_files` left a file out of the bundle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    TooLarge,
+    TooManyTokens,
+    OverBudget,
+    Binary,
+    Minified,
+    NotUtf8,
+    Unreadable,
+    Submodule,
+    UninitializedSubmodule,
+    Filtered,
+    Vendored,
+}
+
+impl SkipReason {
+    fn label(self) -> &'static str {
+        match self {
+            SkipReason::TooLarge => "too large",
+            SkipReason::TooManyTokens => "over token limit",
+            SkipReason::OverBudget => "over token budget",
+            SkipReason::Binary => "binary",
+            SkipReason::Minified => "minified",
+            SkipReason::NotUtf8 => "not UTF-8",
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::Submodule => "submodule",
+            SkipReason::UninitializedSubmodule => "uninitialized submodule",
+            SkipReason::Filtered => "excluded by filters",
+            SkipReason::Vendored => "vendored",
+        }
+    }
+}
+
+/// A file left out of the bundle, and why.
+pub struct SkippedFile {
+    pub relative_path: String,
+    pub reason: SkipReason,
+}
+
+/// The result of walking a tree: files to emit plus everything left out.
+pub struct Collection {
+    pub files: Vec<CollectedFile>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// One-line summary of skipped files grouped by reason, e.g.
+/// "Skipped 17 files: 12 too large, 5 binary".
+fn skip_summary(skipped: &[SkippedFile]) -> Option<String> {
+    if skipped.is_empty() {
+        return None;
+    }
+    let mut counts: Vec<(SkipReason, usize)> = Vec::new();
+    for file in skipped {
+        match counts.iter_mut().find(|(r, _)| *r == file.reason) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((file.reason, 1)),
+        }
+    }
+    counts.sort();
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|(reason, n)| format!("{n} {}", reason.label()))
+        .collect();
+    let noun = if skipped.len() == 1 { "file" } else { "files" };
+    Some(format!(
+        "Skipped {} {noun}: {}",
+        skipped.len(),
+        parts.join(", ")
+    ))
+}
+
+/// Print the skip summary to stderr, or the full per-file list when verbose.
+fn report_skipped(skipped: &[SkippedFile], verbose: bool) {
+    let Some(summary) = skip_summary(skipped) else {
+        return;
+    };
+    if verbose {
+        for file in skipped {
+            eprintln!(
+                "skipped  {:<20} {}",
+                file.reason.label(),
+                file.relative_path
+            );
+        }
+        eprintln!("{summary}");
+    } else {
+        eprintln!("{summary} (-v for details)");
+    }
+}
+
+const DEFAULT_MAX_SIZE: u64 = 102_400;
+
+/// Code fence used unless `--fence` picks another.
+const DEFAULT_FENCE: &str = "```";
+
+/// Path patterns for test code, following common per-language conventions.
+const TEST_PATTERNS: &[&str] = &[
+    "**/test/**",
+    "**/tests/**",
+    "**/__tests__/**",
+    "**/spec/**",
+    "**/*_test.go",
+    "**/*_test.py",
+    "**/test_*.py",
+    "**/conftest.py",
+    "**/*.test.{js,jsx,ts,tsx,mjs,cjs}",
+    "**/*.spec.{js,jsx,ts,tsx,mjs,cjs}",
+    "**/*_spec.rb",
+    "**/*_test.rb",
+    "**/*Test.java",
+    "**/*Tests.java",
+    "**/*Test.kt",
+    "**/*Tests.cs",
+];
+
+/// Options controlling which files `collect_files` reads.
+#[derive(Args, Clone)]
+struct CollectOptions {
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Maximum file size in bytes to include (default: 100KB)
+    #[arg(long, default_value_t = DEFAULT_MAX_SIZE)]
+    max_size: u64,
+
+    /// Maximum estimated tokens per file; larger files are skipped
+    #[arg(long)]
+    max_file_tokens: Option<usize>,
+
+    /// Keep minified and bundled files (*.min.js, source maps, very long lines)
+    #[arg(long)]
+    include_minified: bool,
+
+    /// Under vendor/, third_party/ and similar directories, keep only each
+    /// package's manifest and README plus an outline of its sources
+    #[arg(long)]
+    summarize_vendored: bool,
+
+    /// Emit lockfiles (Cargo.lock, package-lock.json, poetry.lock) verbatim
+    /// instead of a summary of direct dependencies and their versions
+    #[arg(long)]
+    raw_lockfiles: bool,
+
+    /// Replace JSON and YAML files larger than BYTES with their key structure,
+    /// value types and array lengths (applies even above --max-size)
+    #[arg(long, value_name = "BYTES")]
+    skeleton_over: Option<u64>,
+
+    /// What to do with files over --max-size or --max-file-tokens: skip
+    /// (default), truncate, outline (declarations) or structure (outline for
+    /// code, skeleton for JSON/YAML, headings for Markdown)
+    #[arg(long, default_value = "skip", value_name = "STRATEGY")]
+    oversize_strategy: OversizeStrategy,
+
+    /// Shell command that summarizes files over --max-size or
+    /// --max-file-tokens: it reads the content on stdin (path in $GATHER_FILE)
+    /// and its stdout is embedded with a [summarized] marker
+    #[arg(long, value_name = "CMD")]
+    summarize_cmd: Option<String>,
+
+    /// Fetch Git LFS objects up to BYTES with `git lfs` instead of emitting a
+    /// placeholder for their pointer files
+    #[arg(long, value_name = "BYTES")]
+    fetch_lfs: Option<u64>,
+
+    /// What to do with binary files: skip (default), note (a placeholder with
+    /// the size), hexdump or base64 (embedded, up to --max-size)
+    #[arg(long, default_value = "skip", value_name = "POLICY")]
+    binary: BinaryPolicy,
+
+    /// Skip files that aren't valid UTF-8 instead of transcoding UTF-16 and
+    /// legacy encodings such as Latin-1
+    #[arg(long)]
+    strict_utf8: bool,
+}
+
+impl CollectOptions {
+    /// Whether a snapshot can leave out the content of `rel`, `size` bytes:
+    /// the walk skips files over `--max-size` on their size alone, unless
+    /// they're cut down, summarized or noted as binary, or a lockfile
+    /// summary, data skeleton or vendored outline stands in for them.
+    fn skips_unread(&self, rel: &str, size: u64) -> bool {
+        let path = Path::new(rel);
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        let summarized = (!self.raw_lockfiles && summarize::LOCKFILE_NAMES.contains(&name))
+            || (self.skeleton_over.is_some_and(|limit| size > limit)
+                && summarize::SKELETON_EXTENSIONS.contains(&ext))
+            || (self.summarize_vendored
+                && summarize::vendored_role(rel) == Some(VendoredRole::Outline));
+        size > self.max_size
+            && !summarized
+            && self.oversize_strategy == OversizeStrategy::Skip
+            && self.summarize_cmd.is_none()
+            && self.binary == BinaryPolicy::Skip
+    }
+}
+
+impl Default for CollectOptions {
+    fn default() -> Self {
+        CollectOptions {
+            filter: FilterArgs::default(),
+            max_size: DEFAULT_MAX_SIZE,
+            max_file_tokens: None,
+            include_minified: false,
+            summarize_vendored: false,
+            raw_lockfiles: false,
+            skeleton_over: None,
+            oversize_strategy: OversizeStrategy::Skip,
+            summarize_cmd: None,
+            fetch_lfs: None,
+            binary: BinaryPolicy::Skip,
+            strict_utf8: false,
+        }
+    }
+}
+
+fn build_glob_set<S: AsRef<str>>(patterns: &[S]) -> Option<GlobSet> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut builder = GlobSetBuilder::new();
+    for g in patterns {
+        if let Ok(glob) = Glob::new(g.as_ref()) {
+            builder.add(glob);
+        }
+    }
+    builder.build().ok()
+}
+
+/// Compiled include/exclude rules for matching relative paths.
+struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    tests: Option<GlobSet>,
+    langs: Vec<&'static lang::Language>,
+    /// `Some(None)` when `--package` didn't resolve, so nothing matches.
+    package: Option<Option<workspace::PackageScope>>,
+}
+
+impl PathFilter {
+    /// Compile the filter flags. An unknown `--package` matches nothing; call
+    /// `validate_filter` first to report it.
+    fn new(root: &Path, args: &FilterArgs) -> Self {
+        let package = args
+            .package
+            .as_ref()
+            .map(|name| workspace::package_scope(root, name).ok());
+        PathFilter {
+            package,
+            include: build_glob_set(&args.globs),
+            exclude: build_glob_set(&args.excludes),
+            tests: if args.no_tests {
+                build_glob_set(TEST_PATTERNS)
+            } else {
+                None
+            },
+            langs: args.langs.clone(),
+        }
+    }
+
+    /// Whether a root-relative path passes the include and exclude rules.
+    fn matches(&self, rel: &str) -> bool {
+        if let Some(ref set) = self.include {
+            if !set.is_match(rel) {
+                return false;
+            }
+        }
+        if let Some(ref set) = self.exclude {
+            if set.is_match(rel) {
+                return false;
+            }
+        }
+        if let Some(ref set) = self.tests {
+            if set.is_match(rel) {
+                return false;
+            }
+        }
+        if !self.langs.is_empty() && !self.langs.iter().any(|lang| lang.matches(Path::new(rel))) {
+            return false;
+        }
+        if let Some(ref scope) = self.package {
+            if !scope.as_ref().is_some_and(|s| s.contains(rel)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A git state to gather instead of the worktree.
+enum Snapshot {
+    /// A revision (`--at`).
+    Rev(String),
+    /// The index versions of staged files (`--staged`).
+    Staged,
+}
+
+impl Snapshot {
+    fn from_flags(at: Option<String>, staged: bool) -> Option<Self> {
+        match (at, staged) {
+            (Some(rev), _) => Some(Snapshot::Rev(rev)),
+            (None, true) => Some(Snapshot::Staged),
+            (None, false) => None,
+        }
+    }
+}
+
+/// Where a command reads files from, plus the scratch directories backing it
+/// (removed when this is dropped).
+struct Source {
+    root: PathBuf,
+    /// The repository a snapshot was exported from.
+    snapshot: Option<(PathBuf, Snapshot)>,
+    _scratch: Vec<cache::ScratchDir>,
+}
+
+impl Source {
+    /// The local repository: where a snapshot came from, else the root.
+    fn repo(&self) -> &Path {
+        self.snapshot.as_ref().map_or(&self.root, |(repo, _)| repo)
+    }
+
+    /// Header/footer metadata, describing the exported revision for `--at`.
+    fn template_vars(&self) -> TemplateVars {
+        match &self.snapshot {
+            None => TemplateVars::from_root(&self.root),
+            Some((repo, Snapshot::Staged)) => TemplateVars::from_root(repo),
+            Some((repo, Snapshot::Rev(rev))) => {
+                let mut vars = TemplateVars::from_root(repo);
+                vars.branch = rev.clone();
+                vars.commit = git::git_output(repo, &["rev-parse", "--short", rev])
+                    .unwrap_or_else(|| "unknown".to_string());
+                vars
+            }
+        }
+    }
+}
+
+/// Resolve the directory to gather from. A git URL is cloned first (sparsely
+/// when include globs are given); a snapshot's files that pass the path
+/// filters are exported from git into a scratch directory, leaving out the
+/// content of files `opts` would skip as too large.
+fn resolve_root(
+    path: PathBuf,
+    filter: &FilterArgs,
+    clone: &CloneArgs,
+    snapshot: Option<Snapshot>,
+    opts: Option<&CollectOptions>,
+) -> Source {
+    let mut scratch = Vec::new();
+    let text = path.to_string_lossy();
+    let root = if remote::is_remote(&text) {
+        match remote::clone(&text, &filter.globs, &clone.to_options()) {
+            Ok(checkout) => {
+                let root = checkout.path.clone();
+                scratch.push(checkout);
+                root
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(status::ERROR);
+            }
+        }
+    } else {
+        if clone.is_set() {
+            eprintln!(
+                "--ref, --clone-depth and --no-tags apply to git URLs, not local directories \
+                 (for a revision of a local repository, see --at)"
+            );
+            std::process::exit(status::ERROR);
+        }
+        path.canonicalize().unwrap_or(path)
+    };
+    let Some(snapshot) = snapshot else {
+        return Source {
+            root,
+            snapshot: None,
+            _scratch: scratch,
+        };
+    };
+
+    let name = root
+        .file_name()
+        .map_or_else(|| "root".into(), |n| n.to_string_lossy().to_string());
+    let parent = cache::cache_root().join("snapshots");
+    let dir = match cache::scratch_dir(&parent, &name) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Failed to create {}: {e}", parent.display());
+            std::process::exit(status::ERROR);
+        }
+    };
+    // --package is resolved later against the snapshot's own manifests
+    let prefilter = PathFilter::new(
+        &root,
+        &FilterArgs {
+            package: None,
+            ..filter.clone()
+        },
+    );
+    let keep = |rel: &str| prefilter.matches(rel);
+    let unread = |rel: &str, size| opts.is_some_and(|o| o.skips_unread(rel, size));
+    let exported = match &snapshot {
+        Snapshot::Rev(rev) => git::export_tree(&root, rev, &dir.path, &keep, &unread),
+        Snapshot::Staged => git::export_staged(&root, &dir.path, &keep, &unread),
+    };
+    if let Err(e) = exported {
+        eprintln!("{e}");
+        std::process::exit(status::ERROR);
+    }
+    let source_root = dir.path.clone();
+    scratch.push(dir);
+    Source {
+        root: source_root,
+        snapshot: Some((root, snapshot)),
+        _scratch: scratch,
+    }
+}
+
+/// Exit with a helpful message if the filter flags can't be resolved against
+/// `root` (e.g. an unknown `--package`).
+fn validate_filter(root: &Path, args: &FilterArgs) {
+    if let Err(e) = check_filter(root, args) {
+        eprintln!("{e}");
+        std::process::exit(e.exit_code());
+    }
+}
+
+/// Check that `root` can be read, that the `--glob`, `--exclude` and
+/// `--prune` patterns are valid globs, and that the package `--package` and
+/// `--include-package` name exist under `root`.
+fn check_filter(root: &Path, args: &FilterArgs) -> Result<(), Error> {
+    fs::metadata(root).map_err(|source| Error::Root {
+        path: root.to_path_buf(),
+        source,
+    })?;
+    let patterns = args
+        .globs
+        .iter()
+        .map(|p| ("--glob", p))
+        .chain(args.excludes.iter().map(|p| ("--exclude", p)))
+        .chain(args.prunes.iter().map(|p| ("--prune", p)));
+    for (flag, pattern) in patterns {
+        Glob::new(pattern).map_err(|source| Error::Pattern {
+            flag,
+            pattern: pattern.clone(),
+            source,
+        })?;
+    }
+    if let Some(ref name) = args.package {
+        workspace::package_scope(root, name).map_err(Error::Package)?;
+    }
+    for name in &args.include_package {
+        if !is_package_name(name) {
+            return Err(Error::Package(format!(
+                "--include-package {name}: not an npm package name (name or @scope/name)"
+            )));
+        }
+        if !root.join("node_modules").join(name).is_dir() {
+            return Err(Error::Package(format!(
+                "--include-package {name}: node_modules/{name} is not installed"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` is an npm package name, `name` or `@scope/name`, and so
+/// stays inside `node_modules/` when joined onto it.
+fn is_package_name(name: &str) -> bool {
+    let part = |p: &str| {
+        !p.is_empty()
+            && !p.starts_with('.')
+            && p.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c))
+    };
+    match name.strip_prefix('@').map(|scoped| scoped.split_once('/')) {
+        Some(Some((scope, name))) => part(scope) && part(name),
+        Some(None) => false,
+        None => part(name),
+    }
+}
+
+/// Read a file that passed the path filters, applying the size, binary,
+/// encoding, minification and token checks.
+fn read_candidate(path: &Path, opts: &CollectOptions) -> Result<String, SkipReason> {
+    // Check file size
+    let unreadable = |source| {
+        status::report(&Error::Read {
+            path: path.to_path_buf(),
+            source,
+        });
+        SkipReason::Unreadable
+    };
+    let meta = path.metadata().map_err(unreadable)?;
+    let mut too_large = meta.len() > opts.max_size;
+    if too_large && opts.oversize_strategy == OversizeStrategy::Skip && opts.summarize_cmd.is_none()
+    {
+        // A large binary can still be noted without reading all of it
+        if opts.binary != BinaryPolicy::Skip && sniff_binary(path) {
+            return Ok(binary::note(meta.len()));
+        }
+        return Err(SkipReason::TooLarge);
+    }
+
+    // Shrink oversized files to fit both limits: bytes at the language's
+    // ratio first, then less while the estimate is still over the ceiling
+    let ratio = estimate::chars_per_token(lang_tag(path));
+    let mut budget = opts.max_file_tokens.map_or(opts.max_size, |limit| {
+        opts.max_size.min((limit as f64 * ratio) as u64)
+    });
+
+    // A file that will be cut down is only read as far as can be kept
+    let mut bytes = if too_large && opts.summarize_cmd.is_none() {
+        let limit = match opts.oversize_strategy {
+            OversizeStrategy::Truncate => budget + SHRINK_READ_MARGIN,
+            _ => SHRINK_READ_LIMIT.max(budget + SHRINK_READ_MARGIN),
+        };
+        read_prefix(path, limit).map_err(unreadable)?
+    } else {
+        fs::read(path).map_err(unreadable)?
+    };
+
+    // Stand in for Git LFS pointers unless the object is small enough to fetch
+    if let Some(size) = git::lfs_pointer_size(&bytes) {
+        let fetched = opts
+            .fetch_lfs
+            .filter(|max| size <= *max)
+            .and_then(|_| git::lfs_smudge(path.parent().unwrap_or(path), &bytes));
+        match fetched {
+            Some(object) => {
+                too_large = object.len() as u64 > opts.max_size;
+                bytes = object;
+            }
+            None => {
+                return Ok(format!(
+                    "[LFS object: {}, not fetched]\n",
+                    cache::format_bytes(size)
+                ))
+            }
+        }
+    }
+
+    // Transcode UTF-16 (which would otherwise look binary) and legacy encodings
+    if !opts.strict_utf8 {
+        bytes = encoding::to_utf8(bytes);
+    }
+
+    // Skip, note or embed binary files; those too large to embed are noted
+    if is_binary(&bytes) {
+        if too_large && opts.binary != BinaryPolicy::Skip {
+            return Ok(binary::note(meta.len().max(bytes.len() as u64)));
+        }
+        return binary::render(opts.binary, &bytes).ok_or(SkipReason::Binary);
+    }
+
+    let content = String::from_utf8(bytes).map_err(|_| SkipReason::NotUtf8)?;
+
+    // Skip minified/bundled build output
+    if !opts.include_minified && is_minified(path, &content) {
+        return Err(SkipReason::Minified);
+    }
+
+    // Check per-file token ceiling
+    let too_many_tokens = opts
+        .max_file_tokens
+        .is_some_and(|limit| estimate_tokens(path, &content) > limit);
+    if !too_large && !too_many_tokens {
+        return Ok(content);
+    }
+
+    if let Some(ref cmd) = opts.summarize_cmd {
+        match summarize::external(cmd, path, &content) {
+            Ok(summary) => return Ok(summary),
+            Err(e) => status::warn(format!(
+                "--summarize-cmd failed for {}: {e}",
+                path.display()
+            )),
+        }
+    }
+    if opts.oversize_strategy == OversizeStrategy::Skip {
+        return Err(if too_large {
+            SkipReason::TooLarge
+        } else {
+            SkipReason::TooManyTokens
+        });
+    }
+
+    let total = meta.len().max(content.len() as u64) as usize;
+    loop {
+        let shrunk = summarize::shrink(
+            path,
+            &content,
+            opts.oversize_strategy,
+            budget as usize,
+            total,
+        );
+        let tokens = estimate_tokens(path, &shrunk);
+        match opts.max_file_tokens {
+            Some(limit) if tokens > limit && budget > 0 => {
+                budget = (budget as f64 * limit as f64 / tokens as f64 * 0.9) as u64;
+            }
+            _ => return Ok(shrunk),
+        }
+    }
+}
+
+/// Bytes read past the budget of a file that's truncated, so the cut can
+/// fall on a line break.
+const SHRINK_READ_MARGIN: u64 = 4096;
+
+/// Most bytes of an oversized file read for `--oversize-strategy outline`
+/// or `structure`; what lies past this is left out.
+const SHRINK_READ_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// The first `limit` bytes of `path`, less a UTF-8 sequence cut off at the
+/// end.
+fn read_prefix(path: &Path, limit: u64) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+    let mut bytes = Vec::new();
+    fs::File::open(path)?.take(limit).read_to_end(&mut bytes)?;
+    if let Err(e) = std::str::from_utf8(&bytes) {
+        if e.error_len().is_none() {
+            bytes.truncate(e.valid_up_to());
+        }
+    }
+    Ok(bytes)
+}
+
+/// Per-directory ignore file read on top of `.gitignore`, for leaving
+/// fixtures or vendored code out of context without touching `.gitignore`.
+/// Its rules take precedence, so `!pattern` can bring back a git-ignored
+/// path.
+const IGNORE_FILE: &str = ".gatherignore";
+
+/// Walk `root` in parallel honoring hidden-file and ignore rules, returning
+/// each regular file as (absolute path, root-relative path), sorted by the
+/// relative path. Submodules are pruned unless
+/// `--submodules` is set. Packages named by `--include-package` are walked
+/// without ignore rules and appended. An explicit path list replaces the
+/// walk; entries that aren't regular files are dropped.
+fn walk_files(root: &Path, args: &FilterArgs) -> Vec<(PathBuf, String)> {
+    if let Some(ref paths) = args.paths {
+        return paths
+            .iter()
+            .map(|rel| (root.join(rel), rel.clone()))
+            .filter(|(path, _)| path.is_file())
+            .collect();
+    }
+
+    let pruned: HashSet<PathBuf> = git::submodules(root)
+        .into_iter()
+        .filter(|sub| !(args.submodules && sub.initialized))
+        .map(|sub| root.join(sub.path))
+        .collect();
+
+    // Pruned directories are matched by their path and by their name alone
+    let prune = build_glob_set(&args.prunes);
+    let walk_root = root.to_path_buf();
+    let prunes = move |entry: &ignore::DirEntry| {
+        let Some(ref set) = prune else {
+            return false;
+        };
+        let rel = entry
+            .path()
+            .strip_prefix(&walk_root)
+            .unwrap_or(entry.path());
+        entry.depth() > 0
+            && entry.file_type().is_some_and(|t| t.is_dir())
+            && (set.is_match(rel) || set.is_match(entry.file_name()))
+    };
+
+    // Walk on every worker thread; the order entries arrive in varies from
+    // run to run, so sort them once at the end
+    let (tx, rx) = mpsc::channel();
+    let vcs_ignore = !args.no_ignore && !args.no_ignore_vcs;
+    let mut walker = WalkBuilder::new(root);
+    walker
+        .hidden(!args.hidden)
+        .ignore(!args.no_ignore)
+        .parents(!args.no_ignore)
+        .git_ignore(vcs_ignore)
+        .git_global(vcs_ignore)
+        .git_exclude(vcs_ignore)
+        .follow_links(args.follow_symlinks)
+        .max_depth(args.max_depth)
+        .threads(rayon::current_num_threads())
+        .filter_entry(move |entry| {
+            !pruned.contains(entry.path()) && entry.file_name() != ".git" && !prunes(entry)
+        });
+    if !args.no_ignore {
+        walker.add_custom_ignore_filename(IGNORE_FILE);
+    }
+    walker.build_parallel().run(|| {
+        let tx = tx.clone();
+        Box::new(move |entry| {
+            match entry {
+                Ok(entry) => {
+                    // A broken ignore file still lets the walk go on
+                    if let Some(e) = entry.error() {
+                        status::report(&Error::Walk(e.clone()));
+                    }
+                    if entry.path().is_file() {
+                        let _ = tx.send(entry.into_path());
+                    }
+                }
+                // A link back into an enclosing directory; not followed
+                Err(e) if is_loop(&e) => {}
+                Err(e) => status::report(&Error::Walk(e)),
+            }
+            WalkState::Continue
+        })
+    });
+    drop(tx);
+
+    let mut paths: Vec<(PathBuf, String)> = rx
+        .into_iter()
+        .map(|path| {
+            let rel = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            (path, rel)
+        })
+        .collect();
+    paths.sort_by(|a, b| a.1.cmp(&b.1));
+    if args.follow_symlinks {
+        // Keep files reachable by several paths once: where they really are
+        // when that's inside the root, otherwise under their first path
+        let real_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let reals: Vec<Option<PathBuf>> = paths
+            .iter()
+            .map(|(path, _)| fs::canonicalize(path).ok())
+            .collect();
+        let mut chosen: HashMap<&Path, usize> = HashMap::new();
+        for (i, real) in reals.iter().enumerate() {
+            let Some(real) = real else { continue };
+            let here = *real == real_root.join(&paths[i].1);
+            let first = chosen.entry(real).or_insert(i);
+            if here {
+                *first = i;
+            }
+        }
+        let keep: HashSet<usize> = chosen.into_values().collect();
+        let mut i = 0;
+        paths.retain(|_| {
+            i += 1;
+            reals[i - 1].is_none() || keep.contains(&(i - 1))
+        });
+    }
+
+    let mut seen: HashSet<String> = HashSet::new();
+    if !args.include_package.is_empty() {
+        seen.extend(paths.iter().map(|(_, rel)| rel.clone()));
+    }
+    for name in &args.include_package {
+        let package_dir = root.join("node_modules").join(name);
+        if !is_package_name(name) || !package_dir.is_dir() {
+            continue;
+        }
+        // The package's own ignore files often exclude its sources, and nested
+        // node_modules would reopen the floodgates
+        let walker = WalkBuilder::new(&package_dir)
+            .standard_filters(false)
+            .hidden(true)
+            .filter_entry(|entry| entry.file_name() != "node_modules")
+            .build();
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    status::report(&Error::Walk(e));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            let rel = path
+                .strip_prefix(root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string();
+            if seen.insert(rel.clone()) {
+                paths.push((path.to_path_buf(), rel));
+            }
+        }
+    }
+
+    paths
+}
+
+/// Whether a walk error is a symlink leading back to one of the
+/// directories it sits in.
+fn is_loop(e: &ignore::Error) -> bool {
+    match e {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => is_loop(err),
+        _ => false,
+    }
+}
+
+/// Skip entries for submodules that `walk_files` pruned.
+fn skipped_submodules(root: &Path, args: &FilterArgs) -> Vec<SkippedFile> {
+    if args.paths.is_some() {
+        return Vec::new();
+    }
+    git::submodules(root)
+        .into_iter()
+        .filter_map(|sub| {
+            let reason = if !sub.initialized {
+                SkipReason::UninitializedSubmodule
+            } else if !args.submodules {
+                SkipReason::Submodule
+            } else {
+                return None;
+            };
+            Some(SkippedFile {
+                relative_path: sub.path,
+                reason,
+            })
+        })
+        .collect()
+}
+
+fn collect_files(root: &Path, opts: &CollectOptions) -> Collection {
+    collect_files_with(root, opts, None)
+}
+
+/// Like `collect_files`, but take unchanged files' contents from a previous
+/// run instead of reading them again.
+fn collect_files_with(
+    root: &Path,
+    opts: &CollectOptions,
+    previous: Option<&PreviousRun>,
+) -> Collection {
+    let filter = PathFilter::new(root, &opts.filter);
+
+    let mut files = Vec::new();
+    let mut skipped = skipped_submodules(root, &opts.filter);
+
+    // Reading (and any lockfile, skeleton or outline summarizing) is spread
+    // across the thread pool; the sort below restores a stable order
+    let outcomes: Vec<Result<CollectedFile, SkippedFile>> = walk_files(root, &opts.filter)
+        .into_par_iter()
+        .map(|(path, rel)| {
+            let skip = |reason| {
+                Err(SkippedFile {
+                    relative_path: rel.clone(),
+                    reason,
+                })
+            };
+            // Apply include/exclude globs
+            if !filter.matches(&rel) {
+                return skip(SkipReason::Filtered);
+            }
+
+            let role = opts
+                .summarize_vendored
+                .then(|| summarize::vendored_role(&rel))
+                .flatten();
+            if role == Some(VendoredRole::Omit) {
+                return skip(SkipReason::Vendored);
+            }
+
+            let read = || {
+                // Summaries stand in for lockfiles of any size
+                if !opts.raw_lockfiles {
+                    if let Some(summary) = summarize::lockfile(&path) {
+                        return Ok(summary);
+                    }
+                }
+                if let Some(limit) = opts.skeleton_over {
+                    if path.metadata().is_ok_and(|m| m.len() > limit) {
+                        if let Some(skeleton) = summarize::data_skeleton(&path) {
+                            return Ok(skeleton);
+                        }
+                    }
+                }
+                let content = read_candidate(&path, opts)?;
+                Ok(match role {
+                    Some(VendoredRole::Outline) => outline::outline(&path, &content),
+                    _ => content,
+                })
+            };
+            let reused = previous.and_then(|p| p.reuse(&rel, &path));
+            match reused.map_or_else(read, Ok) {
+                Ok(content) => Ok(CollectedFile {
+                    relative_path: rel,
+                    content,
+                }),
+                Err(reason) => skip(reason),
+            }
+        })
+        .collect();
+    for outcome in outcomes {
+        match outcome {
+            Ok(file) => files.push(file),
+            Err(skip) => skipped.push(skip),
+        }
+    }
+
+    let order = |a: &str, b: &str| opts.filter.cmp_paths(a, b);
+    files.sort_by(|a, b| order(&a.relative_path, &b.relative_path));
+    skipped.sort_by(|a, b| order(&a.relative_path, &b.relative_path));
+    Collection { files, skipped }
+}
+
+/// Size-only pass for `tokens --estimate-only`: apply the path filters and
+/// `--max-size` using file metadata, returning (relative path, bytes) without
+/// opening any file.
+fn estimate_sizes(root: &Path, opts: &CollectOptions) -> (Vec<(String, u64)>, Vec<SkippedFile>) {
+    let filter = PathFilter::new(root, &opts.filter);
+
+    let mut sizes = Vec::new();
+    let mut skipped = skipped_submodules(root, &opts.filter);
+
+    for (path, rel) in walk_files(root, &opts.filter) {
+        let reason = if !filter.matches(&rel) {
+            SkipReason::Filtered
+        } else if opts.summarize_vendored
+            && summarize::vendored_role(&rel) == Some(VendoredRole::Omit)
+        {
+            SkipReason::Vendored
+        } else {
+            match path.metadata() {
+                Ok(meta)
+                    if meta.len() > opts.max_size
+                        && opts.oversize_strategy == OversizeStrategy::Skip =>
+                {
+                    SkipReason::TooLarge
+                }
+                Ok(meta) => {
+                    // Shrunk oversized files are counted at their cap
+                    sizes.push((rel, meta.len().min(opts.max_size)));
+                    continue;
+                }
+                Err(source) => {
+                    status::report(&Error::Read { path, source });
+                    SkipReason::Unreadable
+                }
+            }
+        };
+        skipped.push(SkippedFile {
+            relative_path: rel,
+            reason,
+        });
+    }
+
+    let order = |a: &str, b: &str| opts.filter.cmp_paths(a, b);
+    sizes.sort_by(|a, b| order(&a.0, &b.0));
+    skipped.sort_by(|a, b| order(&a.relative_path, &b.relative_path));
+    (sizes, skipped)
+}
+
+/// Token/byte rows with each row's share of the total tokens, followed by a
+/// total line for the `files` the rows cover. With `top` only the first N
+/// rows are listed and the rest are summed in one line, counted as `noun`
+/// (singular and plural).
+fn token_table(
+    rows: &[(String, usize, usize)],
+    top: Option<usize>,
+    noun: [&str; 2],
+    files: usize,
+    total_note: &str,
+) -> String {
+    let total_tokens: usize = rows.iter().map(|(_, tokens, _)| tokens).sum();
+    let total_bytes: usize = rows.iter().map(|(_, _, bytes)| bytes).sum();
+    let share = |tokens: usize| 100.0 * tokens as f64 / total_tokens.max(1) as f64;
+
+    let mut out = String::new();
+    let shown = top.unwrap_or(rows.len()).min(rows.len());
+    for (path, tokens, bytes) in &rows[..shown] {
+        let _ = writeln!(
+            out,
+            "{tokens:>8} tokens  {:>5.1}%  {bytes:>8} bytes  {path}",
+            share(*tokens)
+        );
+    }
+    let rest = &rows[shown..];
+    if !rest.is_empty() {
+        let tokens: usize = rest.iter().map(|(_, tokens, _)| tokens).sum();
+        let bytes: usize = rest.iter().map(|(_, _, bytes)| bytes).sum();
+        let noun = if rest.len() == 1 { noun[0] } else { noun[1] };
+        let _ = writeln!(
+            out,
+            "{tokens:>8} tokens  {:>5.1}%  {bytes:>8} bytes  ... {} more {noun}",
+            share(tokens),
+            rest.len()
+        );
+    }
+
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "{total_tokens:>8} tokens  100.0%  {total_bytes:>8} bytes  total ({files} files{total_note})"
+    );
+    out
+}
+
+/// Per-file token/byte rows summed into the directories `depth` levels
+/// below the root (files nearer the root count toward their own directory,
+/// `./` for the root itself), in path order and labelled with how many
+/// files each holds.
+fn dir_rows(rows: &[(String, usize, usize)], depth: usize) -> Vec<(String, usize, usize)> {
+    let mut dirs: std::collections::BTreeMap<String, (usize, usize, usize)> = Default::default();
+    for (path, tokens, bytes) in rows {
+        let parts: Vec<&str> = path.split('/').collect();
+        let dir = parts[..(parts.len() - 1).min(depth.max(1))].join("/");
+        let entry = dirs.entry(dir).or_default();
+        entry.0 += tokens;
+        entry.1 += bytes;
+        entry.2 += 1;
+    }
+    dirs.into_iter()
+        .map(|(dir, (tokens, bytes, files))| {
+            let dir = if dir.is_empty() { ".".to_string() } else { dir };
+            let noun = if files == 1 { "file" } else { "files" };
+            (format!("{dir}/ ({files} {noun})"), tokens, bytes)
+        })
+        .collect()
+}
+
+/// Order token table rows: by path (as walked), or by tokens or bytes with
+/// the largest first.
+fn sort_token_rows(rows: &mut [(String, usize, usize)], by: &str) {
+    match by {
+        "tokens" => rows.sort_by_key(|row| std::cmp::Reverse(row.1)),
+        "bytes" => rows.sort_by_key(|row| std::cmp::Reverse(row.2)),
+        _ => {}
+    }
+}
+
+/// `fence`, lengthened when a line of `content` could close it: CommonMark
+/// ends a code block at any line made of at least as many of the fence's
+/// characters, so a README with ``` blocks gets a ```` fence.
+fn fence_for(fence: &str, content: &str) -> String {
+    let Some(c) = fence.chars().next() else {
+        return fence.to_string();
+    };
+    let longest = content
+        .lines()
+        .map(|line| line.trim_start().chars().take_while(|&x| x == c).count())
+        .max()
+        .unwrap_or(0);
+    if longest < fence.len() {
+        fence.to_string()
+    } else {
+        c.to_string().repeat(longest + 1)
+    }
+}
+
+fn format_markdown(files: &[CollectedFile], opts: &FormatOptions) -> String {
+    let mut output = String::new();
+
+    if let Some(ref header) = opts.header {
+        let _ = writeln!(output, "{}", header.trim_end_matches('\n'));
+        let _ = writeln!(output);
+    }
+
+    if opts.show_tokens {
+        let total_tokens: usize = count_tokens(files).iter().sum();
+        let total_bytes: usize = files.iter().map(|f| f.content.len()).sum();
+        let _ = writeln!(
+            output,
+            "<!-- {files} files | {bytes} bytes | ~{tokens} tokens -->",
+            files = files.len(),
+            bytes = total_bytes,
+            tokens = total_tokens
+        );
+        let _ = writeln!(output);
+    }
+
+    let file_tokens = if opts.collapsible || opts.group_by_dir {
+        count_tokens(files)
+    } else {
+        Vec::new()
+    };
+
+    let fence = opts.fence.as_deref().unwrap_or(DEFAULT_FENCE);
+    write_markdown_maps(&mut output, opts, fence);
+
+    if let Some((ref session, ref paths)) = opts.unchanged {
+        if !paths.is_empty() {
+            let list: Vec<String> = paths.iter().map(|p| format!("`{p}`")).collect();
+            let _ = writeln!(
+                output,
+                "_Already sent in session {session} and unchanged: {}_",
+                list.join(", ")
+            );
+            let _ = writeln!(output);
+        }
+    }
+
+    let write_file = |output: &mut String, i: usize, heading: &str| {
+        let file = &files[i];
+        let lang = if opts.no_lang_tags {
+            ""
+        } else {
+            opts.lang
+                .unwrap_or_else(|| lang_tag(Path::new(&file.relative_path)))
+        };
+        if opts.collapsible {
+            let _ = writeln!(output, "<details>");
+            let _ = writeln!(
+                output,
+                "<summary>{} ({} tokens)</summary>",
+                xml_escape(&file.relative_path),
+                file_tokens[i]
+            );
+        } else {
+            let _ = writeln!(output, "{heading} `{}`", file.relative_path);
+        }
+        let _ = writeln!(output);
+        // Ensure content doesn't end with extra newlines inside fence
+        let content = file.content.trim_end_matches('\n');
+        let fence = fence_for(fence, content);
+        let _ = writeln!(output, "{fence}{lang}");
+        let _ = writeln!(output, "{content}");
+        let _ = writeln!(output, "{fence}");
+        let _ = writeln!(output);
+        if opts.collapsible {
+            let _ = writeln!(output, "</details>");
+            let _ = writeln!(output);
+        }
+    };
+
+    if opts.group_by_dir {
+        for (dir, members) in dir_groups(files) {
+            let tokens: usize = members.iter().map(|&i| file_tokens[i]).sum();
+            let title = if dir.is_empty() {
+                "Top level".to_string()
+            } else {
+                format!("`{dir}/`")
+            };
+            let _ = writeln!(
+                output,
+                "# {title} ({} files, ~{tokens} tokens)",
+                members.len()
+            );
+            let _ = writeln!(output);
+            for i in members {
+                write_file(&mut output, i, "###");
+            }
+        }
+    } else {
+        for i in 0..files.len() {
+            write_file(&mut output, i, "##");
+        }
+    }
+
+    if let Some(ref footer) = opts.footer {
+        let _ = writeln!(output, "{}", footer.trim_end_matches('\n'));
+    }
+
+    output
+}
+
+/// Write the directory tree and import graph sections, when there are any.
+fn write_markdown_maps(output: &mut String, opts: &FormatOptions, fence: &str) {
+    if let Some(ref tree) = opts.tree {
+        let _ = writeln!(output, "## Directory structure");
+        let _ = writeln!(output);
+        let _ = writeln!(output, "{fence}text");
+        let _ = write!(output, "{tree}");
+        let _ = writeln!(output, "{fence}");
+        let _ = writeln!(output);
+    }
+
+    if let Some(ref graph) = opts.graph {
+        let _ = writeln!(output, "## Dependency graph");
+        let _ = writeln!(output);
+        let _ = writeln!(output, "{fence}mermaid");
+        let _ = write!(output, "{graph}");
+        let _ = writeln!(output, "{fence}");
+        let _ = writeln!(output);
+    }
+}
+
+/// Lines of each file `--format summary` shows when not told otherwise.
+const SUMMARY_LINES: usize = 10;
+
+/// Top-level symbols `--format summary` lists per file before cutting off.
+const SUMMARY_SYMBOLS: usize = 40;
+
+/// A map of the files rather than their contents: for each, its size and
+/// token estimate, the top-level declarations `outline` finds, and its first
+/// few lines.
+fn format_summary(files: &[CollectedFile], opts: &FormatOptions) -> String {
+    let mut output = String::new();
+
+    if let Some(ref header) = opts.header {
+        let _ = writeln!(output, "{}", header.trim_end_matches('\n'));
+        let _ = writeln!(output);
+    }
+
+    let tokens = count_tokens(files);
+    let _ = writeln!(
+        output,
+        "<!-- summary of {} files | {} bytes | ~{} tokens in full -->",
+        files.len(),
+        files.iter().map(|f| f.content.len()).sum::<usize>(),
+        tokens.iter().sum::<usize>()
+    );
+    let _ = writeln!(output);
+
+    let fence = opts.fence.as_deref().unwrap_or(DEFAULT_FENCE);
+    write_markdown_maps(&mut output, opts, fence);
+
+    let head_lines = opts.summary_lines.unwrap_or(SUMMARY_LINES);
+    for (file, tokens) in files.iter().zip(tokens) {
+        let path = Path::new(&file.relative_path);
+        let lines = file.content.lines().count();
+        let _ = writeln!(output, "## `{}`", file.relative_path);
+        let _ = writeln!(output);
+        let _ = writeln!(
+            output,
+            "{} bytes, ~{tokens} tokens, {lines} lines",
+            file.content.len()
+        );
+        let _ = writeln!(output);
+
+        let symbols = outline::symbols(path, &file.content);
+        if !symbols.is_empty() {
+            for symbol in symbols.iter().take(SUMMARY_SYMBOLS) {
+                let _ = writeln!(output, "- `{symbol}`");
+            }
+            if symbols.len() > SUMMARY_SYMBOLS {
+                let _ = writeln!(output, "- ... {} more", symbols.len() - SUMMARY_SYMBOLS);
+            }
+            let _ = writeln!(output);
+        }
+
+        if head_lines > 0 && lines > 0 {
+            let head: Vec<&str> = file.content.lines().take(head_lines).collect();
+            let head = head.join("\n");
+            let lang = if opts.no_lang_tags {
+                ""
+            } else {
+                opts.lang.unwrap_or_else(|| lang_tag(path))
+            };
+            let fence = fence_for(fence, &head);
+            let _ = writeln!(output, "{fence}{lang}");
+            let _ = writeln!(output, "{head}");
+            let _ = writeln!(output, "{fence}");
+            if lines > head_lines {
+                let _ = writeln!(output, "_... {} more lines_", lines - head_lines);
+            }
+            let _ = writeln!(output);
+        }
+    }
+
+    if let Some(ref footer) = opts.footer {
+        let _ = writeln!(output, "{}", footer.trim_end_matches('\n'));
+    }
+
+    output
+}
+
+/// Indices of `files` grouped by top-level directory (empty for files at the
+/// root), with groups in order of their first file so a ranked order
+/// survives.
+fn dir_groups(files: &[CollectedFile]) -> Vec<(&str, Vec<usize>)> {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        let dir = match file.relative_path.split_once('/') {
+            Some((dir, _)) => dir,
+            None => "",
+        };
+        match groups.iter_mut().find(|(d, _)| *d == dir) {
+            Some((_, members)) => members.push(i),
+            None => groups.push((dir, vec![i])),
+        }
+    }
+    groups
+}
+
+/// Escape text for inclusion in XML element content.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escape text for inclusion in a double- or single-quoted XML attribute.
+/// Tabs and line breaks become character references, which parsers would
+/// otherwise normalize to spaces.
+fn xml_attr_escape(text: &str) -> String {
+    xml_escape(text)
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+        .replace('\t', "&#9;")
+        .replace('\n', "&#10;")
+        .replace('\r', "&#13;")
+}
+
+/// `text` as a CDATA section. A `]]>` inside it would end the section
+/// early, so the section is closed after its `]]` and reopened for the `>`.
+fn xml_cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+/// Parse an XML element name for `--xml-root` / `--xml-file-element`.
+fn parse_xml_name(s: &str) -> Result<String, String> {
+    let mut chars = s.chars();
+    let valid_start = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_');
+    let valid_rest = chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+    if valid_start && valid_rest && !s.to_lowercase().starts_with("xml") {
+        Ok(s.to_string())
+    } else {
+        Err(format!("'{s}' is not a valid XML element name"))
+    }
+}
+
+/// Version of the XML output contract (`schema/gather-context.xsd`), emitted
+/// on the root element. Bump it on incompatible changes.
+const XML_SCHEMA_VERSION: u32 = 2;
+
+fn format_xml(files: &[CollectedFile], opts: &FormatOptions) -> String {
+    let mut output = String::new();
+    let root = opts.xml_root.as_deref().unwrap_or("context");
+    let element = opts.xml_file_element.as_deref().unwrap_or("file");
+
+    let _ = writeln!(output, "<{root} version=\"{XML_SCHEMA_VERSION}\">");
+
+    if let Some(ref header) = opts.header {
+        let _ = writeln!(
+            output,
+            "  <header>{}</header>",
+            xml_escape(header.trim_end())
+        );
+    }
+
+    if opts.show_tokens {
+        let total_tokens: usize = count_tokens(files).iter().sum();
+        let _ = writeln!(
+            output,
+            "  <meta files=\"{}\" tokens=\"~{}\"/>",
+            files.len(),
+            total_tokens
+        );
+    }
+
+    if let Some(ref tree) = opts.tree {
+        let _ = writeln!(output, "  <tree>");
+        let _ = write!(output, "{}", xml_escape(tree));
+        let _ = writeln!(output, "  </tree>");
+    }
+
+    if let Some(ref graph) = opts.graph {
+        let _ = writeln!(output, "  <graph format=\"mermaid\">");
+        let _ = write!(output, "{}", xml_escape(graph));
+        let _ = writeln!(output, "  </graph>");
+    }
+
+    if let Some((ref session, ref paths)) = opts.unchanged {
+        if !paths.is_empty() {
+            let _ = writeln!(
+                output,
+                "  <unchanged session=\"{}\">{}</unchanged>",
+                xml_attr_escape(session),
+                xml_escape(&paths.join(", "))
+            );
+        }
+    }
+
+    let file_tokens = if opts.file_meta {
+        count_tokens(files)
+    } else {
+        Vec::new()
+    };
+
+    for (i, file) in files.iter().enumerate() {
+        let _ = write!(
+            output,
+            "  <{element} path=\"{}\"",
+            xml_attr_escape(&file.relative_path)
+        );
+        if opts.file_meta {
+            let lang = opts
+                .lang
+                .unwrap_or_else(|| lang_tag(Path::new(&file.relative_path)));
+            if !lang.is_empty() {
+                let _ = write!(output, " lang=\"{lang}\"");
+            }
+            let _ = write!(
+                output,
+                " bytes=\"{}\" tokens=\"{}\" lines=\"{}\" sha256=\"{}\"",
+                file.content.len(),
+                file_tokens[i],
+                file.content.lines().count(),
+                manifest::sha256_hex(file.content.as_bytes())
+            );
+            let mtime = fs::metadata(opts.root.join(&file.relative_path))
+                .ok()
+                .and_then(|m| manifest::mtime_ns(&m));
+            if let Some(ns) = mtime {
+                let timestamp = template::format_utc_timestamp(ns / 1_000_000_000);
+                let _ = write!(output, " mtime=\"{timestamp}\"");
+            }
+        }
+        let _ = writeln!(output, ">");
+        if opts.xml_cdata {
+            let _ = writeln!(output, "{}", xml_cdata(&file.content));
+        } else {
+            let escaped = xml_escape(&file.content);
+            let _ = write!(output, "{escaped}");
+            if !escaped.ends_with('\n') {
+                let _ = writeln!(output);
+            }
+        }
+        let _ = writeln!(output, "  </{element}>");
+    }
+
+    if let Some(ref footer) = opts.footer {
+        let _ = writeln!(
+            output,
+            "  <footer>{}</footer>",
+            xml_escape(footer.trim_end())
+        );
+    }
+
+    let _ = writeln!(output, "</{root}>");
+
+    output
+}
+
+/// Version of the `--format json` document (`schema/context.schema.json`).
+/// Bump it on incompatible changes.
+const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// The `--format json` document: every file with its metadata, and a
+/// summary record.
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonDocument {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    header: Option<String>,
+    /// Directory tree of the files (`--with-tree`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tree: Option<String>,
+    /// Mermaid import graph of the files (`--deps-graph`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    graph: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unchanged: Option<JsonUnchanged>,
+    files: Vec<JsonFile>,
+    summary: JsonSummary,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    footer: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonFile {
+    path: String,
+    language: Option<String>,
+    bytes: usize,
+    tokens: usize,
+    content: String,
+}
+
+/// Files a session already has, sent again in name only.
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonUnchanged {
+    session: String,
+    paths: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonSummary {
+    files: usize,
+    bytes: usize,
+    tokens: usize,
+}
+
+fn format_json(files: &[CollectedFile], opts: &FormatOptions) -> String {
+    let tokens = count_tokens(files);
+    let entries: Vec<JsonFile> = files
+        .iter()
+        .zip(&tokens)
+        .map(|(file, &tokens)| {
+            let lang = opts
+                .lang
+                .unwrap_or_else(|| lang_tag(Path::new(&file.relative_path)));
+            JsonFile {
+                path: file.relative_path.clone(),
+                language: (!lang.is_empty()).then(|| lang.to_string()),
+                bytes: file.content.len(),
+                tokens,
+                content: file.content.clone(),
+            }
+        })
+        .collect();
+    let doc = JsonDocument {
+        version: JSON_SCHEMA_VERSION,
+        header: opts.header.clone(),
+        tree: opts.tree.clone(),
+        graph: opts.graph.clone(),
+        unchanged: opts
+            .unchanged
+            .as_ref()
+            .filter(|(_, paths)| !paths.is_empty())
+            .map(|(session, paths)| JsonUnchanged {
+                session: session.clone(),
+                paths: paths.clone(),
+            }),
+        summary: JsonSummary {
+            files: entries.len(),
+            bytes: entries.iter().map(|f| f.bytes).sum(),
+            tokens: tokens.iter().sum(),
+        },
+        files: entries,
+        footer: opts.footer.clone(),
+    };
+    let mut output = serde_json::to_string_pretty(&doc).unwrap_or_default();
+    output.push('\n');
+    output
+}
+
+/// Root-relative paths of the files the tree shows, sorted.
+fn tree_paths(root: &Path, args: &FilterArgs) -> Vec<String> {
+    let filter = PathFilter::new(root, args);
+    let mut paths: Vec<String> = walk_files(root, args)
+        .into_iter()
+        .map(|(_, rel)| rel)
+        .filter(|rel| filter.matches(rel))
+        .collect();
+    paths.sort_by(|a, b| args.cmp_paths(a, b));
+    paths
+}
+
+/// Compare `current` against the lockfile at `lock_path`, exiting with an
+/// error listing every mismatch if the tree has drifted.
+fn verify_lock(lock_path: &Path, current: &Lockfile) {
+    let locked = Lockfile::load(lock_path).unwrap_or_else(|e| {
+        eprintln!("Failed to read lockfile {}: {e}", lock_path.display());
+        std::process::exit(status::ERROR);
+    });
+
+    if locked.gather_version != current.gather_version {
+        status::warn(format!(
+            "{} was written by gather {} (running {})",
+            lock_path.display(),
+            locked.gather_version,
+            current.gather_version
+        ));
+    }
+
+    let problems = locked.mismatches(current);
+    if !problems.is_empty() {
+        eprintln!(
+            "Lock verification failed: {} file(s) differ from {}",
+            problems.len(),
+            lock_path.display()
+        );
+        for problem in &problems {
+            eprintln!("  {problem}");
+        }
+        std::process::exit(status::ERROR);
+    }
+}
+
+/// Fetch each `--url` as a file named by its URL. Failures and non-UTF-8
+/// bodies are reported and left out.
+fn fetch_urls(urls: &[String]) -> Vec<CollectedFile> {
+    let cache = http::HttpCache::new();
+    urls.iter()
+        .filter_map(|url| {
+            let fetched = cache
+                .get(url)
+                .and_then(|body| String::from_utf8(body).map_err(|_| "not UTF-8".to_string()));
+            match fetched {
+                Ok(content) => Some(CollectedFile {
+                    relative_path: url.clone(),
+                    content,
+                }),
+                Err(e) => {
+                    status::warn(format!("skipping {url}: {e}"));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// `--buffers-from` entries with paths made relative to `root`. Buffers
+/// outside it are reported and dropped, and repeats keep their first position.
+fn open_buffers(root: &Path, buffers: Vec<buffers::Buffer>) -> Vec<buffers::Buffer> {
+    let mut seen = HashSet::new();
+    buffers
+        .into_iter()
+        .filter_map(|mut buffer| {
+            let Some(rel) = buffers::relative_path(root, &buffer.path) else {
+                status::warn(format!(
+                    "skipping buffer {}: not under {}",
+                    buffer.path,
+                    root.display()
+                ));
+                return None;
+            };
+            buffer.path = rel;
+            seen.insert(buffer.path.clone()).then_some(buffer)
+        })
+        .collect()
+}
+
+/// Read a `--files-from` list from `path` (`-` for stdin): paths separated
+/// by NULs when there are any (`fd -0`, `git ls-files -z`), otherwise one per
+/// line. Relative paths are taken from the current directory, like those `fd`
+/// and `rg -l` print, and made relative to `root`; paths outside it are
+/// reported and dropped, and repeats keep their first position.
+fn load_file_list(root: &Path, path: &Path) -> Result<Vec<String>, String> {
+    let bytes = if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes)
+            .map_err(|e| format!("--files-from: failed to read stdin: {e}"))?;
+        bytes
+    } else {
+        fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?
+    };
+    let cwd = std::env::current_dir().map_err(|e| format!("--files-from: {e}"))?;
+    Ok(parse_file_list(
+        root,
+        &cwd,
+        &String::from_utf8_lossy(&bytes),
+    ))
+}
+
+fn parse_file_list(root: &Path, cwd: &Path, text: &str) -> Vec<String> {
+    let entries: Vec<&str> = if text.contains('\0') {
+        text.split('\0').collect()
+    } else {
+        text.lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .collect()
+    };
+    let mut seen = HashSet::new();
+    entries
+        .into_iter()
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let absolute = cwd.join(entry);
+            let Some(rel) = buffers::relative_path(root, &absolute.to_string_lossy()) else {
+                status::warn(format!(
+                    "--files-from: skipping {entry}: not under {}",
+                    root.display()
+                ));
+                return None;
+            };
+            seen.insert(rel.clone()).then_some(rel)
+        })
+        .collect()
+}
+
+/// Put collected buffers in the editor's order, cut down to the lines around
+/// each cursor or visible range. Files that didn't come from a buffer (such
+/// as `--url` fetches) follow.
+fn focus_buffers(
+    files: Vec<CollectedFile>,
+    buffers: &[buffers::Buffer],
+    context: usize,
+) -> Vec<CollectedFile> {
+    let mut rest = files;
+    let mut focused = Vec::new();
+    for buffer in buffers {
+        if let Some(i) = rest.iter().position(|f| f.relative_path == buffer.path) {
+            let mut file = rest.remove(i);
+            file.content = buffers::excerpt(&file.content, buffer, context);
+            focused.push(file);
+        }
+    }
+    focused.extend(rest);
+    focused
+}
+
+/// `path:line:text` for each line containing `pattern`, or with
+/// `files_only` just the path of each file with a match.
+fn grep_lines(
+    files: &[CollectedFile],
+    pattern: &str,
+    ignore_case: bool,
+    files_only: bool,
+) -> String {
+    let needle = if ignore_case {
+        pattern.to_lowercase()
+    } else {
+        pattern.to_string()
+    };
+    let mut out = String::new();
+    for file in files {
+        for (n, line) in file.content.lines().enumerate() {
+            let found = if ignore_case {
+                line.to_lowercase().contains(&needle)
+            } else {
+                line.contains(&needle)
+            };
+            if !found {
+                continue;
+            }
+            if files_only {
+                let _ = writeln!(out, "{}", file.relative_path);
+                break;
+            }
+            let _ = writeln!(out, "{}:{}:{line}", file.relative_path, n + 1);
+        }
+    }
+    out
+}
+
+/// Files in the tree that the `--transcript` at `path` mentions, in order of
+/// first mention. Every non-ignored file is a candidate, whatever the filters.
+fn transcript_files(root: &Path, path: &Path, opts: &CollectOptions) -> Vec<String> {
+    let text = transcript::load(path).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(status::ERROR);
+    });
+    let mentions = transcript::mentions(&text);
+    let candidates: Vec<String> = walk_files(root, &FilterArgs::default())
+        .into_iter()
+        .map(|(_, rel)| rel)
+        .collect();
+    let files = transcript::resolve(root, &mentions, &candidates, opts.max_size);
+    eprintln!(
+        "Transcript {}: {} paths and symbols mentioned, matching {} files",
+        path.display(),
+        mentions.paths.len() + mentions.symbols.len(),
+        files.len()
+    );
+    files
+}
+
+/// Read any of `paths` that the collection doesn't already have, bypassing
+/// the include/exclude filters but not the size and content checks.
+fn force_include(
+    root: &Path,
+    collection: &mut Collection,
+    paths: &[String],
+    opts: &CollectOptions,
+) {
+    let have: HashSet<&str> = collection
+        .files
+        .iter()
+        .map(|f| f.relative_path.as_str())
+        .collect();
+    let missing: Vec<String> = paths
+        .iter()
+        .filter(|p| !have.contains(p.as_str()))
+        .cloned()
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+    let forced_opts = CollectOptions {
+        filter: FilterArgs {
+            paths: Some(missing),
+            ..FilterArgs::default()
+        },
+        ..opts.clone()
+    };
+    let forced = collect_files(root, &forced_opts);
+    let forced_paths: HashSet<&str> = forced
+        .files
+        .iter()
+        .map(|f| f.relative_path.as_str())
+        .collect();
+    collection
+        .skipped
+        .retain(|s| !forced_paths.contains(s.relative_path.as_str()));
+    collection.files.extend(forced.files);
+    collection
+        .files
+        .sort_by(|a, b| opts.filter.cmp_paths(&a.relative_path, &b.relative_path));
+}
+
+/// Split `files`, in order, into consecutive parts of at most `limit`
+/// estimated tokens. A file larger than the limit gets a part to itself.
+fn partition(files: Vec<CollectedFile>, limit: usize) -> Vec<Vec<CollectedFile>> {
+    let tokens = count_tokens(&files);
+    let mut parts: Vec<Vec<CollectedFile>> = vec![Vec::new()];
+    let mut used = 0;
+    for (file, tokens) in files.into_iter().zip(tokens) {
+        let current = parts.last_mut().expect("there is always a part");
+        if !current.is_empty() && used + tokens > limit {
+            parts.push(Vec::new());
+            used = 0;
+        }
+        used += tokens;
+        parts.last_mut().expect("there is always a part").push(file);
+    }
+    parts
+}
+
+/// Move files named in `paths` to the front, in that order.
+fn order_by_paths(files: &mut [CollectedFile], paths: &[String]) {
+    files.sort_by_key(|f| {
+        paths
+            .iter()
+            .position(|p| *p == f.relative_path)
+            .unwrap_or(usize::MAX)
+    });
+}
+
+/// Keep files while their estimated tokens fit in `budget`, trying them in
+/// `order` (or as they are). A file that doesn't fit is skipped, and smaller
+/// ones after it may still be added. Kept files stay in their original order.
+fn apply_token_budget(
+    root: &Path,
+    collection: &mut Collection,
+    budget: usize,
+    order: Option<BudgetOrder>,
+    filter: &FilterArgs,
+) {
+    let files = std::mem::take(&mut collection.files);
+    let tokens = count_tokens(&files);
+    let mut priority: Vec<usize> = (0..files.len()).collect();
+    match order {
+        None => {}
+        Some(BudgetOrder::Path) => priority
+            .sort_by(|&a, &b| filter.cmp_paths(&files[a].relative_path, &files[b].relative_path)),
+        Some(BudgetOrder::Size) => priority.sort_by_key(|&i| files[i].content.len()),
+        Some(BudgetOrder::Mtime) => {
+            // Newest first; fetched URLs have no mtime and go last
+            let mtime = |i: usize| {
+                fs::metadata(root.join(&files[i].relative_path))
+                    .and_then(|m| m.modified())
+                    .ok()
+            };
+            let mtimes: Vec<_> = (0..files.len()).map(mtime).collect();
+            priority.sort_by_key(|&i| std::cmp::Reverse(mtimes[i]));
+        }
+    }
+
+    let mut keep = vec![false; files.len()];
+    let mut used = 0;
+    for i in priority {
+        if used + tokens[i] <= budget {
+            used += tokens[i];
+            keep[i] = true;
+        }
+    }
+    for (file, keep) in files.into_iter().zip(keep) {
+        if keep {
+            collection.files.push(file);
+        } else {
+            collection.skipped.push(SkippedFile {
+                relative_path: file.relative_path,
+                reason: SkipReason::OverBudget,
+            });
+        }
+    }
+    collection
+        .skipped
+        .sort_by(|a, b| filter.cmp_paths(&a.relative_path, &b.relative_path));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::make_test_dir_with;
+    use std::fs;
+
+    /// A directory with a Rust, a JSON and a Markdown file.
+    fn sample_dir(name: &str) -> PathBuf {
+        make_test_dir_with(
+            name,
+            &[
+                ("hello.rs", "fn main() {}\n"),
+                ("data.json", "{\"key\": \"value\"}\n"),
+                ("notes.md", "# Notes\n"),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        let notes = Path::new("notes.txt");
+        assert_eq!(estimate_tokens(notes, ""), 0);
+        assert_eq!(estimate_tokens(notes, "hi"), 1); // 2 chars -> ceil(2/4) = 1
+        assert_eq!(estimate_tokens(notes, "hello world"), 3); // 11 chars -> ceil(11/4) = 3
+        assert_eq!(estimate_tokens(notes, "abcd"), 1); // exactly 4 chars -> 1 token
+        assert_eq!(estimate_tokens(Path::new("data.json"), "abcd"), 2); // 4 / 3.0 -> 2
+    }
+
+    #[test]
+    fn test_count_tokens_preserves_order() {
+        let files: Vec<CollectedFile> = (0..100)
+            .map(|i| CollectedFile {
+                relative_path: format!("f{i}.txt"),
+                content: "x".repeat(i * 4),
+            })
+            .collect();
+        let counts = count_tokens(&files);
+        assert_eq!(counts, (0..100).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_is_binary() {
+        let dir = sample_dir("is_binary");
+
+        let text_file = dir.join("text.txt");
+        fs::write(&text_file, "hello world").unwrap();
+        assert!(!is_binary(&fs::read(&text_file).unwrap()));
+
+        let bin_file = dir.join("binary.bin");
+        fs::write(&bin_file, b"\x00\x01\x02\x03").unwrap();
+        assert!(is_binary(&fs::read(&bin_file).unwrap()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_minified() {
+        let readable = "function add(a, b) {\n  return a + b;\n}\n".repeat(50);
+        assert!(!is_minified(Path::new("src/add.js"), &readable));
+        assert!(is_minified(Path::new("vendor/jquery.min.js"), "x"));
+        assert!(is_minified(Path::new("dist/app.js.map"), "{}"));
+        assert!(is_minified(
+            Path::new("dist/app.js"),
+            "var a=1;\n//# sourceMappingURL=app.js.map\n"
+        ));
+
+        let one_line = "var a=1;".repeat(200);
+        assert!(is_minified(Path::new("dist/bundle.js"), &one_line));
+        // Long lines in prose are not a minification signal
+        assert!(!is_minified(Path::new("notes.md"), &one_line));
+    }
+
+    #[test]
+    fn test_lang_tag() {
+        assert_eq!(lang_tag(Path::new("main.rs")), "rust");
+        assert_eq!(lang_tag(Path::new("app.py")), "python");
+        assert_eq!(lang_tag(Path::new("index.js")), "javascript");
+        assert_eq!(lang_tag(Path::new("config.toml")), "toml");
+        assert_eq!(lang_tag(Path::new("Makefile")), "");
+    }
+
+    #[test]
+    fn test_collect_files_basic() {
+        let dir = sample_dir("basic");
+        let files = collect_files(&dir, &CollectOptions::default()).files;
+        assert_eq!(files.len(), 3);
+
+        let paths: Vec<&str> = files.iter().map(|f| f.relative_path.as_str()).collect();
+        assert!(paths.contains(&"hello.rs"));
+        assert!(paths.contains(&"data.json"));
+        assert!(paths.contains(&"notes.md"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_glob_filter() {
+        let dir = sample_dir("glob");
+        let opts = CollectOptions {
+            filter: FilterArgs {
+                globs: vec!["*.rs".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let files = collect_files(&dir, &opts).files;
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].relative_path, "hello.rs");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_exclude() {
+        let dir = sample_dir("exclude");
+        let opts = CollectOptions {
+            filter: FilterArgs {
+                excludes: vec!["*.json".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let files = collect_files(&dir, &opts).files;
+        let paths: Vec<&str> = files.iter().map(|f| f.relative_path.as_str()).collect();
+        assert!(!paths.contains(&"data.json"));
+        assert!(paths.contains(&"hello.rs"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_gatherignore() {
+        let dir = sample_dir("gatherignore");
+        fs::create_dir_all(dir.join("fixtures")).unwrap();
+        fs::write(dir.join("fixtures/big.json"), "[]\n").unwrap();
+        fs::write(dir.join(".gatherignore"), "fixtures/\n*.md\n").unwrap();
+        let paths = tree_paths(&dir, &FilterArgs::default());
+        assert_eq!(paths, ["data.json", "hello.rs"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_hidden_and_no_ignore() {
+        let dir = sample_dir("hidden");
+        fs::create_dir_all(dir.join(".github/workflows")).unwrap();
+        fs::write(dir.join(".github/workflows/ci.yml"), "on: push\n").unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(dir.join(".gatherignore"), "*.md\n").unwrap();
+        let paths = |args: FilterArgs| tree_paths(&dir, &args);
+
+        assert_eq!(paths(FilterArgs::default()), ["data.json", "hello.rs"]);
+        assert_eq!(
+            paths(FilterArgs {
+                hidden: true,
+                ..Default::default()
+            }),
+            [
+                ".gatherignore",
+                ".github/workflows/ci.yml",
+                "data.json",
+                "hello.rs"
+            ]
+        );
+        assert_eq!(
+            paths(FilterArgs {
+                no_ignore: true,
+                ..Default::default()
+            }),
+            ["data.json", "hello.rs", "notes.md"]
+        );
+        // .gatherignore isn't a git rule
+        assert_eq!(
+            paths(FilterArgs {
+                no_ignore_vcs: true,
+                ..Default::default()
+            }),
+            ["data.json", "hello.rs"]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_max_depth_and_prune() {
+        let dir = sample_dir("max_depth_prune");
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+        fs::write(dir.join("src/nested/deep.rs"), "fn g() {}\n").unwrap();
+        fs::create_dir_all(dir.join("web/node_modules/pkg")).unwrap();
+        fs::write(dir.join("web/node_modules/pkg/index.js"), "x\n").unwrap();
+        fs::write(dir.join("web/app.js"), "y\n").unwrap();
+        let paths = |args: FilterArgs| tree_paths(&dir, &args);
+
+        assert_eq!(
+            paths(FilterArgs {
+                max_depth: Some(1),
+                ..Default::default()
+            }),
+            ["data.json", "hello.rs", "notes.md"]
+        );
+        // By name at any depth, or by path from the root
+        assert_eq!(
+            paths(FilterArgs {
+                prunes: vec!["node_modules".to_string(), "src/nested".to_string()],
+                ..Default::default()
+            }),
+            [
+                "data.json",
+                "hello.rs",
+                "notes.md",
+                "src/lib.rs",
+                "web/app.js"
+            ]
+        );
+        // Files are never pruned, only directories
+        assert!(paths(FilterArgs {
+            prunes: vec!["*.rs".to_string()],
+            ..Default::default()
+        })
+        .contains(&"hello.rs".to_string()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_files_follow_symlinks() {
+        let dir = sample_dir("follow_symlinks");
+        fs::create_dir_all(dir.join("shared/lib")).unwrap();
+        fs::write(dir.join("shared/lib/util.rs"), "fn util() {}\n").unwrap();
+        fs::create_dir_all(dir.join("app")).unwrap();
+        std::os::unix::fs::symlink(dir.join("shared"), dir.join("app/shared")).unwrap();
+        // A link back up the tree would otherwise recurse forever
+        std::os::unix::fs::symlink(&dir, dir.join("shared/lib/root")).unwrap();
+
+        let plain = tree_paths(&dir, &FilterArgs::default());
+        assert!(!plain.iter().any(|p| p.starts_with("app/")));
+        let followed = FilterArgs {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        // Files under the linked directory are gathered once, where they are
+        assert_eq!(
+            tree_paths(&dir, &followed),
+            ["data.json", "hello.rs", "notes.md", "shared/lib/util.rs"]
+        );
+        // A link to a directory outside the root brings its files in
+        let outside = sample_dir("follow_symlinks_outside");
+        std::os::unix::fs::symlink(&outside, dir.join("app/vendor")).unwrap();
+        assert!(tree_paths(&dir, &followed).contains(&"app/vendor/hello.rs".to_string()));
+        fs::remove_dir_all(&outside).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_max_size() {
+        let dir = sample_dir("maxsize");
+        // Set max size to 5 bytes — should exclude most files
+        let opts = CollectOptions {
+            max_size: 5,
+            ..Default::default()
+        };
+        let files = collect_files(&dir, &opts).files;
+        // All our test files are > 5 bytes
+        assert!(files.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_max_file_tokens() {
+        let dir = sample_dir("max_file_tokens");
+        // hello.rs is 13 bytes (4 tokens); notes.md is 8 bytes (2 tokens)
+        let opts = CollectOptions {
+            max_file_tokens: Some(3),
+            ..Default::default()
+        };
+        let files = collect_files(&dir, &opts).files;
+        let paths: Vec<&str> = files.iter().map(|f| f.relative_path.as_str()).collect();
+        assert_eq!(paths, vec!["notes.md"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_truncate_to_token_limit() {
+        let rows: Vec<String> = (0..2000)
+            .map(|i| format!("{{\"id\": {i}, \"ok\": true}},"))
+            .collect();
+        let json = format!("[\n{}\n]\n", rows.join("\n"));
+        let dir = make_test_dir_with("truncate_tokens", &[("rows.json", &json)]);
+        let opts = CollectOptions {
+            max_file_tokens: Some(1000),
+            oversize_strategy: OversizeStrategy::Truncate,
+            ..Default::default()
+        };
+        let files = collect_files(&dir, &opts).files;
+        let tokens = estimate_tokens(Path::new("rows.json"), &files[0].content);
+        assert!((900..=1000).contains(&tokens), "{tokens} tokens");
+        assert!(files[0].content.contains("[truncated: showing"));
+
+        // Only the start of a file over --max-size is read, but its size is
+        // reported in full
+        let opts = CollectOptions {
+            max_size: 100,
+            oversize_strategy: OversizeStrategy::Truncate,
+            ..Default::default()
+        };
+        let files = collect_files(&dir, &opts).files;
+        let total = format!("of {} bytes]", json.len());
+        assert!(files[0].content.ends_with(&format!("{total}\n")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_skip_reasons() {
+        let dir = sample_dir("skip_reasons");
+        fs::write(dir.join("image.bin"), b"\x89PNG\x00\x00").unwrap();
+        fs::write(dir.join("latin1.txt"), b"caf\xe9\n").unwrap();
+        fs::write(dir.join("big.txt"), "x".repeat(200)).unwrap();
+
+        let opts = CollectOptions {
+            filter: FilterArgs {
+                excludes: vec!["*.md".to_string()],
+                ..Default::default()
+            },
+            max_size: 100,
+            strict_utf8: true,
+            ..Default::default()
+        };
+        let collection = collect_files(&dir, &opts);
+        assert_eq!(collection.files.len(), 2);
+
+        let skipped: Vec<(&str, SkipReason)> = collection
+            .skipped
+            .iter()
+            .map(|s| (s.relative_path.as_str(), s.reason))
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![
+                ("big.txt", SkipReason::TooLarge),
+                ("image.bin", SkipReason::Binary),
+                ("latin1.txt", SkipReason::NotUtf8),
+                ("notes.md", SkipReason::Filtered),
+            ]
+        );
+        assert_eq!(
+            skip_summary(&collection.skipped).unwrap(),
+            "Skipped 4 files: 1 too large, 1 binary, 1 not UTF-8, 1 excluded by filters"
+        );
+
+        // Without --strict-utf8 the Latin-1 file is transcoded
+        let opts = CollectOptions {
+            strict_utf8: false,
+            ..opts
+        };
+        let collection = collect_files(&dir, &opts);
+        let latin1 = collection
+            .files
+            .iter()
+            .find(|f| f.relative_path == "latin1.txt")
+            .unwrap();
+        assert_eq!(latin1.content, "café\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_estimate_sizes() {
+        let dir = sample_dir("estimate_sizes");
+        fs::write(dir.join("big.txt"), "x".repeat(200)).unwrap();
+        let opts = CollectOptions {
+            max_size: 100,
+            ..Default::default()
+        };
+        let (sizes, skipped) = estimate_sizes(&dir, &opts);
+        assert_eq!(
+            sizes,
+            vec![
+                ("data.json".to_string(), 17),
+                ("hello.rs".to_string(), 13),
+                ("notes.md".to_string(), 8),
+            ]
+        );
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].reason, SkipReason::TooLarge);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_submodules() {
+        let dir = sample_dir("submodules");
+        fs::create_dir_all(dir.join("libs/a")).unwrap();
+        fs::create_dir_all(dir.join("libs/b")).unwrap();
+        fs::write(dir.join("libs/a/.git"), "gitdir: ../../.git/modules/a\n").unwrap();
+        fs::write(dir.join("libs/a/lib.rs"), "pub fn a() {}\n").unwrap();
+        fs::write(
+            dir.join(".gitmodules"),
+            "[submodule \"a\"]\n\tpath = libs/a\n[submodule \"b\"]\n\tpath = libs/b\n",
+        )
+        .unwrap();
+
+        let collection = collect_files(&dir, &CollectOptions::default());
+        assert!(!collection
+            .files
+            .iter()
+            .any(|f| f.relative_path == "libs/a/lib.rs"));
+        let reasons: Vec<(&str, SkipReason)> = collection
+            .skipped
+            .iter()
+            .map(|s| (s.relative_path.as_str(), s.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("libs/a", SkipReason::Submodule),
+                ("libs/b", SkipReason::UninitializedSubmodule),
+            ]
+        );
+
+        let opts = CollectOptions {
+            filter: FilterArgs {
+                submodules: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let collection = collect_files(&dir, &opts);
+        assert!(collection
+            .files
+            .iter()
+            .any(|f| f.relative_path == "libs/a/lib.rs"));
+        assert_eq!(collection.skipped.len(), 1);
+        assert_eq!(
+            collection.skipped[0].reason,
+            SkipReason::UninitializedSubmodule
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_token_budget_order() {
+        let dir = sample_dir("budget_order");
+        // data.json ~6 tokens, hello.rs ~4, notes.md ~2
+        let age = |name: &str, secs: u64| {
+            let file = fs::File::options()
+                .write(true)
+                .open(dir.join(name))
+                .unwrap();
+            let time = std::time::SystemTime::now() - std::time::Duration::from_secs(secs);
+            file.set_modified(time).unwrap();
+        };
+        age("data.json", 10);
+        age("notes.md", 20);
+        age("hello.rs", 30);
+        let kept = |order: Option<BudgetOrder>| {
+            let opts = CollectOptions::default();
+            let mut collection = collect_files(&dir, &opts);
+            apply_token_budget(&dir, &mut collection, 10, order, &opts.filter);
+            assert_eq!(collection.skipped.len(), 1);
+            assert_eq!(collection.skipped[0].reason, SkipReason::OverBudget);
+            collection
+                .files
+                .into_iter()
+                .map(|f| f.relative_path)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kept(None), ["data.json", "hello.rs"]);
+        assert_eq!(kept(Some(BudgetOrder::Path)), ["data.json", "hello.rs"]);
+        assert_eq!(kept(Some(BudgetOrder::Size)), ["hello.rs", "notes.md"]);
+        assert_eq!(kept(Some(BudgetOrder::Mtime)), ["data.json", "notes.md"]);
+        assert!("newest".parse::<BudgetOrder>().is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_no_tests() {
+        let dir = sample_dir("no_tests");
+        fs::create_dir_all(dir.join("tests")).unwrap();
+        fs::create_dir_all(dir.join("pkg")).unwrap();
+        fs::write(dir.join("tests/integration.rs"), "#[test]\nfn it() {}\n").unwrap();
+        fs::write(dir.join("pkg/test_util.py"), "def test_x(): pass\n").unwrap();
+        fs::write(dir.join("pkg/server_test.go"), "package pkg\n").unwrap();
+        fs::write(dir.join("pkg/app.spec.ts"), "describe('x')\n").unwrap();
+        fs::write(dir.join("pkg/contest.py"), "x = 1\n").unwrap();
+
+        let opts = CollectOptions {
+            filter: FilterArgs {
+                no_tests: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let files = collect_files(&dir, &opts).files;
+        let paths: Vec<&str> = files.iter().map(|f| f.relative_path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["data.json", "hello.rs", "notes.md", "pkg/contest.py"]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_include_package() {
+        let dir = sample_dir("include_package");
+        fs::write(dir.join(".ignore"), "node_modules/\n").unwrap();
+        for pkg in ["left-pad", "lodash", "left-pad/node_modules/dep"] {
+            fs::create_dir_all(dir.join("node_modules").join(pkg)).unwrap();
+            fs::write(dir.join("node_modules").join(pkg).join("index.js"), "x\n").unwrap();
+        }
+        fs::write(dir.join("node_modules/left-pad/.ignore"), "*.js\n").unwrap();
+
+        let opts = CollectOptions {
+            filter: FilterArgs {
+                include_package: vec!["left-pad".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let files = collect_files(&dir, &opts).files;
+        let paths: Vec<&str> = files.iter().map(|f| f.relative_path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "data.json",
+                "hello.rs",
+                "node_modules/left-pad/index.js",
+                "notes.md"
+            ]
+        );
+
+        // Names can't climb out of node_modules/
+        for name in ["../../outside", "@scope/../../x", "@scope", ".bin"] {
+            let args = FilterArgs {
+                include_package: vec![name.to_string()],
+                ..Default::default()
+            };
+            assert!(check_filter(&dir, &args).is_err(), "{name} accepted");
+        }
+        assert!(is_package_name("@types/node") && is_package_name("lodash.merge"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_exports_summarized_files_over_max_size() {
+        let mut lock = String::from(
+            "version = 3\n\n[[package]]\nname = \"app\"\nversion = \"0.1.0\"\n\
+             dependencies = [\"dep0\"]\n",
+        );
+        for i in 0..20 {
+            lock.push_str(&format!(
+                "\n[[package]]\nname = \"dep{i}\"\nversion = \"1.0.{i}\"\n\
+                 source = \"registry+https://github.com/rust-lang/crates.io-index\"\n"
+            ));
+        }
+        let big = "x".repeat(500);
+        let repo = make_test_dir_with("snapshot_lock", &[("Cargo.lock", &lock), ("big.txt", &big)]);
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success())
+        };
+        if !git(&["init", "-q"]) {
+            return; // git unavailable
+        }
+        git(&["add", "."]);
+        git(&["commit", "-qm", "lock"]);
+
+        let opts = CollectOptions {
+            max_size: 100,
+            ..CollectOptions::default()
+        };
+        let dest = repo.join("snapshot");
+        let unread = |rel: &str, size| opts.skips_unread(rel, size);
+        git::export_tree(&repo, "HEAD", &dest, &|_| true, &unread).unwrap();
+        // The oversized text file is a stand-in; the lockfile is real
+        assert_eq!(fs::read(dest.join("big.txt")).unwrap(), vec![0; 500]);
+        let collection = collect_files(&dest, &opts);
+        assert_eq!(collection.files.len(), 1);
+        assert!(collection.files[0]
+            .content
+            .starts_with("Cargo.lock: 21 packages locked."));
+        assert_eq!(collection.skipped[0].reason, SkipReason::TooLarge);
+
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_summarize_vendored() {
+        let dir = sample_dir("summarize_vendored");
+        let pkg = dir.join("vendor/widget");
+        fs::create_dir_all(pkg.join("src")).unwrap();
+        fs::write(pkg.join("Cargo.toml"), "[package]\nname = \"widget\"\n").unwrap();
+        fs::write(pkg.join("src/lib.rs"), "pub fn spin() {\n    loop {}\n}\n").unwrap();
+        fs::write(pkg.join("logo.svg"), "<svg/>\n").unwrap();
+
+        let opts = CollectOptions {
+            summarize_vendored: true,
+            ..Default::default()
+        };
+        let Collection { files, skipped } = collect_files(&dir, &opts);
+        let paths: Vec<&str> = files.iter().map(|f| f.relative_path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "data.json",
+                "hello.rs",
+                "notes.md",
+                "vendor/widget/Cargo.toml",
+                "vendor/widget/src/lib.rs"
+            ]
+        );
+        assert_eq!(files[4].content, "pub fn spin()\n");
+        assert_eq!(skipped[0].relative_path, "vendor/widget/logo.svg");
+        assert_eq!(skipped[0].reason, SkipReason::Vendored);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_files_lfs_pointer() {
+        let dir = sample_dir("lfs");
+        fs::write(
+            dir.join("model.bin"),
+            "version https://git-lfs.github.com/spec/v1\n\
+             oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n\
+             size 47185920\n",
+        )
+        .unwrap();
+
+        let files = collect_files(&dir, &CollectOptions::default()).files;
+        let lfs = files
+            .iter()
+            .find(|f| f.relative_path == "model.bin")
+            .unwrap();
+        assert_eq!(lfs.content, "[LFS object: 45.0 MB, not fetched]\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_markdown() {
+        let files = vec![CollectedFile {
+            relative_path: "test.rs".to_string(),
+            content: "fn main() {}\n".to_string(),
+        }];
+        let output = format_markdown(&files, &FormatOptions::default());
+        assert!(output.contains("## `test.rs`"));
+        assert!(output.contains("```rust"));
+        assert!(output.contains("fn main() {}"));
+    }
+
+    #[test]
+    fn test_format_markdown_with_tokens() {
+        let files = vec![CollectedFile {
+            relative_path: "test.rs".to_string(),
+            content: "fn main() {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            show_tokens: true,
+            ..Default::default()
+        };
+        let output = format_markdown(&files, &opts);
+        assert!(output.contains("<!-- 1 files"));
+        assert!(output.contains("tokens -->"));
+    }
+
+    #[test]
+    fn test_format_markdown_collapsible() {
+        let files = vec![CollectedFile {
+            relative_path: "a&b.rs".to_string(),
+            content: "fn main() {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            collapsible: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_markdown(&files, &opts),
+            "<details>\n<summary>a&amp;b.rs (4 tokens)</summary>\n\n```rust\nfn main() {}\n```\n\n</details>\n\n"
+        );
+    }
+
+    #[test]
+    fn test_format_markdown_fence_collision() {
+        let files = vec![CollectedFile {
+            relative_path: "README.md".to_string(),
+            content: "# Usage\n\n```sh\nmake\n```\n\n  `````\n".to_string(),
+        }];
+        let output = format_markdown(&files, &FormatOptions::default());
+        assert!(output.contains("``````markdown\n# Usage\n"));
+        assert!(output.ends_with("  `````\n``````\n\n"));
+        // Tilde fences only grow for tilde runs
+        assert_eq!(fence_for("~~~", "```\n"), "~~~");
+        assert_eq!(fence_for("~~~", "~~~~\n"), "~~~~~");
+        assert_eq!(fence_for("```", "a ``` b\n"), "```");
+    }
+
+    #[test]
+    fn test_format_json() {
+        let files = vec![
+            CollectedFile {
+                relative_path: "src/main.rs".to_string(),
+                content: "fn main() {}\n".to_string(),
+            },
+            CollectedFile {
+                relative_path: "LICENSE".to_string(),
+                content: "MIT\n".to_string(),
+            },
+        ];
+        let output = format_json(&files, &FormatOptions::default());
+        let doc: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(doc["version"], 1);
+        assert_eq!(doc["files"][0]["path"], "src/main.rs");
+        assert_eq!(doc["files"][0]["language"], "rust");
+        assert_eq!(doc["files"][0]["bytes"], 13);
+        assert_eq!(doc["files"][0]["content"], "fn main() {}\n");
+        assert!(doc["files"][1]["language"].is_null());
+        assert_eq!(doc["summary"]["files"], 2);
+        assert_eq!(doc["summary"]["bytes"], 17);
+        assert!(doc.get("header").is_none());
+    }
+
+    #[test]
+    fn test_format_markdown_group_by_dir() {
+        let file = |path: &str| CollectedFile {
+            relative_path: path.to_string(),
+            content: "abcd".to_string(),
+        };
+        let files = vec![file("src/b.rs"), file("README.md"), file("src/a/c.rs")];
+        let opts = FormatOptions {
+            group_by_dir: true,
+            no_lang_tags: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_markdown(&files, &opts),
+            "# `src/` (2 files, ~4 tokens)\n\n\
+             ### `src/b.rs`\n\n```\nabcd\n```\n\n\
+             ### `src/a/c.rs`\n\n```\nabcd\n```\n\n\
+             # Top level (1 files, ~1 tokens)\n\n\
+             ### `README.md`\n\n```\nabcd\n```\n\n"
+        );
+    }
+
+    #[test]
+    fn test_format_markdown_fence_options() {
+        let files = vec![CollectedFile {
+            relative_path: "test.rs".to_string(),
+            content: "fn main() {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            fence: Some("~~~~".to_string()),
+            no_lang_tags: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_markdown(&files, &opts),
+            "## `test.rs`\n\n~~~~\nfn main() {}\n~~~~\n\n"
+        );
+    }
+
+    #[test]
+    fn test_format_xml() {
+        let files = vec![CollectedFile {
+            relative_path: "test.rs".to_string(),
+            content: "fn main() {}\n".to_string(),
+        }];
+        let output = format_xml(&files, &FormatOptions::default());
+        assert!(output.contains("<context version=\"2\">"));
+        assert!(output.contains("<file path=\"test.rs\">"));
+        assert!(output.contains("</context>"));
+    }
+
+    #[test]
+    fn test_format_xml_escapes_special_chars() {
+        let files = vec![CollectedFile {
+            relative_path: "test.txt".to_string(),
+            content: "a < b && c > d\n".to_string(),
+        }];
+        let output = format_xml(&files, &FormatOptions::default());
+        assert!(output.contains("a &lt; b &amp;&amp; c &gt; d"));
+    }
+
+    #[test]
+    fn test_format_xml_cdata() {
+        let files = vec![CollectedFile {
+            relative_path: "line\nbreak.txt".to_string(),
+            content: "if a[b[0]]> c && d < e {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            xml_cdata: true,
+            ..Default::default()
+        };
+        let output = format_xml(&files, &opts);
+        assert!(output.contains("<file path=\"line&#10;break.txt\">\n"));
+        assert!(output.contains("<![CDATA[if a[b[0]]]]><![CDATA[> c && d < e {}\n]]>\n"));
+        let doc = roxmltree::Document::parse(&output).unwrap();
+        let file = doc.descendants().find(|n| n.has_tag_name("file")).unwrap();
+        assert_eq!(
+            file.attribute("path"),
+            Some(files[0].relative_path.as_str())
+        );
+        let text: String = file.children().filter_map(|n| n.text()).collect();
+        assert_eq!(text.trim_matches(' '), format!("\n{}\n", files[0].content));
+    }
+
+    #[test]
+    fn test_format_xml_attributes_and_names() {
+        let files = vec![CollectedFile {
+            relative_path: "it's \"a&b\".rs".to_string(),
+            content: "x\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            xml_root: Some("documents".to_string()),
+            xml_file_element: Some("document".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_xml(&files, &opts),
+            "<documents version=\"2\">\n  <document path=\"it&apos;s &quot;a&amp;b&quot;.rs\">\nx\n  </document>\n</documents>\n"
+        );
+        assert!(parse_xml_name("repo-context").is_ok());
+        assert!(parse_xml_name("1file").is_err());
+        assert!(parse_xml_name("my file").is_err());
+        assert!(parse_xml_name("xmlfoo").is_err());
+    }
+
+    #[test]
+    fn test_format_xml_file_meta() {
+        let files = vec![CollectedFile {
+            relative_path: "hello.rs".to_string(),
+            content: "fn main() {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            file_meta: true,
+            root: sample_dir("file_meta"),
+            ..Default::default()
+        };
+        let xml = format_xml(&files, &opts);
+        assert!(xml.contains(
+            "<file path=\"hello.rs\" lang=\"rust\" bytes=\"13\" tokens=\"4\" lines=\"1\" sha256=\""
+        ));
+        assert!(xml.contains(" mtime=\"20"));
+
+        fs::remove_dir_all(&opts.root).unwrap();
+    }
+
+    #[test]
+    fn test_format_xml_validates() {
+        let files = vec![CollectedFile {
+            relative_path: "a<b>.rs".to_string(),
+            content: "if a < b && c > d {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            show_tokens: true,
+            header: Some("head".to_string()),
+            footer: Some("foot".to_string()),
+            file_meta: true,
+            ..Default::default()
+        };
+        let xml = format_xml(&files, &opts);
+        assert!(xml.starts_with("<context version=\"2\">\n"));
+        assert_eq!(
+            validate::validate_xml(&xml, "context", "file"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_format_summary() {
+        let files = vec![
+            CollectedFile {
+                relative_path: "src/lib.rs".to_string(),
+                content:
+                    "//! Shapes.\n\npub struct Point;\n\npub fn origin() -> Point {\n    Point\n}\n"
+                        .to_string(),
+            },
+            CollectedFile {
+                relative_path: "notes.txt".to_string(),
+                content: "one\n".to_string(),
+            },
+        ];
+        let opts = FormatOptions {
+            summary_lines: Some(2),
+            ..Default::default()
+        };
+        let summary = format_summary(&files, &opts);
+        assert!(summary.starts_with("<!-- summary of 2 files | 75 bytes | ~"));
+        assert!(summary.contains(
+            "## `src/lib.rs`\n\n71 bytes, ~19 tokens, 7 lines\n\n\
+             - `pub struct Point;`\n- `pub fn origin() -> Point`\n\n\
+             ```rust\n//! Shapes.\n\n```\n_... 5 more lines_\n\n"
+        ));
+        assert!(
+            summary.ends_with("## `notes.txt`\n\n4 bytes, ~1 tokens, 1 lines\n\n```\none\n```\n\n")
+        );
+    }
+
+    #[test]
+    fn test_format_with_tree() {
+        let files = vec![CollectedFile {
+            relative_path: "src/a.rs".to_string(),
+            content: "fn a() {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            tree: Some(tree::text("app", &["src/a.rs".to_string()])),
+            ..Default::default()
+        };
+        let md = format_markdown(&files, &opts);
+        assert!(
+            md.starts_with("## Directory structure\n\n```text\napp/\n└── src/ (1 file)\n    └── a.rs\n\n1 file\n```\n\n")
+        );
+
+        let xml = format_xml(&files, &opts);
+        assert!(xml
+            .contains("  <tree>\napp/\n└── src/ (1 file)\n    └── a.rs\n\n1 file\n  </tree>\n  <file path=\"src/a.rs\">"));
+        assert_eq!(
+            validate::validate_xml(&xml, "context", "file"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_format_header_footer() {
+        let files = vec![CollectedFile {
+            relative_path: "test.rs".to_string(),
+            content: "fn main() {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            header: Some("# repo@main".to_string()),
+            footer: Some("-- end <ok> --".to_string()),
+            ..Default::default()
+        };
+        let md = format_markdown(&files, &opts);
+        assert!(md.starts_with("# repo@main\n\n"));
+        assert!(md.ends_with("-- end <ok> --\n"));
+
+        let xml = format_xml(&files, &opts);
+        assert!(xml.contains("  <header># repo@main</header>"));
+        assert!(xml.contains("  <footer>-- end &lt;ok&gt; --</footer>\n</context>"));
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        let root = Path::new("/work/repo");
+        let cwd = Path::new("/work/repo");
+        assert_eq!(
+            parse_file_list(root, cwd, "src/a.rs\r\n./src/b.rs\n\nsrc/a.rs\n"),
+            ["src/a.rs", "src/b.rs"]
+        );
+        assert_eq!(
+            parse_file_list(
+                root,
+                Path::new("/work/repo/src"),
+                "a.rs\0with\nnewline.rs\0"
+            ),
+            ["src/a.rs", "src/with\nnewline.rs"]
+        );
+        assert_eq!(
+            parse_file_list(root, cwd, "/work/repo/c.rs\n/elsewhere/d.rs\n"),
+            ["c.rs"]
+        );
+    }
+
+    #[test]
+    fn test_token_table() {
+        let mut rows = vec![
+            ("a.rs".to_string(), 10, 400),
+            ("b.rs".to_string(), 60, 100),
+            ("c.rs".to_string(), 30, 200),
+        ];
+        sort_token_rows(&mut rows, "tokens");
+        assert_eq!(
+            token_table(&rows, Some(1), ["file", "files"], rows.len(), ""),
+            "      60 tokens   60.0%       100 bytes  b.rs\n\
+             \x20     40 tokens   40.0%       600 bytes  ... 2 more files\n\
+             \n\
+             \x20    100 tokens  100.0%       700 bytes  total (3 files)\n"
+        );
+        sort_token_rows(&mut rows, "bytes");
+        assert_eq!(rows[0].0, "a.rs");
+    }
+
+    #[test]
+    fn test_dir_rows() {
+        let rows = vec![
+            ("README.md".to_string(), 5, 20),
+            ("src/lib.rs".to_string(), 20, 80),
+            ("tests/fixtures/a.json".to_string(), 40, 100),
+            ("tests/fixtures/deep/b.json".to_string(), 30, 90),
+            ("tests/it.rs".to_string(), 5, 20),
+        ];
+        assert_eq!(
+            dir_rows(&rows, 1),
+            [
+                ("./ (1 file)".to_string(), 5, 20),
+                ("src/ (1 file)".to_string(), 20, 80),
+                ("tests/ (3 files)".to_string(), 75, 210),
+            ]
+        );
+        let mut dirs = dir_rows(&rows, 2);
+        sort_token_rows(&mut dirs, "tokens");
+        assert_eq!(
+            token_table(&dirs, Some(2), ["directory", "directories"], rows.len(), ""),
+            "      70 tokens   70.0%       190 bytes  tests/fixtures/ (2 files)\n\
+             \x20     20 tokens   20.0%        80 bytes  src/ (1 file)\n\
+             \x20     10 tokens   10.0%        40 bytes  ... 2 more directories\n\
+             \n\
+             \x20    100 tokens  100.0%       310 bytes  total (5 files)\n"
+        );
+    }
+
+    #[test]
+    fn test_grep_lines() {
+        let files = vec![
+            CollectedFile {
+                relative_path: "a.rs".to_string(),
+                content: "fn Fetch() {}\nfetch();\nfetch();\n".to_string(),
+            },
+            CollectedFile {
+                relative_path: "b.rs".to_string(),
+                content: "nothing\n".to_string(),
+            },
+        ];
+        assert_eq!(
+            grep_lines(&files, "fetch", false, false),
+            "a.rs:2:fetch();\na.rs:3:fetch();\n"
+        );
+        assert_eq!(grep_lines(&files, "FETCH", true, true), "a.rs\n");
+        assert_eq!(grep_lines(&files, "absent", false, false), "");
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{status, CollectedFile};
+use globset::{Glob, GlobMatcher};
+
+/// Which lines to keep of the files a pattern matches: inclusive, 1-based
+/// ranges, where an open end runs to the last line.
+#[derive(Clone, Debug)]
+pub struct LineSpec {
+    pub pattern: String,
+    matcher: GlobMatcher,
+    ranges: Vec<(usize, Option<usize>)>,
+}
+
+/// Parse `PATTERN:RANGES`, where RANGES is a comma-separated list of `N`,
+/// `N-M` or `N-` (for example `src/main.rs:100-250` or `lib.rs:1-40,90-`).
+pub fn parse(spec: &str) -> Result<LineSpec, String> {
+    let (pattern, ranges) = spec
+        .rsplit_once(':')
+        .ok_or_else(|| format!("'{spec}': expected PATTERN:RANGES, such as src/main.rs:100-250"))?;
+    if pattern.is_empty() {
+        return Err(format!("'{spec}': missing the file pattern before ':'"));
+    }
+    let ranges = parse_ranges(ranges).map_err(|e| format!("'{spec}': {e}"))?;
+    let matcher = Glob::new(pattern)
+        .map_err(|e| format!("Invalid pattern {pattern}: {e}"))?
+        .compile_matcher();
+    Ok(LineSpec {
+        pattern: pattern.to_string(),
+        matcher,
+        ranges,
+    })
+}
+
+fn parse_ranges(text: &str) -> Result<Vec<(usize, Option<usize>)>, String> {
+    let line = |n: &str| match n.parse::<usize>() {
+        Ok(0) => Err("lines are numbered from 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("'{text}' is not a line range such as 100-250")),
+    };
+    text.split(',')
+        .map(|range| {
+            let (start, end) = match range.split_once('-') {
+                Some((start, "")) => (line(start)?, None),
+                Some((start, end)) => (line(start)?, Some(line(end)?)),
+                None => (line(range)?, Some(line(range)?)),
+            };
+            if end.is_some_and(|end| end < start) {
+                return Err(format!("range {range} ends before it starts"));
+            }
+            Ok((start, end))
+        })
+        .collect()
+}
+
+/// Split a `-g` pattern carrying a line range (`src/main.rs:100-250`) into
+/// the plain pattern and its spec. Patterns without one are `None`; a suffix
+/// of digits, `-` and `,` that isn't a valid range is an error.
+fn split_glob(glob: &str) -> Result<Option<(String, LineSpec)>, String> {
+    let Some((pattern, ranges)) = glob.rsplit_once(':') else {
+        return Ok(None);
+    };
+    let range_like = ranges.starts_with(|c: char| c.is_ascii_digit())
+        && ranges
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '-' || c == ',');
+    if pattern.is_empty() || !range_like {
+        return Ok(None);
+    }
+    Ok(Some((pattern.to_string(), parse(glob)?)))
+}
+
+/// Take the line ranges off the `-g` patterns that carry them, leaving the
+/// plain patterns for selecting files.
+pub fn take_from_globs(globs: &mut [String]) -> Result<Vec<LineSpec>, String> {
+    let mut specs = Vec::new();
+    for glob in globs {
+        if let Some((pattern, spec)) = split_glob(glob)? {
+            *glob = pattern;
+            specs.push(spec);
+        }
+    }
+    Ok(specs)
+}
+
+/// The lines of `content` in `ranges`, each stretch headed by where it came
+/// from. Ranges that overlap or touch are merged; ones that cover the whole
+/// file give it back unchanged.
+pub fn slice(content: &str, ranges: &[(usize, Option<usize>)]) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut ranges: Vec<(usize, usize)> = ranges
+        .iter()
+        .map(|&(start, end)| (start, end.unwrap_or(lines.len()).min(lines.len())))
+        .filter(|(start, end)| start <= end)
+        .collect();
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    if merged == [(1, lines.len())] {
+        return content.to_string();
+    }
+
+    let mut out = String::new();
+    for (start, end) in merged {
+        out.push_str(&format!("[lines {start}-{end} of {}]\n", lines.len()));
+        for line in &lines[start - 1..end] {
+            out.push_str(line);
+        }
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Cut each file a spec matches down to the spec's lines; a file several
+/// specs match keeps the lines of all of them. Specs that match no file, or
+/// only lines past the end of one, are warned about.
+pub fn apply(files: &mut [CollectedFile], specs: &[LineSpec]) {
+    let mut used = vec![false; specs.len()];
+    for file in files.iter_mut() {
+        let ranges: Vec<(usize, Option<usize>)> = specs
+            .iter()
+            .zip(used.iter_mut())
+            .filter(|(spec, _)| spec.matcher.is_match(&file.relative_path))
+            .flat_map(|(spec, used)| {
+                *used = true;
+                spec.ranges.iter().copied()
+            })
+            .collect();
+        if ranges.is_empty() {
+            continue;
+        }
+        let total = file.content.split_inclusive('\n').count();
+        if ranges.iter().all(|&(start, _)| start > total) {
+            status::warn(format!(
+                "{}: the requested lines are past the end ({total} lines)",
+                file.relative_path
+            ));
+        }
+        file.content = slice(&file.content, &ranges);
+    }
+    for (spec, used) in specs.iter().zip(used) {
+        if !used {
+            status::warn(format!("line range for {} matched no files", spec.pattern));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_ranges() {
+        let content: String = (1..=10).map(|n| format!("line {n}\n")).collect();
+        let spec = parse("src/*.rs:2-3,9-").unwrap();
+        assert_eq!(
+            slice(&content, &spec.ranges),
+            "[lines 2-3 of 10]\nline 2\nline 3\n[lines 9-10 of 10]\nline 9\nline 10\n"
+        );
+        // Overlapping and adjacent ranges merge; the whole file stays as is
+        assert_eq!(
+            slice(&content, &[(4, Some(5)), (5, Some(6)), (7, Some(7))]),
+            "[lines 4-7 of 10]\nline 4\nline 5\nline 6\nline 7\n"
+        );
+        assert_eq!(slice(&content, &[(1, Some(50))]), content);
+
+        assert!(parse("src/main.rs").is_err());
+        assert!(parse("src/main.rs:0-4").is_err());
+        assert!(parse("src/main.rs:9-4").is_err());
+        assert!(parse("src/main.rs:a-b").is_err());
+
+        let (pattern, spec) = split_glob("src/main.rs:100-250").unwrap().unwrap();
+        assert_eq!(pattern, "src/main.rs");
+        assert_eq!(spec.ranges, [(100, Some(250))]);
+        assert!(split_glob("src/**/*.rs").unwrap().is_none());
+        assert!(split_glob("C:/work/*.rs").unwrap().is_none());
+        assert!(split_glob("src/a.rs:0-4")
+            .unwrap_err()
+            .contains("numbered from 1"));
+        assert_eq!(
+            split_glob("src/a.rs:5-3").unwrap_err(),
+            "'src/a.rs:5-3': range 5-3 ends before it starts"
+        );
+
+        let mut files = vec![
+            CollectedFile {
+                relative_path: "src/a.rs".to_string(),
+                content: content.clone(),
+            },
+            CollectedFile {
+                relative_path: "README.md".to_string(),
+                content: content.clone(),
+            },
+        ];
+        apply(&mut files, &[spec, parse("src/a.rs:1").unwrap()]);
+        assert_eq!(files[0].content, "[lines 1-1 of 10]\nline 1\n");
+        assert_eq!(files[1].content, content);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::manifest::sha256_hex;
+use crate::CollectedFile;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Exact record of a bundle's inputs, written with `collect --lock` and
+/// checked with `collect --lock <path> --verify`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub gather_version: String,
+    pub files: Vec<LockEntry>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct LockEntry {
+    pub path: String,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+impl Lockfile {
+    pub fn from_files(files: &[CollectedFile]) -> Self {
+        Lockfile {
+            gather_version: env!("CARGO_PKG_VERSION").to_string(),
+            files: files
+                .iter()
+                .map(|f| LockEntry {
+                    path: f.relative_path.clone(),
+                    bytes: f.content.len() as u64,
+                    sha256: sha256_hex(f.content.as_bytes()),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+    }
+
+    /// Describe every way `current` differs from this lock, one line per
+    /// file. An empty result means the tree still matches.
+    pub fn mismatches(&self, current: &Lockfile) -> Vec<String> {
+        let locked: BTreeMap<&str, &LockEntry> =
+            self.files.iter().map(|f| (f.path.as_str(), f)).collect();
+        let now: BTreeMap<&str, &LockEntry> =
+            current.files.iter().map(|f| (f.path.as_str(), f)).collect();
+
+        let mut problems: Vec<(&str, &str)> = Vec::new();
+        for (path, entry) in &locked {
+            match now.get(path) {
+                None => problems.push((path, "missing")),
+                Some(cur) if cur.sha256 != entry.sha256 => problems.push((path, "modified")),
+                Some(_) => {}
+            }
+        }
+        for path in now.keys() {
+            if !locked.contains_key(path) {
+                problems.push((path, "unlocked"));
+            }
+        }
+        problems.sort();
+        problems
+            .into_iter()
+            .map(|(path, kind)| format!("{kind:<9} {path}"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, content: &str) -> CollectedFile {
+        CollectedFile {
+            relative_path: path.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_lockfile_roundtrip_and_verify() {
+        let files = vec![file("a.rs", "fn a() {}\n"), file("b.rs", "fn b() {}\n")];
+        let lock = Lockfile::from_files(&files);
+        assert_eq!(lock.files[0].bytes, 10);
+        assert!(lock.mismatches(&Lockfile::from_files(&files)).is_empty());
+
+        let json = serde_json::to_string(&lock).unwrap();
+        let parsed: Lockfile = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, lock);
+
+        let changed = vec![file("b.rs", "fn b2() {}\n"), file("c.rs", "fn c() {}\n")];
+        assert_eq!(
+            lock.mismatches(&Lockfile::from_files(&changed)),
+            vec!["missing   a.rs", "modified  b.rs", "unlocked  c.rs"]
+        );
+    }
+}
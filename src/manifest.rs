@@ -0,0 +1,267 @@
+use crate::{estimate_tokens, Collection, SkipReason};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// A record of every file a gather considered, for auditing what was sent.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub gather_version: String,
+    pub generated_at: String,
+    pub root: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub included: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<SkipReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Modification time in nanoseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime_ns: Option<u64>,
+}
+
+impl Manifest {
+    /// Build a manifest listing included and skipped files, sorted by path.
+    /// Token counts and hashes for included files are computed in parallel.
+    pub fn from_collection(root: &Path, collection: &Collection) -> Self {
+        let mut files: Vec<ManifestEntry> = collection
+            .files
+            .par_iter()
+            .map(|f| ManifestEntry {
+                path: f.relative_path.clone(),
+                included: true,
+                reason: None,
+                bytes: Some(f.content.len() as u64),
+                tokens: Some(estimate_tokens(Path::new(&f.relative_path), &f.content)),
+                sha256: Some(sha256_hex(f.content.as_bytes())),
+                mtime_ns: fs::metadata(root.join(&f.relative_path))
+                    .ok()
+                    .and_then(|m| mtime_ns(&m)),
+            })
+            .collect();
+        files.extend(collection.skipped.iter().map(|s| ManifestEntry {
+            path: s.relative_path.clone(),
+            included: false,
+            reason: Some(s.reason),
+            bytes: None,
+            tokens: None,
+            sha256: None,
+            mtime_ns: None,
+        }));
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Manifest {
+            gather_version: env!("CARGO_PKG_VERSION").to_string(),
+            generated_at: crate::template::now_utc_timestamp(),
+            root: root.to_string_lossy().to_string(),
+            files,
+        }
+    }
+
+    /// Read a manifest previously written with `--manifest`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Included files keyed by path.
+    fn included(&self) -> BTreeMap<&str, &ManifestEntry> {
+        self.files
+            .iter()
+            .filter(|f| f.included)
+            .map(|f| (f.path.as_str(), f))
+            .collect()
+    }
+
+    /// Write the manifest as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+    }
+}
+
+/// Included-file differences between two manifests. Token counts are the
+/// per-file token estimate (added/removed) or its delta (changed).
+#[derive(Debug, Default, PartialEq)]
+pub struct ManifestDiff {
+    pub added: Vec<(String, i64)>,
+    pub removed: Vec<(String, i64)>,
+    pub changed: Vec<(String, i64)>,
+}
+
+impl ManifestDiff {
+    /// Compare the files included in `old` and `new`. A file that was skipped
+    /// on one side counts as absent there.
+    pub fn between(old: &Manifest, new: &Manifest) -> Self {
+        let old_files = old.included();
+        let new_files = new.included();
+        let tokens = |e: &ManifestEntry| e.tokens.unwrap_or(0) as i64;
+
+        let mut diff = ManifestDiff::default();
+        for (path, entry) in &new_files {
+            match old_files.get(path) {
+                None => diff.added.push((path.to_string(), tokens(entry))),
+                Some(prev) if prev.sha256 != entry.sha256 => diff
+                    .changed
+                    .push((path.to_string(), tokens(entry) - tokens(prev))),
+                Some(_) => {}
+            }
+        }
+        for (path, entry) in &old_files {
+            if !new_files.contains_key(path) {
+                diff.removed.push((path.to_string(), tokens(entry)));
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Render one line per file (`+` added, `-` removed, `~` changed) followed
+    /// by a summary line.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut lines: Vec<(&str, char, i64)> = Vec::new();
+        lines.extend(self.added.iter().map(|(p, t)| (p.as_str(), '+', *t)));
+        lines.extend(self.removed.iter().map(|(p, t)| (p.as_str(), '-', -*t)));
+        lines.extend(self.changed.iter().map(|(p, t)| (p.as_str(), '~', *t)));
+        lines.sort();
+
+        for (path, marker, delta) in &lines {
+            let _ = writeln!(out, "{marker} {path}  ({delta:+} tokens)");
+        }
+        if !self.is_empty() {
+            let _ = writeln!(out);
+        }
+
+        let net: i64 = lines.iter().map(|(_, _, d)| d).sum();
+        let _ = writeln!(
+            out,
+            "{} added, {} removed, {} changed (net {net:+} tokens)",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        );
+        out
+    }
+}
+
+/// A file's modification time in nanoseconds since the Unix epoch.
+pub fn mtime_ns(meta: &Metadata) -> Option<u64> {
+    let since_epoch = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
+/// Lowercase hex SHA-256 digest of `bytes`.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CollectedFile, SkippedFile};
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    fn entry(path: &str, tokens: usize, sha: &str) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            included: true,
+            reason: None,
+            bytes: Some(tokens as u64 * 4),
+            tokens: Some(tokens),
+            sha256: Some(sha.to_string()),
+            mtime_ns: None,
+        }
+    }
+
+    fn manifest(files: Vec<ManifestEntry>) -> Manifest {
+        Manifest {
+            gather_version: "0.1.0".to_string(),
+            generated_at: "2024-01-01T00:00:00Z".to_string(),
+            root: "/repo".to_string(),
+            files,
+        }
+    }
+
+    #[test]
+    fn test_manifest_diff() {
+        let mut skipped = entry("big.json", 0, "");
+        skipped.included = false;
+        let old = manifest(vec![
+            entry("a.rs", 10, "aaa"),
+            entry("b.rs", 20, "bbb"),
+            entry("c.rs", 30, "ccc"),
+            skipped,
+        ]);
+        let new = manifest(vec![
+            entry("a.rs", 10, "aaa"),
+            entry("b.rs", 25, "bbb2"),
+            entry("big.json", 500, "jjj"),
+        ]);
+
+        let diff = ManifestDiff::between(&old, &new);
+        assert_eq!(diff.added, vec![("big.json".to_string(), 500)]);
+        assert_eq!(diff.removed, vec![("c.rs".to_string(), 30)]);
+        assert_eq!(diff.changed, vec![("b.rs".to_string(), 5)]);
+        assert!(diff
+            .render()
+            .ends_with("1 added, 1 removed, 1 changed (net +475 tokens)\n"));
+        assert!(ManifestDiff::between(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_manifest_lists_included_and_skipped() {
+        let collection = Collection {
+            files: vec![CollectedFile {
+                relative_path: "src/lib.rs".to_string(),
+                content: "pub fn f() {}\n".to_string(),
+            }],
+            skipped: vec![SkippedFile {
+                relative_path: "assets/logo.png".to_string(),
+                reason: SkipReason::Binary,
+            }],
+        };
+        let manifest = Manifest::from_collection(Path::new("/repo"), &collection);
+        let json = serde_json::to_value(&manifest).unwrap();
+        let files = json["files"].as_array().unwrap();
+
+        assert_eq!(files[0]["path"], "assets/logo.png");
+        assert_eq!(files[0]["included"], false);
+        assert_eq!(files[0]["reason"], "binary");
+        assert!(files[0].get("sha256").is_none());
+
+        assert_eq!(files[1]["path"], "src/lib.rs");
+        assert_eq!(files[1]["included"], true);
+        assert_eq!(files[1]["bytes"], 14);
+        assert_eq!(files[1]["tokens"], 4);
+        assert!(files[1].get("reason").is_none());
+    }
+}
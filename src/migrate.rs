@@ -0,0 +1,234 @@
+use crate::config::Bundle;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Another tool's config translated into a gather bundle.
+#[derive(Debug, PartialEq)]
+pub struct Imported {
+    /// The tool the config came from, used as the default bundle name.
+    pub tool: &'static str,
+    pub bundle: Bundle,
+    /// Settings gather has no equivalent for, by their key in the source.
+    pub untranslated: Vec<String>,
+}
+
+/// Read a repomix (`repomix.config.json`) or code2prompt (`.c2pconfig`,
+/// TOML) config. JSON files are taken as repomix, anything else as
+/// code2prompt.
+pub fn import(path: &Path) -> Result<Imported, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let is_json =
+        path.extension().is_some_and(|ext| ext == "json") || text.trim_start().starts_with('{');
+    if is_json {
+        let value = serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(from_repomix(&value))
+    } else {
+        let table: toml::Table =
+            toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(from_code2prompt(&table))
+    }
+}
+
+/// `include`, `ignore.customPatterns`, `ignore.useGitignore` and
+/// `output.style` carry over; a bare `**/*` include is dropped since gather
+/// takes everything by default.
+fn from_repomix(config: &Value) -> Imported {
+    let mut bundle = Bundle::default();
+    let mut untranslated = Vec::new();
+    let strings = |value: Option<&Value>| -> Vec<String> {
+        value
+            .and_then(Value::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    bundle.globs = strings(config.get("include"))
+        .into_iter()
+        .filter(|glob| glob != "**/*" && glob != "**")
+        .collect();
+    for pattern in strings(config.pointer("/ignore/customPatterns")) {
+        match gitignore_globs(&pattern) {
+            Some(globs) => bundle.excludes.extend(globs),
+            None => untranslated.push(format!("ignore.customPatterns: {pattern}")),
+        }
+    }
+    if config.pointer("/ignore/useGitignore") == Some(&Value::Bool(false)) {
+        bundle.no_ignore_vcs = true;
+    }
+    match config.pointer("/output/style").and_then(Value::as_str) {
+        Some(style @ ("xml" | "markdown" | "json")) => bundle.format = Some(style.to_string()),
+        Some(style) => untranslated.push(format!("output.style = {style}")),
+        None => {}
+    }
+    if let Some(output) = config.get("output").and_then(Value::as_object) {
+        for (key, value) in output {
+            if key != "style" && is_set(value) {
+                untranslated.push(format!("output.{key}"));
+            }
+        }
+    }
+    if let Some(top) = config.as_object() {
+        for (key, value) in top {
+            if !["include", "ignore", "output", "$schema"].contains(&key.as_str()) && is_set(value)
+            {
+                untranslated.push(key.clone());
+            }
+        }
+    }
+    Imported {
+        tool: "repomix",
+        bundle,
+        untranslated,
+    }
+}
+
+/// `include_patterns`/`exclude_patterns` (arrays or comma-separated
+/// strings, also accepted as `include`/`exclude`) and `output_format`
+/// carry over.
+fn from_code2prompt(config: &toml::Table) -> Imported {
+    let mut bundle = Bundle::default();
+    let mut untranslated = Vec::new();
+    let patterns = |keys: [&str; 2]| -> Vec<String> {
+        let Some(value) = keys.iter().find_map(|key| config.get(*key)) else {
+            return Vec::new();
+        };
+        match value {
+            toml::Value::String(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::to_string)
+                .collect(),
+            toml::Value::Array(items) => items
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+    bundle.globs = patterns(["include_patterns", "include"]);
+    bundle.excludes = patterns(["exclude_patterns", "exclude"]);
+    match config.get("output_format").and_then(|v| v.as_str()) {
+        Some(format @ ("xml" | "markdown" | "json")) => bundle.format = Some(format.to_string()),
+        Some(format) => untranslated.push(format!("output_format = {format}")),
+        None => {}
+    }
+    for (key, value) in config {
+        let known = [
+            "include_patterns",
+            "include",
+            "exclude_patterns",
+            "exclude",
+            "output_format",
+            "path",
+        ];
+        let set = !matches!(value, toml::Value::Boolean(false))
+            && !value.as_str().is_some_and(str::is_empty)
+            && !value.as_array().is_some_and(Vec::is_empty);
+        if !known.contains(&key.as_str()) && set {
+            untranslated.push(key.clone());
+        }
+    }
+    Imported {
+        tool: "code2prompt",
+        bundle,
+        untranslated,
+    }
+}
+
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(false) => false,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+        _ => true,
+    }
+}
+
+/// The globs matching what a .gitignore-style `pattern` ignores: a name
+/// without a slash matches at any depth, a leading slash anchors it to the
+/// root, and a trailing slash limits it to directories. `None` for negated
+/// patterns, which have no exclude equivalent.
+fn gitignore_globs(pattern: &str) -> Option<Vec<String>> {
+    let pattern = pattern.trim();
+    if pattern.starts_with('!') {
+        return None;
+    }
+    let dir_only = pattern.ends_with('/');
+    let trimmed = pattern.trim_end_matches('/');
+    let anchored = trimmed.starts_with('/') || trimmed.contains('/');
+    let base = match trimmed.strip_prefix('/') {
+        Some(rest) => rest.to_string(),
+        None if anchored || trimmed.starts_with("**") => trimmed.to_string(),
+        None => format!("**/{trimmed}"),
+    };
+    if base.ends_with("/**") || dir_only {
+        let dir = base.trim_end_matches("/**");
+        return Some(vec![format!("{dir}/**")]);
+    }
+    Some(vec![base.clone(), format!("{base}/**")])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitignore_globs() {
+        let globs = |p: &str| gitignore_globs(p).unwrap();
+        assert_eq!(globs("fixtures"), ["**/fixtures", "**/fixtures/**"]);
+        assert_eq!(globs("*.log"), ["**/*.log", "**/*.log/**"]);
+        assert_eq!(globs("/build"), ["build", "build/**"]);
+        assert_eq!(globs("tmp/"), ["**/tmp/**"]);
+        assert_eq!(globs("docs/archive"), ["docs/archive", "docs/archive/**"]);
+        assert_eq!(globs("**/generated/**"), ["**/generated/**"]);
+        assert_eq!(gitignore_globs("!keep.log"), None);
+    }
+
+    #[test]
+    fn test_from_repomix() {
+        let config: Value = serde_json::from_str(
+            r#"{
+              "$schema": "https://repomix.com/schemas/latest/schema.json",
+              "include": ["src/**/*.ts", "**/*"],
+              "ignore": {
+                "useGitignore": false,
+                "useDefaultPatterns": true,
+                "customPatterns": ["fixtures", "!keep"]
+              },
+              "output": {"style": "xml", "filePath": "out.xml", "removeComments": false}
+            }"#,
+        )
+        .unwrap();
+        let imported = from_repomix(&config);
+        assert_eq!(imported.bundle.globs, ["src/**/*.ts"]);
+        assert_eq!(imported.bundle.excludes, ["**/fixtures", "**/fixtures/**"]);
+        assert_eq!(imported.bundle.format.as_deref(), Some("xml"));
+        assert!(imported.bundle.no_ignore_vcs);
+        assert_eq!(
+            imported.untranslated,
+            ["ignore.customPatterns: !keep", "output.filePath"]
+        );
+    }
+
+    #[test]
+    fn test_from_code2prompt() {
+        let config: toml::Table = toml::from_str(
+            "include_patterns = \"*.rs, *.toml\"\nexclude_patterns = [\"target/**\"]\n\
+             output_format = \"json\"\nline_numbers = true\nno_codeblock = false\n",
+        )
+        .unwrap();
+        let imported = from_code2prompt(&config);
+        assert_eq!(imported.bundle.globs, ["*.rs", "*.toml"]);
+        assert_eq!(imported.bundle.excludes, ["target/**"]);
+        assert_eq!(imported.bundle.format.as_deref(), Some("json"));
+        assert_eq!(imported.untranslated, ["line_numbers"]);
+    }
+}
@@ -0,0 +1,69 @@
+use std::cmp::Ordering;
+
+/// Compare paths with runs of digits ordered by their numeric value, so
+/// `part2.rs` comes before `part10.rs`. Numbers that differ only in leading
+/// zeros fall back to plain string order, which keeps the order total.
+pub fn cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a, b);
+    loop {
+        match (x.chars().next(), y.chars().next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let (m, x_rest) = split_number(x);
+                let (n, y_rest) = split_number(y);
+                let order = m.len().cmp(&n.len()).then_with(|| m.cmp(n));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                (x, y) = (x_rest, y_rest);
+            }
+            (Some(c), Some(d)) => {
+                if c != d {
+                    return c.cmp(&d);
+                }
+                (x, y) = (&x[c.len_utf8()..], &y[d.len_utf8()..]);
+            }
+        }
+    }
+}
+
+/// The leading digit run of `s` without its leading zeros, and the rest.
+fn split_number(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    (s[..end].trim_start_matches('0'), &s[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut paths = vec![
+            "src/part10.rs",
+            "src/part2.rs",
+            "migrations/migration_0010.sql",
+            "migrations/migration_0009.sql",
+            "src/part1.rs",
+            "src/part01.rs",
+            "src/part.rs",
+            "README.md",
+        ];
+        paths.sort_by(|a, b| cmp(a, b));
+        assert_eq!(
+            paths,
+            [
+                "README.md",
+                "migrations/migration_0009.sql",
+                "migrations/migration_0010.sql",
+                "src/part.rs",
+                "src/part01.rs",
+                "src/part1.rs",
+                "src/part2.rs",
+                "src/part10.rs",
+            ]
+        );
+    }
+}
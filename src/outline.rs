@@ -0,0 +1,387 @@
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser, Tree};
+
+/// How to outline one language: which syntax nodes are declarations, and
+/// what to keep of each.
+struct Grammar {
+    language: fn() -> Language,
+    /// Declarations kept as their signature, with the body dropped
+    functions: &'static [&'static str],
+    /// Declarations kept as their header followed by an outline of their
+    /// members (impls, traits, classes, modules)
+    containers: &'static [&'static str],
+    /// Declarations kept whole, such as type definitions and constants
+    whole: &'static [&'static str],
+    /// Declarations kept as their first line, such as macros
+    headers: &'static [&'static str],
+    /// `const f = () => ...` style declarations, kept as far as the
+    /// function's body when they bind a function
+    bindings: &'static [&'static str],
+    /// Nodes wrapping a declaration, such as `export` or decorators, kept
+    /// along with it
+    wrappers: &'static [&'static str],
+    /// Whether a node directly above a declaration (a comment or attribute
+    /// of the given kind and text) documents it
+    leading: fn(&str, &str) -> bool,
+    /// Whether functions and classes carry a docstring as their first
+    /// statement
+    docstrings: bool,
+}
+
+const RUST: Grammar = Grammar {
+    language: || tree_sitter_rust::LANGUAGE.into(),
+    functions: &["function_item"],
+    containers: &["impl_item", "trait_item", "mod_item"],
+    whole: &[
+        "struct_item",
+        "enum_item",
+        "union_item",
+        "type_item",
+        "const_item",
+        "static_item",
+        "function_signature_item",
+        "associated_type",
+    ],
+    headers: &["macro_definition"],
+    bindings: &[],
+    wrappers: &[],
+    leading: |kind, text| {
+        kind == "attribute_item"
+            || (kind.ends_with("comment") && (text.starts_with("///") || text.starts_with("/**")))
+    },
+    docstrings: false,
+};
+
+const PYTHON: Grammar = Grammar {
+    language: || tree_sitter_python::LANGUAGE.into(),
+    functions: &["function_definition"],
+    containers: &["class_definition"],
+    whole: &[],
+    headers: &[],
+    bindings: &[],
+    wrappers: &["decorated_definition"],
+    leading: |_, _| false,
+    docstrings: true,
+};
+
+const GO: Grammar = Grammar {
+    language: || tree_sitter_go::LANGUAGE.into(),
+    functions: &["function_declaration", "method_declaration"],
+    containers: &[],
+    whole: &["package_clause", "type_declaration"],
+    headers: &[],
+    bindings: &[],
+    wrappers: &[],
+    // Go doc comments are the plain comments directly above a declaration
+    leading: |kind, _| kind == "comment",
+    docstrings: false,
+};
+
+const JS_FUNCTIONS: &[&str] = &[
+    "function_declaration",
+    "generator_function_declaration",
+    "method_definition",
+];
+const JS_BINDINGS: &[&str] = &["lexical_declaration", "variable_declaration"];
+const JS_LEADING: fn(&str, &str) -> bool =
+    |kind, text| kind == "comment" && text.starts_with("/**");
+
+const JAVASCRIPT: Grammar = Grammar {
+    language: || tree_sitter_javascript::LANGUAGE.into(),
+    functions: JS_FUNCTIONS,
+    containers: &["class_declaration"],
+    whole: &["field_definition"],
+    headers: &[],
+    bindings: JS_BINDINGS,
+    wrappers: &["export_statement"],
+    leading: JS_LEADING,
+    docstrings: false,
+};
+
+const TS_CONTAINERS: &[&str] = &[
+    "class_declaration",
+    "abstract_class_declaration",
+    "internal_module",
+    "module",
+];
+const TS_WHOLE: &[&str] = &[
+    "interface_declaration",
+    "type_alias_declaration",
+    "enum_declaration",
+    "function_signature",
+    "method_signature",
+    "abstract_method_signature",
+    "public_field_definition",
+    "ambient_declaration",
+];
+
+const TYPESCRIPT: Grammar = Grammar {
+    language: || tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+    functions: JS_FUNCTIONS,
+    containers: TS_CONTAINERS,
+    whole: TS_WHOLE,
+    headers: &[],
+    bindings: JS_BINDINGS,
+    wrappers: &["export_statement", "expression_statement"],
+    leading: JS_LEADING,
+    docstrings: false,
+};
+
+const TSX: Grammar = Grammar {
+    language: || tree_sitter_typescript::LANGUAGE_TSX.into(),
+    ..TYPESCRIPT
+};
+
+fn grammar(path: &Path) -> Option<&'static Grammar> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match ext {
+        "rs" => Some(&RUST),
+        "py" => Some(&PYTHON),
+        "go" => Some(&GO),
+        "js" | "mjs" | "cjs" | "jsx" => Some(&JAVASCRIPT),
+        "ts" | "mts" | "cts" => Some(&TYPESCRIPT),
+        "tsx" => Some(&TSX),
+        _ => None,
+    }
+}
+
+/// A signature-only view of `content`: function and method signatures,
+/// type definitions, and the headers of impls, traits, classes and modules
+/// with their members outlined beneath, each with the doc comments and
+/// attributes directly above it. Files in languages without a grammar are
+/// returned unchanged.
+pub fn outline(path: &Path, content: &str) -> String {
+    let Some((grammar, tree)) = parse(path, content) else {
+        return content.to_string();
+    };
+    let mut out = String::new();
+    outline_members(grammar, tree.root_node(), content, &mut out);
+    out
+}
+
+/// The first line of each top-level declaration in `content`, such as
+/// `pub fn add(a: i32, b: i32) -> i32` or `class A(B):`, without decorators
+/// or an opening brace. Empty for languages without a grammar.
+pub fn symbols(path: &Path, content: &str) -> Vec<String> {
+    let Some((grammar, tree)) = parse(path, content) else {
+        return Vec::new();
+    };
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.named_children(&mut cursor)
+        .filter_map(|node| {
+            // `mod name;` and the like declare, but don't define, anything
+            let bodyless = grammar.containers.contains(&node.kind())
+                && node.child_by_field_name("body").is_none();
+            let mut decl = String::new();
+            if bodyless || !declaration(grammar, node, node.start_byte(), content, &mut decl) {
+                return None;
+            }
+            signature(&decl)
+        })
+        .collect()
+}
+
+/// The opening of a declaration on one line: its first line, carried on
+/// through any parameter list that wraps, without decorators or a trailing
+/// opening bracket.
+fn signature(decl: &str) -> Option<String> {
+    let mut lines = decl
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .skip_while(|line| line.starts_with('@'));
+    let mut sig = lines.next()?.to_string();
+    let open = |s: &str| s.matches('(').count() > s.matches(')').count();
+    while open(&sig) {
+        let Some(line) = lines.next() else { break };
+        if !sig.ends_with('(') && !line.starts_with(')') {
+            sig.push(' ');
+        }
+        sig.push_str(line);
+    }
+    let sig = sig.replace(",)", ")");
+    let sig = sig.trim_end();
+    if sig.ends_with(['{', '[', '(']) {
+        // `pub const ALL: &[Item] = &[` is better read as its type
+        return Some(match sig.rsplit_once(" = ") {
+            Some((head, _)) => head.to_string(),
+            None => sig.trim_end_matches(['{', '[', '(']).trim_end().to_string(),
+        });
+    }
+    Some(sig.to_string())
+}
+
+fn parse(path: &Path, content: &str) -> Option<(&'static Grammar, Tree)> {
+    let grammar = grammar(path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&(grammar.language)()).ok()?;
+    Some((grammar, parser.parse(content, None)?))
+}
+
+/// Whether `outline` knows the declarations of `path`'s language.
+pub fn supports(path: &Path) -> bool {
+    grammar(path).is_some()
+}
+
+/// Outline the declarations directly under `parent`, each preceded by the
+/// doc comments and attributes that touch it.
+fn outline_members(grammar: &Grammar, parent: Node, src: &str, out: &mut String) {
+    let mut leading: Vec<Node> = Vec::new();
+    let mut cursor = parent.walk();
+    for node in parent.named_children(&mut cursor) {
+        let touches = |above: &Node| above.end_position().row + 1 >= node.start_position().row;
+        if leading.last().is_some_and(|above| !touches(above)) {
+            leading.clear();
+        }
+        if (grammar.leading)(node.kind(), &src[node.byte_range()]) {
+            leading.push(node);
+            continue;
+        }
+        let mut decl = String::new();
+        if declaration(grammar, node, node.start_byte(), src, &mut decl) {
+            for above in &leading {
+                push_lines(out, src, above.start_byte(), &src[above.byte_range()]);
+            }
+            out.push_str(&decl);
+        }
+        leading.clear();
+    }
+}
+
+/// Write the outline of `node` to `out`, starting its text at `start` (the
+/// start of any wrapper around it). Returns whether it is a declaration.
+fn declaration(grammar: &Grammar, node: Node, start: usize, src: &str, out: &mut String) -> bool {
+    let kind = node.kind();
+    let body = node.child_by_field_name("body");
+    if grammar.wrappers.contains(&kind) {
+        let mut cursor = node.walk();
+        let inner: Vec<Node> = node.named_children(&mut cursor).collect();
+        return inner
+            .into_iter()
+            .rev()
+            .any(|inner| declaration(grammar, inner, start, src, out));
+    }
+    if grammar.whole.contains(&kind) || (grammar.containers.contains(&kind) && body.is_none()) {
+        push_lines(out, src, start, &src[start..node.end_byte()]);
+    } else if grammar.headers.contains(&kind) {
+        let text = &src[start..node.end_byte()];
+        let first = text.lines().next().unwrap_or_default();
+        push_lines(
+            out,
+            src,
+            start,
+            first.trim_end().trim_end_matches('{').trim_end(),
+        );
+    } else if grammar.functions.contains(&kind) || grammar.containers.contains(&kind) {
+        let end = body.map_or(node.end_byte(), |b| b.start_byte());
+        push_lines(out, src, start, &src[start..end]);
+        if let Some(body) = body {
+            if grammar.docstrings {
+                docstring(body, src, out);
+            }
+            if grammar.containers.contains(&kind) {
+                outline_members(grammar, body, src, out);
+            }
+        }
+    } else if grammar.bindings.contains(&kind) {
+        let mut cursor = node.walk();
+        let function = node
+            .named_children(&mut cursor)
+            .filter_map(|declarator| declarator.child_by_field_name("value"))
+            .find(|value| {
+                matches!(
+                    value.kind(),
+                    "arrow_function" | "function_expression" | "generator_function"
+                )
+            });
+        let Some(body) = function.and_then(|f| f.child_by_field_name("body")) else {
+            return false;
+        };
+        push_lines(out, src, start, &src[start..body.start_byte()]);
+    } else {
+        return false;
+    }
+    true
+}
+
+/// Write a Python-style docstring, the first statement of `body` when it is
+/// a bare string.
+fn docstring(body: Node, src: &str, out: &mut String) {
+    let Some(first) = body.named_child(0) else {
+        return;
+    };
+    if first.kind() == "expression_statement"
+        && first.named_child(0).is_some_and(|s| s.kind() == "string")
+    {
+        push_lines(out, src, first.start_byte(), &src[first.byte_range()]);
+    }
+}
+
+/// Write `text`, which starts at byte `start` of `src`, indented as it is in
+/// the source.
+fn push_lines(out: &mut String, src: &str, start: usize, text: &str) {
+    let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &src[line_start..start];
+    if indent.trim().is_empty() {
+        out.push_str(indent);
+    }
+    out.push_str(text.trim_end());
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outline_rust() {
+        let src = "use std::fs;\n\n/// Adds.\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\n// not a doc\nstruct Point {\n    x: i32,\n}\n\n#[derive(Debug)]\nenum E { A }\n\nimpl Point {\n    /// Makes one.\n    pub fn new() -> Self {\n        Point { x: 0 }\n    }\n}\n";
+        assert_eq!(
+            outline(Path::new("lib.rs"), src),
+            "/// Adds.\npub fn add(a: i32, b: i32) -> i32\nstruct Point {\n    x: i32,\n}\n#[derive(Debug)]\nenum E { A }\nimpl Point\n    /// Makes one.\n    pub fn new() -> Self\n"
+        );
+    }
+
+    #[test]
+    fn test_outline_python_and_typescript() {
+        let py = "import os\n\n@dataclass\nclass A(B):\n    \"\"\"An A.\"\"\"\n    x = 1\n\n    def run(self, n: int) -> None:\n        print(n)\n";
+        assert_eq!(
+            outline(Path::new("a.py"), py),
+            "@dataclass\nclass A(B):\n    \"\"\"An A.\"\"\"\n    def run(self, n: int) -> None:\n"
+        );
+
+        let ts = "/** Greets. */\nexport function hi(name: string): string {\n  return name;\n}\nexport const add = (a: number, b: number): number => a + b;\ninterface P { x: number }\nclass C {\n  private n = 0;\n  get(): number { return this.n; }\n}\nconsole.log(1);\n";
+        assert_eq!(
+            outline(Path::new("a.ts"), ts),
+            "/** Greets. */\nexport function hi(name: string): string\nexport const add = (a: number, b: number): number =>\ninterface P { x: number }\nclass C\n  private n = 0\n  get(): number\n"
+        );
+    }
+
+    #[test]
+    fn test_outline_unknown_language_unchanged() {
+        assert_eq!(outline(Path::new("notes.md"), "# Hi\n"), "# Hi\n");
+        assert!(symbols(Path::new("notes.md"), "# Hi\n").is_empty());
+    }
+
+    #[test]
+    fn test_symbols() {
+        let src = "use std::fs;\n\n/// Adds.\npub fn add(a: i32) -> i32 {\n    a\n}\n\n#[derive(Debug)]\nstruct Point {\n    x: i32,\n}\n\nimpl Point {\n    fn new() -> Self { Point { x: 0 } }\n}\n";
+        assert_eq!(
+            symbols(Path::new("lib.rs"), src),
+            ["pub fn add(a: i32) -> i32", "struct Point", "impl Point"]
+        );
+        assert_eq!(
+            symbols(
+                Path::new("lib.rs"),
+                "mod a;\nconst fn f(\n    x: u8,\n    y: u8,\n) -> u8 {\n    x\n}\npub const ALL: &[u8] = &[\n    1,\n];\n"
+            ),
+            ["const fn f(x: u8, y: u8) -> u8", "pub const ALL: &[u8]"]
+        );
+        let py = "@dataclass\nclass A(B):\n    x = 1\n\ndef run(n):\n    pass\n";
+        assert_eq!(
+            symbols(Path::new("a.py"), py),
+            ["class A(B):", "def run(n):"]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Write `text` to `path`, or with `append` add it after what `path`
+/// already holds. The result is built in a temporary file beside `path` and
+/// renamed over it, so readers never see a half-written bundle and a failed
+/// write leaves the old file alone. An existing file keeps its permissions.
+pub fn write(path: &Path, text: &str, append: bool) -> Result<(), String> {
+    let fail = |e: &dyn std::fmt::Display| format!("Failed to write {}: {e}", path.display());
+    if path.is_dir() {
+        return Err(fail(&"is a directory"));
+    }
+    let existing = match fs::metadata(path) {
+        Ok(meta) => Some(meta),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(fail(&e)),
+    };
+    let mut contents = if append && existing.is_some() {
+        fs::read(path).map_err(|e| fail(&e))?
+    } else {
+        Vec::new()
+    };
+    contents.extend_from_slice(text.as_bytes());
+
+    let name = path.file_name().ok_or_else(|| fail(&"not a file name"))?;
+    let tmp = path.with_file_name(format!(
+        "{}.tmp{}",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let result = fs::write(&tmp, &contents)
+        .and_then(|()| match &existing {
+            Some(meta) => fs::set_permissions(&tmp, meta.permissions()),
+            None => Ok(()),
+        })
+        .and_then(|()| fs::rename(&tmp, path))
+        .map_err(|e| fail(&e));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// `path` made absolute with symlinks resolved, even if the file doesn't
+/// exist yet (its directory has to), for comparing against walked paths.
+pub fn resolve(path: &Path) -> Option<PathBuf> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+    let dir = dir.unwrap_or(Path::new(".")).canonicalize().ok()?;
+    Some(dir.join(path.file_name()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_replace_and_append() {
+        let dir = crate::test_dir::make_test_dir("output");
+        let path = dir.join("context.md");
+
+        write(&path, "one\n", true).unwrap();
+        write(&path, "two\n", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        write(&path, "three\n", false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "three\n");
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        assert!(write(&dir, "x", false)
+            .unwrap_err()
+            .ends_with("is a directory"));
+        assert!(write(&dir.join("missing/context.md"), "x", false).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
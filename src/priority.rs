@@ -0,0 +1,40 @@
+use std::io;
+
+/// Lower this process's CPU and I/O priority so a gather on a shared build
+/// machine or network filesystem yields to other work.
+///
+/// On Linux this sets the idle I/O scheduling class (like `ionice -c3`) and a
+/// nice value of 19; other Unix systems only get the nice value. Elsewhere it
+/// is a no-op. Linux applies both to the calling thread only, so call this
+/// before starting any threads, which inherit them.
+pub fn lower_priority() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        // SAFETY: setpriority only adjusts the calling process's scheduling
+        // priority; arguments are plain integers.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, 19) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+        const IOPRIO_CLASS_SHIFT: u32 = 13;
+        // SAFETY: ioprio_set only adjusts the calling process's I/O priority.
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0 as libc::c_long,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use crate::search;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// BM25 term-frequency saturation and length normalization, at the usual
+/// values.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// How many times each word of a file's path counts, against once for each
+/// word of its content; `auth/session.rs` is about sessions however rarely
+/// it says so.
+const PATH_WEIGHT: usize = 3;
+
+/// The words of `text` for matching: lowercase runs of letters and digits,
+/// with identifiers also split at underscores and camelCase humps
+/// (`parseHTTPHeader` gives parse, http and header).
+pub fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for run in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = run.chars().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let hump = chars[i].is_uppercase()
+                && (chars[i - 1].is_lowercase()
+                    || chars.get(i + 1).is_some_and(|next| next.is_lowercase())
+                        && chars[i - 1].is_uppercase());
+            if hump {
+                words.push(chars[start..i].iter().collect::<String>().to_lowercase());
+                start = i;
+            }
+        }
+        if start < chars.len() {
+            words.push(chars[start..].iter().collect::<String>().to_lowercase());
+        }
+    }
+    words
+}
+
+/// Files scored against free-text queries with BM25, held in memory for one
+/// run when there is no search index to ask.
+pub struct Ranker {
+    /// Path, word counts and length in words of each file
+    docs: Vec<(String, HashMap<String, usize>, usize)>,
+    /// How many files each word appears in
+    doc_freq: HashMap<String, usize>,
+    avg_len: f64,
+}
+
+impl Ranker {
+    /// Index `files`, given as path and content.
+    pub fn new(files: &[(String, String)]) -> Self {
+        let mut doc_freq: HashMap<String, usize> = HashMap::new();
+        let docs: Vec<(String, HashMap<String, usize>, usize)> = files
+            .iter()
+            .map(|(path, content)| {
+                let mut counts: HashMap<String, usize> = HashMap::new();
+                let mut len = 0;
+                for word in words(path) {
+                    *counts.entry(word).or_default() += PATH_WEIGHT;
+                    len += PATH_WEIGHT;
+                }
+                for word in words(content) {
+                    *counts.entry(word).or_default() += 1;
+                    len += 1;
+                }
+                for word in counts.keys() {
+                    *doc_freq.entry(word.clone()).or_default() += 1;
+                }
+                (path.clone(), counts, len)
+            })
+            .collect();
+        let total: usize = docs.iter().map(|(_, _, len)| len).sum();
+        let avg_len = total as f64 / docs.len().max(1) as f64;
+        Ranker {
+            docs,
+            doc_freq,
+            avg_len,
+        }
+    }
+
+    /// Index the files at `paths` under `root`, leaving out those over
+    /// `max_size` bytes and those that aren't text.
+    pub fn read(root: &Path, paths: &[String], max_size: u64) -> Self {
+        let files: Vec<(String, String)> = paths
+            .par_iter()
+            .filter_map(|rel| {
+                let path = root.join(rel);
+                if fs::metadata(&path).ok()?.len() > max_size {
+                    return None;
+                }
+                Some((rel.clone(), fs::read_to_string(path).ok()?))
+            })
+            .collect();
+        Ranker::new(&files)
+    }
+
+    /// The `limit` files that best match any word of `query`, best first.
+    /// Files matching none of its words are left out.
+    pub fn query(&self, query: &str, limit: usize) -> Vec<String> {
+        let mut terms = words(query);
+        terms.sort();
+        terms.dedup();
+        let n = self.docs.len() as f64;
+        let mut scored: Vec<(f64, &str)> = self
+            .docs
+            .iter()
+            .map(|(path, counts, len)| {
+                let norm = K1 * (1.0 - B + B * *len as f64 / self.avg_len.max(1.0));
+                let score: f64 = terms
+                    .iter()
+                    .filter_map(|term| {
+                        let tf = *counts.get(term)? as f64;
+                        let df = self.doc_freq[term] as f64;
+                        let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+                        Some(idf * tf * (K1 + 1.0) / (tf + norm))
+                    })
+                    .sum();
+                (score, path.as_str())
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        scored
+            .into_iter()
+            .take(limit.max(1))
+            .map(|(_, path)| path.to_string())
+            .collect()
+    }
+}
+
+/// Like `search::retrieve`, over `ranker` instead of the search index.
+pub fn retrieve(ranker: &Ranker, queries: &[String], limit: usize) -> Vec<String> {
+    search::interleave(queries.iter().map(|q| ranker.query(q, limit)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank() {
+        assert_eq!(
+            words("parseHTTPHeader(user_id, v2)"),
+            ["parse", "http", "header", "user", "id", "v2"]
+        );
+
+        let file = |path: &str, content: &str| (path.to_string(), content.to_string());
+        let ranker = Ranker::new(&[
+            file(
+                "src/auth/session.rs",
+                "fn login(user: &User) -> Token { todo!() }",
+            ),
+            file(
+                "src/db.rs",
+                "// no authentication here, just the user table\nfn users() {}",
+            ),
+            file("README.md", "Authentication is handled by the auth module."),
+            file("src/util.rs", "fn clamp(x: i32) -> i32 { x }"),
+        ]);
+        // Path words outweigh a passing mention; unrelated files are left out
+        assert_eq!(
+            ranker.query("auth session", 10),
+            ["src/auth/session.rs", "README.md"]
+        );
+        assert_eq!(ranker.query("authentication", 1), ["README.md"]);
+        assert_eq!(
+            retrieve(&ranker, &["login".to_string(), "users".to_string()], 5),
+            ["src/auth/session.rs", "src/db.rs"]
+        );
+    }
+}
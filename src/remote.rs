@@ -0,0 +1,376 @@
+use crate::cache::{cache_root, scratch_dir, ScratchDir};
+use crate::{binary, credentials, http};
+use std::path::Path;
+use std::process::Command;
+
+/// Whether `path` names a remote git repository rather than a local directory.
+pub fn is_remote(path: &str) -> bool {
+    ["https://", "http://", "ssh://", "git://", "file://", "git@"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+/// How much of the remote repository to fetch.
+pub struct CloneOptions {
+    /// Commits of history to fetch; 0 fetches all of it.
+    pub depth: u32,
+    /// Branch, tag or commit SHA to check out instead of the default branch.
+    pub reference: Option<String>,
+    pub tags: bool,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        CloneOptions {
+            depth: 1,
+            reference: None,
+            tags: true,
+        }
+    }
+}
+
+/// Clone `url` into a fresh directory under the cache. With include globs
+/// the clone is partial (`--filter=blob:none`) and sparse, so only blobs for
+/// matching paths are downloaded; otherwise, or when a glob has no
+/// sparse-checkout equivalent, it's a plain shallow clone.
+pub fn clone(url: &str, globs: &[String], opts: &CloneOptions) -> Result<ScratchDir, String> {
+    clone_into(&cache_root().join("clones"), url, globs, opts)
+}
+
+fn clone_into(
+    parent: &Path,
+    url: &str,
+    globs: &[String],
+    opts: &CloneOptions,
+) -> Result<ScratchDir, String> {
+    let checkout =
+        scratch_dir(parent, &repo_name(url)).map_err(|e| format!("{}: {e}", parent.display()))?;
+    let dir = checkout.path.to_string_lossy().to_string();
+
+    // `--branch` only takes branch and tag names; a SHA is fetched separately
+    let sha = opts.reference.as_deref().filter(|r| is_commit_sha(r));
+    let patterns = sparse_patterns(globs);
+    let sparse = patterns.is_some();
+    let depth = opts.depth.to_string();
+    let mut history = Vec::new();
+    if opts.depth > 0 {
+        history.extend(["--depth", depth.as_str()]);
+    }
+    if !opts.tags {
+        history.push("--no-tags");
+    }
+
+    let mut args = vec!["clone", "--quiet"];
+    args.extend(&history);
+    if sparse {
+        args.push("--filter=blob:none");
+    }
+    if sparse || sha.is_some() {
+        args.push("--no-checkout");
+    }
+    if let (Some(reference), None) = (opts.reference.as_deref(), sha) {
+        args.extend(["--branch", reference]);
+    }
+    args.extend(["--", url, &dir]);
+    run_git(url, None, &args)?;
+
+    let mut target = Vec::new();
+    if let Some(sha) = sha {
+        // Servers only accept full SHAs in a fetch; an abbreviated one has to
+        // be within the history the clone already brought down
+        if sha.len() == 40 {
+            let mut fetch = vec!["fetch", "--quiet"];
+            fetch.extend(&history);
+            fetch.extend(["origin", sha]);
+            run_git(url, Some(&checkout.path), &fetch)?;
+        }
+        target.push(sha);
+    }
+    if let Some(patterns) = &patterns {
+        let mut set = vec!["sparse-checkout", "set", "--no-cone", "--"];
+        set.extend(patterns.iter().map(String::as_str));
+        run_git(url, Some(&checkout.path), &set)?;
+    }
+    if sparse || sha.is_some() {
+        let mut co = vec!["checkout", "--quiet"];
+        co.extend(target);
+        run_git(url, Some(&checkout.path), &co)?;
+    }
+    Ok(checkout)
+}
+
+/// Whether `reference` looks like a (possibly abbreviated) commit SHA.
+fn is_commit_sha(reference: &str) -> bool {
+    (7..=40).contains(&reference.len()) && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The `git sparse-checkout` (gitignore-style) patterns covering `globs`,
+/// or `None` to clone everything when there are no globs or one can't be
+/// translated. Braces are expanded, and since a glob's `*` also matches `/`,
+/// patterns are widened to match at any depth rather than missing files.
+fn sparse_patterns(globs: &[String]) -> Option<Vec<String>> {
+    if globs.is_empty() {
+        return None;
+    }
+    let mut patterns = Vec::new();
+    for glob in globs {
+        for alternative in expand_braces(glob) {
+            patterns.push(sparse_pattern(&alternative)?);
+        }
+    }
+    Some(patterns)
+}
+
+/// `glob` with each `{a,b}` alternation expanded into its own pattern.
+fn expand_braces(glob: &str) -> Vec<String> {
+    let (mut depth, mut class, mut escaped) = (0, false, false);
+    let (mut open, mut commas) = (None, Vec::new());
+    for (i, c) in glob.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' if !class => class = true,
+            ']' if class => class = false,
+            _ if class => {}
+            '{' => {
+                depth += 1;
+                if depth == 1 {
+                    open = Some(i);
+                }
+            }
+            ',' if depth == 1 => commas.push(i),
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth > 0 {
+                    continue;
+                }
+                let start = open.unwrap_or_default();
+                let bounds: Vec<usize> = std::iter::once(start)
+                    .chain(commas.iter().copied())
+                    .chain([i])
+                    .collect();
+                return bounds
+                    .windows(2)
+                    .flat_map(|w| {
+                        let (head, tail) = (&glob[..start], &glob[i + 1..]);
+                        expand_braces(&format!("{head}{}{tail}", &glob[w[0] + 1..w[1]]))
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+    vec![glob.to_string()]
+}
+
+/// One brace-free glob as a gitignore-style pattern. A `*` component becomes
+/// `**`, and a last component starting with `*` (`*.rs`) is matched in
+/// every directory below; `None` for other wildcards that could span
+/// directories, which gitignore patterns can't express.
+fn sparse_pattern(glob: &str) -> Option<String> {
+    let glob = glob.strip_prefix("./").unwrap_or(glob);
+    let components: Vec<&str> = glob.split('/').collect();
+    let last = components.len() - 1;
+    let mut parts = Vec::new();
+    for (i, component) in components.iter().enumerate() {
+        let wild = |s: &str| s.contains(['*', '?']) || s.contains("[!") || s.contains("[^");
+        match *component {
+            "*" | "**" => parts.push("**".to_string()),
+            _ if !wild(component) => parts.push(component.to_string()),
+            _ if i == last && component.starts_with('*') && !wild(&component[1..]) => {
+                if last > 0 {
+                    parts.push("**".to_string());
+                }
+                parts.push(component.to_string());
+            }
+            _ => return None,
+        }
+    }
+    let pattern = parts.join("/");
+    // A leading `!` or `#` means negation or a comment in gitignore syntax
+    Some(if pattern.starts_with(['!', '#']) {
+        format!("\\{pattern}")
+    } else {
+        pattern
+    })
+}
+
+/// The repository name from a clone URL, for readable clone directories.
+pub fn repo_name(url: &str) -> String {
+    let name = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or("repo");
+    let name = name.strip_suffix(".git").unwrap_or(name);
+    if name.is_empty() {
+        "repo".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Run git against `url`'s host with the configured CA bundle and any known
+/// credentials, never prompting on the terminal.
+fn run_git(url: &str, dir: Option<&Path>, args: &[&str]) -> Result<(), String> {
+    let mut cmd = Command::new("git");
+    if let Some(dir) = dir {
+        cmd.arg("-C").arg(dir);
+    }
+    cmd.args(args).env("GIT_TERMINAL_PROMPT", "0");
+    if let Some(path) = http::ca_bundle_path() {
+        cmd.env("GIT_SSL_CAINFO", path);
+    }
+    // Passed through the environment so the token stays out of `ps`
+    if let Some(cred) = credentials::for_url(url) {
+        let user = match cred.forge {
+            credentials::Forge::Github => "x-access-token",
+            credentials::Forge::Gitlab => "oauth2",
+        };
+        let basic = binary::base64(format!("{user}:{}", cred.token).as_bytes());
+        cmd.env("GIT_CONFIG_COUNT", "1")
+            .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+            .env(
+                "GIT_CONFIG_VALUE_0",
+                format!("Authorization: Basic {basic}"),
+            );
+    }
+
+    let output = cmd
+        .output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(format!("git {} {url}: {}", args[0], stderr.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_is_remote_and_repo_name() {
+        assert!(is_remote("https://github.com/org/repo"));
+        assert!(is_remote("git@github.com:org/repo.git"));
+        assert!(!is_remote("./src"));
+        assert!(!is_remote("/home/me/https"));
+        assert_eq!(repo_name("https://github.com/org/repo/"), "repo");
+        assert_eq!(repo_name("git@github.com:org/tool.git"), "tool");
+        assert_eq!(repo_name("git@host:solo.git"), "solo");
+    }
+
+    #[test]
+    fn test_is_commit_sha() {
+        assert!(is_commit_sha("4a5d949"));
+        assert!(is_commit_sha("4a5d949c7a52880a0d3e1b2f4c6d8e9fa1b2c3d4"));
+        assert!(!is_commit_sha("main"));
+        assert!(!is_commit_sha("v1.2.0"));
+        assert!(!is_commit_sha("abc"));
+    }
+
+    #[test]
+    fn test_sparse_patterns() {
+        let patterns = |globs: &[&str]| {
+            sparse_patterns(&globs.iter().map(|g| g.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(patterns(&[]), None);
+        assert_eq!(
+            patterns(&["src/*.{rs,toml}", "./docs/**", "*.md"]).unwrap(),
+            ["src/**/*.rs", "src/**/*.toml", "docs/**", "*.md"]
+        );
+        assert_eq!(
+            patterns(&["{a,b/{c,d}}/*/x.[ch]", "!x"]).unwrap(),
+            ["a/**/x.[ch]", "b/c/**/x.[ch]", "b/d/**/x.[ch]", "\\!x"]
+        );
+        assert_eq!(patterns(&["src/[{]x}.rs"]).unwrap(), ["src/[{]x}.rs"]);
+        // `*` inside a name could span directories; `?` could match a `/`
+        assert_eq!(patterns(&["src/*.rs", "src/a*b.rs"]), None);
+        assert_eq!(patterns(&["src/foo*/mod.rs"]), None);
+        assert_eq!(patterns(&["src/a?.rs"]), None);
+        assert_eq!(patterns(&["src/[!a].rs"]), None);
+    }
+
+    #[test]
+    fn test_clone_of_local_repo() {
+        let base = crate::test_dir::make_test_dir("remote");
+        let origin = base.join("origin");
+        fs::create_dir_all(origin.join("src")).unwrap();
+        fs::create_dir_all(origin.join("docs")).unwrap();
+        fs::write(origin.join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+        fs::write(origin.join("docs/guide.md"), "# Guide\n").unwrap();
+        fs::create_dir_all(origin.join("src/inner")).unwrap();
+        fs::write(origin.join("src/inner/mod.rs"), "").unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&origin)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        };
+        if !git(&["init", "-q"]) {
+            return; // git unavailable
+        }
+        git(&["add", "."]);
+        git(&["commit", "-qm", "first"]);
+        git(&["tag", "v1"]);
+        let first = crate::git::git_output(&origin, &["rev-parse", "HEAD"]).unwrap();
+        fs::write(origin.join("src/lib.rs"), "pub fn g() {}\n").unwrap();
+        git(&["commit", "-qam", "second"]);
+
+        let url = format!("file://{}", origin.display());
+        let clones = base.join("clones");
+        let lib = |c: &ScratchDir| fs::read_to_string(c.path.join("src/lib.rs")).unwrap();
+        let commits = |c: &ScratchDir| {
+            crate::git::git_output(&c.path, &["rev-list", "--count", "HEAD"]).unwrap()
+        };
+
+        // Sparse: only the globbed paths are checked out
+        let src = ["src/**".to_string()];
+        let checkout = clone_into(&clones, &url, &src, &CloneOptions::default()).unwrap();
+        assert_eq!(lib(&checkout), "pub fn g() {}\n");
+        assert!(!checkout.path.join("docs/guide.md").exists());
+        assert_eq!(commits(&checkout), "1");
+        let dir = checkout.path.parent().unwrap().to_path_buf();
+        drop(checkout);
+        assert!(!dir.exists());
+
+        // Braces and a nested match, as the walk would pick them
+        let braced = ["src/*.{rs,toml}".to_string()];
+        let checkout = clone_into(&clones, &url, &braced, &CloneOptions::default()).unwrap();
+        assert!(checkout.path.join("src/lib.rs").exists());
+        assert!(checkout.path.join("src/inner/mod.rs").exists());
+        assert!(!checkout.path.join("docs/guide.md").exists());
+        // A glob gitignore syntax can't express clones everything
+        let other = ["src/l?b.rs".to_string()];
+        let checkout = clone_into(&clones, &url, &other, &CloneOptions::default()).unwrap();
+        assert!(checkout.path.join("docs/guide.md").exists());
+        drop(checkout);
+
+        // Full history at a tag, and a SHA combined with sparse checkout
+        let at_tag = CloneOptions {
+            depth: 0,
+            reference: Some("v1".to_string()),
+            tags: true,
+        };
+        let checkout = clone_into(&clones, &url, &[], &at_tag).unwrap();
+        assert_eq!(lib(&checkout), "pub fn f() {}\n");
+        assert!(checkout.path.join("docs/guide.md").exists());
+        let at_sha = CloneOptions {
+            reference: Some(first.clone()),
+            tags: false,
+            ..CloneOptions::default()
+        };
+        let checkout = clone_into(&clones, &url, &src, &at_sha).unwrap();
+        assert_eq!(lib(&checkout), "pub fn f() {}\n");
+        assert!(!checkout.path.join("docs/guide.md").exists());
+        drop(checkout);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+use serde_json::{json, Value};
+use std::env;
+
+/// Exact token counting through a provider's API, for `tokens --count-remote`.
+pub struct RemoteCounter {
+    provider: &'static str,
+    model: String,
+    api_key: String,
+}
+
+impl RemoteCounter {
+    /// A counter for `provider` (`anthropic` or `openai`), or an error naming
+    /// the missing API key variable.
+    pub fn new(provider: &str, model: Option<String>) -> Result<Self, String> {
+        let (provider, key_var, default_model) = match provider {
+            "anthropic" => ("anthropic", "ANTHROPIC_API_KEY", "claude-sonnet-4-5"),
+            "openai" => ("openai", "OPENAI_API_KEY", "gpt-4o"),
+            other => return Err(format!("unknown provider '{other}'")),
+        };
+        let api_key = env::var(key_var)
+            .ok()
+            .filter(|k| !k.is_empty())
+            .ok_or_else(|| format!("{key_var} is not set"))?;
+        Ok(RemoteCounter {
+            provider,
+            model: model.unwrap_or_else(|| default_model.to_string()),
+            api_key,
+        })
+    }
+
+    /// "provider model", for reporting.
+    pub fn describe(&self) -> String {
+        format!("{} {}", self.provider, self.model)
+    }
+
+    /// Exact input token count of `text` sent as a single user message.
+    pub fn count(&self, text: &str) -> Result<usize, String> {
+        let agent = crate::http::agent()?;
+        let response = match self.provider {
+            "anthropic" => agent
+                .post("https://api.anthropic.com/v1/messages/count_tokens")
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", "2023-06-01")
+                .send_json(json!({
+                    "model": self.model,
+                    "messages": [{"role": "user", "content": text}]
+                })),
+            _ => agent
+                .post("https://api.openai.com/v1/responses/input_tokens")
+                .header("authorization", &format!("Bearer {}", self.api_key))
+                .send_json(json!({"model": self.model, "input": text})),
+        };
+
+        let mut response = response.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body: Value = response
+            .body_mut()
+            .read_json()
+            .map_err(|e| format!("HTTP {status}: {e}"))?;
+        parse_count(&body)
+    }
+}
+
+/// Both providers report the count as `input_tokens`.
+fn parse_count(body: &Value) -> Result<usize, String> {
+    body.get("input_tokens")
+        .and_then(|n| n.as_u64())
+        .map(|n| n as usize)
+        .ok_or_else(|| format!("unexpected response: {body}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_count() {
+        assert_eq!(parse_count(&json!({"input_tokens": 1234})), Ok(1234));
+        assert!(parse_count(&json!({"error": {"type": "invalid_request_error"}})).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    clipboard, collect_files, count_tokens, format_json, format_markdown, format_summary,
+    format_xml, skip_summary, tree, tree_paths, CollectOptions, FormatOptions, OutputFormat,
+};
+use globset::Glob;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const HELP: &str = "\
+add GLOB...       include files matching GLOB
+exclude GLOB...   exclude files matching GLOB
+drop GLOB...      forget an include or exclude pattern
+clear             forget every pattern
+show              list the current patterns
+files             list the files that would be gathered
+tree              show them as a tree
+tokens            estimate the bundle's size
+format FORMAT     markdown, xml or json
+emit [PATH]       write the bundle to PATH, or print it
+copy              put the bundle on the clipboard
+quit              leave (also Ctrl-D)
+";
+
+/// The selection being built up at the prompt.
+pub struct Repl {
+    root: PathBuf,
+    opts: CollectOptions,
+    format: OutputFormat,
+}
+
+/// What the prompt loop should do after a command.
+#[derive(Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+impl Repl {
+    pub fn new(root: PathBuf, opts: CollectOptions, format: OutputFormat) -> Self {
+        Repl { root, opts, format }
+    }
+
+    /// Read commands from stdin until `quit` or end of input.
+    pub fn run(&mut self) {
+        eprintln!(
+            "gather repl in {} (type `help` for commands)",
+            self.root.display()
+        );
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            eprint!("gather> ");
+            let _ = io::stderr().flush();
+            let Some(Ok(line)) = lines.next() else {
+                eprintln!();
+                break;
+            };
+            let mut out = String::new();
+            let control = self.handle(&line, &mut out);
+            print!("{out}");
+            let _ = io::stdout().flush();
+            if control == Control::Quit {
+                break;
+            }
+        }
+    }
+
+    /// Run one command line, appending its output to `out`.
+    pub fn handle(&mut self, line: &str, out: &mut String) -> Control {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Control::Continue;
+        };
+        let args: Vec<&str> = words.collect();
+        match command {
+            "help" | "?" => out.push_str(HELP),
+            "quit" | "exit" | "q" => return Control::Quit,
+            "add" | "exclude" => {
+                for glob in &args {
+                    if let Err(e) = Glob::new(glob) {
+                        let _ = writeln!(out, "invalid pattern {glob}: {e}");
+                        continue;
+                    }
+                    let list = if command == "add" {
+                        &mut self.opts.filter.globs
+                    } else {
+                        &mut self.opts.filter.excludes
+                    };
+                    if !list.iter().any(|g| g == glob) {
+                        list.push(glob.to_string());
+                    }
+                }
+                self.summarize(out);
+            }
+            "drop" | "rm" => {
+                let filter = &mut self.opts.filter;
+                filter.globs.retain(|g| !args.contains(&g.as_str()));
+                filter.excludes.retain(|g| !args.contains(&g.as_str()));
+                self.summarize(out);
+            }
+            "clear" => {
+                self.opts.filter.globs.clear();
+                self.opts.filter.excludes.clear();
+                self.summarize(out);
+            }
+            "show" => {
+                let filter = &self.opts.filter;
+                let _ = writeln!(out, "include: {}", pattern_list(&filter.globs));
+                let _ = writeln!(out, "exclude: {}", pattern_list(&filter.excludes));
+                let _ = writeln!(out, "format:  {}", format_name(&self.format));
+            }
+            "files" => {
+                for path in tree_paths(&self.root, &self.opts.filter) {
+                    let _ = writeln!(out, "{path}");
+                }
+            }
+            "tree" => {
+                let paths = tree_paths(&self.root, &self.opts.filter);
+                out.push_str(&tree::text(&root_name(&self.root), &paths));
+            }
+            "tokens" => self.summarize(out),
+            "format" => match args.first().map(|f| f.parse::<OutputFormat>()) {
+                Some(Ok(format)) => self.format = format,
+                Some(Err(e)) => {
+                    let _ = writeln!(out, "{e}");
+                }
+                None => {
+                    let _ = writeln!(out, "format: {}", format_name(&self.format));
+                }
+            },
+            "emit" => {
+                let bundle = self.render();
+                match args.first() {
+                    Some(path) => match fs::write(path, &bundle) {
+                        Ok(()) => {
+                            let _ = writeln!(out, "Wrote {path}");
+                        }
+                        Err(e) => {
+                            let _ = writeln!(out, "Failed to write {path}: {e}");
+                        }
+                    },
+                    None => out.push_str(&bundle),
+                }
+            }
+            "copy" => match clipboard::copy(&self.render()) {
+                Ok(()) => {
+                    let _ = writeln!(out, "Copied to the clipboard");
+                }
+                Err(e) => {
+                    let _ = writeln!(out, "{e}");
+                }
+            },
+            _ => {
+                let _ = writeln!(out, "unknown command {command}; type `help`");
+            }
+        }
+        Control::Continue
+    }
+
+    /// "12 files, ~3400 tokens", plus what was skipped.
+    fn summarize(&self, out: &mut String) {
+        let collection = collect_files(&self.root, &self.opts);
+        let tokens: usize = count_tokens(&collection.files).iter().sum();
+        let _ = writeln!(out, "{} files, ~{tokens} tokens", collection.files.len());
+        if let Some(summary) = skip_summary(&collection.skipped) {
+            let _ = writeln!(out, "{summary}");
+        }
+    }
+
+    fn render(&self) -> String {
+        let files = collect_files(&self.root, &self.opts).files;
+        let opts = FormatOptions {
+            root: self.root.clone(),
+            ..Default::default()
+        };
+        match self.format {
+            OutputFormat::Markdown => format_markdown(&files, &opts),
+            OutputFormat::Xml => format_xml(&files, &opts),
+            OutputFormat::Json => format_json(&files, &opts),
+            OutputFormat::Summary => format_summary(&files, &opts),
+        }
+    }
+}
+
+fn pattern_list(patterns: &[String]) -> String {
+    if patterns.is_empty() {
+        "(none)".to_string()
+    } else {
+        patterns.join(" ")
+    }
+}
+
+fn format_name(format: &OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Markdown => "markdown",
+        OutputFormat::Xml => "xml",
+        OutputFormat::Json => "json",
+        OutputFormat::Summary => "summary",
+    }
+}
+
+fn root_name(root: &Path) -> String {
+    root.file_name()
+        .unwrap_or(root.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl_commands() {
+        let root = crate::test_dir::make_test_dir("repl");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+        fs::write(root.join("src/lib_test.rs"), "fn t() {}\n").unwrap();
+        fs::write(root.join("README.md"), "# Hi\n").unwrap();
+
+        let mut repl = Repl::new(
+            root.clone(),
+            CollectOptions::default(),
+            OutputFormat::Markdown,
+        );
+        let mut run = |line: &str| {
+            let mut out = String::new();
+            let control = repl.handle(line, &mut out);
+            (control, out)
+        };
+        assert!(run("add src/**").1.starts_with("2 files, ~"));
+        assert!(run("exclude *_test.rs").1.starts_with("1 files, ~"));
+        assert_eq!(run("files").1, "src/lib.rs\n");
+        assert_eq!(
+            run("show").1,
+            "include: src/**\nexclude: *_test.rs\nformat:  markdown\n"
+        );
+        assert!(run("add [").1.starts_with("invalid pattern ["));
+        run("format xml");
+        assert!(run("emit").1.contains("<file path=\"src/lib.rs\">"));
+        assert!(run("drop src/** *_test.rs").1.starts_with("3 files"));
+        assert!(run("bogus").1.starts_with("unknown command bogus"));
+        assert_eq!(run("quit").0, Control::Quit);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
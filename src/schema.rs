@@ -0,0 +1,120 @@
+/// Schemas for the documents gather writes, embedded from `schema/` so
+/// `gather schema` works from an installed binary.
+pub const SCHEMAS: &[(&str, &str)] = &[
+    ("manifest", include_str!("../schema/manifest.schema.json")),
+    ("lock", include_str!("../schema/lock.schema.json")),
+    ("xml", include_str!("../schema/gather-context.xsd")),
+    ("json", include_str!("../schema/context.schema.json")),
+];
+
+/// The schema for `document`, if there is one.
+pub fn get(document: &str) -> Option<&'static str> {
+    SCHEMAS
+        .iter()
+        .find(|(name, _)| *name == document)
+        .map(|(_, schema)| *schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{Manifest, ManifestEntry};
+    use crate::SkipReason;
+
+    /// Top-level and per-entry property names declared by a JSON Schema.
+    fn properties(schema: &serde_json::Value, pointer: &str) -> Vec<String> {
+        let mut keys: Vec<String> = schema
+            .pointer(pointer)
+            .and_then(|p| p.as_object())
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_manifest_schema_matches_serialization() {
+        let schema: serde_json::Value = serde_json::from_str(get("manifest").unwrap()).unwrap();
+        let manifest = Manifest {
+            gather_version: "0.1.0".to_string(),
+            generated_at: "2024-01-01T00:00:00Z".to_string(),
+            root: "/repo".to_string(),
+            files: vec![ManifestEntry {
+                path: "a.rs".to_string(),
+                included: false,
+                reason: Some(SkipReason::Vendored),
+                bytes: Some(1),
+                tokens: Some(1),
+                sha256: Some("0".repeat(64)),
+                mtime_ns: Some(1),
+            }],
+        };
+        let json = serde_json::to_value(&manifest).unwrap();
+
+        let mut top: Vec<String> = json.as_object().unwrap().keys().cloned().collect();
+        top.sort();
+        assert_eq!(top, properties(&schema, "/properties"));
+
+        let mut entry: Vec<String> = json["files"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        entry.sort();
+        assert_eq!(entry, properties(&schema, "/$defs/entry/properties"));
+
+        let reasons = schema
+            .pointer("/$defs/entry/properties/reason/enum")
+            .unwrap();
+        assert!(reasons
+            .as_array()
+            .unwrap()
+            .contains(&json["files"][0]["reason"]));
+    }
+
+    #[test]
+    fn test_context_schema_matches_serialization() {
+        use crate::{format_json, CollectedFile, FormatOptions};
+        let schema: serde_json::Value = serde_json::from_str(get("json").unwrap()).unwrap();
+        let files = [CollectedFile {
+            relative_path: "a.rs".to_string(),
+            content: "fn a() {}\n".to_string(),
+        }];
+        let opts = FormatOptions {
+            header: Some("head".to_string()),
+            footer: Some("foot".to_string()),
+            tree: Some("a.rs\n".to_string()),
+            graph: Some("graph LR\n".to_string()),
+            unchanged: Some(("s".to_string(), vec!["b.rs".to_string()])),
+            ..Default::default()
+        };
+        let json: serde_json::Value = serde_json::from_str(&format_json(&files, &opts)).unwrap();
+
+        let mut top: Vec<String> = json.as_object().unwrap().keys().cloned().collect();
+        top.sort();
+        assert_eq!(top, properties(&schema, "/properties"));
+        let mut file: Vec<String> = json["files"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        file.sort();
+        assert_eq!(file, properties(&schema, "/$defs/file/properties"));
+        assert_eq!(json["version"], schema["properties"]["version"]["const"]);
+    }
+
+    #[test]
+    fn test_lock_schema_parses() {
+        let schema: serde_json::Value = serde_json::from_str(get("lock").unwrap()).unwrap();
+        assert_eq!(
+            properties(&schema, "/properties/files/items/properties"),
+            vec!["bytes", "path", "sha256"]
+        );
+        assert!(get("xml").unwrap().contains("xs:schema"));
+        assert!(get("yaml").is_none());
+    }
+}
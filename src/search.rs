@@ -0,0 +1,311 @@
+use crate::{cache, CollectedFile};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::query::{AllQuery, BooleanQuery, Query, QueryParser, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, STORED, STRING, TEXT,
+};
+use tantivy::tokenizer::{LowerCaser, NgramTokenizer, TextAnalyzer};
+use tantivy::{doc, Index, IndexReader, TantivyDocument, Term};
+
+/// Tokenizer for the trigram field that narrows literal searches.
+const TRIGRAM: &str = "gather_trigram";
+/// Memory the index writer may use across its threads.
+const WRITER_MEMORY: usize = 100 * 1024 * 1024;
+
+/// Where `gather index build` keeps the search index for `root`.
+pub fn index_dir(root: &Path) -> PathBuf {
+    cache::local_dir(root).join("index")
+}
+
+/// Whether `gather index build` has been run for `root`.
+pub fn exists(root: &Path) -> bool {
+    index_dir(root).join("meta.json").exists()
+}
+
+/// A built search index: file contents stored by path, indexed both as
+/// lowercase trigrams (for substring lookups) and as words (for ranked
+/// queries).
+pub struct SearchIndex {
+    reader: IndexReader,
+    index: Index,
+    path: Field,
+    content: Field,
+    grams: Field,
+    words: Field,
+}
+
+/// One matching file and its stored content.
+pub struct Hit {
+    pub path: String,
+    pub content: String,
+    pub score: f32,
+}
+
+fn schema() -> Schema {
+    let mut builder = Schema::builder();
+    builder.add_text_field("path", STRING | STORED);
+    builder.add_text_field("content", STORED);
+    let grams = TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(TRIGRAM)
+            .set_index_option(IndexRecordOption::Basic),
+    );
+    builder.add_text_field("grams", grams);
+    builder.add_text_field("words", TEXT);
+    builder.build()
+}
+
+fn register_tokenizers(index: &Index) -> Result<(), String> {
+    let trigrams = NgramTokenizer::new(3, 3, false).map_err(|e| e.to_string())?;
+    index.tokenizers().register(
+        TRIGRAM,
+        TextAnalyzer::builder(trigrams).filter(LowerCaser).build(),
+    );
+    Ok(())
+}
+
+/// Replace the index under `root` with one holding `files`.
+pub fn build(root: &Path, files: &[CollectedFile]) -> Result<(), String> {
+    let dir = index_dir(root);
+    let fail = |e: &dyn std::fmt::Display| format!("{}: {e}", dir.display());
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| fail(&e))?;
+    }
+    cache::ensure_local_dir(root).map_err(|e| fail(&e))?;
+    fs::create_dir_all(&dir).map_err(|e| fail(&e))?;
+
+    let schema = schema();
+    let index = Index::create_in_dir(&dir, schema.clone()).map_err(|e| fail(&e))?;
+    register_tokenizers(&index)?;
+    let field = |name| schema.get_field(name).expect("field is in the schema");
+    let (path, content, grams, words) = (
+        field("path"),
+        field("content"),
+        field("grams"),
+        field("words"),
+    );
+
+    let mut writer = index
+        .writer::<TantivyDocument>(WRITER_MEMORY)
+        .map_err(|e| fail(&e))?;
+    for file in files {
+        writer
+            .add_document(doc!(
+                path => file.relative_path.as_str(),
+                content => file.content.as_str(),
+                grams => file.content.as_str(),
+                words => format!("{}\n{}", file.relative_path, file.content),
+            ))
+            .map_err(|e| fail(&e))?;
+    }
+    writer.commit().map_err(|e| fail(&e))?;
+    Ok(())
+}
+
+impl SearchIndex {
+    /// Open the index built for `root`.
+    pub fn open(root: &Path) -> Result<Self, String> {
+        let dir = index_dir(root);
+        if !exists(root) {
+            return Err(format!(
+                "No search index at {}; run `gather index build` first",
+                dir.display()
+            ));
+        }
+        let fail = |e: &dyn std::fmt::Display| format!("{}: {e}", dir.display());
+        let index = Index::open_in_dir(&dir).map_err(|e| fail(&e))?;
+        register_tokenizers(&index)?;
+        let reader = index.reader().map_err(|e| fail(&e))?;
+        let schema = index.schema();
+        let field = |name| schema.get_field(name).map_err(|e| fail(&e));
+        Ok(SearchIndex {
+            path: field("path")?,
+            content: field("content")?,
+            grams: field("grams")?,
+            words: field("words")?,
+            reader,
+            index,
+        })
+    }
+
+    /// Files that may contain `pattern`: every trigram of it must occur in
+    /// the file, case-insensitively. Callers confirm the actual match.
+    /// Patterns shorter than three characters can't be narrowed and return
+    /// every file.
+    pub fn candidates(&self, pattern: &str) -> Result<Vec<Hit>, String> {
+        let query: Box<dyn Query> = match trigrams(pattern) {
+            grams if grams.is_empty() => Box::new(AllQuery),
+            grams => Box::new(BooleanQuery::intersection(
+                grams
+                    .iter()
+                    .map(|gram| {
+                        let term = Term::from_field_text(self.grams, gram);
+                        Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>
+                    })
+                    .collect(),
+            )),
+        };
+        let searcher = self.reader.searcher();
+        let found = searcher
+            .search(&query, &DocSetCollector)
+            .map_err(|e| e.to_string())?;
+        let mut hits = found
+            .into_iter()
+            .map(|address| self.hit(address, 0.0))
+            .collect::<Result<Vec<_>, _>>()?;
+        hits.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(hits)
+    }
+
+    /// The `limit` best files for a free-text query over paths and contents,
+    /// best first. Supports the usual syntax: `+must`, `-not`, `"a phrase"`.
+    pub fn query(&self, text: &str, limit: usize) -> Result<Vec<Hit>, String> {
+        let mut parser = QueryParser::for_index(&self.index, vec![self.words]);
+        parser.set_conjunction_by_default();
+        let query = parser
+            .parse_query(text)
+            .map_err(|e| format!("--query: {e}"))?;
+        let searcher = self.reader.searcher();
+        let top = searcher
+            .search(&query, &TopDocs::with_limit(limit.max(1)))
+            .map_err(|e| e.to_string())?;
+        top.into_iter()
+            .map(|(score, address)| self.hit(address, score))
+            .collect()
+    }
+
+    fn hit(&self, address: tantivy::DocAddress, score: f32) -> Result<Hit, String> {
+        let doc: TantivyDocument = self
+            .reader
+            .searcher()
+            .doc(address)
+            .map_err(|e| e.to_string())?;
+        let text = |field| {
+            doc.get_first(field)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        Ok(Hit {
+            path: text(self.path),
+            content: text(self.content),
+            score,
+        })
+    }
+}
+
+/// Paths retrieved for several queries at once: up to `limit` per query,
+/// merged round-robin so each query's best files come before any query's
+/// weaker ones, with files found by more than one query kept once.
+pub fn retrieve(
+    index: &SearchIndex,
+    queries: &[String],
+    limit: usize,
+) -> Result<Vec<String>, String> {
+    let mut results = Vec::new();
+    for query in queries {
+        results.push(index.query(query, limit)?);
+    }
+    Ok(interleave(
+        results
+            .into_iter()
+            .map(|hits| hits.into_iter().map(|h| h.path).collect())
+            .collect(),
+    ))
+}
+
+pub fn interleave(lists: Vec<Vec<String>>) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    let mut merged = Vec::new();
+    let longest = lists.iter().map(Vec::len).max().unwrap_or(0);
+    for rank in 0..longest {
+        for list in &lists {
+            if let Some(path) = list.get(rank) {
+                if seen.insert(path.clone()) {
+                    merged.push(path.clone());
+                }
+            }
+        }
+    }
+    merged
+}
+
+/// Queries from a file, one per line; blank lines and `#` comments are
+/// skipped.
+pub fn read_queries(path: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// The distinct lowercase character trigrams of `pattern`.
+fn trigrams(pattern: &str) -> BTreeSet<String> {
+    let chars: Vec<char> = pattern.to_lowercase().chars().collect();
+    chars.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigrams() {
+        let grams: Vec<String> = trigrams("Scratch").into_iter().collect();
+        assert_eq!(grams, ["atc", "cra", "rat", "scr", "tch"]);
+        assert!(trigrams("ab").is_empty());
+    }
+
+    #[test]
+    fn test_interleave() {
+        let list = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            interleave(vec![list(&["a", "b", "c"]), list(&["d", "a"]), list(&[])]),
+            ["a", "d", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_build_and_search() {
+        let root = crate::test_dir::make_test_dir("search");
+        let file = |path: &str, content: &str| CollectedFile {
+            relative_path: path.to_string(),
+            content: content.to_string(),
+        };
+        let files = [
+            file("src/cache.rs", "pub fn scratch_dir() -> ScratchDir {}\n"),
+            file("src/http.rs", "fn fetch(url: &str) {}\n"),
+            file(
+                "README.md",
+                "Scratch directories are swept after an hour.\n",
+            ),
+        ];
+        assert!(SearchIndex::open(&root).is_err());
+        build(&root, &files).unwrap();
+        let index = SearchIndex::open(&root).unwrap();
+
+        let paths = |hits: Vec<Hit>| hits.into_iter().map(|h| h.path).collect::<Vec<_>>();
+        assert_eq!(
+            paths(index.candidates("cratch").unwrap()),
+            ["README.md", "src/cache.rs"]
+        );
+        assert_eq!(paths(index.candidates("fn").unwrap()).len(), 3);
+        let hits = index.candidates("url: &str").unwrap();
+        assert_eq!(hits[0].content, "fn fetch(url: &str) {}\n");
+        assert_eq!(paths(index.query("swept hour", 10).unwrap()), ["README.md"]);
+        assert_eq!(paths(index.query("http", 10).unwrap()), ["src/http.rs"]);
+        let queries = ["fetch url".to_string(), "scratch".to_string()];
+        assert_eq!(
+            retrieve(&index, &queries, 10).unwrap(),
+            ["src/http.rs", "src/cache.rs", "README.md"]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,586 @@
+use crate::api::Formatter;
+use crate::{
+    check_filter, collect_files, count_tokens, lang, token_table, tool_schema, tree, tree_paths,
+    CollectOptions, FilterArgs, OutputFormat,
+};
+use serde_json::{json, Map, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+/// MCP protocol revisions the server speaks, newest first.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Threads answering HTTP requests; further connections wait for one to
+/// come free.
+const WORKERS: usize = 4;
+
+/// Longest HTTP request or header line read, and most header lines.
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+
+/// Host names an HTTP request may be addressed to besides the bound
+/// address; anything else is a page elsewhere reaching us through DNS
+/// rebinding.
+const LOCAL_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// Arguments every tool takes; `gather_collect` takes these and more.
+const SELECTION_ARGS: &[&str] = &["path", "globs", "excludes", "langs", "no_tests"];
+
+/// Run one of the tools in `tool_schema` on the directory being served,
+/// `root` (canonical), returning its output or what was wrong with the call.
+pub fn call(root: &Path, tool: &str, args: &Value) -> Result<String, String> {
+    let extra: &[&str] = match tool {
+        "gather_collect" => &["format", "max_file_tokens"],
+        "gather_tree" | "gather_tokens" => &[],
+        _ => return Err(format!("unknown tool {tool}")),
+    };
+    let empty = Map::new();
+    let args = match args {
+        Value::Object(args) => args,
+        Value::Null => &empty,
+        _ => return Err(format!("{tool} takes an object of arguments")),
+    };
+    if let Some(key) = args
+        .keys()
+        .find(|key| !SELECTION_ARGS.contains(&key.as_str()) && !extra.contains(&key.as_str()))
+    {
+        return Err(format!("unknown argument `{key}` for {tool}"));
+    }
+
+    let dir = subdir(root, args.get("path"))?;
+    let langs = strings(args, "langs")?
+        .iter()
+        .map(|name| lang::parse(name))
+        .collect::<Result<_, _>>()?;
+    let opts = CollectOptions {
+        filter: FilterArgs {
+            globs: strings(args, "globs")?,
+            excludes: strings(args, "excludes")?,
+            langs,
+            no_tests: flag(args, "no_tests")?,
+            ..Default::default()
+        },
+        max_file_tokens: match args.get("max_file_tokens") {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(
+                value
+                    .as_u64()
+                    .ok_or("`max_file_tokens` must be a positive integer")?
+                    as usize,
+            ),
+        },
+        ..Default::default()
+    };
+    check_filter(&dir, &opts.filter).map_err(|e| e.to_string())?;
+
+    // Symlinked files are followed by the walk; keep only those that really
+    // live under the served directory
+    let inside = |rel: &str| {
+        dir.join(rel)
+            .canonicalize()
+            .is_ok_and(|path| path.starts_with(root))
+    };
+    if tool == "gather_tree" {
+        let name = dir.file_name().unwrap_or(dir.as_os_str()).to_string_lossy();
+        let mut paths = tree_paths(&dir, &opts.filter);
+        paths.retain(|rel| inside(rel));
+        return Ok(tree::text(&name, &paths));
+    }
+    let mut files = collect_files(&dir, &opts).files;
+    files.retain(|f| inside(&f.relative_path));
+    if files.is_empty() {
+        return Err("No files found matching the given criteria.".to_string());
+    }
+    if tool == "gather_tokens" {
+        let rows: Vec<(String, usize, usize)> = count_tokens(&files)
+            .into_iter()
+            .zip(&files)
+            .map(|(tokens, f)| (f.relative_path.clone(), tokens, f.content.len()))
+            .collect();
+        return Ok(token_table(&rows, None, ["file", "files"], rows.len(), ""));
+    }
+    let format = match args.get("format") {
+        None | Some(Value::Null) => OutputFormat::Markdown,
+        Some(value) => value.as_str().ok_or("`format` must be a string")?.parse()?,
+    };
+    Ok(Formatter::new(format).format(&files))
+}
+
+/// The directory `path` names under `root`, which it may not leave.
+fn subdir(root: &Path, path: Option<&Value>) -> Result<PathBuf, String> {
+    let rel = match path {
+        None | Some(Value::Null) => ".",
+        Some(Value::String(rel)) => rel,
+        Some(_) => return Err("`path` must be a string".to_string()),
+    };
+    let dir = root
+        .join(rel)
+        .canonicalize()
+        .map_err(|e| format!("Cannot read {rel}: {e}"))?;
+    if !dir.starts_with(root) {
+        return Err(format!(
+            "{rel} is outside the served directory {}",
+            root.display()
+        ));
+    }
+    Ok(dir)
+}
+
+fn strings(args: &Map<String, Value>, key: &str) -> Result<Vec<String>, String> {
+    let invalid = || format!("`{key}` must be an array of strings");
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string).ok_or_else(invalid))
+            .collect(),
+        Some(_) => Err(invalid()),
+    }
+}
+
+fn flag(args: &Map<String, Value>, key: &str) -> Result<bool, String> {
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(false),
+        Some(value) => value.as_bool().ok_or(format!("`{key}` must be a boolean")),
+    }
+}
+
+/// Serve the tools to a Model Context Protocol client over stdin and stdout,
+/// one JSON-RPC message per line, until stdin closes.
+pub fn mcp(root: &Path) {
+    eprintln!("gather MCP server for {} on stdio", root.display());
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => respond(root, &message),
+            Err(e) => Some(error(Value::Null, -32700, &format!("Parse error: {e}"))),
+        };
+        if let Some(reply) = reply {
+            let _ = writeln!(stdout, "{reply}");
+            let _ = stdout.flush();
+        }
+    }
+}
+
+/// The reply to one JSON-RPC message, or none for notifications.
+fn respond(root: &Path, message: &Value) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let method = message.get("method")?.as_str()?;
+    let params = &message["params"];
+    let result = match method {
+        "initialize" => {
+            let requested = params["protocolVersion"].as_str().unwrap_or_default();
+            let version = PROTOCOL_VERSIONS
+                .iter()
+                .find(|v| **v == requested)
+                .unwrap_or(&PROTOCOL_VERSIONS[0]);
+            json!({
+                "protocolVersion": version,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "gather", "version": env!("CARGO_PKG_VERSION")}
+            })
+        }
+        "ping" => json!({}),
+        "tools/list" => json!({"tools": tool_schema::definitions("mcp")}),
+        "tools/call" => {
+            let Some(name) = params["name"].as_str() else {
+                return Some(error(id, -32602, "tools/call needs a tool name"));
+            };
+            // A failed call is a result the model can read, not a protocol error
+            let (text, failed) = match call(root, name, &params["arguments"]) {
+                Ok(text) => (text, false),
+                Err(e) => (e, true),
+            };
+            json!({"content": [{"type": "text", "text": text}], "isError": failed})
+        }
+        _ => return Some(error(id, -32601, &format!("Method not found: {method}"))),
+    };
+    Some(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// Serve the tools over HTTP on `addr` until the process is stopped:
+/// `GET /collect`, `/tree` and `/tokens`, with the tool arguments as query
+/// parameters (`?glob=src/**&glob=*.toml&format=json`). Connections are
+/// answered by a pool of [`WORKERS`] threads.
+pub fn http(root: &Path, addr: &str) -> Result<(), String> {
+    let listener = TcpListener::bind(addr).map_err(|e| format!("Cannot listen on {addr}: {e}"))?;
+    let local = listener.local_addr().map_err(|e| e.to_string())?;
+    eprintln!("gather serving {} on http://{local}", root.display());
+    let (tx, rx) = mpsc::sync_channel::<TcpStream>(WORKERS);
+    let rx = Arc::new(Mutex::new(rx));
+    for _ in 0..WORKERS {
+        let (rx, root) = (Arc::clone(&rx), root.to_path_buf());
+        std::thread::spawn(move || loop {
+            let next = rx.lock().ok().and_then(|rx| rx.recv().ok());
+            let Some(stream) = next else {
+                break;
+            };
+            handle(&root, local, stream);
+        });
+    }
+    for stream in listener.incoming().flatten() {
+        if tx.send(stream).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Read one request from `stream` and write the response. `local` is the
+/// address the server is bound to.
+fn handle(root: &Path, local: SocketAddr, mut stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let request = read_request(&mut BufReader::new(&stream));
+    let (status, content_type, body) = match request {
+        Err(status) => (status, "text/plain", format!("{status}\n")),
+        Ok((_, _, host)) if !allowed_host(&host, local) => (
+            "403 Forbidden",
+            "text/plain",
+            format!("Host `{host}` not allowed; use localhost or {local}\n"),
+        ),
+        Ok((method, target, _)) if method == "GET" => route(root, &target),
+        Ok((method, _, _)) => (
+            "405 Method Not Allowed",
+            "text/plain",
+            format!("{method} not allowed; use GET\n"),
+        ),
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+/// The method, target and Host header of the request `reader` holds, or the
+/// error status for one that is malformed or too large.
+fn read_request(reader: &mut impl BufRead) -> Result<(String, String, String), &'static str> {
+    let request_line = read_line(reader, "414 URI Too Long")?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+    let mut host = String::new();
+    for _ in 0..=MAX_HEADERS {
+        let line = read_line(reader, "431 Request Header Fields Too Large")?;
+        if line.trim().is_empty() {
+            return Ok((method, target, host));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("host") {
+                host = value.trim().to_string();
+            }
+        }
+    }
+    Err("431 Request Header Fields Too Large")
+}
+
+/// One line of at most [`MAX_LINE`] bytes, or `too_long` for a longer one.
+fn read_line(reader: &mut impl BufRead, too_long: &'static str) -> Result<String, &'static str> {
+    let mut line = String::new();
+    match reader.by_ref().take(MAX_LINE as u64).read_line(&mut line) {
+        Ok(0) | Err(_) => Err("400 Bad Request"),
+        Ok(_) if !line.ends_with('\n') && line.len() == MAX_LINE => Err(too_long),
+        Ok(_) => Ok(line),
+    }
+}
+
+/// Whether a request for `host` (the Host header, maybe with a port) is
+/// addressed to this server rather than to a name rebound onto it.
+fn allowed_host(host: &str, local: SocketAddr) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    let bound = match local {
+        SocketAddr::V4(addr) => addr.ip().to_string(),
+        SocketAddr::V6(addr) => format!("[{}]", addr.ip()),
+    };
+    !name.is_empty() && (LOCAL_HOSTS.contains(&name) || name == bound)
+}
+
+/// The status, content type and body for `GET target`.
+fn route(root: &Path, target: &str) -> (&'static str, &'static str, String) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let tool = match path.trim_end_matches('/') {
+        "/collect" => "gather_collect",
+        "/tree" => "gather_tree",
+        "/tokens" => "gather_tokens",
+        _ => {
+            return (
+                "404 Not Found",
+                "text/plain",
+                format!("No endpoint {path}; use /collect, /tree or /tokens\n"),
+            )
+        }
+    };
+    let result = query_args(query).and_then(|args| {
+        let content_type = match args["format"].as_str().map(str::to_lowercase).as_deref() {
+            Some("json") => "application/json",
+            Some("xml") => "application/xml",
+            Some("summary") => "text/plain",
+            _ if tool == "gather_collect" => "text/markdown",
+            _ => "text/plain",
+        };
+        Ok((content_type, call(root, tool, &args)?))
+    });
+    match result {
+        Ok((content_type, body)) => ("200 OK", content_type, body),
+        Err(e) => ("400 Bad Request", "text/plain", e + "\n"),
+    }
+}
+
+/// A query string as tool arguments: `glob`, `exclude` and `lang` may repeat
+/// (`lang` also takes a comma-separated list) and become the `globs`,
+/// `excludes` and `langs` lists; `no_tests` and `max_file_tokens` are parsed;
+/// anything else is passed on as a string for `call` to judge.
+fn query_args(query: &str) -> Result<Value, String> {
+    let mut args = Map::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let (key, value) = (percent_decode(key), percent_decode(value));
+        let values: Vec<&str> = match key.as_str() {
+            "lang" => value.split(',').map(str::trim).collect(),
+            "glob" | "exclude" => vec![value.as_str()],
+            "no_tests" => {
+                let yes = match value.as_str() {
+                    "" | "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => return Err(format!("no_tests={value}: expected true or false")),
+                };
+                args.insert(key, json!(yes));
+                continue;
+            }
+            "max_file_tokens" => {
+                let n: u64 = value
+                    .parse()
+                    .map_err(|_| format!("max_file_tokens={value}: expected a number"))?;
+                args.insert(key, json!(n));
+                continue;
+            }
+            _ => {
+                args.insert(key, json!(value));
+                continue;
+            }
+        };
+        let list = args.entry(format!("{key}s")).or_insert_with(|| json!([]));
+        if let Value::Array(list) = list {
+            list.extend(values.into_iter().map(|v| json!(v)));
+        }
+    }
+    Ok(Value::Object(args))
+}
+
+/// `%XX` escapes and `+` for space, as in URL query strings.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (_, Some(byte)) => {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => out.push(b' '),
+            (byte, _) => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::make_test_dir_with;
+    use std::fs;
+
+    fn served_dir(name: &str) -> PathBuf {
+        let files = [("src/lib.rs", "pub fn f() {}\n"), ("README.md", "# Hi\n")];
+        make_test_dir_with(&format!("serve_{name}"), &files)
+            .canonicalize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_call() {
+        let root = served_dir("call");
+        assert_eq!(
+            call(&root, "gather_collect", &json!({"globs": ["**/*.rs"]})).unwrap(),
+            "## `src/lib.rs`\n\n```rust\npub fn f() {}\n```\n\n"
+        );
+        assert!(call(&root, "gather_tree", &Value::Null)
+            .unwrap()
+            .contains("└── lib.rs"));
+        assert!(call(&root, "gather_tokens", &json!({"path": "src"}))
+            .unwrap()
+            .ends_with("total (1 files)\n"));
+
+        assert_eq!(
+            call(&root, "gather_tree", &json!({"format": "xml"})).unwrap_err(),
+            "unknown argument `format` for gather_tree"
+        );
+        assert_eq!(
+            call(&root, "gather_collect", &json!({"globs": "*.rs"})).unwrap_err(),
+            "`globs` must be an array of strings"
+        );
+        assert!(call(&root, "gather_tree", &json!({"path": ".."}))
+            .unwrap_err()
+            .starts_with(".. is outside the served directory"));
+        assert!(call(&root, "gather_collect", &json!({"langs": ["go"]})).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_call_stays_inside_root() {
+        let root = served_dir("symlink");
+        let outside = served_dir("symlink_outside");
+        fs::write(outside.join("secret.txt"), "hunter2\n").unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), root.join("notes.txt")).unwrap();
+
+        let collected = call(&root, "gather_collect", &Value::Null).unwrap();
+        assert!(!collected.contains("hunter2") && !collected.contains("notes.txt"));
+        assert!(!call(&root, "gather_tree", &Value::Null)
+            .unwrap()
+            .contains("notes.txt"));
+
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn test_respond() {
+        let root = served_dir("respond");
+        let request = |id: u64, method: &str, params: Value| {
+            respond(
+                &root,
+                &json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}),
+            )
+            .unwrap()
+        };
+
+        let init = request(1, "initialize", json!({"protocolVersion": "2025-03-26"}));
+        assert_eq!(init["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(init["result"]["serverInfo"]["name"], "gather");
+        // Notifications get no reply
+        assert!(respond(
+            &root,
+            &json!({"jsonrpc": "2.0", "method": "notifications/initialized"})
+        )
+        .is_none());
+
+        let list = request(2, "tools/list", Value::Null);
+        assert_eq!(list["result"]["tools"].as_array().unwrap().len(), 3);
+
+        let called = request(
+            3,
+            "tools/call",
+            json!({"name": "gather_collect", "arguments": {"globs": ["README.md"]}}),
+        );
+        assert_eq!(called["id"], 3);
+        assert_eq!(called["result"]["isError"], false);
+        assert!(called["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("# Hi"));
+        let failed = request(4, "tools/call", json!({"name": "gather_nope"}));
+        assert_eq!(failed["result"]["isError"], true);
+
+        assert_eq!(
+            request(5, "resources/list", Value::Null)["error"]["code"],
+            -32601
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_http() {
+        assert_eq!(percent_decode("src%2F**%2A.rs+x%zz"), "src/***.rs x%zz");
+        assert_eq!(
+            query_args("glob=src/**&glob=*.toml&lang=rust,toml&no_tests&max_file_tokens=500")
+                .unwrap(),
+            json!({
+                "globs": ["src/**", "*.toml"],
+                "langs": ["rust", "toml"],
+                "no_tests": true,
+                "max_file_tokens": 500
+            })
+        );
+        assert!(query_args("max_file_tokens=lots").is_err());
+
+        let root = served_dir("http");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_root = root.clone();
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming().take(4) {
+                handle(&server_root, addr, stream.unwrap());
+            }
+        });
+        let get = |request: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            io::Read::read_to_string(&mut stream, &mut response).unwrap();
+            response
+        };
+
+        let json =
+            get("GET /collect?glob=src%2F*.rs&format=json HTTP/1.1\r\nHost: localhost:1\r\n\r\n");
+        assert!(json.starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/json"));
+        let body: Value = serde_json::from_str(json.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["files"][0]["path"], "src/lib.rs");
+
+        let tree = get(&format!("GET /tree HTTP/1.1\r\nhost: {addr}\r\n\r\n"));
+        assert!(tree.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/plain"));
+        assert!(tree.ends_with("2 files\n"));
+
+        let outside = "GET /tree?path=.. HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n";
+        assert!(get(outside).starts_with("HTTP/1.1 400"));
+        // A rebound name, or none at all, is turned away
+        let rebound = "GET /tree HTTP/1.1\r\nHost: attacker.example:8377\r\n\r\n";
+        assert!(get(rebound).starts_with("HTTP/1.1 403"));
+        server.join().unwrap();
+        assert!(route(&root, "/nope").0.starts_with("404"));
+        assert!(!allowed_host("", addr));
+
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(
+            read_request(&mut long.as_bytes()).unwrap_err(),
+            "414 URI Too Long"
+        );
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: y\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert_eq!(
+            read_request(&mut many.as_bytes()).unwrap_err(),
+            "431 Request Header Fields Too Large"
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
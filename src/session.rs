@@ -0,0 +1,295 @@
+use crate::manifest::sha256_hex;
+use crate::{cache, template, CollectedFile};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a named session has already delivered: the content hash of each
+/// file as last sent, and one record per gather.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub files: BTreeMap<String, String>,
+    pub runs: Vec<Run>,
+    /// Rendered parts of a split gather still waiting for `gather next`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queue: Vec<QueuedPart>,
+}
+
+/// One undelivered part, rendered to a file beside the session's state.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueuedPart {
+    pub number: usize,
+    pub total: usize,
+    /// File name under the session's parts directory.
+    pub file: String,
+    /// Content hash of each file in the part, recorded as sent on delivery.
+    pub files: BTreeMap<String, String>,
+    pub tokens: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Run {
+    pub at: String,
+    /// Files emitted in full.
+    pub sent: usize,
+    /// Files only listed as unchanged.
+    pub unchanged: usize,
+    pub tokens: usize,
+}
+
+/// Where session state lives: `.gather/sessions/` in the repository.
+pub fn sessions_dir(root: &Path) -> PathBuf {
+    cache::local_dir(root).join("sessions")
+}
+
+/// Reject names that would escape the sessions directory or hide the file.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let ok = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if ok {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid session name '{name}': use letters, digits, '-', '_' and '.'"
+        ))
+    }
+}
+
+fn state_path(root: &Path, name: &str) -> PathBuf {
+    sessions_dir(root).join(format!("{name}.json"))
+}
+
+fn parts_dir(root: &Path, name: &str) -> PathBuf {
+    sessions_dir(root).join(format!("{name}.parts"))
+}
+
+impl Session {
+    /// The state of session `name`, empty if it hasn't been used yet.
+    pub fn load(root: &Path, name: &str) -> Result<Self, String> {
+        validate_name(name)?;
+        let path = state_path(root, name);
+        match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Session::default()),
+            Err(e) => Err(format!("{}: {e}", path.display())),
+        }
+    }
+
+    pub fn save(&self, root: &Path, name: &str) -> Result<(), String> {
+        let path = state_path(root, name);
+        let fail = |e: &dyn std::fmt::Display| format!("{}: {e}", path.display());
+        cache::ensure_local_dir(root).map_err(|e| fail(&e))?;
+        fs::create_dir_all(sessions_dir(root)).map_err(|e| fail(&e))?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| fail(&e))?;
+        fs::write(&path, json + "\n").map_err(|e| fail(&e))
+    }
+
+    /// Split `files` into those to send (new, or changed since they were
+    /// sent) and the paths of those the session already has verbatim.
+    pub fn split(&self, files: Vec<CollectedFile>) -> (Vec<CollectedFile>, Vec<String>) {
+        let mut fresh = Vec::new();
+        let mut unchanged = Vec::new();
+        for file in files {
+            let sent = self.files.get(&file.relative_path);
+            if sent.is_some_and(|hash| *hash == sha256_hex(file.content.as_bytes())) {
+                unchanged.push(file.relative_path);
+            } else {
+                fresh.push(file);
+            }
+        }
+        (fresh, unchanged)
+    }
+
+    /// Note that `sent` was delivered in full, alongside `unchanged` reminders.
+    pub fn record(&mut self, sent: &[CollectedFile], unchanged: usize, tokens: usize) {
+        self.record_hashes(hashes(sent), unchanged, tokens);
+    }
+
+    fn record_hashes(&mut self, sent: BTreeMap<String, String>, unchanged: usize, tokens: usize) {
+        self.runs.push(Run {
+            at: template::now_utc_timestamp(),
+            sent: sent.len(),
+            unchanged,
+            tokens,
+        });
+        self.files.extend(sent);
+    }
+
+    /// Hold parts 2.. of a split gather for `gather next`, replacing any
+    /// parts still queued from an earlier gather. `parts` are (rendered
+    /// text, files, tokens); `first` is the number the first of them gets.
+    pub fn queue_parts(
+        &mut self,
+        root: &Path,
+        name: &str,
+        parts: Vec<(String, &[CollectedFile], usize)>,
+        first: usize,
+        total: usize,
+    ) -> Result<(), String> {
+        let dir = parts_dir(root, name);
+        let _ = fs::remove_dir_all(&dir);
+        self.queue.clear();
+        if parts.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        for (i, (rendered, files, tokens)) in parts.into_iter().enumerate() {
+            let number = first + i;
+            let file = format!("{number:03}");
+            let path = dir.join(&file);
+            fs::write(&path, rendered).map_err(|e| format!("{}: {e}", path.display()))?;
+            self.queue.push(QueuedPart {
+                number,
+                total,
+                file,
+                files: hashes(files),
+                tokens,
+            });
+        }
+        Ok(())
+    }
+
+    /// Take the next queued part and record its files as sent. Returns the
+    /// part and its rendered text, or `None` when nothing is left.
+    pub fn next_part(
+        &mut self,
+        root: &Path,
+        name: &str,
+    ) -> Result<Option<(QueuedPart, String)>, String> {
+        if self.queue.is_empty() {
+            return Ok(None);
+        }
+        let part = self.queue.remove(0);
+        let path = parts_dir(root, name).join(&part.file);
+        let rendered = fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let _ = fs::remove_file(&path);
+        if self.queue.is_empty() {
+            let _ = fs::remove_dir(parts_dir(root, name));
+        }
+        self.record_hashes(part.files.clone(), 0, part.tokens);
+        Ok(Some((part, rendered)))
+    }
+
+    /// Tokens sent over every gather in the session.
+    pub fn tokens_sent(&self) -> usize {
+        self.runs.iter().map(|r| r.tokens).sum()
+    }
+
+    /// When the session was last used, if ever.
+    pub fn last_used(&self) -> Option<&str> {
+        self.runs.last().map(|r| r.at.as_str())
+    }
+}
+
+fn hashes(files: &[CollectedFile]) -> BTreeMap<String, String> {
+    files
+        .iter()
+        .map(|f| (f.relative_path.clone(), sha256_hex(f.content.as_bytes())))
+        .collect()
+}
+
+/// Every session recorded under `root`, by name.
+pub fn list(root: &Path) -> Vec<(String, Session)> {
+    let Ok(entries) = fs::read_dir(sessions_dir(root)) else {
+        return Vec::new();
+    };
+    let mut sessions: Vec<(String, Session)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry
+                .file_name()
+                .to_string_lossy()
+                .strip_suffix(".json")?
+                .to_string();
+            let session = Session::load(root, &name).ok()?;
+            Some((name, session))
+        })
+        .collect();
+    sessions.sort_by(|a, b| a.0.cmp(&b.0));
+    sessions
+}
+
+/// Forget session `name`. Returns whether there was anything to forget.
+pub fn reset(root: &Path, name: &str) -> Result<bool, String> {
+    validate_name(name)?;
+    let path = state_path(root, name);
+    let _ = fs::remove_dir_all(parts_dir(root, name));
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, content: &str) -> CollectedFile {
+        CollectedFile {
+            relative_path: path.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let root = crate::test_dir::make_test_dir("session");
+
+        let mut session = Session::load(&root, "review").unwrap();
+        let (fresh, unchanged) = session.split(vec![file("a.rs", "1"), file("b.rs", "2")]);
+        assert_eq!(fresh.len(), 2);
+        assert!(unchanged.is_empty());
+        session.record(&fresh, 0, 10);
+        session.save(&root, "review").unwrap();
+
+        let session = Session::load(&root, "review").unwrap();
+        assert_eq!(session.runs.len(), 1);
+        let (fresh, unchanged) = session.split(vec![
+            file("a.rs", "1"),
+            file("b.rs", "changed"),
+            file("c.rs", "3"),
+        ]);
+        let fresh: Vec<&str> = fresh.iter().map(|f| f.relative_path.as_str()).collect();
+        assert_eq!(fresh, ["b.rs", "c.rs"]);
+        assert_eq!(unchanged, ["a.rs"]);
+        assert!(root.join(".gather/.gitignore").exists());
+        let names: Vec<String> = list(&root).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["review"]);
+        assert!(reset(&root, "review").unwrap());
+        assert!(!reset(&root, "review").unwrap());
+        assert!(list(&root).is_empty());
+
+        let mut session = Session::default();
+        let parts = [file("a.rs", "1")];
+        let later = [file("b.rs", "2")];
+        session
+            .queue_parts(
+                &root,
+                "split",
+                vec![
+                    ("part 2".to_string(), &parts, 1),
+                    ("part 3".to_string(), &later, 1),
+                ],
+                2,
+                3,
+            )
+            .unwrap();
+        let (part, text) = session.next_part(&root, "split").unwrap().unwrap();
+        assert_eq!((part.number, part.total, text.as_str()), (2, 3, "part 2"));
+        assert!(session.files.contains_key("a.rs"));
+        assert_eq!(session.queue.len(), 1);
+        session.next_part(&root, "split").unwrap().unwrap();
+        assert!(session.next_part(&root, "split").unwrap().is_none());
+        assert!(!parts_dir(&root, "split").exists());
+
+        assert!(validate_name("../escape").is_err());
+        assert!(validate_name(".hidden").is_err());
+        assert!(validate_name("feature-42").is_ok());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
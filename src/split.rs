@@ -0,0 +1,147 @@
+use crate::{count_tokens, output, CollectedFile};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The index written next to split parts: which files each part holds and
+/// roughly how many tokens it comes to, in the order to feed them.
+#[derive(Serialize)]
+pub struct PartsManifest {
+    pub gather_version: String,
+    pub parts: Vec<PartEntry>,
+    pub total_tokens: usize,
+}
+
+#[derive(Serialize)]
+pub struct PartEntry {
+    /// File name of the part, beside the manifest.
+    pub path: String,
+    pub files: Vec<String>,
+    pub tokens: usize,
+}
+
+/// Where part `n` (1-based) of a split `output` goes: `context.md` becomes
+/// `context-001.md`, `context-002.md`, ...
+pub fn part_path(output: &Path, n: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}-{n:03}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{n:03}"),
+    };
+    output.with_file_name(name)
+}
+
+/// Where the parts manifest of a split `output` goes: `context-parts.json`.
+pub fn manifest_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{stem}-parts.json"))
+}
+
+/// Whether `path` is one of the numbered parts of a split `output`, or its
+/// manifest.
+pub fn is_part(output: &Path, path: &Path) -> bool {
+    if path.parent() != output.parent() {
+        return false;
+    }
+    if path == manifest_path(output) {
+        return true;
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let Some(part_stem) = path.file_stem() else {
+        return false;
+    };
+    path.extension() == output.extension()
+        && part_stem
+            .to_string_lossy()
+            .strip_prefix(&format!("{stem}-"))
+            .is_some_and(|n| n.len() >= 3 && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Write each rendered part of `output` to its own numbered file, then the
+/// manifest, and remove numbered files left over from an earlier split into
+/// more parts. Returns the manifest's path.
+pub fn write_parts(
+    output: &Path,
+    rendered: &[String],
+    parts: &[Vec<CollectedFile>],
+) -> Result<PathBuf, String> {
+    let mut entries = Vec::new();
+    for (i, (text, files)) in rendered.iter().zip(parts).enumerate() {
+        let path = part_path(output, i + 1);
+        output::write(&path, text, false)?;
+        entries.push(PartEntry {
+            path: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            files: files.iter().map(|f| f.relative_path.clone()).collect(),
+            tokens: count_tokens(files).iter().sum(),
+        });
+    }
+    let mut stale = rendered.len() + 1;
+    while fs::remove_file(part_path(output, stale)).is_ok() {
+        stale += 1;
+    }
+
+    let manifest = PartsManifest {
+        gather_version: env!("CARGO_PKG_VERSION").to_string(),
+        total_tokens: entries.iter().map(|e| e.tokens).sum(),
+        parts: entries,
+    };
+    let path = manifest_path(output);
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    output::write(&path, &format!("{json}\n"), false)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, content: &str) -> CollectedFile {
+        CollectedFile {
+            relative_path: path.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_write_parts() {
+        let dir = crate::test_dir::make_test_dir("split");
+        let output = dir.join("context.md");
+        assert_eq!(part_path(&output, 2), dir.join("context-002.md"));
+        assert_eq!(part_path(Path::new("out"), 12), Path::new("out-012"));
+        assert!(is_part(&output, &dir.join("context-012.md")));
+        assert!(is_part(&output, &dir.join("context-parts.json")));
+        assert!(!is_part(&output, &dir.join("context-a12.md")));
+        assert!(!is_part(&output, &dir.join("context-012.xml")));
+
+        let parts = vec![
+            vec![file("a.md", "aaaa"), file("b.md", "bbbb")],
+            vec![file("c.md", "cccc")],
+            vec![file("d.md", "dddd")],
+        ];
+        let rendered = ["one", "two", "three"].map(String::from);
+        write_parts(&output, &rendered, &parts).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("context-003.md")).unwrap(),
+            "three"
+        );
+
+        // A smaller split replaces the old parts and drops the extra one
+        let manifest = write_parts(&output, &rendered[..2], &parts[..2]).unwrap();
+        assert!(!dir.join("context-003.md").exists());
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(manifest).unwrap()).unwrap();
+        assert_eq!(json["parts"][0]["path"], "context-001.md");
+        assert_eq!(
+            json["parts"][0]["files"],
+            serde_json::json!(["a.md", "b.md"])
+        );
+        assert_eq!(json["parts"][1]["tokens"], 1);
+        assert_eq!(json["total_tokens"], 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+use crate::manifest::{mtime_ns, sha256_hex};
+use crate::{lang_tag, template, CollectedFile};
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE files (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    dir TEXT NOT NULL,
+    depth INTEGER NOT NULL,
+    language TEXT NOT NULL,
+    bytes INTEGER NOT NULL,
+    lines INTEGER NOT NULL,
+    tokens INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    mtime_ns INTEGER,
+    content TEXT
+);
+";
+
+/// Write `files` to a fresh SQLite database at `db_path`: a `files` table of
+/// paths and metrics, a `meta` table describing the run, and a `files_fts`
+/// FTS5 table over path and content. Without `with_content` the text is
+/// indexed but not stored (a contentless FTS table), so searches return
+/// matching `rowid`s to join against `files.id`.
+///
+/// The database is built beside `db_path` and renamed over it, so readers
+/// never see a half-written index.
+pub fn export(
+    db_path: &Path,
+    root: &Path,
+    files: &[CollectedFile],
+    tokens: &[usize],
+    with_content: bool,
+) -> Result<(), String> {
+    let tmp = db_path.with_extension(format!("tmp{}", std::process::id()));
+    let _ = fs::remove_file(&tmp);
+    let result = write_db(&tmp, root, files, tokens, with_content)
+        .map_err(|e| format!("{}: {e}", db_path.display()))
+        .and_then(|()| {
+            fs::rename(&tmp, db_path).map_err(|e| format!("{}: {e}", db_path.display()))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn write_db(
+    path: &Path,
+    root: &Path,
+    files: &[CollectedFile],
+    tokens: &[usize],
+    with_content: bool,
+) -> rusqlite::Result<()> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    conn.execute_batch(if with_content {
+        "CREATE VIRTUAL TABLE files_fts USING fts5(path, content, content='files', content_rowid='id');"
+    } else {
+        "CREATE VIRTUAL TABLE files_fts USING fts5(path, content, content='');"
+    })?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut meta = tx.prepare("INSERT INTO meta (key, value) VALUES (?1, ?2)")?;
+        meta.execute(params!["gather_version", env!("CARGO_PKG_VERSION")])?;
+        meta.execute(params!["root", root.to_string_lossy()])?;
+        meta.execute(params!["indexed_at", template::now_utc_timestamp()])?;
+        meta.execute(params!["content", if with_content { "1" } else { "0" }])?;
+
+        let mut insert = tx.prepare(
+            "INSERT INTO files (id, path, dir, depth, language, bytes, lines, tokens, sha256, mtime_ns, content)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+        let mut fts =
+            tx.prepare("INSERT INTO files_fts (rowid, path, content) VALUES (?1, ?2, ?3)")?;
+        for (i, (file, tokens)) in files.iter().zip(tokens).enumerate() {
+            let id = i as i64 + 1;
+            let path = &file.relative_path;
+            let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+            let mtime = fs::metadata(root.join(path))
+                .ok()
+                .and_then(|m| mtime_ns(&m))
+                .map(|ns| ns as i64);
+            insert.execute(params![
+                id,
+                path,
+                dir,
+                path.matches('/').count() as i64,
+                lang_tag(Path::new(path)),
+                file.content.len() as i64,
+                file.content.lines().count() as i64,
+                *tokens as i64,
+                sha256_hex(file.content.as_bytes()),
+                mtime,
+                with_content.then_some(&file.content),
+            ])?;
+            fts.execute(params![id, path, file.content])?;
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, content: &str) -> CollectedFile {
+        CollectedFile {
+            relative_path: path.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_export_and_search() {
+        let dir = crate::test_dir::make_test_dir("sqlite");
+        let files = [
+            file("src/cache.rs", "fn scratch_dir() {}\n"),
+            file("README.md", "# Usage\n"),
+        ];
+
+        for with_content in [true, false] {
+            let db = dir.join("index.db");
+            export(&db, &dir, &files, &[5, 3], with_content).unwrap();
+            let conn = Connection::open(&db).unwrap();
+            let (dir_name, language, tokens): (String, String, i64) = conn
+                .query_row(
+                    "SELECT dir, language, tokens FROM files WHERE path = 'src/cache.rs'",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+            assert_eq!(
+                (dir_name.as_str(), language.as_str(), tokens),
+                ("src", "rust", 5)
+            );
+            let hit: String = conn
+                .query_row(
+                    "SELECT f.path FROM files_fts JOIN files f ON f.id = files_fts.rowid
+                     WHERE files_fts MATCH 'scratch_dir'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(hit, "src/cache.rs");
+            let stored: Option<String> = conn
+                .query_row("SELECT content FROM files WHERE id = 2", [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(stored.is_some(), with_content);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
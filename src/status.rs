@@ -0,0 +1,49 @@
+use crate::Error;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Exit code when nothing matched the selection (see `--allow-empty`).
+pub const NO_MATCHES: i32 = 2;
+/// Exit code when the output was written but something was warned about.
+pub const WARNINGS: i32 = 3;
+/// Exit code for hard errors: bad arguments or config, unreadable input,
+/// failed git or network operations.
+pub const ERROR: i32 = 4;
+
+/// The exit code so far for a run that gets to the end.
+static CODE: AtomicI32 = AtomicI32::new(0);
+
+/// Print `message` as a warning and make the run exit with [`WARNINGS`].
+pub fn warn(message: impl Display) {
+    eprintln!("warning: {message}");
+    CODE.fetch_max(WARNINGS, Ordering::Relaxed);
+}
+
+/// Print an error the run carries on past as a warning, and make the run
+/// exit with the error's code.
+pub fn report(error: &Error) {
+    eprintln!("warning: {error}");
+    CODE.fetch_max(error.exit_code(), Ordering::Relaxed);
+}
+
+/// The exit code for a run that got to the end: 0, or the highest code of
+/// anything warned about or reported.
+pub fn code() -> i32 {
+    CODE.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warn_sets_exit_code() {
+        warn("something looked off");
+        assert!(code() >= WARNINGS);
+        report(&Error::Read {
+            path: "gone.rs".into(),
+            source: std::io::ErrorKind::PermissionDenied.into(),
+        });
+        assert_eq!(code(), WARNINGS);
+    }
+}
@@ -0,0 +1,556 @@
+use crate::outline;
+use std::collections::BTreeSet;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Directory names that hold vendored copies of third-party packages.
+const VENDOR_DIRS: &[&str] = &[
+    "vendor",
+    "vendored",
+    "third_party",
+    "third-party",
+    "external",
+];
+
+/// Package manifests kept in full when summarizing vendored packages.
+const MANIFEST_NAMES: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "go.mod",
+    "pyproject.toml",
+    "setup.py",
+    "setup.cfg",
+    "composer.json",
+    "Gemfile",
+    "pom.xml",
+    "build.gradle",
+    "CMakeLists.txt",
+    "modules.txt",
+];
+
+/// What `--oversize-strategy` does with files over the size or token limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OversizeStrategy {
+    #[default]
+    Skip,
+    Truncate,
+    Outline,
+    Structure,
+}
+
+impl std::str::FromStr for OversizeStrategy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(OversizeStrategy::Skip),
+            "truncate" => Ok(OversizeStrategy::Truncate),
+            "outline" => Ok(OversizeStrategy::Outline),
+            "structure" => Ok(OversizeStrategy::Structure),
+            _ => Err(format!(
+                "Unknown oversize strategy: {s}. Use 'skip', 'truncate', 'outline' or 'structure'."
+            )),
+        }
+    }
+}
+
+/// Reduce an oversized file of `total` bytes, of which `content` may be only
+/// the start, to at most `budget` bytes using `strategy`. Outline and
+/// structure fall back to truncation for languages they don't understand,
+/// and their result is itself truncated if still over budget.
+pub fn shrink(
+    path: &Path,
+    content: &str,
+    strategy: OversizeStrategy,
+    budget: usize,
+    total: usize,
+) -> String {
+    let reduced = match strategy {
+        OversizeStrategy::Skip | OversizeStrategy::Truncate => None,
+        OversizeStrategy::Outline => {
+            outline::supports(path).then(|| outline::outline(path, content))
+        }
+        OversizeStrategy::Structure => skeleton_of(path, content)
+            .or_else(|| outline::supports(path).then(|| outline::outline(path, content)))
+            .or_else(|| markdown_headings(path, content)),
+    };
+    let reduced = reduced.as_deref().unwrap_or(content);
+    truncate(reduced, budget, total)
+}
+
+/// Run `--summarize-cmd` through the shell with `content` on stdin and the
+/// file's path in `$GATHER_FILE`, returning its output under a `[summarized]`
+/// marker. Fails if the command exits unsuccessfully or prints nothing.
+pub fn external(cmd: &str, path: &Path, content: &str) -> io::Result<String> {
+    let mut command = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.args(["/C", cmd]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", cmd]);
+        c
+    };
+    let mut child = command
+        .env("GATHER_FILE", path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // Write on another thread so a command that prints before draining its
+    // input can't deadlock against us
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = content.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    // A summarizer may legitimately stop reading early
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!("exited with {}", output.status)));
+    }
+    let summary = String::from_utf8_lossy(&output.stdout);
+    if summary.trim().is_empty() {
+        return Err(io::Error::other("produced no output"));
+    }
+    Ok(format!("[summarized]\n{}\n", summary.trim_end()))
+}
+
+/// The first `budget` bytes of `text`, cut at a line boundary, with a marker
+/// noting how much of the original `total` bytes is shown.
+fn truncate(text: &str, budget: usize, total: usize) -> String {
+    if text.len() <= budget {
+        return text.to_string();
+    }
+    let mut end = budget;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind('\n').map_or(end, |i| i + 1);
+    format!(
+        "{}\n[truncated: showing {end} of {total} bytes]\n",
+        &text[..end]
+    )
+}
+
+/// The heading lines of a Markdown document.
+fn markdown_headings(path: &Path, content: &str) -> Option<String> {
+    if !matches!(path.extension()?.to_str()?, "md" | "markdown") {
+        return None;
+    }
+    let mut out = String::new();
+    let mut in_fence = false;
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence && line.starts_with('#') {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    Some(out)
+}
+
+/// How `--summarize-vendored` treats a file inside a vendored package.
+#[derive(Debug, PartialEq)]
+pub enum VendoredRole {
+    /// Manifest or README: kept in full.
+    Keep,
+    /// Source in a language `outline` understands: reduced to declarations.
+    Outline,
+    /// Everything else: left out.
+    Omit,
+}
+
+/// The role of `rel` if it lies under a vendor directory, or `None` for
+/// first-party files.
+pub fn vendored_role(rel: &str) -> Option<VendoredRole> {
+    let parts: Vec<&str> = rel.split(['/', '\\']).collect();
+    let (name, dirs) = parts.split_last()?;
+    if !dirs.iter().any(|d| VENDOR_DIRS.contains(d)) {
+        return None;
+    }
+
+    let is_readme = name.to_ascii_lowercase().starts_with("readme");
+    Some(
+        if is_readme || MANIFEST_NAMES.contains(name) || name.ends_with(".gemspec") {
+            VendoredRole::Keep
+        } else if outline::supports(Path::new(name)) {
+            VendoredRole::Outline
+        } else {
+            VendoredRole::Omit
+        },
+    )
+}
+
+/// The lockfiles [`lockfile`] summarizes.
+pub const LOCKFILE_NAMES: &[&str] = &["Cargo.lock", "package-lock.json", "poetry.lock"];
+
+/// A compact summary of a lockfile's direct dependencies and their locked
+/// versions, or `None` if `path` is not a supported lockfile (Cargo.lock,
+/// package-lock.json, poetry.lock) or cannot be parsed.
+pub fn lockfile(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    if !LOCKFILE_NAMES.contains(&name) {
+        return None;
+    }
+    let text = fs::read_to_string(path).ok()?;
+    match name {
+        "Cargo.lock" => cargo_lock_summary(&text),
+        "package-lock.json" => npm_lock_summary(&text),
+        _ => {
+            let pyproject = path.with_file_name("pyproject.toml");
+            poetry_lock_summary(&text, fs::read_to_string(pyproject).ok().as_deref())
+        }
+    }
+}
+
+/// Direct dependencies of every local (source-less) package in a Cargo.lock.
+fn cargo_lock_summary(text: &str) -> Option<String> {
+    let lock: toml::Table = toml::from_str(text).ok()?;
+    let packages = lock.get("package")?.as_array()?;
+    let field = |p: &toml::Value, key: &str| p.get(key).and_then(|v| v.as_str()).map(String::from);
+
+    let mut out = format!(
+        "Cargo.lock: {} packages locked. Direct dependencies:\n",
+        packages.len()
+    );
+    for local in packages.iter().filter(|p| p.get("source").is_none()) {
+        let _ = write!(
+            out,
+            "\n{} {}\n",
+            field(local, "name")?,
+            field(local, "version")?
+        );
+        let deps = local.get("dependencies").and_then(|d| d.as_array());
+        for dep in deps.into_iter().flatten().filter_map(|d| d.as_str()) {
+            // "name", "name version" or "name version (source)"
+            let mut parts = dep.split_whitespace();
+            let dep_name = parts.next()?;
+            let version = match parts.next() {
+                Some(v) => v.to_string(),
+                None => packages
+                    .iter()
+                    .find(|p| field(p, "name").as_deref() == Some(dep_name))
+                    .and_then(|p| field(p, "version"))
+                    .unwrap_or_default(),
+            };
+            let _ = writeln!(out, "  {dep_name} {version}");
+        }
+    }
+    Some(out)
+}
+
+/// Direct dependencies of the root package in a v2/v3 package-lock.json.
+fn npm_lock_summary(text: &str) -> Option<String> {
+    let lock: serde_json::Value = serde_json::from_str(text).ok()?;
+    let packages = lock.get("packages")?.as_object()?;
+    let root = packages.get("")?;
+
+    let mut out = format!(
+        "package-lock.json: {} packages locked. Direct dependencies:\n\n",
+        packages.len().saturating_sub(1)
+    );
+    for (key, note) in [
+        ("dependencies", ""),
+        ("devDependencies", " (dev)"),
+        ("optionalDependencies", " (optional)"),
+    ] {
+        let Some(deps) = root.get(key).and_then(|d| d.as_object()) else {
+            continue;
+        };
+        for (name, range) in deps {
+            let version = packages
+                .get(&format!("node_modules/{name}"))
+                .and_then(|p| p.get("version"))
+                .or(Some(range))
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let _ = writeln!(out, "  {name} {version}{note}");
+        }
+    }
+    Some(out)
+}
+
+/// Locked versions of the dependencies declared in pyproject.toml, or of every
+/// locked package when there is no pyproject.toml alongside.
+fn poetry_lock_summary(text: &str, pyproject: Option<&str>) -> Option<String> {
+    let lock: toml::Table = toml::from_str(text).ok()?;
+    let packages = lock.get("package")?.as_array()?;
+    let direct = pyproject.and_then(pyproject_dependencies);
+
+    let mut out = format!("poetry.lock: {} packages locked. ", packages.len());
+    out.push_str(match direct {
+        Some(_) => "Direct dependencies:\n\n",
+        None => "Locked packages:\n\n",
+    });
+    for package in packages {
+        let (Some(name), Some(version)) = (
+            package.get("name").and_then(|v| v.as_str()),
+            package.get("version").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        if direct
+            .as_ref()
+            .is_none_or(|d| d.contains(&normalize_python_name(name)))
+        {
+            let _ = writeln!(out, "  {name} {version}");
+        }
+    }
+    Some(out)
+}
+
+/// Normalized names of the dependencies a pyproject.toml declares, from both
+/// Poetry tables and PEP 621 `[project]`.
+fn pyproject_dependencies(text: &str) -> Option<BTreeSet<String>> {
+    let doc: toml::Table = toml::from_str(text).ok()?;
+    let mut names = BTreeSet::new();
+
+    let poetry = doc.get("tool").and_then(|t| t.get("poetry"));
+    let mut tables: Vec<&toml::Value> = Vec::new();
+    if let Some(poetry) = poetry {
+        tables.extend(poetry.get("dependencies"));
+        tables.extend(poetry.get("dev-dependencies"));
+        if let Some(groups) = poetry.get("group").and_then(|g| g.as_table()) {
+            tables.extend(groups.values().filter_map(|g| g.get("dependencies")));
+        }
+    }
+    for table in tables.iter().filter_map(|t| t.as_table()) {
+        names.extend(
+            table
+                .keys()
+                .filter(|k| *k != "python")
+                .map(|k| normalize_python_name(k)),
+        );
+    }
+
+    if let Some(deps) = doc
+        .get("project")
+        .and_then(|p| p.get("dependencies"))
+        .and_then(|d| d.as_array())
+    {
+        for spec in deps.iter().filter_map(|d| d.as_str()) {
+            let end = spec
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                .unwrap_or(spec.len());
+            names.insert(normalize_python_name(&spec[..end]));
+        }
+    }
+    Some(names)
+}
+
+/// The extensions of the files [`data_skeleton`] reads.
+pub const SKELETON_EXTENSIONS: &[&str] = &["json", "yaml", "yml"];
+
+/// Object keys listed per level of a skeleton before the rest are elided.
+const SKELETON_MAX_KEYS: usize = 50;
+
+/// The key structure of a JSON or YAML file with value types and array
+/// lengths in place of values, or `None` if `path` is neither or fails to
+/// parse. Arrays show the shape of their first element.
+pub fn data_skeleton(path: &Path) -> Option<String> {
+    skeleton_of(path, &fs::read_to_string(path).ok()?)
+}
+
+fn skeleton_of(path: &Path, text: &str) -> Option<String> {
+    let ext = path.extension()?.to_str()?;
+    let value: serde_json::Value = match ext {
+        "json" => serde_json::from_str(text).ok()?,
+        "yaml" | "yml" => serde_yaml::from_str(text).ok()?,
+        _ => return None,
+    };
+
+    let mut out = format!(
+        "# Structure of {} ({}): value types and array lengths\n",
+        path.file_name()?.to_string_lossy(),
+        crate::cache::format_bytes(text.len() as u64)
+    );
+    write_skeleton(&mut out, &value, 0);
+    out.push('\n');
+    Some(out)
+}
+
+fn write_skeleton(out: &mut String, value: &serde_json::Value, depth: usize) {
+    use serde_json::Value;
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(_) => out.push_str("boolean"),
+        Value::Number(_) => out.push_str("number"),
+        Value::String(_) => out.push_str("string"),
+        Value::Array(items) => match items.first() {
+            None => out.push_str("[]"),
+            Some(first) => {
+                let _ = write!(out, "[{}] ", items.len());
+                write_skeleton(out, first, depth);
+            }
+        },
+        Value::Object(map) if map.is_empty() => out.push_str("{}"),
+        Value::Object(map) => {
+            let indent = "  ".repeat(depth + 1);
+            out.push_str("{\n");
+            for (key, child) in map.iter().take(SKELETON_MAX_KEYS) {
+                let _ = write!(out, "{indent}{key:?}: ");
+                write_skeleton(out, child, depth + 1);
+                out.push('\n');
+            }
+            if map.len() > SKELETON_MAX_KEYS {
+                let _ = writeln!(
+                    out,
+                    "{indent}... {} more keys",
+                    map.len() - SKELETON_MAX_KEYS
+                );
+            }
+            out.push_str(&"  ".repeat(depth));
+            out.push('}');
+        }
+    }
+}
+
+fn normalize_python_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace(['_', '.'], "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vendored_role() {
+        assert_eq!(vendored_role("src/main.rs"), None);
+        assert_eq!(
+            vendored_role("vendor/github.com/pkg/errors/README.md"),
+            Some(VendoredRole::Keep)
+        );
+        assert_eq!(
+            vendored_role("third_party/zlib/CMakeLists.txt"),
+            Some(VendoredRole::Keep)
+        );
+        assert_eq!(
+            vendored_role("crates/app/vendor/foo/src/lib.rs"),
+            Some(VendoredRole::Outline)
+        );
+        assert_eq!(
+            vendored_role("vendor/foo/data/table.bin"),
+            Some(VendoredRole::Omit)
+        );
+    }
+
+    #[test]
+    fn test_cargo_lock_summary() {
+        let lock = "version = 3\n\n\
+            [[package]]\nname = \"app\"\nversion = \"0.1.0\"\ndependencies = [\"serde\", \"syn 2.0.48\"]\n\n\
+            [[package]]\nname = \"serde\"\nversion = \"1.0.197\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+            [[package]]\nname = \"syn\"\nversion = \"2.0.48\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n";
+        assert_eq!(
+            cargo_lock_summary(lock).unwrap(),
+            "Cargo.lock: 3 packages locked. Direct dependencies:\n\napp 0.1.0\n  serde 1.0.197\n  syn 2.0.48\n"
+        );
+    }
+
+    #[test]
+    fn test_npm_lock_summary() {
+        let lock = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": {"dependencies": {"react": "^18.0.0"}, "devDependencies": {"vitest": "^1.0.0"}},
+                "node_modules/react": {"version": "18.2.0"},
+                "node_modules/vitest": {"version": "1.2.0"},
+                "node_modules/loose-envify": {"version": "1.4.0"}
+            }
+        }"#;
+        assert_eq!(
+            npm_lock_summary(lock).unwrap(),
+            "package-lock.json: 3 packages locked. Direct dependencies:\n\n  react 18.2.0\n  vitest 1.2.0 (dev)\n"
+        );
+    }
+
+    #[test]
+    fn test_poetry_lock_summary() {
+        let lock = "[[package]]\nname = \"requests\"\nversion = \"2.31.0\"\n\n\
+            [[package]]\nname = \"urllib3\"\nversion = \"2.2.1\"\n\n\
+            [[package]]\nname = \"typing_extensions\"\nversion = \"4.10.0\"\n";
+        let pyproject = "[tool.poetry.dependencies]\npython = \"^3.11\"\nrequests = \"^2.31\"\n\n\
+            [project]\ndependencies = [\"typing-extensions>=4\"]\n";
+        assert_eq!(
+            poetry_lock_summary(lock, Some(pyproject)).unwrap(),
+            "poetry.lock: 3 packages locked. Direct dependencies:\n\n  requests 2.31.0\n  typing_extensions 4.10.0\n"
+        );
+        assert!(poetry_lock_summary(lock, None)
+            .unwrap()
+            .contains("  urllib3 2.2.1\n"));
+    }
+
+    #[test]
+    fn test_data_skeleton() {
+        let dir = crate::test_dir::make_test_dir("skeleton");
+        let json = dir.join("fixture.json");
+        fs::write(
+            &json,
+            r#"{"name": "x", "tags": [], "items": [{"id": 1, "ok": true}, {"id": 2, "ok": false}]}"#,
+        )
+        .unwrap();
+        let yaml = dir.join("config.yaml");
+        fs::write(&yaml, "server:\n  port: 8080\n  hosts: [a, b, c]\n").unwrap();
+
+        let skeleton = data_skeleton(&json).unwrap();
+        assert!(skeleton.ends_with(
+            "{\n  \"items\": [2] {\n    \"id\": number\n    \"ok\": boolean\n  }\n  \"name\": string\n  \"tags\": []\n}\n"
+        ));
+        assert!(data_skeleton(&yaml).unwrap().ends_with(
+            "{\n  \"server\": {\n    \"hosts\": [3] string\n    \"port\": number\n  }\n}\n"
+        ));
+        assert!(data_skeleton(&dir.join("missing.toml")).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_shrink() {
+        let rust = "/// Doc.\npub fn a() {\n    body();\n}\n".repeat(10);
+        let path = Path::new("src/lib.rs");
+        assert_eq!(
+            shrink(path, &rust, OversizeStrategy::Outline, 1000, rust.len()),
+            "/// Doc.\npub fn a()\n".repeat(10)
+        );
+        assert_eq!(
+            shrink(path, &rust, OversizeStrategy::Truncate, 30, 5000),
+            "/// Doc.\npub fn a() {\n\n[truncated: showing 22 of 5000 bytes]\n"
+        );
+
+        let md = "# Title\ntext\n```\n# not a heading\n```\n## Part\nmore\n";
+        assert_eq!(
+            shrink(
+                Path::new("a.md"),
+                md,
+                OversizeStrategy::Structure,
+                100,
+                md.len()
+            ),
+            "# Title\n## Part\n"
+        );
+        // Outline doesn't know Markdown, so it truncates
+        assert!(shrink(
+            Path::new("a.md"),
+            md,
+            OversizeStrategy::Outline,
+            10,
+            md.len()
+        )
+        .starts_with("# Title\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_summarizer() {
+        let summary = external("wc -l | tr -d ' '", Path::new("a.txt"), "a\nb\nc\n").unwrap();
+        assert_eq!(summary, "[summarized]\n3\n");
+        assert!(external("echo $GATHER_FILE", Path::new("x/a.txt"), "")
+            .unwrap()
+            .contains("x/a.txt"));
+        assert!(external("exit 3", Path::new("a.txt"), "").is_err());
+        assert!(external("true", Path::new("a.txt"), "").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::git::git_output;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Repository metadata available to `--header` / `--footer` templates.
+///
+/// Placeholders are written as `{name}`; unknown placeholders are left as-is.
+pub struct TemplateVars {
+    pub repo: String,
+    pub branch: String,
+    pub commit: String,
+    pub timestamp: String,
+}
+
+impl TemplateVars {
+    /// Gather metadata for `root`. Git values fall back to `unknown` outside a
+    /// repository so templates always render.
+    pub fn from_root(root: &Path) -> Self {
+        let repo = git_output(root, &["rev-parse", "--show-toplevel"])
+            .map(|top| Path::new(&top).to_path_buf())
+            .unwrap_or_else(|| root.to_path_buf());
+        let repo = repo
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let branch = git_output(root, &["rev-parse", "--abbrev-ref", "HEAD"])
+            .unwrap_or_else(|| "unknown".to_string());
+        let commit = git_output(root, &["rev-parse", "--short", "HEAD"])
+            .unwrap_or_else(|| "unknown".to_string());
+
+        TemplateVars {
+            repo,
+            branch,
+            commit,
+            timestamp: now_utc_timestamp(),
+        }
+    }
+
+    /// Substitute `{repo}`, `{branch}`, `{commit}` (alias `{sha}`), `{timestamp}`
+    /// and `{date}` in `template`.
+    pub fn render(&self, template: &str) -> String {
+        let date = self.timestamp.get(..10).unwrap_or(&self.timestamp);
+        template
+            .replace("{repo}", &self.repo)
+            .replace("{branch}", &self.branch)
+            .replace("{commit}", &self.commit)
+            .replace("{sha}", &self.commit)
+            .replace("{timestamp}", &self.timestamp)
+            .replace("{date}", date)
+    }
+}
+
+/// The current time as an RFC 3339 UTC timestamp.
+pub fn now_utc_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format_utc_timestamp(secs)
+}
+
+/// Format seconds since the Unix epoch as an RFC 3339 UTC timestamp.
+pub fn format_utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date.
+/// Howard Hinnant's algorithm, valid for the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_utc_timestamp() {
+        assert_eq!(format_utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let vars = TemplateVars {
+            repo: "gather".to_string(),
+            branch: "main".to_string(),
+            commit: "abc1234".to_string(),
+            timestamp: "2024-05-01T12:00:00Z".to_string(),
+        };
+        assert_eq!(
+            vars.render("{repo}@{branch} ({sha}) {date} {unknown}"),
+            "gather@main (abc1234) 2024-05-01 {unknown}"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A fresh, empty directory under the system temp dir, unique to this test
+/// process and call.
+pub fn make_test_dir(name: &str) -> PathBuf {
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let dir = std::env::temp_dir().join(format!("gather_test_{name}_{}_{id}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// [`make_test_dir`] holding `files`, given as relative path and content.
+pub fn make_test_dir_with(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = make_test_dir(name);
+    for (path, content) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
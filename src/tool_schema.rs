@@ -0,0 +1,149 @@
+use crate::lang::LANGUAGES;
+use serde_json::{json, Value};
+
+/// One gather operation exposed as an agent tool.
+struct Tool {
+    name: &'static str,
+    description: &'static str,
+    parameters: Value,
+}
+
+/// Parameters shared by every tool: where to look and which files to select.
+fn selection_properties() -> Value {
+    json!({
+        "path": {
+            "type": "string",
+            "description": "Root directory to gather from, relative to the working directory. Defaults to \".\"."
+        },
+        "globs": {
+            "type": "array",
+            "items": {"type": "string"},
+            "description": "Only include files matching these glob patterns, e.g. [\"src/**/*.rs\"]."
+        },
+        "excludes": {
+            "type": "array",
+            "items": {"type": "string"},
+            "description": "Exclude files matching these glob patterns, e.g. [\"*.lock\"]."
+        },
+        "langs": {
+            "type": "array",
+            "items": {
+                "type": "string",
+                "enum": LANGUAGES.iter().map(|lang| lang.name).collect::<Vec<_>>()
+            },
+            "description": "Only include files in these languages, e.g. [\"rust\", \"toml\"]."
+        },
+        "no_tests": {
+            "type": "boolean",
+            "description": "Exclude test directories and test-named files."
+        }
+    })
+}
+
+fn tools() -> Vec<Tool> {
+    let mut collect = selection_properties();
+    collect["format"] = json!({
+        "type": "string",
+        "enum": ["markdown", "xml", "json", "summary"],
+        "description": "Output format. Defaults to markdown. summary gives each file's size, top-level symbols and first lines instead of its contents."
+    });
+    collect["max_file_tokens"] = json!({
+        "type": "integer",
+        "minimum": 1,
+        "description": "Skip files whose estimated token count exceeds this."
+    });
+
+    let object = |properties: Value| {
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": [],
+            "additionalProperties": false
+        })
+    };
+
+    vec![
+        Tool {
+            name: "gather_collect",
+            description: "Read the contents of files in a codebase, respecting .gitignore, and return them as one \
+                          markdown or XML document with a section per file. Use globs to narrow the selection; \
+                          check the size first with gather_tokens on large trees.",
+            parameters: object(collect),
+        },
+        Tool {
+            name: "gather_tree",
+            description: "Show the directory structure of a codebase as an indented tree, respecting .gitignore. \
+                          Cheap; use it to orient before collecting file contents.",
+            parameters: object(selection_properties()),
+        },
+        Tool {
+            name: "gather_tokens",
+            description: "Estimate the token count of each selected file and the total, without returning \
+                          contents. Use it to size a gather_collect call against your context budget.",
+            parameters: object(selection_properties()),
+        },
+    ]
+}
+
+/// Tool definitions for `provider` (`openai` or `anthropic`) as a JSON array,
+/// ready to pass in the provider's `tools` request field.
+pub fn render(provider: &str) -> Option<String> {
+    serde_json::to_string_pretty(&definitions(provider)?)
+        .ok()
+        .map(|s| s + "\n")
+}
+
+/// Each tool's definition in `provider`'s shape: `openai`, `anthropic`, or
+/// `mcp` for a Model Context Protocol `tools/list` result.
+pub fn definitions(provider: &str) -> Option<Vec<Value>> {
+    tools()
+        .into_iter()
+        .map(|tool| match provider {
+            "openai" => Some(json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters
+                }
+            })),
+            "anthropic" => Some(json!({
+                "name": tool.name,
+                "description": tool.description,
+                "input_schema": tool.parameters
+            })),
+            "mcp" => Some(json!({
+                "name": tool.name,
+                "description": tool.description,
+                "inputSchema": tool.parameters
+            })),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_providers() {
+        let openai: Value = serde_json::from_str(&render("openai").unwrap()).unwrap();
+        let anthropic: Value = serde_json::from_str(&render("anthropic").unwrap()).unwrap();
+        assert_eq!(openai.as_array().unwrap().len(), 3);
+
+        assert_eq!(openai[0]["type"], "function");
+        assert_eq!(openai[0]["function"]["name"], "gather_collect");
+        assert_eq!(anthropic[0]["name"], "gather_collect");
+        assert_eq!(
+            openai[0]["function"]["parameters"],
+            anthropic[0]["input_schema"]
+        );
+        assert!(anthropic[2]["input_schema"]["properties"]["globs"].is_object());
+        assert!(render("gemini").is_none());
+        assert_eq!(
+            definitions("mcp").unwrap()[1]["inputSchema"],
+            anthropic[1]["input_schema"]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Declaration keywords that introduce a named symbol across the languages
+/// gather outlines.
+const DEFINERS: &[&str] = &[
+    "fn",
+    "struct",
+    "enum",
+    "trait",
+    "type",
+    "mod",
+    "class",
+    "def",
+    "func",
+    "function",
+    "interface",
+    "const",
+];
+
+/// What a conversation refers to: path-like words and code identifiers, in
+/// the order they first appear.
+#[derive(Debug, Default, PartialEq)]
+pub struct Mentions {
+    pub paths: Vec<String>,
+    pub symbols: Vec<String>,
+}
+
+/// Read a chat log. JSON and JSON Lines exports (message arrays, API
+/// request/response dumps) are flattened to their string values; anything
+/// else is taken as plain text.
+pub fn load(path: &Path) -> Result<String, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(&text) {
+        return Ok(strings(&value).join("\n"));
+    }
+    let lines: Option<Vec<serde_json::Value>> = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).ok())
+        .collect();
+    match lines {
+        Some(values) if !values.is_empty() => Ok(values
+            .iter()
+            .flat_map(strings)
+            .collect::<Vec<_>>()
+            .join("\n")),
+        _ => Ok(text),
+    }
+}
+
+fn strings(value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::String(s) => vec![s.clone()],
+        serde_json::Value::Array(items) => items.iter().flat_map(strings).collect(),
+        serde_json::Value::Object(map) => map.values().flat_map(strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Pull path-like words (with a `/` or a file extension) and identifiers
+/// written as inline code or calls (`` `Cache` ``, `load()`) out of `text`.
+pub fn mentions(text: &str) -> Mentions {
+    let mut found = Mentions::default();
+    let mut seen = HashSet::new();
+
+    for word in text.split(|c: char| !is_path_char(c)) {
+        // Drop trailing punctuation and :line[:col] suffixes
+        let word = word.trim_matches(|c| c == '.' || c == ':' || c == '-');
+        let word = word.split(':').next().unwrap_or(word);
+        let word = word.strip_prefix("./").unwrap_or(word);
+        if looks_like_path(word) && seen.insert(word.to_string()) {
+            found.paths.push(word.to_string());
+        }
+    }
+
+    let mut add_symbol = |name: &str| {
+        if name.len() >= 3 && is_identifier(name) && seen.insert(name.to_string()) {
+            found.symbols.push(name.to_string());
+        }
+    };
+    for (i, span) in text.split('`').enumerate() {
+        if i % 2 == 1 && !span.contains(char::is_whitespace) {
+            let code = span.trim_end_matches("()").trim_end_matches(';');
+            for part in code.split([':', '.']) {
+                add_symbol(part);
+            }
+        }
+    }
+    for word in text.split(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '(' | ')'))) {
+        if let Some(name) = word.strip_suffix("()") {
+            add_symbol(name);
+        }
+    }
+    found
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '/' | '-' | ':' | '@' | '+')
+}
+
+fn looks_like_path(word: &str) -> bool {
+    if word.is_empty() || word.contains("://") || word.starts_with('@') {
+        return false;
+    }
+    let name = word.rsplit('/').next().unwrap_or(word);
+    let has_extension = name.rsplit_once('.').is_some_and(|(stem, ext)| {
+        !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()) && !stem.is_empty()
+    });
+    has_extension || (word.contains('/') && !word.ends_with('/'))
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Files among `files` (root-relative) that the conversation mentions: those
+/// whose path equals or ends with a mentioned path, then those defining a
+/// mentioned symbol (read from under `root`, skipping files over
+/// `max_size`). Returned in order of first mention.
+pub fn resolve(root: &Path, mentions: &Mentions, files: &[String], max_size: u64) -> Vec<String> {
+    let mut resolved = Vec::new();
+    let mut seen = HashSet::new();
+    for mention in &mentions.paths {
+        let relative = crate::buffers::relative_path(root, mention);
+        let Some(mention) = relative.as_deref() else {
+            continue;
+        };
+        for file in files {
+            let matches = file == mention
+                || file
+                    .strip_suffix(mention)
+                    .is_some_and(|prefix| prefix.ends_with('/'));
+            if matches && seen.insert(file.clone()) {
+                resolved.push(file.clone());
+            }
+        }
+    }
+
+    if mentions.symbols.is_empty() {
+        return resolved;
+    }
+    let defining: Vec<Vec<usize>> = files
+        .par_iter()
+        .map(|file| {
+            let path = root.join(file);
+            if path.metadata().map_or(true, |m| m.len() > max_size) {
+                return Vec::new();
+            }
+            let Ok(content) = fs::read_to_string(&path) else {
+                return Vec::new();
+            };
+            mentions
+                .symbols
+                .iter()
+                .enumerate()
+                .filter(|(_, symbol)| defines(&content, symbol))
+                .map(|(i, _)| i)
+                .collect()
+        })
+        .collect();
+    for i in 0..mentions.symbols.len() {
+        for (file, symbols) in files.iter().zip(&defining) {
+            if symbols.contains(&i) && seen.insert(file.clone()) {
+                resolved.push(file.clone());
+            }
+        }
+    }
+    resolved
+}
+
+/// Whether `content` declares `symbol`, e.g. `pub fn symbol(`,
+/// `class symbol:` or `func (r *T) symbol(`.
+pub fn defines(content: &str, symbol: &str) -> bool {
+    content.lines().any(|line| {
+        let words: Vec<&str> = line
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|w| !w.is_empty())
+            .collect();
+        words
+            .windows(2)
+            .any(|pair| pair[1] == symbol && DEFINERS.contains(&pair[0]))
+            || (line.trim_start().starts_with("func (") && line.contains(&format!(") {symbol}(")))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mentions() {
+        let text = "The bug is in src/cache.rs:63 (see `ScratchDir::drop`). \
+                    Also check README.md and call `load()` or parse_unmerged() \
+                    before https://example.com/x.html.";
+        let found = mentions(text);
+        assert_eq!(found.paths, ["src/cache.rs", "README.md"]);
+        assert_eq!(
+            found.symbols,
+            ["ScratchDir", "drop", "load", "parse_unmerged"]
+        );
+    }
+
+    #[test]
+    fn test_defines() {
+        assert!(defines(
+            "pub fn scratch_dir(parent: &Path) {",
+            "scratch_dir"
+        ));
+        assert!(defines("pub struct ScratchDir {", "ScratchDir"));
+        assert!(defines("class Cache:\n", "Cache"));
+        assert!(defines("func (c *Cache) Evict() {", "Evict"));
+        assert!(!defines("let dir = scratch_dir(parent);", "scratch_dir"));
+    }
+
+    #[test]
+    fn test_load_json_transcript() {
+        let dir = crate::test_dir::make_test_dir("transcript");
+        let path = dir.join("session.jsonl");
+        fs::write(
+            &path,
+            "{\"role\":\"user\",\"content\":\"fix src/a.rs\"}\n\
+             {\"role\":\"assistant\",\"content\":[{\"type\":\"text\",\"text\":\"done\"}]}\n",
+        )
+        .unwrap();
+        let text = load(&path).unwrap();
+        assert!(text.contains("fix src/a.rs"));
+        assert!(text.contains("done"));
+        assert!(!text.contains("\"role\""));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as FmtWrite;
+
+/// A directory in the tree: its entries in the order first seen, with
+/// subdirectories carrying their own entries.
+#[derive(Default)]
+struct Dir<'a> {
+    entries: Vec<(&'a str, Option<Dir<'a>>)>,
+    /// Index into `entries` of each subdirectory, by name
+    subdirs: HashMap<&'a str, usize>,
+    /// Files anywhere beneath
+    files: usize,
+}
+
+impl<'a> Dir<'a> {
+    fn insert(&mut self, parts: &[&'a str]) {
+        self.files += 1;
+        match parts {
+            [] => {}
+            [name] => self.entries.push((name, None)),
+            [name, rest @ ..] => {
+                let i = *self.subdirs.entry(name).or_insert_with(|| {
+                    self.entries.push((name, Some(Dir::default())));
+                    self.entries.len() - 1
+                });
+                if let Some(dir) = &mut self.entries[i].1 {
+                    dir.insert(rest);
+                }
+            }
+        }
+    }
+
+    fn render(&self, prefix: &str, out: &mut String) {
+        for (i, (name, dir)) in self.entries.iter().enumerate() {
+            let last = i + 1 == self.entries.len();
+            let branch = if last { "└── " } else { "├── " };
+            match dir {
+                None => {
+                    let _ = writeln!(out, "{prefix}{branch}{name}");
+                }
+                Some(dir) => {
+                    let noun = if dir.files == 1 { "file" } else { "files" };
+                    let _ = writeln!(out, "{prefix}{branch}{name}/ ({} {noun})", dir.files);
+                    let indent = if last { "    " } else { "│   " };
+                    dir.render(&format!("{prefix}{indent}"), out);
+                }
+            }
+        }
+    }
+}
+
+/// `paths` (sorted, `/`-separated) drawn as a tree under `root_name`, with
+/// each directory's file count, followed by the total.
+pub fn text(root_name: &str, paths: &[String]) -> String {
+    let mut root = Dir::default();
+    for path in paths {
+        root.insert(&path.split('/').collect::<Vec<_>>());
+    }
+    let mut out = format!("{root_name}/\n");
+    root.render("", &mut out);
+    let noun = if paths.len() == 1 { "file" } else { "files" };
+    let _ = writeln!(out, "\n{} {noun}", paths.len());
+    out
+}
+
+/// A Mermaid flowchart with an edge from each directory to its entries.
+pub fn mermaid(root_name: &str, paths: &[String]) -> String {
+    let mut out = String::from("flowchart LR\n");
+    let _ = writeln!(out, "  n0[\"{}/\"]", mermaid_label(root_name));
+
+    // Directory path -> node id; the root is ""
+    let mut dirs: BTreeMap<String, usize> = BTreeMap::from([(String::new(), 0)]);
+    let mut next = 1;
+    for path in paths {
+        let parts: Vec<&str> = path.split('/').collect();
+        let mut parent = 0;
+        for depth in 0..parts.len() - 1 {
+            let dir = parts[..=depth].join("/");
+            parent = match dirs.get(&dir) {
+                Some(id) => *id,
+                None => {
+                    let id = next;
+                    next += 1;
+                    let label = mermaid_label(parts[depth]);
+                    let _ = writeln!(out, "  n{parent} --> n{id}[\"{label}/\"]");
+                    dirs.insert(dir, id);
+                    id
+                }
+            };
+        }
+        let label = mermaid_label(parts.last().unwrap_or(&""));
+        let _ = writeln!(out, "  n{parent} --> n{next}[\"{label}\"]");
+        next += 1;
+    }
+    out
+}
+
+fn mermaid_label(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mermaid_tree() {
+        let paths = ["Cargo.toml", "src/main.rs", "src/util/io.rs"].map(String::from);
+        assert_eq!(
+            mermaid("app", &paths),
+            "flowchart LR\n  n0[\"app/\"]\n  n0 --> n1[\"Cargo.toml\"]\n  \
+             n0 --> n2[\"src/\"]\n  n2 --> n3[\"main.rs\"]\n  \
+             n2 --> n4[\"util/\"]\n  n4 --> n5[\"io.rs\"]\n"
+        );
+        assert_eq!(
+            text("app", &paths),
+            "app/\n├── Cargo.toml\n└── src/ (2 files)\n    ├── main.rs\n    \
+             └── util/ (1 file)\n        └── io.rs\n\n3 files\n"
+        );
+        // Directories with nothing but subdirectories still get their line
+        let deep = ["a/b/c/x.rs", "a/y.rs"].map(String::from);
+        assert_eq!(
+            text("r", &deep),
+            "r/\n└── a/ (2 files)\n    ├── b/ (1 file)\n    │   └── c/ (1 file)\n    \
+             │       └── x.rs\n    └── y.rs\n\n2 files\n"
+        );
+    }
+}
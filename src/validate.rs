@@ -0,0 +1,230 @@
+use crate::XML_SCHEMA_VERSION;
+use roxmltree::{Document, Node};
+
+/// The children of the root element in schema order, each with the
+/// attributes it may carry. `file` stands for the configured file element.
+const CHILDREN: [(&str, &[&str]); 7] = [
+    ("header", &[]),
+    ("meta", &["files", "tokens"]),
+    ("tree", &[]),
+    ("graph", &["format"]),
+    ("unchanged", &["session"]),
+    ("file", FILE_ATTRIBUTES),
+    ("footer", &[]),
+];
+
+const FILE_ATTRIBUTES: &[&str] = &[
+    "path", "lang", "bytes", "tokens", "lines", "sha256", "mtime",
+];
+
+/// Check rendered XML against the contract in `schema/gather-context.xsd`,
+/// with `root` and `element` standing in for the default `context` and `file`
+/// element names. Returns one message per violation.
+pub fn validate_xml(xml: &str, root: &str, element: &str) -> Vec<String> {
+    let doc = match Document::parse(xml) {
+        Ok(doc) => doc,
+        Err(e) => return vec![format!("not well-formed: {e}")],
+    };
+    let mut errors = Vec::new();
+
+    let top = doc.root_element();
+    if top.tag_name().name() != root {
+        errors.push(format!(
+            "root element is <{}>, expected <{root}>",
+            top.tag_name().name()
+        ));
+    }
+    match top.attribute("version") {
+        Some(v) if v == XML_SCHEMA_VERSION.to_string() => {}
+        Some(v) => errors.push(format!(
+            "root version is {v}, expected {XML_SCHEMA_VERSION}"
+        )),
+        None => errors.push("root element has no version attribute".to_string()),
+    }
+    check_attributes(top, &["version"], &mut errors);
+
+    let mut last: Option<usize> = None;
+    for child in top.children() {
+        if child.is_text() {
+            if !child.text().unwrap_or("").trim().is_empty() {
+                errors.push(format!("unexpected text directly inside <{root}>"));
+            }
+            continue;
+        }
+        if !child.is_element() {
+            continue;
+        }
+        let name = child.tag_name().name();
+        let Some(rank) = CHILDREN.iter().position(|(o, _)| {
+            if *o == "file" {
+                name == element
+            } else {
+                *o == name
+            }
+        }) else {
+            errors.push(format!("unexpected element <{name}>"));
+            continue;
+        };
+        // Only file elements may repeat
+        if last.is_some_and(|l| rank < l || (rank == l && CHILDREN[rank].0 != "file")) {
+            errors.push(format!("<{name}> is out of order or repeated"));
+        }
+        last = last.max(Some(rank));
+
+        if child.children().any(|c| c.is_element()) {
+            errors.push(format!("<{name}> must contain only text"));
+        }
+        check_attributes(child, CHILDREN[rank].1, &mut errors);
+        match CHILDREN[rank].0 {
+            "unchanged" if child.attribute("session").is_none() => {
+                errors.push("<unchanged> without a session".to_string());
+            }
+            "graph" if child.attribute("format") != Some("mermaid") => {
+                errors.push("<graph> format must be mermaid".to_string());
+            }
+            "meta" => {
+                require_uint(child, "files", &mut errors);
+                match child.attribute("tokens") {
+                    Some(t) if t.strip_prefix('~').is_some_and(is_uint) => {}
+                    _ => errors.push("<meta> tokens must be written as ~N".to_string()),
+                }
+            }
+            "file" => check_file(child, &mut errors),
+            _ => {}
+        }
+    }
+    errors
+}
+
+fn check_file(file: Node, errors: &mut Vec<String>) {
+    let Some(path) = file.attribute("path") else {
+        errors.push(format!("<{}> without a path", file.tag_name().name()));
+        return;
+    };
+    for attr in ["bytes", "tokens", "lines"] {
+        if file.attribute(attr).is_some() {
+            require_uint(file, attr, errors);
+        }
+    }
+    if let Some(sha) = file.attribute("sha256") {
+        if sha.len() != 64 || !sha.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            errors.push(format!("{path}: sha256 is not 64 lowercase hex digits"));
+        }
+    }
+    if let Some(mtime) = file.attribute("mtime") {
+        // YYYY-MM-DDTHH:MM:SSZ
+        let shape = mtime.len() == 20
+            && mtime.char_indices().all(|(i, c)| match i {
+                4 | 7 => c == '-',
+                10 => c == 'T',
+                13 | 16 => c == ':',
+                19 => c == 'Z',
+                _ => c.is_ascii_digit(),
+            });
+        if !shape {
+            errors.push(format!("{path}: mtime is not an RFC 3339 UTC timestamp"));
+        }
+    }
+}
+
+fn check_attributes(node: Node, allowed: &[&str], errors: &mut Vec<String>) {
+    for attr in node.attributes() {
+        if !allowed.contains(&attr.name()) {
+            errors.push(format!(
+                "<{}> has unexpected attribute {}",
+                node.tag_name().name(),
+                attr.name()
+            ));
+        }
+    }
+}
+
+fn require_uint(node: Node, attr: &str, errors: &mut Vec<String>) {
+    if !node.attribute(attr).is_some_and(is_uint) {
+        errors.push(format!(
+            "<{}> {attr} must be a non-negative integer",
+            node.tag_name().name()
+        ));
+    }
+}
+
+fn is_uint(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_xml() {
+        let good = "<context version=\"2\">\n  <header>h</header>\n  <meta files=\"1\" tokens=\"~4\"/>\n  \
+                    <tree>ctx\n  a.rs\n  </tree>\n  \
+                    <graph format=\"mermaid\">graph LR\n  </graph>\n  \
+                    <unchanged session=\"s\">b.rs</unchanged>\n  \
+                    <file path=\"a.rs\" bytes=\"3\" mtime=\"2024-01-01T00:00:00Z\">x\n  </file>\n</context>\n";
+        assert!(validate_xml(good, "context", "file").is_empty());
+
+        let bad = "<context>\n  <file path=\"a\" bytes=\"-1\">x</file>\n  <header/>\n  <file/>\n  <tree/>\n</context>\n";
+        let errors = validate_xml(bad, "context", "file");
+        assert_eq!(
+            errors,
+            vec![
+                "root element has no version attribute",
+                "<file> bytes must be a non-negative integer",
+                "<header> is out of order or repeated",
+                "<file> without a path",
+                "<tree> is out of order or repeated",
+            ]
+        );
+        assert!(validate_xml("<context>", "context", "file")[0].starts_with("not well-formed"));
+    }
+
+    #[test]
+    fn test_rules_match_schema() {
+        let xsd = include_str!("../schema/gather-context.xsd");
+        let doc = Document::parse(xsd).unwrap();
+        let named = |kind: &str, name: &str| {
+            doc.descendants()
+                .find(|n| n.tag_name().name() == kind && n.attribute("name") == Some(name))
+                .unwrap()
+        };
+        let attributes = |node: Node| -> Vec<String> {
+            node.descendants()
+                .filter(|n| n.tag_name().name() == "attribute")
+                .map(|n| n.attribute("name").unwrap().to_string())
+                .collect()
+        };
+
+        let sequence = named("element", "context")
+            .descendants()
+            .find(|n| n.tag_name().name() == "sequence")
+            .unwrap();
+        let children: Vec<Node> = sequence.children().filter(|n| n.is_element()).collect();
+        assert_eq!(
+            children
+                .iter()
+                .map(|n| n.attribute("name").unwrap())
+                .collect::<Vec<_>>(),
+            CHILDREN.map(|(name, _)| name)
+        );
+        for (child, (name, allowed)) in children.iter().zip(CHILDREN) {
+            let declared = match child.attribute("type") {
+                Some("fileType") => attributes(named("complexType", "fileType")),
+                _ => attributes(*child),
+            };
+            assert_eq!(declared, allowed, "attributes of <{name}>");
+            let repeats = child.attribute("maxOccurs") == Some("unbounded");
+            assert_eq!(repeats, name == "file", "repetition of <{name}>");
+        }
+        let root_attributes: Vec<&str> = sequence
+            .parent()
+            .unwrap()
+            .children()
+            .filter(|n| n.tag_name().name() == "attribute")
+            .filter_map(|n| n.attribute("name"))
+            .collect();
+        assert_eq!(root_attributes, ["version"]);
+        assert!(xsd.contains(&format!("version {XML_SCHEMA_VERSION}.")));
+    }
+}
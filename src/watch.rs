@@ -0,0 +1,124 @@
+use crate::{output, split, walk_files, FilterArgs, PathFilter};
+use notify::{RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+/// What a gather would read: each matched file's path, size and mtime.
+/// Two equal fingerprints produce the same bundle.
+pub type Fingerprint = Vec<(String, u64, Option<SystemTime>)>;
+
+/// The fingerprint of the files `filter` selects under `root`.
+pub fn fingerprint(root: &Path, filter: &FilterArgs) -> Fingerprint {
+    let path_filter = PathFilter::new(root, filter);
+    walk_files(root, filter)
+        .into_iter()
+        .filter(|(_, rel)| path_filter.matches(rel))
+        .map(|(path, rel)| {
+            let meta = path.metadata().ok();
+            let len = meta.as_ref().map_or(0, |m| m.len());
+            (rel, len, meta.and_then(|m| m.modified().ok()))
+        })
+        .collect()
+}
+
+/// Watch `root` and call `regenerate` once at the start and again after
+/// every burst of changes (settled for `debounce`) that alters the
+/// fingerprint. Changes under `.git/` and to the `written` paths (the
+/// bundle and anything else the gather itself writes, along with split
+/// parts and the temporary files they're written through) are ignored. Runs until the
+/// watcher fails.
+pub fn watch(
+    root: &Path,
+    filter: &FilterArgs,
+    written: &[PathBuf],
+    debounce: Duration,
+    mut regenerate: impl FnMut(),
+) -> Result<(), String> {
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("{}: {e}", root.display()))?;
+    let written: Vec<PathBuf> = written.iter().filter_map(|p| output::resolve(p)).collect();
+    let is_written = |path: &Path| {
+        // Files are written through a `NAME.tmpPID` file beside them
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let target = match name.rsplit_once(".tmp") {
+            Some((base, pid)) if pid.bytes().all(|b| b.is_ascii_digit()) => {
+                path.with_file_name(base)
+            }
+            _ => path.to_path_buf(),
+        };
+        written
+            .iter()
+            .any(|w| target == *w || split::is_part(w, &target))
+    };
+    let relevant = |path: &Path| !path.starts_with(root.join(".git")) && !is_written(path);
+    let snapshot = || -> Fingerprint {
+        fingerprint(&root, filter)
+            .into_iter()
+            .filter(|(rel, _, _)| !is_written(&root.join(rel)))
+            .collect()
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).map_err(|e| format!("Failed to watch: {e}"))?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch {}: {e}", root.display()))?;
+
+    let mut last = snapshot();
+    regenerate();
+    loop {
+        let mut changed = false;
+        let mut event = rx.recv().map_err(|e| format!("Watcher stopped: {e}"))?;
+        loop {
+            match event {
+                Ok(event) => changed |= event.paths.iter().any(|p| relevant(p)),
+                Err(e) => crate::status::warn(format!("watch: {e}")),
+            }
+            event = match rx.recv_timeout(debounce) {
+                Ok(next) => next,
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(e) => return Err(format!("Watcher stopped: {e}")),
+            };
+        }
+        if !changed {
+            continue;
+        }
+        let current = snapshot();
+        if current != last {
+            last = current;
+            regenerate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_fingerprint_tracks_matched_files() {
+        let root = crate::test_dir::make_test_dir("watch");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "fn a() {}\n").unwrap();
+        fs::write(root.join("notes.txt"), "x").unwrap();
+        let filter = FilterArgs {
+            globs: vec!["src/**".to_string()],
+            ..Default::default()
+        };
+
+        let before = fingerprint(&root, &filter);
+        assert_eq!(before.len(), 1);
+        fs::write(root.join("notes.txt"), "changed, but not selected").unwrap();
+        assert_eq!(fingerprint(&root, &filter), before);
+        fs::write(root.join("src/lib.rs"), "fn a() { b() }\n").unwrap();
+        assert_ne!(fingerprint(&root, &filter), before);
+        fs::write(root.join("src/new.rs"), "").unwrap();
+        assert_eq!(fingerprint(&root, &filter).len(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
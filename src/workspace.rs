@@ -0,0 +1,461 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkspaceKind {
+    Cargo,
+    Npm,
+    Pnpm,
+    Go,
+}
+
+impl WorkspaceKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            WorkspaceKind::Cargo => "cargo",
+            WorkspaceKind::Npm => "npm/yarn",
+            WorkspaceKind::Pnpm => "pnpm",
+            WorkspaceKind::Go => "go",
+        }
+    }
+}
+
+/// A member package of a workspace.
+#[derive(Debug, PartialEq)]
+pub struct Package {
+    pub name: String,
+    /// Directory relative to the workspace root, using `/` separators.
+    pub dir: String,
+}
+
+/// A workspace detected at the repository root.
+#[derive(Debug)]
+pub struct Workspace {
+    pub kind: WorkspaceKind,
+    /// Root manifests that describe the workspace itself.
+    pub manifests: Vec<String>,
+    pub packages: Vec<Package>,
+}
+
+/// The paths `--package` selects: one member directory plus the root
+/// manifests of its workspace.
+pub struct PackageScope {
+    pub dir: String,
+    pub manifests: Vec<String>,
+}
+
+impl PackageScope {
+    pub fn contains(&self, rel: &str) -> bool {
+        self.dir.is_empty()
+            || rel == self.dir
+            || rel
+                .strip_prefix(self.dir.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+            || self.manifests.iter().any(|m| m == rel)
+    }
+}
+
+/// Detect Cargo, npm/yarn, pnpm and Go workspaces declared at `root`.
+pub fn detect(root: &Path) -> Vec<Workspace> {
+    [
+        cargo_workspace(root),
+        npm_workspace(root),
+        pnpm_workspace(root),
+        go_workspace(root),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Resolve `name` (a package name or member directory) to its scope. The
+/// error lists the available packages.
+pub fn package_scope(root: &Path, name: &str) -> Result<PackageScope, String> {
+    let workspaces = detect(root);
+    if workspaces.is_empty() {
+        return Err(format!(
+            "--package {name}: no Cargo, npm/yarn, pnpm or Go workspace found in {}",
+            root.display()
+        ));
+    }
+
+    for ws in &workspaces {
+        let dir = name.trim_end_matches('/');
+        if let Some(pkg) = ws.packages.iter().find(|p| p.name == name || p.dir == dir) {
+            return Ok(PackageScope {
+                dir: pkg.dir.clone(),
+                manifests: ws.manifests.clone(),
+            });
+        }
+    }
+
+    let mut available: Vec<String> = workspaces
+        .iter()
+        .flat_map(|ws| {
+            ws.packages
+                .iter()
+                .map(move |p| format!("{} ({}, {})", p.name, p.dir, ws.kind.label()))
+        })
+        .collect();
+    available.sort();
+    Err(format!(
+        "Unknown package '{name}'. Available packages:\n  {}",
+        available.join("\n  ")
+    ))
+}
+
+fn cargo_workspace(root: &Path) -> Option<Workspace> {
+    let text = fs::read_to_string(root.join("Cargo.toml")).ok()?;
+    let doc: toml::Table = toml::from_str(&text).ok()?;
+    let ws = doc.get("workspace")?.as_table()?;
+    let strings = |key: &str| -> Vec<String> {
+        ws.get(key)
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let dirs = expand_members(root, &strings("members"), &strings("exclude"), "Cargo.toml");
+    let packages = dirs
+        .into_iter()
+        .map(|dir| {
+            let name = fs::read_to_string(root.join(&dir).join("Cargo.toml"))
+                .ok()
+                .and_then(|t| toml::from_str::<toml::Table>(&t).ok())
+                .and_then(|t| t.get("package")?.get("name")?.as_str().map(String::from));
+            Package {
+                name: name.unwrap_or_else(|| dir_name(&dir)),
+                dir,
+            }
+        })
+        .collect();
+
+    Some(Workspace {
+        kind: WorkspaceKind::Cargo,
+        manifests: vec!["Cargo.toml".to_string()],
+        packages,
+    })
+}
+
+fn npm_workspace(root: &Path) -> Option<Workspace> {
+    let text = fs::read_to_string(root.join("package.json")).ok()?;
+    let doc: serde_json::Value = serde_json::from_str(&text).ok()?;
+    let patterns = match doc.get("workspaces")? {
+        serde_json::Value::Array(a) => a.clone(),
+        serde_json::Value::Object(o) => o.get("packages")?.as_array()?.clone(),
+        _ => return None,
+    };
+    let (includes, excludes) = split_negated(
+        patterns
+            .iter()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect(),
+    );
+    let dirs = expand_members(root, &includes, &excludes, "package.json");
+
+    Some(Workspace {
+        kind: WorkspaceKind::Npm,
+        manifests: vec!["package.json".to_string()],
+        packages: npm_packages(root, dirs),
+    })
+}
+
+fn pnpm_workspace(root: &Path) -> Option<Workspace> {
+    let text = fs::read_to_string(root.join("pnpm-workspace.yaml")).ok()?;
+    let (includes, excludes) = split_negated(yaml_list(&text, "packages"));
+    let dirs = expand_members(root, &includes, &excludes, "package.json");
+
+    let mut manifests = vec!["pnpm-workspace.yaml".to_string()];
+    if root.join("package.json").is_file() {
+        manifests.insert(0, "package.json".to_string());
+    }
+    Some(Workspace {
+        kind: WorkspaceKind::Pnpm,
+        manifests,
+        packages: npm_packages(root, dirs),
+    })
+}
+
+fn go_workspace(root: &Path) -> Option<Workspace> {
+    let text = fs::read_to_string(root.join("go.work")).ok()?;
+    let mut dirs = Vec::new();
+    let mut in_block = false;
+    for line in text.lines() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if in_block {
+            if line == ")" {
+                in_block = false;
+            } else if !line.is_empty() {
+                dirs.push(line.to_string());
+            }
+        } else if let Some(rest) = line.strip_prefix("use") {
+            let rest = rest.trim();
+            if rest == "(" {
+                in_block = true;
+            } else if !rest.is_empty() {
+                dirs.push(rest.to_string());
+            }
+        }
+    }
+
+    let packages = dirs
+        .into_iter()
+        .map(|dir| normalize_dir(&dir))
+        .map(|dir| {
+            let module = fs::read_to_string(root.join(&dir).join("go.mod"))
+                .ok()
+                .and_then(|t| {
+                    t.lines().find_map(|l| {
+                        l.trim()
+                            .strip_prefix("module ")
+                            .map(|m| m.trim().to_string())
+                    })
+                });
+            Package {
+                name: module.unwrap_or_else(|| dir_name(&dir)),
+                dir,
+            }
+        })
+        .collect();
+
+    Some(Workspace {
+        kind: WorkspaceKind::Go,
+        manifests: vec!["go.work".to_string()],
+        packages,
+    })
+}
+
+fn npm_packages(root: &Path, dirs: Vec<String>) -> Vec<Package> {
+    dirs.into_iter()
+        .map(|dir| {
+            let name = fs::read_to_string(root.join(&dir).join("package.json"))
+                .ok()
+                .and_then(|t| serde_json::from_str::<serde_json::Value>(&t).ok())
+                .and_then(|v| v.get("name")?.as_str().map(String::from));
+            Package {
+                name: name.unwrap_or_else(|| dir_name(&dir)),
+                dir,
+            }
+        })
+        .collect()
+}
+
+/// Split workspace patterns into includes and `!`-prefixed excludes.
+fn split_negated(patterns: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let (excludes, includes): (Vec<String>, Vec<String>) =
+        patterns.into_iter().partition(|p| p.starts_with('!'));
+    let excludes = excludes.into_iter().map(|p| p[1..].to_string()).collect();
+    (includes, excludes)
+}
+
+/// The string items of a top-level YAML sequence, e.g. `packages:` in
+/// `pnpm-workspace.yaml`. Handles the block style pnpm documents.
+fn yaml_list(text: &str, key: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut in_list = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t', '-']) {
+            in_list = trimmed.strip_suffix(':') == Some(key);
+            continue;
+        }
+        if in_list {
+            if let Some(item) = trimmed.strip_prefix('-') {
+                let item = item.split(" #").next().unwrap_or("").trim();
+                items.push(item.trim_matches(['\'', '"']).to_string());
+            }
+        }
+    }
+    items
+}
+
+/// Directories under `root` containing `manifest` whose relative path matches
+/// one of `members` and none of `excludes`.
+fn expand_members(
+    root: &Path,
+    members: &[String],
+    excludes: &[String],
+    manifest: &str,
+) -> Vec<String> {
+    let include = member_globs(members);
+    let exclude = member_globs(excludes);
+
+    let mut dirs: Vec<String> = WalkBuilder::new(root)
+        .hidden(true)
+        .build()
+        .flatten()
+        .filter(|e| e.file_name() == manifest)
+        .filter_map(|e| {
+            let dir = e.path().parent()?.strip_prefix(root).ok()?;
+            let dir = dir.to_string_lossy().replace('\\', "/");
+            (!dir.is_empty()).then_some(dir)
+        })
+        .filter(|dir| include.is_match(dir) && !exclude.is_match(dir))
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn member_globs(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        if let Ok(glob) = GlobBuilder::new(&normalize_dir(pattern))
+            .literal_separator(true)
+            .build()
+        {
+            builder.add(glob);
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+fn normalize_dir(dir: &str) -> String {
+    let dir = dir.trim().trim_end_matches('/');
+    let dir = dir.strip_prefix("./").unwrap_or(dir);
+    if dir == "." {
+        String::new()
+    } else {
+        dir.to_string()
+    }
+}
+
+fn dir_name(dir: &str) -> String {
+    dir.rsplit('/').next().unwrap_or(dir).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, rel: &str, content: &str) {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_cargo_workspace() {
+        let root = crate::test_dir::make_test_dir("workspace_cargo");
+        write(
+            &root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\", \"tools/cli\"]\nexclude = [\"crates/old\"]\n",
+        );
+        write(
+            &root,
+            "crates/core/Cargo.toml",
+            "[package]\nname = \"app-core\"\n",
+        );
+        write(
+            &root,
+            "crates/old/Cargo.toml",
+            "[package]\nname = \"old\"\n",
+        );
+        write(
+            &root,
+            "crates/core/nested/Cargo.toml",
+            "[package]\nname = \"nested\"\n",
+        );
+        write(
+            &root,
+            "tools/cli/Cargo.toml",
+            "[package]\nname = \"app-cli\"\n",
+        );
+
+        let ws = detect(&root);
+        assert_eq!(ws.len(), 1);
+        assert_eq!(ws[0].kind, WorkspaceKind::Cargo);
+        assert_eq!(
+            ws[0].packages,
+            vec![
+                Package {
+                    name: "app-core".to_string(),
+                    dir: "crates/core".to_string()
+                },
+                Package {
+                    name: "app-cli".to_string(),
+                    dir: "tools/cli".to_string()
+                },
+            ]
+        );
+
+        let scope = package_scope(&root, "app-core").unwrap();
+        assert!(scope.contains("crates/core/src/lib.rs"));
+        assert!(scope.contains("Cargo.toml"));
+        assert!(!scope.contains("crates/core-extra/src/lib.rs"));
+        assert!(!scope.contains("tools/cli/src/main.rs"));
+
+        let err = package_scope(&root, "missing").err().unwrap();
+        assert!(err.contains("app-cli (tools/cli, cargo)"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_pnpm_and_npm_workspaces() {
+        let root = crate::test_dir::make_test_dir("workspace_pnpm");
+        write(
+            &root,
+            "pnpm-workspace.yaml",
+            "packages:\n  - 'packages/*'\n  - \"!packages/skip\"\nother: 1\n",
+        );
+        write(
+            &root,
+            "package.json",
+            "{\"workspaces\": {\"packages\": [\"apps/*\"]}}",
+        );
+        write(
+            &root,
+            "packages/ui/package.json",
+            "{\"name\": \"@acme/ui\"}",
+        );
+        write(&root, "packages/skip/package.json", "{\"name\": \"skip\"}");
+        write(&root, "apps/web/package.json", "{\"name\": \"web\"}");
+
+        let ws = detect(&root);
+        assert_eq!(ws.len(), 2);
+        assert_eq!(ws[0].kind, WorkspaceKind::Npm);
+        assert_eq!(ws[0].packages[0].name, "web");
+        assert_eq!(ws[1].kind, WorkspaceKind::Pnpm);
+        assert_eq!(ws[1].packages.len(), 1);
+        assert_eq!(ws[1].packages[0].name, "@acme/ui");
+
+        let scope = package_scope(&root, "@acme/ui").unwrap();
+        assert_eq!(scope.manifests, vec!["package.json", "pnpm-workspace.yaml"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_go_workspace() {
+        let root = crate::test_dir::make_test_dir("workspace_go");
+        write(
+            &root,
+            "go.work",
+            "go 1.22\n\nuse (\n\t./svc/api // main service\n\t./lib\n)\n",
+        );
+        write(
+            &root,
+            "svc/api/go.mod",
+            "module example.com/api\n\ngo 1.22\n",
+        );
+        write(&root, "lib/go.mod", "module example.com/lib\n");
+
+        let ws = detect(&root);
+        assert_eq!(ws[0].kind, WorkspaceKind::Go);
+        assert_eq!(ws[0].packages[0].name, "example.com/api");
+        assert_eq!(ws[0].packages[0].dir, "svc/api");
+        assert!(package_scope(&root, "lib").unwrap().contains("lib/util.go"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}